@@ -0,0 +1,65 @@
+/*
+ * C API of libbf (feature `capi`).
+ *
+ * See the documentation of the `libbf::capi` module for details.
+ */
+#ifndef LIBBF_H
+#define LIBBF_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BF_OK 0
+#define BF_ERR_INVALID_ARGUMENT 1
+#define BF_ERR_PARSE 2
+#define BF_ERR_RUNTIME 3
+#define BF_ERR_PANIC 4
+
+#define BF_MEMSIZE_FIXED 0
+#define BF_MEMSIZE_RIGHT_INFINITE 1
+#define BF_MEMSIZE_BOTH_INFINITE 2
+
+typedef struct BfParser BfParser;
+typedef struct BfProgram BfProgram;
+
+typedef struct BfErrorInfo {
+    uint32_t code;
+    size_t pos_in_chars;
+} BfErrorInfo;
+
+typedef struct BfRunConfig {
+    int memsize_kind;
+    size_t memsize;
+} BfRunConfig;
+
+typedef int (*BfInputCallback)(void *userdata);
+typedef int (*BfOutputCallback)(void *userdata, uint8_t byte);
+
+const char *bf_last_error_message(void);
+
+BfParser *bf_parser_new_bf(void);
+void bf_parser_free(BfParser *parser);
+
+int bf_parse(const BfParser *parser,
+             const char *src,
+             size_t len,
+             BfProgram **out_program,
+             BfErrorInfo *out_err);
+void bf_program_free(BfProgram *program);
+
+int bf_run(const BfProgram *program,
+           BfInputCallback in_cb,
+           BfOutputCallback out_cb,
+           void *userdata,
+           const BfRunConfig *config,
+           BfErrorInfo *out_err);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LIBBF_H */
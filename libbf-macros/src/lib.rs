@@ -0,0 +1,162 @@
+//! Procedural macros for libbf.
+//!
+//! Use them through `libbf` with the `macros` feature, not directly.
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::{Error, LitStr};
+
+/// Build a program from a Brainfuck string literal at compile time.
+///
+/// The input is `<path of libbf>, <literal>`, which `libbf::bf!` passes as `$crate, $source`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn bf_str(input: TokenStream) -> TokenStream {
+    match bf_str_internal(input.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn bf_str_internal(input: TokenStream2) -> syn::Result<TokenStream2> {
+    let mut krate = TokenStream2::new();
+    let mut tokens = input.into_iter();
+    for token in tokens.by_ref() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => break,
+            token => krate.extend([token]),
+        }
+    }
+    let literal: LitStr = syn::parse2(tokens.collect())?;
+    let instructions = parse(&literal.value())
+        .map_err(|message| Error::new(literal.span(), message))?
+        .into_iter()
+        .map(|inst| inst.to_tokens(&krate));
+    Ok(quote! {
+        #krate::program::Program::new([#(#instructions),*])
+    })
+}
+
+enum Instruction {
+    PAdd(isize),
+    DAdd(isize),
+    Output,
+    Input,
+    UntilZero(Vec<Instruction>),
+}
+
+impl Instruction {
+    fn to_tokens(&self, krate: &TokenStream2) -> TokenStream2 {
+        let path = quote!(#krate::program::Instruction);
+        match self {
+            Self::PAdd(operand) => quote!(#path::PAdd(#operand)),
+            Self::DAdd(operand) => quote!(#path::DAdd(#operand)),
+            Self::Output => quote!(#path::Output),
+            Self::Input => quote!(#path::Input),
+            Self::UntilZero(body) => {
+                let body = body.iter().map(|inst| inst.to_tokens(krate));
+                quote!(#path::UntilZero(::std::vec![#(#body),*]))
+            }
+        }
+    }
+}
+
+// Parse a source with the standard Brainfuck tokens like the parser of libbf does:
+// characters other than `><+-.,[]` are comments, and a run of increments/decrements
+// is folded into one instruction, which is dropped if its operand is 0.
+//
+// The error message has the same form as `libbf::error::ParseError`.
+fn parse(source: &str) -> Result<Vec<Instruction>, String> {
+    // (the position of the loop head, the instructions before the loop)
+    let mut stack: Vec<(usize, Vec<Instruction>)> = vec![];
+    let mut current = vec![];
+    let mut run = None;
+    for (pos, c) in source.chars().enumerate() {
+        let (kind, operand) = match c {
+            '>' => (RunKind::Pointer, 1),
+            '<' => (RunKind::Pointer, -1),
+            '+' => (RunKind::Data, 1),
+            '-' => (RunKind::Data, -1),
+            '.' | ',' | '[' | ']' => {
+                flush(&mut current, run.take());
+                match c {
+                    '.' => current.push(Instruction::Output),
+                    ',' => current.push(Instruction::Input),
+                    '[' => stack.push((pos, std::mem::take(&mut current))),
+                    _ => {
+                        let Some((_, outer)) = stack.pop() else {
+                            return Err(format!("{pos}: Unexpected end-of-loop"));
+                        };
+                        let body = std::mem::replace(&mut current, outer);
+                        current.push(Instruction::UntilZero(body));
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        match &mut run {
+            Some((run_kind, sum)) if *run_kind == kind => *sum += operand,
+            _ => flush(&mut current, run.replace((kind, operand))),
+        }
+    }
+    flush(&mut current, run);
+    match stack.last() {
+        Some((head, _)) => Err(format!(
+            "{}: Unexpected end-of-file (the loop at {head} is not closed)",
+            source.chars().count()
+        )),
+        None => Ok(current),
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum RunKind {
+    Pointer,
+    Data,
+}
+
+// Push the instruction of a run unless its operand is 0.
+fn flush(instructions: &mut Vec<Instruction>, run: Option<(RunKind, isize)>) {
+    match run {
+        Some((_, 0)) | None => {}
+        Some((RunKind::Pointer, operand)) => instructions.push(Instruction::PAdd(operand)),
+        Some((RunKind::Data, operand)) => instructions.push(Instruction::DAdd(operand)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("+[-]]").err().as_deref(),
+            Some("4: Unexpected end-of-loop")
+        );
+        assert_eq!(
+            parse("[[-]").err().as_deref(),
+            Some("4: Unexpected end-of-file (the loop at 0 is not closed)")
+        );
+    }
+
+    #[test]
+    fn test_bf_str_internal() {
+        let expanded = bf_str_internal(quote!(libbf, "+>-< comment [.,]")).unwrap();
+        let expected = quote! {
+            libbf::program::Program::new([
+                libbf::program::Instruction::DAdd(1isize),
+                libbf::program::Instruction::PAdd(1isize),
+                libbf::program::Instruction::DAdd(-1isize),
+                libbf::program::Instruction::PAdd(-1isize),
+                libbf::program::Instruction::UntilZero(::std::vec![
+                    libbf::program::Instruction::Output,
+                    libbf::program::Instruction::Input
+                ])
+            ])
+        };
+        assert_eq!(expanded.to_string(), expected.to_string());
+        assert!(bf_str_internal(quote!(libbf, "]")).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Document analysis for language servers.
+//!
+//! [`analyze`] parses a source once and collects everything a language server needs for it:
+//! diagnostics, semantic tokens, folding ranges, bracket pairs and document symbols.
+//! Every range has both the position in Unicode scalar units and the offset in UTF-16 code units,
+//! which the Language Server Protocol uses by default.
+//!
+//! # Examples
+//!
+//! ```
+//! use libbf::{analysis, parser::Parser, token::simple::SimpleTokenSpec};
+//!
+//! let parser = Parser::new(
+//!     SimpleTokenSpec {
+//!         ptr_inc: '>',
+//!         ptr_dec: '<',
+//!         data_inc: '+',
+//!         data_dec: '-',
+//!         output: '.',
+//!         input: ',',
+//!         loop_head: '[',
+//!         loop_tail: ']',
+//!     }
+//!     .to_tokenizer(),
+//! );
+//! let analysis = analysis::analyze("😀+[->+<]", &parser);
+//!
+//! assert!(analysis.diagnostics.is_empty());
+//! assert_eq!(analysis.folding_ranges[0].chars, 2..8);
+//! assert_eq!(analysis.folding_ranges[0].utf16, 3..9);
+//! ```
+use std::ops::Range;
+
+use crate::{
+    parser::Parser,
+    program::{FatInstruction, SourceToken},
+    token::{TokenStream, TokenType, Tokenizer},
+};
+
+/// A range of a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceRange {
+    /// The range in Unicode scalar units.
+    pub chars: Range<usize>,
+    /// The range in UTF-16 code units.
+    pub utf16: Range<usize>,
+}
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The source cannot be parsed.
+    Error,
+    /// The source can be parsed, but probably has a mistake.
+    Warning,
+}
+
+/// A diagnostic message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The severity.
+    pub severity: Severity,
+    /// The range which the diagnostic is about.
+    pub range: SourceRange,
+    /// The stable error code (see [`ErrorKind::code`](crate::error::ErrorKind::code)),
+    /// or `None` for warnings.
+    pub code: Option<&'static str>,
+    /// The message.
+    pub message: String,
+}
+
+/// A token for semantic highlighting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SemanticToken {
+    /// The range of the token.
+    pub range: SourceRange,
+    /// The token type.
+    pub token_type: TokenType,
+}
+
+/// A pair of a loop head and its tail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketPair {
+    /// The range of the loop head.
+    pub head: SourceRange,
+    /// The range of the loop tail.
+    pub tail: SourceRange,
+}
+
+/// A document symbol, which is a top-level loop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentSymbol {
+    /// The name of the symbol (e.g. `loop #1`).
+    pub name: String,
+    /// The range from the loop head to the loop tail.
+    pub range: SourceRange,
+}
+
+/// The result of [`analyze`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DocumentAnalysis {
+    /// Errors and warnings in source order.
+    ///
+    /// If the source cannot be parsed, this has only the parse error.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The tokens in source order.
+    ///
+    /// If the source cannot be parsed, this still has the tokens up to a tokenize error if any.
+    pub semantic_tokens: Vec<SemanticToken>,
+    /// The ranges from each loop head to its tail, in the order of the loop heads.
+    pub folding_ranges: Vec<SourceRange>,
+    /// The bracket pairs, in the order of the loop heads.
+    pub bracket_pairs: Vec<BracketPair>,
+    /// The top-level loops in source order.
+    pub symbols: Vec<DocumentSymbol>,
+}
+
+impl DocumentAnalysis {
+    /// Find the bracket which matches the bracket at `pos_in_chars`.
+    ///
+    /// Returns the range of the loop tail if `pos_in_chars` is in a loop head, and vice versa.
+    pub fn matching_bracket(&self, pos_in_chars: usize) -> Option<&SourceRange> {
+        self.bracket_pairs.iter().find_map(|pair| {
+            if pair.head.chars.contains(&pos_in_chars) {
+                Some(&pair.tail)
+            } else if pair.tail.chars.contains(&pos_in_chars) {
+                Some(&pair.head)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Analyze `source` parsed by `parser`.
+///
+/// The following warnings are reported:
+///
+/// - a run of increments/decrements which cancels out (e.g. `+-`)
+/// - an empty loop (`[]`), which never ends once entered
+pub fn analyze<T>(source: &str, parser: &Parser<T>) -> DocumentAnalysis
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    let offsets = Utf16Offsets::new(source);
+    let mut analysis = DocumentAnalysis::default();
+
+    let fat = match parser.parse_fat_str(source) {
+        Ok(fat) => fat,
+        Err(e) => {
+            let pos = e.pos_in_chars();
+            analysis.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                range: offsets.range(pos..(pos + 1).min(offsets.len_in_chars())),
+                code: Some(e.kind().code()),
+                message: e.to_string(),
+            });
+            // Highlight the tokens anyway, since the source is usually being edited.
+            let mut stream = parser.tokenizer().token_stream(source);
+            while let Ok(info) = stream.next() {
+                let Some(token) = info.token else {
+                    break;
+                };
+                let len = token.token_str.chars().count();
+                analysis.semantic_tokens.push(SemanticToken {
+                    range: offsets.range(info.pos_in_chars..info.pos_in_chars + len),
+                    token_type: token.token_type,
+                });
+            }
+            return analysis;
+        }
+    };
+
+    let mut loops = 0;
+    for inst in fat.instructions() {
+        if let FatInstruction::UntilZero { head, tail, .. } = inst {
+            loops += 1;
+            analysis.symbols.push(DocumentSymbol {
+                name: format!("loop #{loops}"),
+                range: offsets.range(head.pos_in_chars..end(tail)),
+            });
+        }
+        collect(inst, &offsets, &mut analysis);
+    }
+    analysis
+        .semantic_tokens
+        .sort_by_key(|token| token.range.chars.start);
+    analysis
+        .diagnostics
+        .sort_by_key(|diagnostic| diagnostic.range.chars.start);
+    analysis
+}
+
+fn collect(inst: &FatInstruction, offsets: &Utf16Offsets, analysis: &mut DocumentAnalysis) {
+    let push_token = |token: &SourceToken, analysis: &mut DocumentAnalysis| {
+        analysis.semantic_tokens.push(SemanticToken {
+            range: offsets.range(token.pos_in_chars..end(token)),
+            token_type: token.token_type,
+        });
+    };
+    match inst {
+        FatInstruction::PAdd(operand, run) | FatInstruction::DAdd(operand, run) => {
+            run.iter().for_each(|token| push_token(token, analysis));
+            if *operand == 0 {
+                analysis.diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    range: offsets.range(run[0].pos_in_chars..end(&run[run.len() - 1])),
+                    code: None,
+                    message: "these increments and decrements cancel out".to_string(),
+                });
+            }
+        }
+        FatInstruction::Output(token)
+        | FatInstruction::Input(token)
+        | FatInstruction::Breakpoint(token)
+        | FatInstruction::AssertCell(_, token) => push_token(token, analysis),
+        FatInstruction::UntilZero { head, body, tail } => {
+            push_token(head, analysis);
+            push_token(tail, analysis);
+            let head_range = offsets.range(head.pos_in_chars..end(head));
+            let tail_range = offsets.range(tail.pos_in_chars..end(tail));
+            analysis
+                .folding_ranges
+                .push(offsets.range(head.pos_in_chars..end(tail)));
+            analysis.bracket_pairs.push(BracketPair {
+                head: head_range,
+                tail: tail_range,
+            });
+            if body.is_empty() {
+                analysis.diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    range: offsets.range(head.pos_in_chars..end(tail)),
+                    code: None,
+                    message: "this loop never ends once entered".to_string(),
+                });
+            }
+            body.iter()
+                .for_each(|inst| collect(inst, offsets, analysis));
+        }
+    }
+}
+
+// The end position of a token in Unicode scalar units.
+fn end(token: &SourceToken) -> usize {
+    token.pos_in_chars + token.token_str.chars().count()
+}
+
+// The UTF-16 offset of each character position.
+struct Utf16Offsets(Vec<usize>);
+
+impl Utf16Offsets {
+    fn new(source: &str) -> Self {
+        let mut offsets = Vec::with_capacity(source.len() + 1);
+        let mut offset = 0;
+        offsets.push(0);
+        for c in source.chars() {
+            offset += c.len_utf16();
+            offsets.push(offset);
+        }
+        Self(offsets)
+    }
+
+    fn len_in_chars(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    fn range(&self, chars: Range<usize>) -> SourceRange {
+        SourceRange {
+            utf16: self.0[chars.start]..self.0[chars.end],
+            chars,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_parser;
+
+    fn chars(ranges: impl IntoIterator<Item = SourceRange>) -> Vec<Range<usize>> {
+        ranges.into_iter().map(|range| range.chars).collect()
+    }
+
+    #[test]
+    fn test_analyze_nested() {
+        // 0123456789012345678
+        // +[>[-]<-]𝄞[[]+-]
+        let source = "+[>[-]<-]𝄞[[]+-]";
+        let analysis = analyze(source, &bf_parser());
+
+        assert_eq!(
+            chars(analysis.folding_ranges.clone()),
+            [1..9, 3..6, 10..16, 11..13]
+        );
+        assert_eq!(analysis.folding_ranges[2].utf16, 11..17);
+        let pairs = analysis
+            .bracket_pairs
+            .iter()
+            .map(|pair| (pair.head.chars.start, pair.tail.chars.start))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, [(1, 8), (3, 5), (10, 15), (11, 12)]);
+        assert_eq!(analysis.matching_bracket(5).unwrap().chars, 3..4);
+        assert_eq!(analysis.matching_bracket(10).unwrap().chars, 15..16);
+        assert!(analysis.matching_bracket(2).is_none());
+
+        let symbols = analysis
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.range.chars.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, [("loop #1", 1..9), ("loop #2", 10..16)]);
+
+        assert_eq!(analysis.semantic_tokens.len(), 15);
+        assert!(analysis
+            .semantic_tokens
+            .windows(2)
+            .all(|pair| pair[0].range.chars.start < pair[1].range.chars.start));
+        assert_eq!(analysis.semantic_tokens[9].token_type, TokenType::LoopHead);
+        assert_eq!(analysis.semantic_tokens[9].range.utf16, 11..12);
+
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.range.chars.clone(), d.code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                (Severity::Warning, 11..13, None),
+                (Severity::Warning, 13..15, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_error() {
+        let analysis = analyze("+[-]x]>", &bf_parser());
+        assert_eq!(analysis.diagnostics.len(), 1);
+        let diagnostic = &analysis.diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.range.chars, 5..6);
+        assert_eq!(diagnostic.code, Some("E0002"));
+        assert_eq!(analysis.semantic_tokens.len(), 6);
+        assert!(analysis.folding_ranges.is_empty());
+
+        let analysis = analyze("[", &bf_parser());
+        assert_eq!(analysis.diagnostics[0].range.chars, 1..1);
+        assert_eq!(analysis.diagnostics[0].code, Some("E0001"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Command line interface of libbf.
+//!
+//! This binary is built when feature `cli` is enabled.
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+use libbf::{
+    error::{ParseError, RuntimeError},
+    fmt::FmtOptions,
+    predefined::Dialect,
+    program::Program,
+    runtime::{Runner, RuntimeConfig, StepRunner},
+};
+
+const USAGE: &str = "\
+usage: bf run FILE [--dialect DIALECT] [--memsize N|right-inf|both-inf|bounded:L:R] [--max-steps N]
+       bf check FILE [--dialect DIALECT] [--memsize N|right-inf|both-inf|bounded:L:R]
+       bf fmt FILE [--dialect DIALECT] [--width N] [--indent N] [--group N] [--align]
+
+DIALECT is one of: bf (default), ook, asm";
+
+// An error which terminates the command.
+enum CliError {
+    Usage(String),
+    Failure(String),
+}
+
+// A subcommand.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Run,
+    Check,
+    Fmt,
+}
+
+// The token separator of a dialect for formatting.
+fn token_separator(dialect: Dialect) -> Result<&'static str, CliError> {
+    match dialect {
+        Dialect::Bf => Ok(""),
+        Dialect::Ook => Ok(" "),
+        // a mnemonic with a count is a source string of several tokens.
+        Dialect::Asm => Err(CliError::Usage(
+            "dialect `asm` cannot be formatted".to_string(),
+        )),
+    }
+}
+
+struct Options {
+    file: String,
+    dialect: Dialect,
+    config: RuntimeConfig,
+    max_steps: Option<u64>,
+    fmt: FmtOptions,
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match run_command(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(CliError::Failure(message)) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_command(args: &[String]) -> Result<(), CliError> {
+    match args.split_first() {
+        Some((command, rest)) if command == "run" => run(&parse_options(rest, Command::Run)?),
+        Some((command, rest)) if command == "check" => check(&parse_options(rest, Command::Check)?),
+        Some((command, rest)) if command == "fmt" => fmt(&parse_options(rest, Command::Fmt)?),
+        Some((command, _)) => Err(CliError::Usage(format!("unknown command `{command}`"))),
+        None => Err(CliError::Usage("no command is given".to_string())),
+    }
+}
+
+fn parse_options(args: &[String], command: Command) -> Result<Options, CliError> {
+    let mut file = None;
+    let mut dialect = Dialect::Bf;
+    let mut config = RuntimeConfig::default();
+    let mut max_steps = None;
+    let mut fmt = FmtOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| CliError::Usage(format!("`{name}` requires a value")))
+        };
+        match arg.as_str() {
+            "--dialect" => {
+                dialect = match value(arg)?.as_str() {
+                    "bf" => Dialect::Bf,
+                    "ook" => Dialect::Ook,
+                    "asm" => Dialect::Asm,
+                    other => return Err(CliError::Usage(format!("unknown dialect `{other}`"))),
+                }
+            }
+            "--memsize" if command != Command::Fmt => {
+                config.memsize = value(arg)?
+                    .parse()
+                    .map_err(|e| CliError::Usage(format!("`{arg}`: {e}")))?
+            }
+            "--max-steps" if command == Command::Run => {
+                max_steps = Some(parse_number(arg, value(arg)?)?)
+            }
+            "--width" if command == Command::Fmt => fmt.max_width = parse_number(arg, value(arg)?)?,
+            "--indent" if command == Command::Fmt => {
+                fmt.indent_width = parse_number(arg, value(arg)?)?
+            }
+            "--group" if command == Command::Fmt => {
+                fmt.group_size = Some(parse_number(arg, value(arg)?)?)
+            }
+            "--align" if command == Command::Fmt => fmt.align_brackets = true,
+            option if option.starts_with("--") => {
+                return Err(CliError::Usage(format!("unknown option `{option}`")))
+            }
+            path if file.is_none() => file = Some(path.to_string()),
+            extra => return Err(CliError::Usage(format!("unexpected argument `{extra}`"))),
+        }
+    }
+
+    if command == Command::Fmt {
+        fmt.token_separator = token_separator(dialect)?.to_string();
+    }
+    Ok(Options {
+        file: file.ok_or_else(|| CliError::Usage("no file is given".to_string()))?,
+        dialect,
+        config,
+        max_steps,
+        fmt,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::Usage(format!("invalid value `{value}` for `{name}`")))
+}
+
+// Read the file.
+fn read_source(options: &Options) -> Result<String, CliError> {
+    fs::read_to_string(&options.file)
+        .map_err(|e| CliError::Failure(format!("error: failed to read {}: {e}", options.file)))
+}
+
+// Render a parse error with its source context.
+fn parse_failure(options: &Options, source: &str, e: ParseError) -> CliError {
+    // "error: ..." and " --> line:column" of the rendered error get the code and the file.
+    let rendered = e
+        .render(source)
+        .replacen("error:", &format!("error[{}]:", e.kind().code()), 1)
+        .replacen("--> ", &format!("--> {}:", options.file), 1);
+    CliError::Failure(rendered)
+}
+
+// Read and parse the file.
+fn load(options: &Options) -> Result<Program, CliError> {
+    let source = read_source(options)?;
+    options
+        .dialect
+        .parse_str(&source)
+        .map_err(|e| parse_failure(options, &source, e))
+}
+
+fn run(options: &Options) -> Result<(), CliError> {
+    let program = load(options)?;
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+    let config = options.config.clone();
+    let result = match options.max_steps {
+        None => Runner::with_config(&program, io::stdin(), &mut output, config)
+            .run()
+            .map_err(runtime_failure),
+        Some(max_steps) => run_steps(&program, &mut output, config, max_steps),
+    };
+    let flushed = output
+        .flush()
+        .map_err(|e| CliError::Failure(format!("error: failed to write output: {e}")));
+    result.and(flushed)
+}
+
+fn run_steps(
+    program: &Program,
+    output: impl Write,
+    config: RuntimeConfig,
+    max_steps: u64,
+) -> Result<(), CliError> {
+    let mut runner = StepRunner::with_config(program, io::stdin(), output, config);
+    for _ in 0..max_steps {
+        if !runner.is_running() {
+            return Ok(());
+        }
+        runner.step().map_err(runtime_failure)?;
+    }
+    if runner.is_running() {
+        Err(CliError::Failure(format!(
+            "error: the program did not finish in {max_steps} steps"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn runtime_failure(e: RuntimeError) -> CliError {
+    CliError::Failure(format!("error[{}]: {e}", e.kind().code()))
+}
+
+fn check(options: &Options) -> Result<(), CliError> {
+    let program = load(options)?;
+    // the validation is best-effort, so its findings do not fail the check.
+    if let Err(e) = program.validate_runnable(options.config.memsize) {
+        eprintln!("warning[{}]: {}: {e}", e.kind().code(), options.file);
+    }
+    println!("{}: ok", options.file);
+    Ok(())
+}
+
+fn fmt(options: &Options) -> Result<(), CliError> {
+    let source = read_source(options)?;
+    let fat = options
+        .dialect
+        .parse_fat_str(&source)
+        .map_err(|e| parse_failure(options, &source, e))?;
+    print!("{}", libbf::fmt::format(&fat, &options.fmt));
+    Ok(())
+}
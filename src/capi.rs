@@ -0,0 +1,458 @@
+//! C API.
+//!
+//! This module is enabled when feature `capi` is enabled.
+//! It exposes `extern "C"` functions to embed the interpreter into C programs.
+//! The declarations for C are in `include/libbf.h`.
+//!
+//! To build a shared library, build this crate as `cdylib` with the feature enabled, e.g.
+//!
+//! ```sh
+//! cargo rustc --release --features capi --crate-type cdylib
+//! ```
+//!
+//! # Conventions
+//!
+//!  - Functions which can fail return one of the `BF_*` status codes. [`BF_OK`] means success.
+//!  - On failure, [`bf_last_error_message`] returns a message describing the error.
+//!  - No function unwinds into C. A panic is caught and reported as [`BF_ERR_PANIC`].
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CString},
+    io::{self, Read, Write},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    error::{ErrorKind, ParseError, RuntimeError},
+    parser::Parser,
+    predefined::bf,
+    program::Program,
+    runtime::{self, MemorySize, RuntimeConfig},
+    token::simple::SimpleTokenizer,
+};
+
+/// Success.
+pub const BF_OK: c_int = 0;
+/// An argument is invalid (e.g. a null pointer or a source which is not UTF-8).
+pub const BF_ERR_INVALID_ARGUMENT: c_int = 1;
+/// The source has a syntax error.
+pub const BF_ERR_PARSE: c_int = 2;
+/// The program failed while running.
+pub const BF_ERR_RUNTIME: c_int = 3;
+/// A panic occurred in the library.
+pub const BF_ERR_PANIC: c_int = 4;
+
+/// [`BfRunConfig::memsize_kind`] for [`MemorySize::Fixed`].
+pub const BF_MEMSIZE_FIXED: c_int = 0;
+/// [`BfRunConfig::memsize_kind`] for [`MemorySize::RightInfinite`].
+pub const BF_MEMSIZE_RIGHT_INFINITE: c_int = 1;
+/// [`BfRunConfig::memsize_kind`] for [`MemorySize::BothInfinite`].
+pub const BF_MEMSIZE_BOTH_INFINITE: c_int = 2;
+
+/// An opaque Brainfuck parser.
+pub struct BfParser(Parser<SimpleTokenizer>);
+
+/// An opaque parsed program.
+pub struct BfProgram(Program);
+
+/// Details of an error.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BfErrorInfo {
+    /// The numeric part of the stable error code (e.g. `1` for `E0001`, see [`ErrorKind::code`]).
+    pub code: u32,
+    /// The position where a parse error occurred in Unicode scalar units. 0 for other errors.
+    pub pos_in_chars: usize,
+}
+
+/// A runtime configuration.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BfRunConfig {
+    /// One of `BF_MEMSIZE_*`.
+    pub memsize_kind: c_int,
+    /// The memory size for [`BF_MEMSIZE_FIXED`]. Ignored for other kinds.
+    pub memsize: usize,
+}
+
+/// An input callback.
+///
+/// Returns the next byte (0 to 255), -1 for end-of-file or other negative values for errors.
+pub type BfInputCallback = unsafe extern "C" fn(userdata: *mut c_void) -> c_int;
+
+/// An output callback.
+///
+/// Returns 0 on success or non-zero values for errors.
+pub type BfOutputCallback = unsafe extern "C" fn(userdata: *mut c_void, byte: u8) -> c_int;
+
+thread_local! {
+    static LAST_ERROR_MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<Vec<u8>>) {
+    let mut message = message.into();
+    message.retain(|&b| b != 0);
+    let message = CString::new(message).expect("NUL bytes are removed");
+    LAST_ERROR_MESSAGE.with(|m| *m.borrow_mut() = Some(message));
+}
+
+// Run `f` and convert a panic into `BF_ERR_PANIC`.
+fn guard(f: impl FnOnce() -> c_int) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(status) => status,
+        Err(payload) => {
+            set_last_error(format!("panic: {}", panic_message(&payload)));
+            BF_ERR_PANIC
+        }
+    }
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown"
+    }
+}
+
+fn error_info(kind: ErrorKind, pos_in_chars: usize) -> BfErrorInfo {
+    BfErrorInfo {
+        code: kind.code()[1..]
+            .parse()
+            .expect("error codes are `E` and digits"),
+        pos_in_chars,
+    }
+}
+
+/// Get the message of the last error which occurred on the calling thread.
+///
+/// Returns null if no error has occurred. The returned string is valid until
+/// the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn bf_last_error_message() -> *const c_char {
+    LAST_ERROR_MESSAGE.with(|m| m.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Create a new Brainfuck parser.
+///
+/// Returns null if a panic occurred. The parser must be freed by [`bf_parser_free`].
+#[no_mangle]
+pub extern "C" fn bf_parser_new_bf() -> *mut BfParser {
+    match panic::catch_unwind(|| Box::new(BfParser(bf::parser()))) {
+        Ok(parser) => Box::into_raw(parser),
+        Err(payload) => {
+            set_last_error(format!("panic: {}", panic_message(&payload)));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a parser created by [`bf_parser_new_bf`].
+///
+/// # Safety
+///
+/// `parser` must be null or a pointer returned by [`bf_parser_new_bf`] which is not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_parser_free(parser: *mut BfParser) {
+    if !parser.is_null() {
+        drop(Box::from_raw(parser));
+    }
+}
+
+/// Parse `len` bytes of UTF-8 source at `src`.
+///
+/// On success, stores a new program to `*out_program`, which must be freed by [`bf_program_free`].
+/// On failure, stores the details to `*out_err` if `out_err` is not null.
+///
+/// # Safety
+///
+///  - `parser` must be a valid parser.
+///  - `src` must point to `len` readable bytes (it may be null if `len` is 0).
+///  - `out_program` must be a valid pointer to write.
+///  - `out_err` must be null or a valid pointer to write.
+#[no_mangle]
+pub unsafe extern "C" fn bf_parse(
+    parser: *const BfParser,
+    src: *const c_char,
+    len: usize,
+    out_program: *mut *mut BfProgram,
+    out_err: *mut BfErrorInfo,
+) -> c_int {
+    guard(|| {
+        if parser.is_null() || out_program.is_null() || (src.is_null() && len > 0) {
+            set_last_error("null pointer argument");
+            return BF_ERR_INVALID_ARGUMENT;
+        }
+        let bytes = if len == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(src as *const u8, len)
+        };
+        let source = match std::str::from_utf8(bytes) {
+            Ok(source) => source,
+            Err(e) => {
+                set_last_error(format!("source is not UTF-8: {e}"));
+                return BF_ERR_INVALID_ARGUMENT;
+            }
+        };
+        match (*parser).0.parse_str(source) {
+            Ok(program) => {
+                *out_program = Box::into_raw(Box::new(BfProgram(program)));
+                BF_OK
+            }
+            Err(e) => {
+                report_parse_error(&e, out_err);
+                BF_ERR_PARSE
+            }
+        }
+    })
+}
+
+unsafe fn report_parse_error(error: &ParseError, out_err: *mut BfErrorInfo) {
+    set_last_error(error.to_string());
+    if !out_err.is_null() {
+        *out_err = error_info(error.kind(), error.pos_in_chars());
+    }
+}
+
+/// Free a program created by [`bf_parse`].
+///
+/// # Safety
+///
+/// `program` must be null or a pointer returned by [`bf_parse`] which is not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bf_program_free(program: *mut BfProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+struct CallbackInput {
+    callback: BfInputCallback,
+    userdata: *mut c_void,
+}
+
+impl Read for CallbackInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match unsafe { (self.callback)(self.userdata) } {
+            -1 => Ok(0),
+            byte @ 0..=255 => {
+                buf[0] = byte as u8;
+                Ok(1)
+            }
+            status => Err(io::Error::other(format!(
+                "input callback failed with {status}"
+            ))),
+        }
+    }
+}
+
+struct CallbackOutput {
+    callback: BfOutputCallback,
+    userdata: *mut c_void,
+}
+
+impl Write for CallbackOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            let status = unsafe { (self.callback)(self.userdata, byte) };
+            if status != 0 {
+                return Err(io::Error::other(format!(
+                    "output callback failed with {status}"
+                )));
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run a program.
+///
+/// Input and output are performed through `in_cb` and `out_cb` with `userdata`.
+/// If `config` is null, the default configuration is used.
+/// On failure, stores the details to `*out_err` if `out_err` is not null.
+///
+/// # Safety
+///
+///  - `program` must be a valid program.
+///  - `in_cb` and `out_cb` must be safe to call with `userdata`.
+///  - `config` must be null or a valid pointer to read.
+///  - `out_err` must be null or a valid pointer to write.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run(
+    program: *const BfProgram,
+    in_cb: Option<BfInputCallback>,
+    out_cb: Option<BfOutputCallback>,
+    userdata: *mut c_void,
+    config: *const BfRunConfig,
+    out_err: *mut BfErrorInfo,
+) -> c_int {
+    guard(|| {
+        let (Some(in_cb), Some(out_cb)) = (in_cb, out_cb) else {
+            set_last_error("null callback argument");
+            return BF_ERR_INVALID_ARGUMENT;
+        };
+        if program.is_null() {
+            set_last_error("null pointer argument");
+            return BF_ERR_INVALID_ARGUMENT;
+        }
+        let mut runtime_config = RuntimeConfig::default();
+        if !config.is_null() {
+            runtime_config.memsize = match ((*config).memsize_kind, (*config).memsize) {
+                (BF_MEMSIZE_FIXED, len) => MemorySize::Fixed(len),
+                (BF_MEMSIZE_RIGHT_INFINITE, _) => MemorySize::RightInfinite,
+                (BF_MEMSIZE_BOTH_INFINITE, _) => MemorySize::BothInfinite,
+                (kind, _) => {
+                    set_last_error(format!("unknown memory size kind {kind}"));
+                    return BF_ERR_INVALID_ARGUMENT;
+                }
+            };
+        }
+        let input = CallbackInput {
+            callback: in_cb,
+            userdata,
+        };
+        let output = CallbackOutput {
+            callback: out_cb,
+            userdata,
+        };
+        match runtime::run_with_config(&(*program).0, input, output, runtime_config) {
+            Ok(()) => BF_OK,
+            Err(e) => {
+                report_runtime_error(&e, out_err);
+                BF_ERR_RUNTIME
+            }
+        }
+    })
+}
+
+unsafe fn report_runtime_error(error: &RuntimeError, out_err: *mut BfErrorInfo) {
+    set_last_error(error.to_string());
+    if !out_err.is_null() {
+        *out_err = error_info(error.kind(), 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    // The userdata of the test callbacks.
+    struct Io {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    unsafe extern "C" fn read_byte(userdata: *mut c_void) -> c_int {
+        let io = &mut *(userdata as *mut Io);
+        if io.input.is_empty() {
+            -1
+        } else {
+            io.input.remove(0) as c_int
+        }
+    }
+
+    unsafe extern "C" fn write_byte(userdata: *mut c_void, byte: u8) -> c_int {
+        let io = &mut *(userdata as *mut Io);
+        io.output.push(byte);
+        0
+    }
+
+    unsafe extern "C" fn fail_write(_userdata: *mut c_void, _byte: u8) -> c_int {
+        -1
+    }
+
+    unsafe fn parse(parser: *const BfParser, src: &str) -> (c_int, *mut BfProgram, BfErrorInfo) {
+        let mut program = ptr::null_mut();
+        let mut err = BfErrorInfo::default();
+        let status = bf_parse(
+            parser,
+            src.as_ptr() as *const c_char,
+            src.len(),
+            &mut program,
+            &mut err,
+        );
+        (status, program, err)
+    }
+
+    #[test]
+    fn test_capi_smoke() {
+        unsafe {
+            let parser = bf_parser_new_bf();
+            assert!(!parser.is_null());
+
+            let (status, program, _) = parse(parser, ",[.,]");
+            assert_eq!(status, BF_OK);
+            let mut io = Io {
+                input: b"echo".to_vec(),
+                output: vec![],
+            };
+            let config = BfRunConfig {
+                memsize_kind: BF_MEMSIZE_FIXED,
+                memsize: 1,
+            };
+            let status = bf_run(
+                program,
+                Some(read_byte),
+                Some(write_byte),
+                &mut io as *mut Io as *mut c_void,
+                &config,
+                ptr::null_mut(),
+            );
+            assert_eq!(status, BF_ERR_RUNTIME);
+            assert_eq!(io.output, b"echo");
+            let message = CStr::from_ptr(bf_last_error_message());
+            assert_eq!(message.to_str().unwrap(), "detected EOF");
+
+            io.input = b"x".to_vec();
+            let mut err = BfErrorInfo::default();
+            let status = bf_run(
+                program,
+                Some(read_byte),
+                Some(fail_write),
+                &mut io as *mut Io as *mut c_void,
+                ptr::null(),
+                &mut err,
+            );
+            assert_eq!(status, BF_ERR_RUNTIME);
+            assert_eq!(err.code, 201);
+            bf_program_free(program);
+
+            let (status, program, err) = parse(parser, "+[>+");
+            assert_eq!(status, BF_ERR_PARSE);
+            assert!(program.is_null());
+            assert_eq!(
+                err,
+                BfErrorInfo {
+                    code: 1,
+                    pos_in_chars: 4
+                }
+            );
+
+            let (status, _, _) = parse(ptr::null(), "+");
+            assert_eq!(status, BF_ERR_INVALID_ARGUMENT);
+
+            bf_parser_free(parser);
+        }
+    }
+
+    #[test]
+    fn test_capi_catches_panic() {
+        let status = guard(|| panic!("boom"));
+        assert_eq!(status, BF_ERR_PANIC);
+        let message = unsafe { CStr::from_ptr(bf_last_error_message()) };
+        assert_eq!(message.to_str().unwrap(), "panic: boom");
+    }
+}
@@ -0,0 +1,289 @@
+//! Program generators.
+use std::collections::HashMap;
+
+use crate::program::{Instruction, Program};
+
+/// A strategy of [`print_program`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrintStrategy {
+    /// Use a single cell, adjusting it by the difference to each next byte.
+    Naive,
+    /// Initialize some cells to multiples of 10 by a loop, then print each byte from the cell
+    /// which is the cheapest to reach and adjust.
+    ///
+    /// This usually generates much shorter programs than [`PrintStrategy::Naive`]
+    /// except for very short outputs.
+    #[default]
+    Multiply,
+}
+
+/// The maximum number of cells initialized by [`PrintStrategy::Multiply`].
+const MAX_REGISTERS: usize = 8;
+/// The loop count of the initialization of [`PrintStrategy::Multiply`].
+const MULTIPLIER: u8 = 10;
+
+/// Generate a program which outputs `bytes`.
+///
+/// The program uses only pointer/data increments/decrements, outputs and loops,
+/// so it can be rendered in any Brainfuck-like language.
+/// It uses the cells at non-negative addresses only and reads no input.
+/// Cells are assumed to wrap around at 256.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{codegen::{print_program, PrintStrategy}, runtime};
+///
+/// let program = print_program(b"Hello World!\n", PrintStrategy::Multiply);
+///
+/// assert_eq!(runtime::run_collect(&program, &[][..]).unwrap(), b"Hello World!\n");
+/// ```
+pub fn print_program(bytes: &[u8], strategy: PrintStrategy) -> Program {
+    let instructions = match strategy {
+        PrintStrategy::Naive => print_from(bytes, &[0], vec![]),
+        PrintStrategy::Multiply => print_multiply(bytes),
+    };
+    Program::new(instructions)
+}
+
+/// Generate a program which sets the cells `0..bytes.len()` to `bytes` and moves the pointer back to 0.
+///
+/// This is equivalent to `memory_init_program_at(bytes, 0)`.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{codegen::memory_init_program, runtime::StepRunner};
+///
+/// let program = memory_init_program(b"libbf");
+/// let mut runner = StepRunner::new(&program, &[][..], vec![]);
+/// while runner.is_running() {
+///     runner.step().unwrap();
+/// }
+///
+/// assert_eq!(runner.get_pointer(), 0);
+/// assert_eq!(*runner.get_data_at_mut(4).unwrap(), b'f');
+/// ```
+pub fn memory_init_program(bytes: &[u8]) -> Program {
+    memory_init_program_at(bytes, 0)
+}
+
+/// Generate a program which sets the cells `offset..offset + bytes.len()` to `bytes`
+/// and moves the pointer back to 0.
+///
+/// The cells are assumed to be 0 and wrap around at 256. Values are built by a loop which adds
+/// a multiple of 10 to every cell at once, then each cell is adjusted by the remainder.
+/// The cell just after the range is used as the loop counter and is left 0.
+/// Other cells are not touched, so the program can be prepended to another program
+/// which expects the contents.
+pub fn memory_init_program_at(bytes: &[u8], offset: usize) -> Program {
+    // the multiplier and the remainder of each cell
+    let terms = bytes
+        .iter()
+        .map(|&byte| {
+            (-(u8::MAX as isize / MULTIPLIER as isize / 2)..=(u8::MAX / MULTIPLIER) as isize)
+                .map(|quotient| {
+                    let base = (quotient * MULTIPLIER as isize) as u8;
+                    (quotient, byte.wrapping_sub(base) as i8 as isize)
+                })
+                .min_by_key(|&(quotient, remainder)| quotient.abs() + remainder.abs())
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let counter = (offset + bytes.len()) as isize;
+    let mut instructions = vec![];
+    let mut pointer = 0;
+    let mut move_to = |instructions: &mut Vec<Instruction>, address: isize| {
+        if address != pointer {
+            instructions.push(Instruction::PAdd(address - pointer));
+            pointer = address;
+        }
+    };
+
+    if terms.iter().any(|&(quotient, _)| quotient != 0) {
+        move_to(&mut instructions, counter);
+        instructions.push(Instruction::DAdd(MULTIPLIER as isize));
+        let mut body = vec![];
+        for (i, &(quotient, _)) in terms.iter().enumerate() {
+            if quotient != 0 {
+                move_to(&mut body, (offset + i) as isize);
+                body.push(Instruction::DAdd(quotient));
+            }
+        }
+        move_to(&mut body, counter);
+        body.push(Instruction::DAdd(-1));
+        instructions.push(Instruction::UntilZero(body));
+    }
+    for (i, &(_, remainder)) in terms.iter().enumerate().rev() {
+        if remainder != 0 {
+            move_to(&mut instructions, (offset + i) as isize);
+            instructions.push(Instruction::DAdd(remainder));
+        }
+    }
+    move_to(&mut instructions, 0);
+    Program::new(instructions)
+}
+
+fn print_multiply(bytes: &[u8]) -> Vec<Instruction> {
+    if bytes.is_empty() {
+        return vec![];
+    }
+
+    // Choose the most frequent quotients of the bytes divided by the multiplier (rounded).
+    let mut frequencies = HashMap::new();
+    for (order, &byte) in bytes.iter().enumerate() {
+        let quotient = ((byte as u16 + MULTIPLIER as u16 / 2) / MULTIPLIER as u16) as u8;
+        frequencies.entry(quotient).or_insert((0, order)).0 += 1;
+    }
+    let mut quotients = frequencies.into_iter().collect::<Vec<_>>();
+    // more frequent first, then earlier first
+    quotients.sort_by_key(|&(_, (frequency, order))| (usize::MAX - frequency, order));
+    quotients.truncate(MAX_REGISTERS);
+    // in the order of first appearance
+    quotients.sort_by_key(|&(_, (_, order))| order);
+
+    // cell 0: loop counter, cell 1..: registers
+    let mut body = vec![];
+    for &(quotient, _) in &quotients {
+        body.push(Instruction::PAdd(1));
+        if quotient != 0 {
+            body.push(Instruction::DAdd(quotient as isize));
+        }
+    }
+    body.push(Instruction::PAdd(-(quotients.len() as isize)));
+    body.push(Instruction::DAdd(-1));
+    let instructions = vec![
+        Instruction::DAdd(MULTIPLIER as isize),
+        Instruction::UntilZero(body),
+    ];
+
+    let mut cells = vec![0];
+    cells.extend(
+        quotients
+            .iter()
+            .map(|&(quotient, _)| quotient.wrapping_mul(MULTIPLIER)),
+    );
+    print_from(bytes, &cells, instructions)
+}
+
+// Append instructions which output `bytes` to `instructions`, starting at cell 0 with `cells`.
+fn print_from(bytes: &[u8], cells: &[u8], mut instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut cells = cells.to_vec();
+    let mut pointer = 0;
+    for &byte in bytes {
+        let (_, target, delta) = cells
+            .iter()
+            .enumerate()
+            .map(|(address, &value)| {
+                let delta = byte.wrapping_sub(value) as i8 as isize;
+                let distance = address.abs_diff(pointer);
+                (distance + delta.unsigned_abs(), address, delta)
+            })
+            .min()
+            .unwrap();
+        if target != pointer {
+            instructions.push(Instruction::PAdd(target as isize - pointer as isize));
+            pointer = target;
+        }
+        if delta != 0 {
+            instructions.push(Instruction::DAdd(delta));
+        }
+        instructions.push(Instruction::Output);
+        cells[pointer] = byte;
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime;
+
+    // The length of the program in Brainfuck.
+    fn bf_len(instructions: &[Instruction]) -> usize {
+        instructions
+            .iter()
+            .map(|inst| match inst {
+                Instruction::PAdd(operand) | Instruction::DAdd(operand) => operand.unsigned_abs(),
+                Instruction::UntilZero(body) => bf_len(body) + 2,
+                _ => 1,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_print_program() {
+        let inputs: &[&[u8]] = &[
+            b"",
+            b"\0",
+            b"\xff",
+            b"\0\xff\0\xff\x80\x7f",
+            b"Hello World!\n",
+            b"zyxwvutsrqponmlkjihgfedcba",
+            &(0..=255).rev().collect::<Vec<u8>>(),
+            b"The quick brown fox jumps over the lazy dog. 0123456789 THE QUICK BROWN FOX",
+        ];
+        for &bytes in inputs {
+            for strategy in [PrintStrategy::Naive, PrintStrategy::Multiply] {
+                let program = print_program(bytes, strategy);
+                let output = runtime::run_collect(&program, &[][..]).unwrap();
+                assert_eq!(output, bytes, "{strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_memory_init_program() {
+        use crate::runtime::StepRunner;
+
+        let inputs: &[&[u8]] = &[
+            b"",
+            b"\0\0",
+            b"\xff\x00\x80\x7f\x01\xf6",
+            b"Hello World!\n",
+            &(0..=255).collect::<Vec<u8>>(),
+        ];
+        for &bytes in inputs {
+            for offset in [0, 3] {
+                let program = memory_init_program_at(bytes, offset);
+                // much shorter than setting each cell by increments/decrements
+                let naive = bytes
+                    .iter()
+                    .map(|&byte| byte.min(byte.wrapping_neg()) as usize + 1)
+                    .sum::<usize>();
+                if bytes.len() > 2 {
+                    assert!(bf_len(program.instructions()) * 2 < naive + 2 * offset);
+                }
+
+                let mut runner = StepRunner::new(&program, &[][..], vec![]);
+                while runner.is_running() {
+                    runner.step().unwrap();
+                }
+                let state = runner.fork_state();
+                assert_eq!(state.pointer(), 0);
+                let end = (offset + bytes.len()) as isize;
+                let tape = (0..end + 2)
+                    .map(|address| state.data_at(address).unwrap())
+                    .collect::<Vec<_>>();
+                let mut expected = vec![0; offset];
+                expected.extend_from_slice(bytes);
+                expected.extend([0, 0]);
+                assert_eq!(tape, expected, "{offset}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiply_is_shorter() {
+        let bytes = b"Hello World!\n";
+        let naive = print_program(bytes, PrintStrategy::Naive);
+        let multiply = print_program(bytes, PrintStrategy::Multiply);
+        assert!(
+            bf_len(multiply.instructions()) * 3 < bf_len(naive.instructions()) * 2,
+            "{} vs {}",
+            bf_len(multiply.instructions()),
+            bf_len(naive.instructions())
+        );
+    }
+}
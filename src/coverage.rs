@@ -0,0 +1,411 @@
+//! Source-level coverage.
+//!
+//! [`report`] combines a [`Profile`] recorded by [`Runner::run_profiled`](crate::runtime::Runner::run_profiled)
+//! with the source tokens of a [`FatProgram`], and tells which source tokens were executed.
+//!
+//! # Examples
+//!
+//! ```
+//! use libbf::{
+//!     coverage, parser::Parser, runtime::{Profile, Runner}, token::simple::SimpleTokenSpec,
+//! };
+//!
+//! let parser = Parser::new(
+//!     SimpleTokenSpec {
+//!         ptr_inc: '>',
+//!         ptr_dec: '<',
+//!         data_inc: '+',
+//!         data_dec: '-',
+//!         output: '.',
+//!         input: ',',
+//!         loop_head: '[',
+//!         loop_tail: ']',
+//!     }
+//!     .to_tokenizer(),
+//! );
+//! let source = "++[-]\n[>+<-] never entered\n";
+//! let fat = parser.parse_fat_str(source).unwrap();
+//! let program = fat.to_program();
+//! let mut profile = Profile::new();
+//! Runner::new(&program, &[][..], vec![]).run_profiled(&mut profile).unwrap();
+//!
+//! let report = coverage::report(&fat, &profile);
+//! assert_eq!(
+//!     report.render(source),
+//!     "\
+//! 2 | ++[-]
+//! 1 | [>+<-] never entered
+//!   |  ^^^^^
+//! ",
+//! );
+//! ```
+use std::fmt::Write;
+
+use crate::{
+    error::char_width,
+    program::{FatInstruction, FatProgram, ProgramIndex, SourceToken},
+    runtime::Profile,
+    token::TokenType,
+};
+
+/// The coverage of a source token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenCoverage {
+    /// The token type.
+    pub token_type: TokenType,
+    /// The position of the token in the source string which is counted in Unicode scalar units.
+    pub pos_in_chars: usize,
+    /// The length of the token string which is counted in Unicode scalar units.
+    pub len_in_chars: usize,
+    /// The number of times the token was executed.
+    pub count: u64,
+}
+
+impl TokenCoverage {
+    /// Returns `true` if the token was executed at least once.
+    pub fn is_covered(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// A coverage report of a source.
+///
+/// It is created by [`report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    tokens: Vec<TokenCoverage>,
+}
+
+/// Create a coverage report of `fat` from `profile`.
+///
+/// `profile` must be recorded by running `fat.to_program()`.
+/// The count of each token is determined as follows:
+///
+///  - A token of a pointer or data increment/decrement, an output or an input has
+///    the count of the instruction which it was built into.
+///  - A loop head has the number of times the loop was reached,
+///    and a loop tail has the number of times the loop body finished.
+///  - A run of increments/decrements whose sum is zero (e.g. `+-`) is a no-op which
+///    [`FatProgram::to_program`] drops. It has the count of the previous instruction in the same
+///    sequence, or of the next one if it is the first. If the sequence has no other instructions,
+///    it has the number of times the sequence was entered (for the top-level sequence,
+///    1 if the program ran at all).
+pub fn report(fat: &FatProgram, profile: &Profile) -> CoverageReport {
+    let mut tokens = Vec::new();
+    let entries = u64::from(profile.iter().next().is_some());
+    collect(
+        fat.instructions(),
+        profile,
+        &mut vec![],
+        entries,
+        &mut tokens,
+    );
+    CoverageReport { tokens }
+}
+
+impl CoverageReport {
+    /// Get the coverage of each token in source order.
+    pub fn tokens(&self) -> &[TokenCoverage] {
+        &self.tokens
+    }
+
+    /// Get an iterator over the tokens which were never executed.
+    pub fn uncovered(&self) -> impl Iterator<Item = &TokenCoverage> {
+        self.tokens.iter().filter(|token| !token.is_covered())
+    }
+
+    /// Render an annotated source listing.
+    ///
+    /// `source` must be the source string which the fat program was parsed from.
+    /// Each source line is preceded by a gutter with the maximum count of the tokens which start
+    /// in the line (blank if none). A line with unexecuted tokens is followed by a line which marks
+    /// them with `^`.
+    pub fn render(&self, source: &str) -> String {
+        let lines = self.lines(source);
+        let gutter = lines
+            .iter()
+            .filter_map(|line| line.count)
+            .max()
+            .map_or(1, |count| count.to_string().len());
+
+        let mut result = String::new();
+        for line in &lines {
+            let count = line.count.map_or(String::new(), |count| count.to_string());
+            writeln!(
+                result,
+                "{}",
+                format!("{count:>gutter$} | {}", line.text).trim_end()
+            )
+            .unwrap();
+            if line.uncovered.is_empty() {
+                continue;
+            }
+            let markers = line
+                .text
+                .chars()
+                .enumerate()
+                .map(|(column, c)| match c {
+                    _ if line.uncovered.contains(&column) => "^".repeat(char_width(c)),
+                    '\t' => "\t".to_string(),
+                    c => " ".repeat(char_width(c)),
+                })
+                .collect::<String>();
+            writeln!(result, "{:>gutter$} | {}", "", markers.trim_end()).unwrap();
+        }
+        result
+    }
+
+    /// Export the coverage as LCOV line records.
+    ///
+    /// `source` must be the source string which the fat program was parsed from, and
+    /// `source_file` is its file name. A line with tokens has a `DA` record with the maximum count
+    /// of the tokens which start in the line.
+    pub fn lcov(&self, source_file: &str, source: &str) -> String {
+        let lines = self.lines(source);
+        let mut result = format!("TN:\nSF:{source_file}\n");
+        let mut found = 0;
+        let mut hit = 0;
+        for (number, line) in lines.iter().enumerate() {
+            if let Some(count) = line.count {
+                writeln!(result, "DA:{},{count}", number + 1).unwrap();
+                found += 1;
+                if count > 0 {
+                    hit += 1;
+                }
+            }
+        }
+        write!(result, "LF:{found}\nLH:{hit}\nend_of_record\n").unwrap();
+        result
+    }
+
+    fn lines<'a>(&self, source: &'a str) -> Vec<Line<'a>> {
+        let source = source.strip_suffix('\n').unwrap_or(source);
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        for text in source.split('\n') {
+            lines.push(Line {
+                text: text.strip_suffix('\r').unwrap_or(text),
+                start: line_start,
+                count: None,
+                uncovered: Vec::new(),
+            });
+            line_start += text.chars().count() + 1;
+        }
+
+        for token in &self.tokens {
+            let number = lines.partition_point(|line| line.start <= token.pos_in_chars) - 1;
+            let line = &mut lines[number];
+            line.count = Some(
+                line.count
+                    .map_or(token.count, |count| count.max(token.count)),
+            );
+            if !token.is_covered() {
+                let column = token.pos_in_chars - line.start;
+                line.uncovered.extend(column..column + token.len_in_chars);
+            }
+        }
+        lines
+    }
+}
+
+// A source line.
+struct Line<'a> {
+    // the line without the line terminator.
+    text: &'a str,
+    // the position of the line in chars.
+    start: usize,
+    // the maximum count of the tokens which start in the line.
+    count: Option<u64>,
+    // the columns of unexecuted tokens.
+    uncovered: Vec<usize>,
+}
+
+fn collect(
+    instructions: &[FatInstruction],
+    profile: &Profile,
+    path: &mut Vec<usize>,
+    entries: u64,
+    tokens: &mut Vec<TokenCoverage>,
+) {
+    // the count of each instruction, and the index for loops. `None` for no-ops.
+    let mut counts = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+    for inst in instructions {
+        counts.push(match inst {
+            FatInstruction::PAdd(0, _) | FatInstruction::DAdd(0, _) => None,
+            FatInstruction::UntilZero { .. } => {
+                path.push(i);
+                let index = ProgramIndex::from_path(path.as_slice());
+                path.pop();
+                i += 1;
+                let reached = profile.count(&index) - profile.loop_entries(&index);
+                Some((reached, Some(index)))
+            }
+            _ => {
+                path.push(i);
+                let count = profile.count(&ProgramIndex::from_path(path.as_slice()));
+                path.pop();
+                i += 1;
+                Some((count, None))
+            }
+        });
+    }
+
+    let mut i = 0;
+    for (n, inst) in instructions.iter().enumerate() {
+        let count = match &counts[n] {
+            Some((count, _)) => *count,
+            None => counts[..n]
+                .iter()
+                .rev()
+                .chain(&counts[n + 1..])
+                .find_map(|count| count.as_ref().map(|(count, _)| *count))
+                .unwrap_or(entries),
+        };
+        match inst {
+            FatInstruction::PAdd(operand, run) | FatInstruction::DAdd(operand, run) => {
+                tokens.extend(run.iter().map(|token| token_coverage(token, count)));
+                if *operand != 0 {
+                    i += 1;
+                }
+            }
+            FatInstruction::Output(token)
+            | FatInstruction::Input(token)
+            | FatInstruction::Breakpoint(token)
+            | FatInstruction::AssertCell(_, token) => {
+                tokens.push(token_coverage(token, count));
+                i += 1;
+            }
+            FatInstruction::UntilZero { head, body, tail } => {
+                let index = counts[n].as_ref().and_then(|(_, index)| index.as_ref());
+                let entries = index.map_or(0, |index| profile.loop_entries(index));
+                tokens.push(token_coverage(head, count));
+                path.push(i);
+                collect(body, profile, path, entries, tokens);
+                path.pop();
+                tokens.push(token_coverage(tail, entries));
+                i += 1;
+            }
+        }
+    }
+}
+
+fn token_coverage(token: &SourceToken, count: u64) -> TokenCoverage {
+    TokenCoverage {
+        token_type: token.token_type,
+        pos_in_chars: token.pos_in_chars,
+        len_in_chars: token.token_str.chars().count(),
+        count,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::Parser, runtime::Runner, testing::bf_parser, token::simple::SimpleTokenizer,
+    };
+
+    fn parser() -> Parser<SimpleTokenizer> {
+        bf_parser()
+    }
+
+    const SOURCE: &str = "+[-]+-\n[\n  [-] unreachable\n]\n.\n";
+
+    fn run_report() -> CoverageReport {
+        let fat = parser().parse_fat_str(SOURCE).unwrap();
+        let program = fat.to_program();
+        let mut profile = Profile::new();
+        let mut output = vec![];
+        Runner::new(&program, &[][..], &mut output)
+            .run_profiled(&mut profile)
+            .unwrap();
+        report(&fat, &profile)
+    }
+
+    #[test]
+    fn test_report() {
+        let report = run_report();
+        let counts = report
+            .tokens()
+            .iter()
+            .map(|token| (token.pos_in_chars, token.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (3, 1),
+                // the no-op `+-` has the count of the previous loop.
+                (4, 1),
+                (5, 1),
+                (7, 1),
+                // the unreachable loop
+                (11, 0),
+                (12, 0),
+                (13, 0),
+                // the outer loop reached but its body never finished.
+                (27, 0),
+                (29, 1),
+            ]
+        );
+        assert_eq!(
+            report
+                .uncovered()
+                .map(|t| t.pos_in_chars)
+                .collect::<Vec<_>>(),
+            [11, 12, 13, 27]
+        );
+    }
+
+    #[test]
+    fn test_render_and_lcov() {
+        let report = run_report();
+        assert_eq!(
+            report.render(SOURCE),
+            "\
+1 | +[-]+-
+1 | [
+0 |   [-] unreachable
+  |   ^^^
+0 | ]
+  | ^
+1 | .
+"
+        );
+        assert_eq!(
+            report.lcov("test.bf", SOURCE),
+            "\
+TN:
+SF:test.bf
+DA:1,1
+DA:2,1
+DA:3,0
+DA:4,0
+DA:5,1
+LF:5
+LH:3
+end_of_record
+"
+        );
+    }
+
+    #[test]
+    fn test_report_no_ops_only() {
+        let fat = parser().parse_fat_str("+-[<>]").unwrap();
+        let program = fat.to_program();
+        let mut profile = Profile::new();
+        Runner::new(&program, &[][..], vec![])
+            .run_profiled(&mut profile)
+            .unwrap();
+        let counts = report(&fat, &profile)
+            .tokens()
+            .iter()
+            .map(|token| token.count)
+            .collect::<Vec<_>>();
+        // `<>` in the never entered loop has the number of times the loop body was entered.
+        assert_eq!(counts, [1, 1, 1, 0, 0, 0]);
+    }
+}
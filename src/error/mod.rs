@@ -1,21 +1,37 @@
 //! This module contains error definitions.
+mod render;
 
-use std::io;
+pub(crate) use self::render::char_width;
+
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use thiserror::Error;
 
+use crate::{program::ProgramIndex, runtime::MemorySize};
+
 /// A parse error.
 ///
 /// Each variant has the position where the error occurred in Uincode scalar units,
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
 pub enum ParseError {
     /// An error returned when a parser unexpectedly reached to "end of file".
     ///
     /// This error typically occurred when a loop was not closed.
-    #[error("{pos_in_chars}: Unexpected end-of-file")]
+    #[error(
+        "{pos_in_chars}: Unexpected end-of-file{}",
+        unclosed_loop_note(.loop_head_pos_in_chars)
+    )]
     UnexpectedEndOfFile {
         /// The position where the error occurred.
         pos_in_chars: usize,
+        /// The position of the head of the innermost loop which is not closed, if any.
+        loop_head_pos_in_chars: Option<usize>,
     },
 
     /// An error returned when a parser unexpectedly reached to an end-of-loop.
@@ -35,52 +51,624 @@ pub enum ParseError {
         /// Error details.
         message: String,
     },
+
+    /// An error returned when a run of increments/decrements exceeds the operand limit
+    /// (see [`OperandLimit::Error`](crate::parser::OperandLimit::Error)).
+    #[error("{pos_in_chars}: operand too large (limit: {limit})")]
+    OperandTooLarge {
+        /// The position of the token which exceeds the limit.
+        pos_in_chars: usize,
+        /// The limit of the operand magnitude.
+        limit: usize,
+    },
+}
+
+/// A kind of an error.
+///
+/// Unlike error types, whose variants may be added in future versions, each kind has a stable
+/// code (see [`ErrorKind::code`]) which can be used in documentation and machine-readable output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// [`ParseError::UnexpectedEndOfFile`]
+    UnexpectedEndOfFile,
+    /// [`ParseError::UnexpectedEndOfLoop`]
+    UnexpectedEndOfLoop,
+    /// [`ParseError::MiscError`]
+    MiscSyntaxError,
+    /// [`ParseOrIoError::IncludeCycle`]
+    IncludeCycle,
+    /// [`ParseError::OperandTooLarge`]
+    OperandTooLarge,
+    /// [`RuntimeError::OutOfMemoryBounds`]
+    OutOfMemoryBounds,
+    /// [`RuntimeError::Eof`]
+    Eof,
+    /// [`RuntimeError::InputNotReady`]
+    InputNotReady,
+    /// [`RuntimeError::Cancelled`]
+    Cancelled,
+    /// [`RuntimeError::OutputLimitExceeded`]
+    OutputLimitExceeded,
+    /// [`RuntimeError::AssertionFailed`]
+    AssertionFailed,
+    /// [`ParseOrIoError::IoError`] and [`RuntimeError::IoError`]
+    IoError,
+    /// [`ValidationError::PointerMoveOutOfBounds`]
+    PointerMoveOutOfBounds,
+    /// [`ValidationError::InfiniteLoop`]
+    InfiniteLoop,
+}
+
+impl ErrorKind {
+    /// Get the stable code of the kind.
+    ///
+    /// | code    | kind                                   |
+    /// |---------|----------------------------------------|
+    /// | `E0001` | [`ErrorKind::UnexpectedEndOfFile`]     |
+    /// | `E0002` | [`ErrorKind::UnexpectedEndOfLoop`]     |
+    /// | `E0003` | [`ErrorKind::MiscSyntaxError`]         |
+    /// | `E0004` | [`ErrorKind::IncludeCycle`]            |
+    /// | `E0005` | [`ErrorKind::OperandTooLarge`]         |
+    /// | `E0101` | [`ErrorKind::OutOfMemoryBounds`]       |
+    /// | `E0102` | [`ErrorKind::Eof`]                     |
+    /// | `E0103` | [`ErrorKind::InputNotReady`]           |
+    /// | `E0104` | [`ErrorKind::Cancelled`]               |
+    /// | `E0105` | [`ErrorKind::OutputLimitExceeded`]     |
+    /// | `E0106` | [`ErrorKind::AssertionFailed`]         |
+    /// | `E0201` | [`ErrorKind::IoError`]                 |
+    /// | `E0301` | [`ErrorKind::PointerMoveOutOfBounds`]  |
+    /// | `E0302` | [`ErrorKind::InfiniteLoop`]            |
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnexpectedEndOfFile => "E0001",
+            Self::UnexpectedEndOfLoop => "E0002",
+            Self::MiscSyntaxError => "E0003",
+            Self::IncludeCycle => "E0004",
+            Self::OperandTooLarge => "E0005",
+            Self::OutOfMemoryBounds => "E0101",
+            Self::Eof => "E0102",
+            Self::InputNotReady => "E0103",
+            Self::Cancelled => "E0104",
+            Self::OutputLimitExceeded => "E0105",
+            Self::AssertionFailed => "E0106",
+            Self::IoError => "E0201",
+            Self::PointerMoveOutOfBounds => "E0301",
+            Self::InfiniteLoop => "E0302",
+        }
+    }
+}
+
+impl ParseError {
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnexpectedEndOfFile { .. } => ErrorKind::UnexpectedEndOfFile,
+            Self::UnexpectedEndOfLoop { .. } => ErrorKind::UnexpectedEndOfLoop,
+            Self::MiscError { .. } => ErrorKind::MiscSyntaxError,
+            Self::OperandTooLarge { .. } => ErrorKind::OperandTooLarge,
+        }
+    }
+
+    /// Get the position where the error occurred in Unicode scalar units.
+    pub fn pos_in_chars(&self) -> usize {
+        match self {
+            Self::UnexpectedEndOfFile { pos_in_chars, .. }
+            | Self::UnexpectedEndOfLoop { pos_in_chars }
+            | Self::MiscError { pos_in_chars, .. }
+            | Self::OperandTooLarge { pos_in_chars, .. } => *pos_in_chars,
+        }
+    }
+
+    /// Get the byte range in `source` of the character where the error occurred.
+    ///
+    /// `source` must be the source string which was passed to the parser.
+    /// This is useful to integrate with diagnostic libraries which take byte offsets.
+    /// If the error occurred at the end of the source, the range is empty.
+    pub fn byte_span(&self, source: &str) -> Range<usize> {
+        match source.char_indices().nth(self.pos_in_chars()) {
+            Some((start, c)) => start..start + c.len_utf8(),
+            None => source.len()..source.len(),
+        }
+    }
+
+    // The error message without the position.
+    fn message(&self) -> String {
+        match self {
+            Self::UnexpectedEndOfFile { .. } => "Unexpected end-of-file".to_string(),
+            Self::UnexpectedEndOfLoop { .. } => "Unexpected end-of-loop".to_string(),
+            Self::MiscError { message, .. } => format!("syntax error: {message}"),
+            Self::OperandTooLarge { limit, .. } => format!("operand too large (limit: {limit})"),
+        }
+    }
+}
+
+fn unclosed_loop_note(loop_head_pos_in_chars: &Option<usize>) -> String {
+    match loop_head_pos_in_chars {
+        Some(pos) => format!(" (the loop at {pos} is not closed)"),
+        None => String::new(),
+    }
 }
 
 /// A parse Error or IO Error.
-#[derive(Debug, Error)]
+///
+/// This error is cloneable. The IO error is shared by [`Arc`] for that purpose.
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
 pub enum ParseOrIoError {
     // A parse error.
     #[error("{0}")]
     ParseError(#[from] ParseError),
 
-    // An IO error.
-    #[error("{0}")]
-    IoError(#[from] io::Error),
+    /// An IO error which occurred while reading the source.
+    #[error(
+        "failed reading source{} after {bytes_read} bytes: {error}",
+        source_path_note(.path)
+    )]
+    IoError {
+        /// The caused error.
+        #[source]
+        error: Arc<io::Error>,
+        /// The number of bytes which were read successfully before the error.
+        bytes_read: usize,
+        /// The path of the source file, if the source was read from a file.
+        path: Option<PathBuf>,
+    },
+
+    /// An error returned when source files include each other recursively.
+    ///
+    /// See [`parse_with_includes`](crate::parser::parse_with_includes).
+    #[error("include cycle detected: `{}` includes itself", .path.display())]
+    IncludeCycle {
+        /// The path of the file which was included recursively.
+        path: PathBuf,
+    },
+}
+
+impl ParseOrIoError {
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ParseError(e) => e.kind(),
+            Self::IoError { .. } => ErrorKind::IoError,
+            Self::IncludeCycle { .. } => ErrorKind::IncludeCycle,
+        }
+    }
+
+    // Attach the path of the source file to an IO error.
+    pub(crate) fn with_path(self, source_path: &Path) -> Self {
+        match self {
+            Self::IoError {
+                error, bytes_read, ..
+            } => Self::IoError {
+                error,
+                bytes_read,
+                path: Some(source_path.to_path_buf()),
+            },
+            e => e,
+        }
+    }
+}
+
+impl From<io::Error> for ParseOrIoError {
+    /// Converts into [`ParseOrIoError::IoError`] with no bytes read and no path.
+    fn from(error: io::Error) -> Self {
+        Self::IoError {
+            error: Arc::new(error),
+            bytes_read: 0,
+            path: None,
+        }
+    }
+}
+
+fn source_path_note(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!(" `{}`", path.display()),
+        None => String::new(),
+    }
 }
 
 /// A program runtime error.
-#[derive(Debug, Error)]
+///
+/// This error is cloneable. The IO error is shared by [`Arc`] for that purpose.
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
 pub enum RuntimeError {
     /// An error returned when a program accesses a momory that is out of range.
     ///
     /// An "access" occurs when a deta increment/decrement, input or output instruction is performed
-    /// and does not occur when the data pointer just points out of range.
-    #[error("out of memory bounds [{address}]")]
+    /// or a loop condition is checked, and does not occur when the data pointer just points out of range.
+    #[error(
+        "out of memory bounds [{address}] ({access}, valid range: {})",
+        valid_range(.memsize)
+    )]
     OutOfMemoryBounds {
         /// The address where the instruction tried to access.
         address: isize,
+        /// The memory size of the runtime.
+        memsize: MemorySize,
+        /// The kind of the access.
+        access: AccessKind,
     },
 
     /// An IO error.
     ///
     /// This error occurs when an input or output instruction is failed except end-of-file.
     #[error("IO error: {0}")]
-    IoError(#[from] io::Error),
+    IoError(#[source] Arc<io::Error>),
 
     /// An error returned when an input instruction detects an end-of-file.
     #[error("detected EOF")]
     Eof,
+
+    /// An error returned when the input is not ready yet
+    /// (i.e. reading it failed with [`io::ErrorKind::WouldBlock`]).
+    ///
+    /// This error is recoverable: the input instruction is not completed and can be retried later
+    /// (see [`WouldBlockPolicy`](crate::runtime::WouldBlockPolicy)).
+    #[error("input is not ready")]
+    InputNotReady,
+
+    /// An error returned when the run is cancelled by a [`CancelFlag`](crate::runtime::CancelFlag).
+    #[error("cancelled")]
+    Cancelled,
+
+    /// An error returned when a program outputs more bytes than the limit
+    /// (see [`run_to_capped_vec`](crate::runtime::run_to_capped_vec)).
+    #[error("output limit exceeded ({limit} bytes)")]
+    OutputLimitExceeded {
+        /// The limit in bytes.
+        limit: usize,
+        /// The output up to the limit.
+        output: Vec<u8>,
+    },
+
+    /// An error returned when an [`Instruction::AssertCell`](crate::program::Instruction::AssertCell)
+    /// finds an unexpected value.
+    #[error("assertion failed at [{address}]: expected {expected}, but got {actual}")]
+    AssertionFailed {
+        /// The address of the cell.
+        address: isize,
+        /// The expected value.
+        expected: u32,
+        /// The actual value.
+        actual: u32,
+    },
+}
+
+/// A kind of a memory access which causes [`RuntimeError::OutOfMemoryBounds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AccessKind {
+    /// A data access by data increment/decrement, input, output, etc.
+    Data,
+    /// A read of the current cell to check the condition of a loop.
+    LoopCondition,
+}
+
+impl std::fmt::Display for AccessKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Data => write!(f, "data access"),
+            Self::LoopCondition => write!(f, "loop condition read"),
+        }
+    }
+}
+
+fn valid_range(memsize: &MemorySize) -> String {
+    match memsize {
+        MemorySize::Fixed(len) => format!("[0, {len})"),
+        MemorySize::RightInfinite => "[0, +inf)".to_string(),
+        MemorySize::BothInfinite => "(-inf, +inf)".to_string(),
+        MemorySize::LeftRightBounded { left, right } => format!("[-{left}, {right})"),
+    }
+}
+
+impl RuntimeError {
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::OutOfMemoryBounds { .. } => ErrorKind::OutOfMemoryBounds,
+            Self::IoError(_) => ErrorKind::IoError,
+            Self::Eof => ErrorKind::Eof,
+            Self::InputNotReady => ErrorKind::InputNotReady,
+            Self::Cancelled => ErrorKind::Cancelled,
+            Self::OutputLimitExceeded { .. } => ErrorKind::OutputLimitExceeded,
+            Self::AssertionFailed { .. } => ErrorKind::AssertionFailed,
+        }
+    }
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(error: io::Error) -> Self {
+        Self::IoError(Arc::new(error))
+    }
+}
+
+/// A parse error or a runtime error, returned by functions which parse and run a program
+/// (e.g. [`run_source_with_inline_input`](crate::parser::run_source_with_inline_input)).
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
+pub enum ParseOrRuntimeError {
+    /// A parse error.
+    #[error("{0}")]
+    ParseError(#[from] ParseError),
+    /// A runtime error.
+    #[error("{0}")]
+    RuntimeError(#[from] RuntimeError),
+}
+
+impl ParseOrRuntimeError {
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ParseError(e) => e.kind(),
+            Self::RuntimeError(e) => e.kind(),
+        }
+    }
+}
+
+/// An error detected by [`Program::validate_runnable`](crate::program::Program::validate_runnable)
+/// without running the program.
+#[derive(Debug, Clone, Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// An error returned when a pointer move is so large that the pointer always goes out of range,
+    /// wherever in range it starts from.
+    #[error(
+        "{index}: pointer move {operand} exceeds the memory (valid range: {})",
+        valid_range(.memsize)
+    )]
+    PointerMoveOutOfBounds {
+        /// The index of the [`Instruction::PAdd`](crate::program::Instruction::PAdd) instruction.
+        index: ProgramIndex,
+        /// The operand of the instruction.
+        operand: isize,
+        /// The memory size which was validated against.
+        memsize: MemorySize,
+    },
+
+    /// An error returned when a loop never terminates once it is entered.
+    ///
+    /// This is detected on a best-effort basis: only loops whose body never changes
+    /// the memory or the pointer are detected.
+    #[error("{index}: loop never terminates once entered")]
+    InfiniteLoop {
+        /// The index of the [`Instruction::UntilZero`](crate::program::Instruction::UntilZero) instruction.
+        index: ProgramIndex,
+    },
+}
+
+impl ValidationError {
+    /// Get the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::PointerMoveOutOfBounds { .. } => ErrorKind::PointerMoveOutOfBounds,
+            Self::InfiniteLoop { .. } => ErrorKind::InfiniteLoop,
+        }
+    }
+}
+
+/// An error returned when parsing a [`ProgramIndex`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ParseIndexError {
+    /// The string has no positions.
+    #[error("empty program index")]
+    Empty,
+    /// A position is not a decimal number.
+    #[error("invalid program index segment `{segment}`")]
+    InvalidSegment {
+        /// The segment which is not a number.
+        segment: String,
+    },
+}
+
+/// An error returned when a stage of a [`pipeline`](crate::runtime::pipeline) fails.
+#[derive(Debug, Clone, Error)]
+#[error("stage {stage}: {error}")]
+pub struct PipelineError {
+    /// The index of the failed stage in the programs.
+    pub stage: usize,
+    /// The error of the stage.
+    #[source]
+    pub error: RuntimeError,
+}
+
+/// An error returned when parsing a [`MemorySize`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "invalid memory size `{input}` (expected `N`, `fixed:N`, `right-infinite`, `both-infinite` or `bounded:L:R`)"
+)]
+pub struct ParseMemorySizeError {
+    /// The string which failed to parse.
+    pub input: String,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn parse_error_byte_span() {
+        let source = "＋＋]";
+        assert_eq!(
+            ParseError::UnexpectedEndOfLoop { pos_in_chars: 2 }.byte_span(source),
+            6..7
+        );
+        assert_eq!(
+            ParseError::MiscError {
+                pos_in_chars: 1,
+                message: String::new()
+            }
+            .byte_span(source),
+            3..6
+        );
+        assert_eq!(
+            ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 3,
+                loop_head_pos_in_chars: None
+            }
+            .byte_span(source),
+            7..7
+        );
+    }
+
+    #[test]
+    fn errors_are_cloneable() {
+        use std::error::Error as _;
+
+        let parse_error = ParseError::UnexpectedEndOfLoop { pos_in_chars: 3 };
+        assert_eq!(parse_error.clone().to_string(), "3: Unexpected end-of-loop");
+
+        let error = ParseOrIoError::from(io::Error::other("broken source"));
+        let cloned = error.clone();
+        assert_eq!(
+            error.to_string(),
+            "failed reading source after 0 bytes: broken source"
+        );
+        assert_eq!(
+            cloned.to_string(),
+            "failed reading source after 0 bytes: broken source"
+        );
+        assert_eq!(cloned.source().unwrap().to_string(), "broken source");
+
+        let error = RuntimeError::from(io::Error::other("broken pipe"));
+        let cloned = error.clone();
+        assert_eq!(error.to_string(), "IO error: broken pipe");
+        assert_eq!(cloned.to_string(), "IO error: broken pipe");
+        assert_eq!(cloned.source().unwrap().to_string(), "broken pipe");
+        if let (RuntimeError::IoError(a), RuntimeError::IoError(b)) = (&error, &cloned) {
+            assert!(Arc::ptr_eq(a, b));
+        } else {
+            panic!("unexpected variant");
+        }
+    }
+
+    #[test]
+    fn unexpected_end_of_file_string() {
+        assert_eq!(
+            "12: Unexpected end-of-file",
+            ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 12,
+                loop_head_pos_in_chars: None
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "12: Unexpected end-of-file (the loop at 3 is not closed)",
+            ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 12,
+                loop_head_pos_in_chars: Some(3)
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn error_codes() {
+        let parse_errors = [
+            ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 0,
+                loop_head_pos_in_chars: None,
+            },
+            ParseError::UnexpectedEndOfLoop { pos_in_chars: 0 },
+            ParseError::MiscError {
+                pos_in_chars: 0,
+                message: String::new(),
+            },
+            ParseError::OperandTooLarge {
+                pos_in_chars: 0,
+                limit: 1,
+            },
+        ];
+        let codes = parse_errors
+            .iter()
+            .map(|e| e.kind().code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["E0001", "E0002", "E0003", "E0005"]);
+
+        assert_eq!(
+            ParseOrIoError::from(parse_errors[1].clone()).kind().code(),
+            "E0002"
+        );
+        assert_eq!(
+            ParseOrIoError::from(io::Error::other("")).kind().code(),
+            "E0201"
+        );
+        assert_eq!(
+            ParseOrIoError::IncludeCycle {
+                path: PathBuf::new()
+            }
+            .kind()
+            .code(),
+            "E0004"
+        );
+
+        let runtime_errors = [
+            RuntimeError::OutOfMemoryBounds {
+                address: 0,
+                memsize: MemorySize::Fixed(0),
+                access: AccessKind::Data,
+            },
+            RuntimeError::Eof,
+            RuntimeError::InputNotReady,
+            RuntimeError::Cancelled,
+            RuntimeError::OutputLimitExceeded {
+                limit: 0,
+                output: vec![],
+            },
+            RuntimeError::AssertionFailed {
+                address: 0,
+                expected: 0,
+                actual: 1,
+            },
+            RuntimeError::from(io::Error::other("")),
+        ];
+        let codes = runtime_errors
+            .iter()
+            .map(|e| e.kind().code())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            ["E0101", "E0102", "E0103", "E0104", "E0105", "E0106", "E0201"]
+        );
+
+        let index = ProgramIndex::new_for_test([0]);
+        let validation_errors = [
+            ValidationError::PointerMoveOutOfBounds {
+                index: index.clone(),
+                operand: 0,
+                memsize: MemorySize::Fixed(0),
+            },
+            ValidationError::InfiniteLoop { index },
+        ];
+        let codes = validation_errors
+            .iter()
+            .map(|e| e.kind().code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["E0301", "E0302"]);
+    }
+
     #[test]
     fn runtime_error_string() {
         assert_eq!(
-            "out of memory bounds [123]",
-            RuntimeError::OutOfMemoryBounds { address: 123 }.to_string()
+            "out of memory bounds [123] (data access, valid range: [0, 100))",
+            RuntimeError::OutOfMemoryBounds {
+                address: 123,
+                memsize: MemorySize::Fixed(100),
+                access: AccessKind::Data,
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "out of memory bounds [-1] (data access, valid range: [0, +inf))",
+            RuntimeError::OutOfMemoryBounds {
+                address: -1,
+                memsize: MemorySize::RightInfinite,
+                access: AccessKind::Data,
+            }
+            .to_string()
         );
     }
 }
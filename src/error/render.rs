@@ -0,0 +1,340 @@
+//! Human-readable rendering of parse errors with source context.
+use super::ParseError;
+
+/// The number of columns a tab stop occupies.
+const TAB_WIDTH: usize = 4;
+
+/// The maximum number of columns of a source line to be rendered.
+const MAX_LINE_WIDTH: usize = 80;
+
+/// The marker which indicates that a part of a source line is omitted.
+const ELLIPSIS: &str = "...";
+
+impl ParseError {
+    /// Render the error with its source context.
+    ///
+    /// `source` must be the source string which was passed to the parser.
+    /// The result consists of the message, the line and column number (both 1-based) and
+    /// the offending source line with a caret under the position where the error occurred.
+    ///
+    /// ```text
+    /// error: Unexpected end-of-loop
+    ///  --> 2:4
+    ///   |
+    /// 2 | +++]
+    ///   |    ^
+    /// ```
+    ///
+    /// For [`ParseError::UnexpectedEndOfFile`] with the position of an unclosed loop,
+    /// a note with the line and column of the loop head follows.
+    ///
+    /// Tabs are expanded, East Asian wide characters occupy two columns and
+    /// lines longer than 80 columns are clamped around the position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::Parser, token::simple::SimpleTokenSpec};
+    ///
+    /// let parser = Parser::new(
+    ///     SimpleTokenSpec {
+    ///         ptr_inc: '>',
+    ///         ptr_dec: '<',
+    ///         data_inc: '+',
+    ///         data_dec: '-',
+    ///         output: '.',
+    ///         input: ',',
+    ///         loop_head: '[',
+    ///         loop_tail: ']',
+    ///     }
+    ///     .to_tokenizer(),
+    /// );
+    /// let source = "+[>+<-]\n+++]";
+    /// let err = parser.parse_str(source).unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     err.render(source),
+    ///     "error: Unexpected end-of-loop\n --> 2:4\n  |\n2 | +++]\n  |    ^",
+    /// );
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let location = Location::new(source, self.pos_in_chars());
+        let line_number = location.line_number.to_string();
+        let pad = " ".repeat(line_number.len());
+        let (text, caret_offset, caret_width) = render_line(location.line, location.column);
+
+        let mut lines = vec![
+            format!("error: {}", self.message()),
+            format!("{pad}--> {line_number}:{}", location.column + 1),
+            format!("{pad} |"),
+            format!("{line_number} | {text}"),
+            format!(
+                "{pad} | {}{}",
+                " ".repeat(caret_offset),
+                "^".repeat(caret_width)
+            ),
+        ];
+        if let Self::UnexpectedEndOfFile {
+            loop_head_pos_in_chars: Some(loop_head),
+            ..
+        } = self
+        {
+            let head = Location::new(source, *loop_head);
+            lines.push(format!(
+                "{pad} = note: the loop at {}:{} is not closed",
+                head.line_number,
+                head.column + 1
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+// A location of a position in a source.
+struct Location<'a> {
+    // 1-based line number.
+    line_number: usize,
+    // The line without the line terminator.
+    line: &'a str,
+    // 0-based column in Unicode scalar units.
+    column: usize,
+}
+
+impl<'a> Location<'a> {
+    fn new(source: &'a str, pos_in_chars: usize) -> Self {
+        // An EOF just after a trailing line terminator is shown at the end of the last line.
+        let (source, pos_in_chars) = if pos_in_chars >= source.chars().count() {
+            let trimmed = source
+                .strip_suffix('\n')
+                .map(|s| s.strip_suffix('\r').unwrap_or(s))
+                .unwrap_or(source);
+            (trimmed, trimmed.chars().count())
+        } else {
+            (source, pos_in_chars)
+        };
+
+        let mut line_number = 1;
+        let mut line_start = 0;
+        let mut column = 0;
+        for (index, (pos, c)) in source.char_indices().enumerate() {
+            if index == pos_in_chars {
+                break;
+            }
+            if c == '\n' {
+                line_number += 1;
+                line_start = pos + 1;
+                column = 0;
+            } else {
+                column += 1;
+            }
+        }
+
+        let line = source[line_start..].split('\n').next().unwrap_or("");
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Self {
+            line_number,
+            line,
+            column,
+        }
+    }
+}
+
+// Render a source line.
+//
+// Returns the rendered text, the offset of the caret and the width of the caret in columns.
+fn render_line(line: &str, column: usize) -> (String, usize, usize) {
+    // (rendered piece, start column, width) of each character
+    let mut cells = Vec::new();
+    let mut total_width = 0;
+    let mut caret = None;
+    for (index, c) in line.chars().enumerate() {
+        let (piece, width) = match c {
+            '\t' => {
+                let width = TAB_WIDTH - total_width % TAB_WIDTH;
+                (" ".repeat(width), width)
+            }
+            c if c.is_control() => ('\u{FFFD}'.to_string(), 1),
+            c => (c.to_string(), char_width(c)),
+        };
+        if index == column {
+            caret = Some((total_width, if c == '\t' { 1 } else { width.max(1) }));
+        }
+        cells.push((piece, total_width, width));
+        total_width += width;
+    }
+    let (caret_column, caret_width) = caret.unwrap_or((total_width, 1));
+
+    if total_width <= MAX_LINE_WIDTH {
+        let text = cells.into_iter().map(|(piece, _, _)| piece).collect();
+        return (text, caret_column, caret_width);
+    }
+
+    // Clamp the line around the caret.
+    let start = caret_column.saturating_sub(MAX_LINE_WIDTH / 2).min(
+        total_width
+            .max(caret_column + caret_width)
+            .saturating_sub(MAX_LINE_WIDTH),
+    );
+    let end = start + MAX_LINE_WIDTH;
+    let visible = cells
+        .into_iter()
+        .filter(|&(_, cell_start, width)| cell_start >= start && cell_start + width <= end)
+        .collect::<Vec<_>>();
+    let visible_start = visible
+        .first()
+        .map_or(start, |&(_, cell_start, _)| cell_start);
+    let visible_end = visible
+        .last()
+        .map_or(start, |&(_, cell_start, width)| cell_start + width);
+
+    let mut text = String::new();
+    let mut prefix_width = 0;
+    if visible_start > 0 {
+        text.push_str(ELLIPSIS);
+        prefix_width = ELLIPSIS.len();
+    }
+    text.extend(visible.into_iter().map(|(piece, _, _)| piece));
+    if visible_end < total_width {
+        text.push_str(ELLIPSIS);
+    }
+    (
+        text,
+        caret_column - visible_start + prefix_width,
+        caret_width,
+    )
+}
+
+// The number of columns a character occupies in a terminal.
+//
+// This is a simple approximation of East Asian Width and does not cover all characters.
+pub(crate) fn char_width(c: char) -> usize {
+    match c as u32 {
+        // combining marks, zero width spaces and variation selectors
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_end_of_loop() {
+        let source = "+[>+<-]\n+++]\n.";
+        let err = ParseError::UnexpectedEndOfLoop { pos_in_chars: 11 };
+        assert_eq!(
+            err.render(source),
+            "\
+error: Unexpected end-of-loop
+ --> 2:4
+  |
+2 | +++]
+  |    ^"
+        );
+    }
+
+    #[test]
+    fn test_render_end_of_file_after_trailing_newline() {
+        let source = "+\n[>+\n";
+        let err = ParseError::UnexpectedEndOfFile {
+            pos_in_chars: 6,
+            loop_head_pos_in_chars: Some(2),
+        };
+        assert_eq!(
+            err.render(source),
+            "\
+error: Unexpected end-of-file
+ --> 2:4
+  |
+2 | [>+
+  |    ^
+  = note: the loop at 2:1 is not closed"
+        );
+    }
+
+    #[test]
+    fn test_render_misc_error_with_wide_gutter() {
+        let source = "\n".repeat(9) + "Ook. Ook? Ook!\r\n";
+        let err = ParseError::MiscError {
+            pos_in_chars: 19,
+            message: "Odd number of Ook tokens".to_string(),
+        };
+        assert_eq!(
+            err.render(&source),
+            "\
+error: syntax error: Odd number of Ook tokens
+  --> 10:11
+   |
+10 | Ook. Ook? Ook!
+   |           ^"
+        );
+    }
+
+    #[test]
+    fn test_render_tabs_and_wide_characters() {
+        let source = "\tいろは]";
+        let err = ParseError::UnexpectedEndOfLoop { pos_in_chars: 3 };
+        assert_eq!(
+            err.render(source),
+            "\
+error: Unexpected end-of-loop
+ --> 1:4
+  |
+1 |     いろは]
+  |         ^^"
+        );
+    }
+
+    #[test]
+    fn test_render_long_line() {
+        let source = "+".repeat(100) + "]" + &"-".repeat(100);
+        let err = ParseError::UnexpectedEndOfLoop { pos_in_chars: 100 };
+        let expected = format!(
+            "\
+error: Unexpected end-of-loop
+ --> 1:101
+  |
+1 | ...{}]{}...
+  |    {}^",
+            "+".repeat(40),
+            "-".repeat(39),
+            " ".repeat(40),
+        );
+        assert_eq!(err.render(&source), expected);
+    }
+
+    #[test]
+    fn test_render_long_line_end() {
+        let source = "[".to_string() + &"+".repeat(100);
+        let err = ParseError::UnexpectedEndOfFile {
+            pos_in_chars: 101,
+            loop_head_pos_in_chars: None,
+        };
+        let expected = format!(
+            "\
+error: Unexpected end-of-file
+ --> 1:102
+  |
+1 | ...{}
+  |    {}^",
+            "+".repeat(79),
+            " ".repeat(79),
+        );
+        assert_eq!(err.render(&source), expected);
+    }
+}
@@ -0,0 +1,327 @@
+//! Minifier.
+use super::RenderSpec;
+use crate::{
+    program::{FatProgram, Instruction, Program},
+    token::TokenType,
+};
+
+/// Minifying options.
+#[derive(Debug, Clone, Default)]
+pub struct MinifyOptions {
+    /// If `true`, adjacent pointer or data increments/decrements are merged and
+    /// those with operand 0 are dropped before rendering.
+    pub shrink: bool,
+}
+
+/// Size statistics of a minified source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinifyStats {
+    /// The size of the original source in bytes.
+    pub original_bytes: usize,
+    /// The size of the minified source in bytes.
+    pub minified_bytes: usize,
+}
+
+impl MinifyStats {
+    /// Compare an original source and its minified source.
+    pub fn new(original: &str, minified: &str) -> Self {
+        Self {
+            original_bytes: original.len(),
+            minified_bytes: minified.len(),
+        }
+    }
+
+    /// Get the number of bytes saved.
+    ///
+    /// This is negative if the minified source is larger than the original
+    /// (e.g. an optimized [`Instruction::Fill`] is rendered as a long sequence).
+    pub fn bytes_saved(&self) -> isize {
+        self.original_bytes as isize - self.minified_bytes as isize
+    }
+}
+
+/// Minify a program.
+///
+/// The program is rendered with the tokens of `spec` separated by its token separator,
+/// without any comments or line breaks. To get the smallest source from a specification
+/// with alternatives, use [`RenderSpec::shortest`].
+///
+/// Instructions made by the optimizer are rendered as follows:
+///
+///  - [`Instruction::SetZero`] is rendered as `[-]` (or `[+]` if the data increment token is shorter).
+///  - [`Instruction::DSet`] is rendered as `[-]` followed by the value in data increments.
+///  - [`Instruction::Fill`] sets the cells one by one and moves the pointer back.
+///
+/// The result is parsed into an equivalent program by a parser of the specification,
+/// as long as the token separator keeps the tokens apart.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     fmt::{minify, MinifyOptions, RenderSpec},
+///     program::{Instruction::*, Program},
+///     token::simple::SimpleMultiTokenSpec,
+/// };
+///
+/// let spec = RenderSpec::shortest(
+///     &SimpleMultiTokenSpec {
+///         ptr_inc: &[">", "→"],
+///         ptr_dec: &["<", "←"],
+///         data_inc: &["+", "＋"],
+///         data_dec: &["-", "−"],
+///         output: &["."],
+///         input: &[","],
+///         loop_head: &["["],
+///         loop_tail: &["]"],
+///     },
+///     "",
+/// )
+/// .unwrap();
+/// let program = Program::new([DAdd(2), PAdd(1), DAdd(-1), PAdd(0), DAdd(-1), SetZero]);
+/// let opts = MinifyOptions { shrink: true };
+///
+/// assert_eq!(minify(&program, &spec, &opts), "++>--[-]");
+/// ```
+pub fn minify(program: &Program, spec: &RenderSpec, opts: &MinifyOptions) -> String {
+    let mut tokens = Vec::new();
+    if opts.shrink {
+        render(&shrink(program.instructions()), spec, &mut tokens);
+    } else {
+        render(program.instructions(), spec, &mut tokens);
+    }
+    tokens.join(&spec.token_separator)
+}
+
+/// Minify a program which keeps its source tokens.
+///
+/// Unless `opts.shrink` is `true`, each source token is rendered as one token of `spec`,
+/// so the token sequence is kept as is. Otherwise, this is equivalent to
+/// `minify(&fat.to_program(), spec, opts)`.
+pub fn minify_fat(fat: &FatProgram, spec: &RenderSpec, opts: &MinifyOptions) -> String {
+    if opts.shrink {
+        return minify(&fat.to_program(), spec, opts);
+    }
+    fat.tokens()
+        .into_iter()
+        .map(|token| spec.token_str(token.token_type))
+        .collect::<Vec<_>>()
+        .join(&spec.token_separator)
+}
+
+// Merge adjacent increments/decrements and drop those with operand 0.
+fn shrink(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut result: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    for inst in instructions {
+        let inst = match inst {
+            Instruction::UntilZero(sub) => Instruction::UntilZero(shrink(sub)),
+            inst => inst.clone(),
+        };
+        match (result.last_mut(), inst) {
+            (Some(Instruction::PAdd(last)), Instruction::PAdd(operand))
+            | (Some(Instruction::DAdd(last)), Instruction::DAdd(operand)) => *last += operand,
+            (_, Instruction::PAdd(0) | Instruction::DAdd(0)) => {}
+            (_, inst) => result.push(inst),
+        }
+        if matches!(
+            result.last(),
+            Some(Instruction::PAdd(0) | Instruction::DAdd(0))
+        ) {
+            result.pop();
+        }
+    }
+    result
+}
+
+pub(super) fn render<'a>(
+    instructions: &[Instruction],
+    spec: &'a RenderSpec,
+    tokens: &mut Vec<&'a str>,
+) {
+    let repeat = |tokens: &mut Vec<&'a str>, token_type, count| {
+        tokens.extend(std::iter::repeat_n(spec.token_str(token_type), count))
+    };
+    let set_zero = |tokens: &mut Vec<&'a str>| {
+        let dec = spec.token_str(TokenType::DDec);
+        let inc = spec.token_str(TokenType::DInc);
+        tokens.extend([
+            spec.token_str(TokenType::LoopHead),
+            if inc.len() < dec.len() { inc } else { dec },
+            spec.token_str(TokenType::LoopTail),
+        ]);
+    };
+    for inst in instructions {
+        match inst {
+            Instruction::PAdd(operand) if *operand >= 0 => {
+                repeat(tokens, TokenType::PInc, operand.unsigned_abs())
+            }
+            Instruction::PAdd(operand) => repeat(tokens, TokenType::PDec, operand.unsigned_abs()),
+            Instruction::DAdd(operand) if *operand >= 0 => {
+                repeat(tokens, TokenType::DInc, operand.unsigned_abs())
+            }
+            Instruction::DAdd(operand) => repeat(tokens, TokenType::DDec, operand.unsigned_abs()),
+            Instruction::Output => tokens.push(spec.token_str(TokenType::Output)),
+            Instruction::Input => tokens.push(spec.token_str(TokenType::Input)),
+            Instruction::Breakpoint => {
+                if spec.breakpoint.is_some() {
+                    tokens.push(spec.token_str(TokenType::Breakpoint));
+                }
+            }
+            Instruction::AssertCell { .. } => {}
+            Instruction::UntilZero(sub) => {
+                tokens.push(spec.token_str(TokenType::LoopHead));
+                render(sub, spec, tokens);
+                tokens.push(spec.token_str(TokenType::LoopTail));
+            }
+            Instruction::SetZero => set_zero(tokens),
+            Instruction::DSet(value) => {
+                set_zero(tokens);
+                repeat(tokens, TokenType::DInc, *value as usize);
+            }
+            Instruction::Fill { len, value } => {
+                for i in 0..*len {
+                    if i > 0 {
+                        repeat(tokens, TokenType::PInc, 1);
+                    }
+                    set_zero(tokens);
+                    repeat(tokens, TokenType::DInc, *value as usize);
+                }
+                repeat(tokens, TokenType::PDec, len.saturating_sub(1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        optimize::optimize,
+        parser::Parser,
+        runtime,
+        testing::Generator,
+        token::simple::{SimpleMultiTokenSpec, SimpleMultiTokenSpec1},
+    };
+
+    const SPEC: SimpleMultiTokenSpec1<&str> = SimpleMultiTokenSpec {
+        ptr_inc: &["right", ">"],
+        ptr_dec: &["left", "<"],
+        data_inc: &["inc", "+"],
+        data_dec: &["dec", "-"],
+        output: &["out", "."],
+        input: &["in", ","],
+        loop_head: &["[", "loop"],
+        loop_tail: &["]", "end"],
+    };
+
+    fn parser() -> Parser<crate::token::simple::SimpleTokenizer> {
+        Parser::new(SPEC.to_tokenizer())
+    }
+
+    // Generate a random source in `SPEC` with balanced loops and comments.
+    fn random_source(generator: &mut Generator) -> String {
+        let mut next = || generator.next_u64();
+        let tokens = [
+            SPEC.ptr_inc,
+            SPEC.ptr_dec,
+            SPEC.data_inc,
+            SPEC.data_dec,
+            SPEC.output,
+            SPEC.input,
+        ];
+        let len = next() % 100;
+        let mut source = String::new();
+        let mut depth = 0;
+        for _ in 0..len {
+            let n = next();
+            match n % 9 {
+                6 => {
+                    source.push_str(SPEC.loop_head[(n / 9 % 2) as usize]);
+                    depth += 1;
+                }
+                7 if depth > 0 => {
+                    source.push_str(SPEC.loop_tail[(n / 9 % 2) as usize]);
+                    depth -= 1;
+                }
+                8 => source.push_str(" ? "),
+                k => source.push_str(tokens[k as usize % 6][(n / 9 % 2) as usize]),
+            }
+            source.push(' ');
+        }
+        source.push_str(&"] ".repeat(depth));
+        source
+    }
+
+    #[test]
+    fn test_minify_reparses_to_same_program() {
+        let spec = RenderSpec::shortest(&SPEC, "").unwrap();
+        let parser = parser();
+        let mut generator = Generator::new(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            let source = random_source(&mut generator);
+            let fat = parser.parse_fat_str(&source).unwrap();
+            let program = fat.to_program();
+            // adjacent increments/decrements are merged on re-parsing.
+            let shrunk = shrink(program.instructions());
+            for opts in [
+                MinifyOptions { shrink: false },
+                MinifyOptions { shrink: true },
+            ] {
+                for minified in [
+                    minify(&program, &spec, &opts),
+                    minify_fat(&fat, &spec, &opts),
+                ] {
+                    let reparsed = parser.parse_str(&minified).unwrap();
+                    assert_eq!(
+                        shrink(reparsed.instructions()),
+                        shrunk,
+                        "source: {source:?}, minified: {minified:?}"
+                    );
+                    assert!(MinifyStats::new(&source, &minified).bytes_saved() >= 0);
+                }
+            }
+            // the token sequence is kept.
+            let minified = minify_fat(&fat, &spec, &MinifyOptions::default());
+            let reparsed = parser.parse_fat_str(&minified).unwrap();
+            assert_eq!(reparsed.to_program().instructions(), program.instructions());
+            assert_eq!(reparsed.tokens().len(), fat.tokens().len());
+        }
+    }
+
+    #[test]
+    fn test_minify_optimized_program() {
+        let spec = RenderSpec::shortest(&SPEC, "").unwrap();
+        let program = Program::new([
+            Instruction::DAdd(7),
+            Instruction::DSet(3),
+            Instruction::PAdd(1),
+            Instruction::Fill { len: 3, value: 2 },
+            Instruction::DAdd(1),
+            Instruction::Output,
+            Instruction::PAdd(-1),
+            Instruction::Output,
+        ]);
+        let minified = minify(&program, &spec, &MinifyOptions::default());
+        assert_eq!(minified, "+++++++[-]+++>[-]++>[-]++>[-]++<<+.<.");
+
+        let source = include_str!("../../tests/data/hello.bf");
+        let program = optimize(&parser().parse_str(source).unwrap());
+        let minified = minify(&program, &spec, &MinifyOptions { shrink: true });
+        let mut output = vec![];
+        runtime::run(
+            &parser().parse_str(&minified).unwrap(),
+            &[][..],
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(output, b"Hello World!\n");
+    }
+
+    #[test]
+    fn test_minify_stats() {
+        let stats = MinifyStats::new("+ + +", "+++");
+        assert_eq!(stats.original_bytes, 5);
+        assert_eq!(stats.minified_bytes, 3);
+        assert_eq!(stats.bytes_saved(), 2);
+    }
+}
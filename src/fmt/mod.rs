@@ -0,0 +1,338 @@
+//! Source formatter.
+//!
+//! [`format`] re-renders a [`FatProgram`] with the original token strings,
+//! so it works for any language which the program was parsed from,
+//! as long as each token has its own source string. (For example, it does not work for
+//! [`predefined::asm`](crate::predefined::asm), whose mnemonic with a count is one source string
+//! of several tokens.)
+//!
+//! # Examples
+//!
+//! ```
+//! use libbf::{fmt::{self, FmtOptions}, parser::Parser, token::simple::SimpleTokenSpec};
+//!
+//! let parser = Parser::new(
+//!     SimpleTokenSpec {
+//!         ptr_inc: '>',
+//!         ptr_dec: '<',
+//!         data_inc: '+',
+//!         data_dec: '-',
+//!         output: '.',
+//!         input: ',',
+//!         loop_head: '[',
+//!         loop_tail: ']',
+//!     }
+//!     .to_tokenizer(),
+//! );
+//! let fat = parser.parse_fat_str("++[>++[>+<-]<-] copy").unwrap();
+//!
+//! assert_eq!(
+//!     fmt::format(&fat, &FmtOptions::default()),
+//!     "++[\n  >++[>+<-]<-\n]\n",
+//! );
+//! ```
+mod minify;
+mod obfuscate;
+mod translate;
+
+use crate::{
+    error::char_width,
+    program::{FatInstruction, FatProgram},
+};
+
+pub use self::minify::{minify, minify_fat, MinifyOptions, MinifyStats};
+pub use self::obfuscate::{obfuscate, ObfuscateOptions};
+pub use self::translate::{translate, RenderSpec, TranslateOptions};
+
+/// How to handle comments (characters which are not tokens).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum CommentMode {
+    /// Comments are dropped.
+    ///
+    /// This is the only mode for now, since [`FatProgram`] does not keep comments.
+    #[default]
+    Drop,
+}
+
+/// Formatting options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmtOptions {
+    /// The maximum number of columns of a line, including the indentation.
+    ///
+    /// A token longer than this is put on its own line.
+    pub max_width: usize,
+    /// The number of columns of one indentation level.
+    pub indent_width: usize,
+    /// If `Some(n)`, a run of pointer or data increments/decrements is split
+    /// into groups of `n` tokens separated by a space (e.g. `++++ ++++`).
+    pub group_size: Option<usize>,
+    /// If `true`, loop heads and loop tails are put on their own lines and aligned vertically.
+    /// Otherwise, a loop head ends the current line and a loop tail starts a new line.
+    pub align_brackets: bool,
+    /// If `true`, a loop which contains no loops is kept on one line if it fits.
+    pub inline_innermost_loops: bool,
+    /// The separator between tokens in a line (e.g. `" "` for Ook!).
+    pub token_separator: String,
+    /// How to handle comments.
+    pub comments: CommentMode,
+}
+
+impl Default for FmtOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 80,
+            indent_width: 2,
+            group_size: None,
+            align_brackets: false,
+            inline_innermost_loops: true,
+            token_separator: String::new(),
+            comments: CommentMode::Drop,
+        }
+    }
+}
+
+/// Format a program.
+///
+/// Each loop body is indented by one level. The result ends with a line feed
+/// unless the program is empty, and it is parsed into the same [`Program`](crate::program::Program)
+/// as `fat` by the parser which `fat` was parsed with.
+pub fn format(fat: &FatProgram, opts: &FmtOptions) -> String {
+    let mut writer = Writer::new(opts);
+    writer.instructions(fat.instructions());
+    writer.finish()
+}
+
+// A line-based writer.
+struct Writer<'a> {
+    opts: &'a FmtOptions,
+    lines: Vec<String>,
+    line: String,
+    line_width: usize,
+    depth: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(opts: &'a FmtOptions) -> Self {
+        Self {
+            opts,
+            lines: Vec::new(),
+            line: String::new(),
+            line_width: 0,
+            depth: 0,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.newline();
+        self.lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    // Start a new line unless the current line is empty.
+    fn newline(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+            self.line_width = 0;
+        }
+    }
+
+    // Put a word, which is not split across lines.
+    //
+    // The word is preceded by a space if `group_break` is `true`, or by the token separator otherwise.
+    fn word(&mut self, word: &str, group_break: bool) {
+        let separator = if group_break {
+            " "
+        } else {
+            self.opts.token_separator.as_str()
+        };
+        let word_width = str_width(word);
+        if !self.line.is_empty()
+            && self.line_width + str_width(separator) + word_width > self.opts.max_width
+        {
+            self.newline();
+        }
+        if self.line.is_empty() {
+            let indent = self.depth * self.opts.indent_width;
+            self.line.push_str(&" ".repeat(indent));
+            self.line_width = indent;
+        } else {
+            self.line.push_str(separator);
+            self.line_width += str_width(separator);
+        }
+        self.line.push_str(word);
+        self.line_width += word_width;
+    }
+
+    fn instructions(&mut self, instructions: &[FatInstruction]) {
+        for instruction in instructions {
+            match instruction {
+                FatInstruction::PAdd(_, run) | FatInstruction::DAdd(_, run) => {
+                    for (i, token) in run.iter().enumerate() {
+                        let group_break = self.opts.group_size.is_some_and(|n| i > 0 && i % n == 0);
+                        self.word(&token.token_str, group_break);
+                    }
+                }
+                FatInstruction::Output(token)
+                | FatInstruction::Input(token)
+                | FatInstruction::Breakpoint(token)
+                | FatInstruction::AssertCell(_, token) => self.word(&token.token_str, false),
+                FatInstruction::UntilZero { head, body, tail } => {
+                    if self.opts.inline_innermost_loops && self.inline_loop(instruction) {
+                        continue;
+                    }
+                    if self.opts.align_brackets {
+                        self.newline();
+                    }
+                    self.word(&head.token_str, false);
+                    self.newline();
+                    self.depth += 1;
+                    self.instructions(body);
+                    self.newline();
+                    self.depth -= 1;
+                    self.word(&tail.token_str, false);
+                    if self.opts.align_brackets {
+                        self.newline();
+                    }
+                }
+            }
+        }
+    }
+
+    // Put a loop on one line if it contains no loops and fits in the width.
+    //
+    // Returns `true` if the loop was put.
+    fn inline_loop(&mut self, instruction: &FatInstruction) -> bool {
+        let FatInstruction::UntilZero { head, body, tail } = instruction else {
+            return false;
+        };
+        if body
+            .iter()
+            .any(|inst| matches!(inst, FatInstruction::UntilZero { .. }))
+        {
+            return false;
+        }
+
+        let opts = FmtOptions {
+            max_width: usize::MAX,
+            inline_innermost_loops: false,
+            ..self.opts.clone()
+        };
+        let mut inline = Writer::new(&opts);
+        inline.word(&head.token_str, false);
+        inline.instructions(body);
+        inline.word(&tail.token_str, false);
+        let text = inline.line;
+
+        let width = str_width(&text);
+        let indent = self.depth * self.opts.indent_width;
+        let fits_current = self.line.is_empty()
+            || self.line_width + str_width(&self.opts.token_separator) + width
+                <= self.opts.max_width;
+        if self.opts.align_brackets || !fits_current {
+            if indent + width > self.opts.max_width {
+                return false;
+            }
+            self.newline();
+        }
+        self.word(&text, false);
+        if self.opts.align_brackets {
+            self.newline();
+        }
+        true
+    }
+}
+
+fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{bf_parser, bf_spec, Generator};
+
+    const HELLO_BF: &str = include_str!("../../tests/data/hello.bf");
+
+    #[test]
+    fn test_format_golden_default() {
+        let fat = bf_parser().parse_fat_str(HELLO_BF).unwrap();
+        assert_eq!(
+            format(&fat, &FmtOptions::default()),
+            include_str!("../../tests/data/fmt/hello.default.bf")
+        );
+    }
+
+    #[test]
+    fn test_format_golden_aligned() {
+        let fat = bf_parser().parse_fat_str(HELLO_BF).unwrap();
+        let opts = FmtOptions {
+            max_width: 30,
+            group_size: Some(4),
+            align_brackets: true,
+            ..FmtOptions::default()
+        };
+        assert_eq!(
+            format(&fat, &opts),
+            include_str!("../../tests/data/fmt/hello.aligned.bf")
+        );
+    }
+
+    #[cfg(feature = "ook")]
+    #[test]
+    fn test_format_golden_ook() {
+        let parser = crate::predefined::ook::parser();
+        let fat = parser
+            .parse_fat_str(include_str!("../../tests/data/hello.ook"))
+            .unwrap();
+        let opts = FmtOptions {
+            max_width: 60,
+            indent_width: 4,
+            token_separator: " ".to_string(),
+            ..FmtOptions::default()
+        };
+        let formatted = format(&fat, &opts);
+        assert_eq!(formatted, include_str!("../../tests/data/fmt/hello.ook"));
+        assert_eq!(
+            parser.parse_str(&formatted).unwrap().instructions(),
+            fat.to_program().instructions()
+        );
+    }
+
+    #[test]
+    fn test_format_reparses_to_same_program() {
+        let parser = bf_parser();
+        let options = [
+            FmtOptions::default(),
+            FmtOptions {
+                max_width: 5,
+                indent_width: 1,
+                group_size: Some(2),
+                ..FmtOptions::default()
+            },
+            FmtOptions {
+                max_width: 12,
+                align_brackets: true,
+                inline_innermost_loops: false,
+                ..FmtOptions::default()
+            },
+        ];
+        let spec = RenderSpec::new(&bf_spec(), "");
+        let mut generator = Generator::new(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let program = generator.program(200, 4, true);
+            let source = generator.source_for(&program, &spec);
+            let fat = parser.parse_fat_str(&source).unwrap();
+            for opts in &options {
+                let formatted = format(&fat, opts);
+                let reparsed = parser.parse_fat_str(&formatted).unwrap();
+                assert_eq!(
+                    reparsed.to_program().instructions(),
+                    fat.to_program().instructions(),
+                    "source: {source:?}, formatted: {formatted:?}"
+                );
+                // formatting is idempotent.
+                assert_eq!(format(&reparsed, opts), formatted);
+            }
+        }
+    }
+}
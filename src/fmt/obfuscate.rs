@@ -0,0 +1,342 @@
+//! Obfuscator.
+use super::{minify::render, RenderSpec};
+use crate::{
+    prng::SplitMix64,
+    program::{Instruction, Program},
+    token::TokenType,
+};
+
+/// Obfuscating options.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObfuscateOptions {
+    /// The seed of the random choices. The same seed gives the same source.
+    pub seed: u64,
+    /// The probability in `[0, 1]` that noise is inserted between two instructions,
+    /// and that an increment/decrement run is split by a comment.
+    pub noise_ratio: f64,
+}
+
+impl Default for ObfuscateOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            noise_ratio: 0.3,
+        }
+    }
+}
+
+// Characters for comments. Those which appear in the tokens of a specification are not used.
+const COMMENT_CHARS: &str =
+    "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_#$%&*/=?!@^~";
+
+/// Obfuscate a program.
+///
+/// The program is rendered like [`minify`](super::minify), with the following noise inserted at
+/// random:
+///
+///  - comments, also between the tokens of an increment/decrement run,
+///  - pairs of pointer moves which cancel each other out (e.g. `><`),
+///  - pairs of data increments which cancel each other out (e.g. `+-`), only where the current
+///    cell has just been accessed, so that they never access a cell out of memory bounds, and
+///  - dead loops with random bodies, only right after a loop, where the current cell is zero.
+///
+/// The result is parsed into a program which behaves the same as the original
+/// by a non-strict parser of the specification with [`FoldMode::Cancel`](crate::parser::FoldMode::Cancel).
+/// Comments are made of characters which do not appear in any token of `spec`,
+/// and are omitted if there are no such characters.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     fmt::{obfuscate, ObfuscateOptions, RenderSpec},
+///     parser::Parser,
+///     program::{Instruction::*, Program},
+///     runtime,
+///     token::simple::SimpleTokenSpec,
+/// };
+///
+/// let tokens = SimpleTokenSpec {
+///     ptr_inc: '>',
+///     ptr_dec: '<',
+///     data_inc: '+',
+///     data_dec: '-',
+///     output: '.',
+///     input: ',',
+///     loop_head: '[',
+///     loop_tail: ']',
+/// };
+/// let program = Program::new([DAdd(3), UntilZero(vec![Output, DAdd(-1)])]);
+/// let opts = ObfuscateOptions { seed: 42, noise_ratio: 0.8 };
+/// let source = obfuscate(&program, &RenderSpec::new(&tokens, ""), &opts);
+///
+/// let mut output = vec![];
+/// let reparsed = Parser::new(tokens.to_tokenizer()).parse_str(&source).unwrap();
+/// runtime::run(&reparsed, &[][..], &mut output).unwrap();
+/// assert_eq!(output, [3, 2, 1]);
+/// ```
+pub fn obfuscate(program: &Program, spec: &RenderSpec, opts: &ObfuscateOptions) -> String {
+    let used = [
+        TokenType::PInc,
+        TokenType::PDec,
+        TokenType::DInc,
+        TokenType::DDec,
+        TokenType::Output,
+        TokenType::Input,
+        TokenType::LoopHead,
+        TokenType::LoopTail,
+        TokenType::Breakpoint,
+    ]
+    .map(|token_type| spec.token_str(token_type))
+    .concat();
+    let mut obfuscator = Obfuscator {
+        spec,
+        noise_ratio: opts.noise_ratio,
+        comment_chars: COMMENT_CHARS
+            .chars()
+            .filter(|c| !used.contains(*c))
+            .collect(),
+        rng: SplitMix64::new(opts.seed),
+        pieces: vec![],
+    };
+    obfuscator.obfuscate(program.instructions(), CellState::Unknown);
+    obfuscator.pieces.join(&spec.token_separator)
+}
+
+// What is known about the current cell at a point of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellState {
+    Unknown,
+    // The cell has just been accessed, so it is in memory bounds.
+    Accessed,
+    // The cell has just been checked to be zero by a loop.
+    Zero,
+}
+
+struct Obfuscator<'a> {
+    spec: &'a RenderSpec,
+    noise_ratio: f64,
+    comment_chars: Vec<char>,
+    rng: SplitMix64,
+    pieces: Vec<String>,
+}
+
+impl Obfuscator<'_> {
+    fn obfuscate(&mut self, instructions: &[Instruction], mut state: CellState) {
+        self.noise(state);
+        for instruction in instructions {
+            state = match instruction {
+                Instruction::PAdd(0) | Instruction::DAdd(0) => state,
+                Instruction::PAdd(n) => {
+                    self.split_run(*n, TokenType::PInc, TokenType::PDec);
+                    CellState::Unknown
+                }
+                Instruction::DAdd(n) => {
+                    self.split_run(*n, TokenType::DInc, TokenType::DDec);
+                    CellState::Accessed
+                }
+                Instruction::UntilZero(body) => {
+                    self.token(TokenType::LoopHead);
+                    self.obfuscate(body, CellState::Accessed);
+                    self.token(TokenType::LoopTail);
+                    CellState::Zero
+                }
+                // rendered as nothing
+                Instruction::Breakpoint if self.spec.breakpoint.is_none() => state,
+                Instruction::AssertCell { .. } => state,
+                Instruction::Breakpoint => {
+                    self.token(TokenType::Breakpoint);
+                    state
+                }
+                other => {
+                    self.render(other);
+                    match other {
+                        Instruction::SetZero => CellState::Zero,
+                        _ => CellState::Accessed,
+                    }
+                }
+            };
+            self.noise(state);
+        }
+    }
+
+    // Render an increment/decrement run, possibly split by comments.
+    fn split_run(&mut self, n: isize, inc: TokenType, dec: TokenType) {
+        let token = if n > 0 { inc } else { dec };
+        let mut rest = n.unsigned_abs();
+        while rest > 0 {
+            let len = if rest >= 2 && self.chance() {
+                1 + self.rng.next_u64() as usize % (rest - 1)
+            } else {
+                rest
+            };
+            for _ in 0..len {
+                self.token(token);
+            }
+            rest -= len;
+            if rest > 0 {
+                self.comment();
+            }
+        }
+    }
+
+    // Insert noise at random.
+    fn noise(&mut self, state: CellState) {
+        if !self.chance() {
+            return;
+        }
+        let pair = |a, b, swap| if swap { [b, a] } else { [a, b] };
+        match (self.rng.next_u64() % 4, state) {
+            (1, _) => {
+                let swap = self.rng.next_u64() % 2 == 1;
+                for token in pair(TokenType::PInc, TokenType::PDec, swap) {
+                    self.token(token);
+                }
+            }
+            (2, CellState::Accessed | CellState::Zero) => {
+                let swap = self.rng.next_u64() % 2 == 1;
+                for token in pair(TokenType::DInc, TokenType::DDec, swap) {
+                    self.token(token);
+                }
+            }
+            (3, CellState::Zero) => self.dead_loop(),
+            _ => self.comment(),
+        }
+    }
+
+    // Insert a loop which is never entered.
+    fn dead_loop(&mut self) {
+        const BODY: [TokenType; 6] = [
+            TokenType::PInc,
+            TokenType::PDec,
+            TokenType::DInc,
+            TokenType::DDec,
+            TokenType::Output,
+            TokenType::Input,
+        ];
+        self.token(TokenType::LoopHead);
+        for _ in 0..1 + self.rng.next_u64() % 4 {
+            let token = BODY[self.rng.next_u64() as usize % BODY.len()];
+            self.token(token);
+        }
+        self.token(TokenType::LoopTail);
+    }
+
+    fn comment(&mut self) {
+        if self.comment_chars.is_empty() {
+            return;
+        }
+        let comment = (0..1 + self.rng.next_u64() % 6)
+            .map(|_| {
+                let i = self.rng.next_u64() as usize % self.comment_chars.len();
+                self.comment_chars[i]
+            })
+            .collect::<String>();
+        self.pieces.push(comment);
+    }
+
+    fn token(&mut self, token_type: TokenType) {
+        self.pieces
+            .push(self.spec.token_str(token_type).to_string());
+    }
+
+    fn render(&mut self, instruction: &Instruction) {
+        let mut tokens = vec![];
+        render(std::slice::from_ref(instruction), self.spec, &mut tokens);
+        self.pieces.extend(tokens.into_iter().map(str::to_string));
+    }
+
+    fn chance(&mut self) -> bool {
+        ((self.rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < self.noise_ratio
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::Parser,
+        runtime::{Budget, BudgetOutcome, StepRunner},
+        testing::{bf_spec, normalize, Generator},
+        token::simple::{SimpleMultiTokenSpec, SimpleMultiTokenSpec1},
+    };
+
+    #[test]
+    fn test_obfuscate_reparses_to_equivalent_program() {
+        const SPEC: SimpleMultiTokenSpec1<&str> = SimpleMultiTokenSpec {
+            ptr_inc: &["right"],
+            ptr_dec: &["left"],
+            data_inc: &["inc"],
+            data_dec: &["dec"],
+            output: &["out"],
+            input: &["in"],
+            loop_head: &["loop"],
+            loop_tail: &["end"],
+        };
+        let bf = bf_spec();
+        let specs = [
+            (RenderSpec::new(&bf, ""), Parser::new(bf.to_tokenizer())),
+            (
+                RenderSpec::shortest(&SPEC, " ").unwrap(),
+                Parser::new(SPEC.to_tokenizer()),
+            ),
+        ];
+        let input = [3, 1, 4, 1, 5, 9, 2, 6];
+        // `None` if the program does not finish in `max_steps`.
+        let run = |program: &Program, max_steps| {
+            let mut output = vec![];
+            let mut runner = StepRunner::new(program, &input[..], &mut output);
+            let budget = Budget {
+                max_steps: Some(max_steps),
+                max_duration: None,
+            };
+            let ok = match runner.run_budgeted(budget) {
+                Ok(BudgetOutcome::Finished { .. }) => true,
+                Ok(_) => return None,
+                Err(_) => false,
+            };
+            Some((ok, output))
+        };
+        let mut generator = Generator::new(0x2545_f491_4f6c_dd1d);
+        for i in 0..200 {
+            let program = generator.program(60, 4, true);
+            let expected = normalize(program.instructions());
+            let expected_run = run(&program, 10_000);
+            for (spec, parser) in &specs {
+                let opts = ObfuscateOptions {
+                    seed: i,
+                    noise_ratio: 0.5,
+                };
+                let source = obfuscate(&program, spec, &opts);
+                let reparsed = parser.parse_str(&source).unwrap();
+                assert_eq!(
+                    normalize(reparsed.instructions()),
+                    expected,
+                    "source: {source:?}"
+                );
+                if expected_run.is_some() {
+                    assert_eq!(run(&reparsed, 100_000), expected_run, "source: {source:?}");
+                }
+                // deterministic for the same seed.
+                assert_eq!(obfuscate(&program, spec, &opts), source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_obfuscate_without_noise() {
+        let bf = bf_spec();
+        let program = Program::new([
+            Instruction::DAdd(3),
+            Instruction::UntilZero(vec![Instruction::PAdd(-2), Instruction::Output]),
+        ]);
+        let opts = ObfuscateOptions {
+            seed: 1,
+            noise_ratio: 0.0,
+        };
+        assert_eq!(
+            obfuscate(&program, &RenderSpec::new(&bf, ""), &opts),
+            "+++[<<.]"
+        );
+    }
+}
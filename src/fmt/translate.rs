@@ -0,0 +1,272 @@
+//! Dialect-to-dialect translation.
+use super::{format, FmtOptions};
+use crate::{
+    program::{FatInstruction, FatProgram, SourceToken},
+    token::{
+        simple::{SimpleMultiTokenSpec, SimpleTokenSpec1},
+        TokenType,
+    },
+};
+
+/// A specification of how to render tokens in a dialect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderSpec {
+    /// The string of each token.
+    pub tokens: SimpleTokenSpec1<String>,
+    /// The separator between tokens in a line (e.g. `" "` for Ook!).
+    pub token_separator: String,
+    /// The string of the breakpoint token, if the dialect has one.
+    ///
+    /// If `None`, breakpoints are dropped when rendering.
+    pub breakpoint: Option<String>,
+}
+
+impl RenderSpec {
+    /// Create a new render specification from a token specification and a token separator.
+    pub fn new<S: ToString>(tokens: &SimpleTokenSpec1<S>, token_separator: &str) -> Self {
+        Self {
+            tokens: SimpleTokenSpec1 {
+                ptr_inc: tokens.ptr_inc.to_string(),
+                ptr_dec: tokens.ptr_dec.to_string(),
+                data_inc: tokens.data_inc.to_string(),
+                data_dec: tokens.data_dec.to_string(),
+                output: tokens.output.to_string(),
+                input: tokens.input.to_string(),
+                loop_head: tokens.loop_head.to_string(),
+                loop_tail: tokens.loop_tail.to_string(),
+            },
+            token_separator: token_separator.to_string(),
+            breakpoint: None,
+        }
+    }
+
+    /// Set the string of the breakpoint token.
+    pub fn with_breakpoint(mut self, breakpoint: impl ToString) -> Self {
+        self.breakpoint = Some(breakpoint.to_string());
+        self
+    }
+
+    /// Create a new render specification which uses the shortest alternative (in bytes) of each token.
+    ///
+    /// If some token has no alternatives, this returns `None`.
+    #[allow(clippy::type_complexity)]
+    pub fn shortest<S1, S2, S3, S4, S5, S6, S7, S8>(
+        tokens: &SimpleMultiTokenSpec<S1, S2, S3, S4, S5, S6, S7, S8>,
+        token_separator: &str,
+    ) -> Option<Self>
+    where
+        S1: ToString,
+        S2: ToString,
+        S3: ToString,
+        S4: ToString,
+        S5: ToString,
+        S6: ToString,
+        S7: ToString,
+        S8: ToString,
+    {
+        fn shortest(alternatives: &[impl ToString]) -> Option<String> {
+            alternatives
+                .iter()
+                .map(ToString::to_string)
+                .min_by_key(String::len)
+        }
+        Some(Self {
+            tokens: SimpleTokenSpec1 {
+                ptr_inc: shortest(tokens.ptr_inc)?,
+                ptr_dec: shortest(tokens.ptr_dec)?,
+                data_inc: shortest(tokens.data_inc)?,
+                data_dec: shortest(tokens.data_dec)?,
+                output: shortest(tokens.output)?,
+                input: shortest(tokens.input)?,
+                loop_head: shortest(tokens.loop_head)?,
+                loop_tail: shortest(tokens.loop_tail)?,
+            },
+            token_separator: token_separator.to_string(),
+            breakpoint: None,
+        })
+    }
+
+    /// Get the string of a token type.
+    ///
+    /// For [`TokenType::Breakpoint`], this returns an empty string if the dialect has no breakpoint token.
+    /// For [`TokenType::AssertCell`], this always returns an empty string, since an assertion token
+    /// has its own expected value.
+    pub fn token_str(&self, token_type: TokenType) -> &str {
+        let tokens = &self.tokens;
+        match token_type {
+            TokenType::PInc => &tokens.ptr_inc,
+            TokenType::PDec => &tokens.ptr_dec,
+            TokenType::DInc => &tokens.data_inc,
+            TokenType::DDec => &tokens.data_dec,
+            TokenType::Output => &tokens.output,
+            TokenType::Input => &tokens.input,
+            TokenType::LoopHead => &tokens.loop_head,
+            TokenType::LoopTail => &tokens.loop_tail,
+            TokenType::Breakpoint => self.breakpoint.as_deref().unwrap_or(""),
+            TokenType::AssertCell => "",
+        }
+    }
+}
+
+/// Translation options.
+#[derive(Debug, Clone, Default)]
+pub struct TranslateOptions<'a> {
+    /// The source string which the program was parsed from.
+    ///
+    /// If `Some`, the line breaks and the indentation of the source are kept:
+    /// each target token is put on the line of its source token.
+    /// If `None`, the translated program is laid out by [`format`] with `fmt`.
+    pub source: Option<&'a str>,
+    /// The formatting options used when `source` is `None`.
+    ///
+    /// Its `token_separator` is replaced with that of the target.
+    pub fmt: FmtOptions,
+}
+
+/// Translate a program into another dialect.
+///
+/// Each source token is translated into one target token, so `+++` becomes three
+/// data increments of the target, not one increment by three.
+/// Comments are dropped, since [`FatProgram`] does not keep them.
+/// Breakpoints are dropped if the target has no breakpoint token. Assertions are always dropped.
+///
+/// The result is parsed into the same [`Program`](crate::program::Program) as `fat`
+/// by a parser of the target dialect.
+pub fn translate(fat: &FatProgram, target: &RenderSpec, opts: &TranslateOptions) -> String {
+    match opts.source {
+        Some(source) => translate_keeping_layout(fat, target, source),
+        None => {
+            let translated = FatProgram::new(translate_instructions(fat.instructions(), target));
+            let opts = FmtOptions {
+                token_separator: target.token_separator.clone(),
+                ..opts.fmt.clone()
+            };
+            format(&translated, &opts)
+        }
+    }
+}
+
+fn translate_instructions(
+    instructions: &[FatInstruction],
+    target: &RenderSpec,
+) -> Vec<FatInstruction> {
+    instructions
+        .iter()
+        .filter(|inst| match inst {
+            FatInstruction::Breakpoint(_) => target.breakpoint.is_some(),
+            FatInstruction::AssertCell(..) => false,
+            _ => true,
+        })
+        .map(|inst| translate_instruction(inst, target))
+        .collect()
+}
+
+fn translate_instruction(instruction: &FatInstruction, target: &RenderSpec) -> FatInstruction {
+    let token = |token: &SourceToken| SourceToken {
+        token_str: target.token_str(token.token_type).to_string(),
+        ..token.clone()
+    };
+    match instruction {
+        FatInstruction::PAdd(operand, run) => {
+            FatInstruction::PAdd(*operand, run.iter().map(token).collect())
+        }
+        FatInstruction::DAdd(operand, run) => {
+            FatInstruction::DAdd(*operand, run.iter().map(token).collect())
+        }
+        FatInstruction::Output(t) => FatInstruction::Output(token(t)),
+        FatInstruction::Input(t) => FatInstruction::Input(token(t)),
+        FatInstruction::Breakpoint(t) => FatInstruction::Breakpoint(token(t)),
+        FatInstruction::AssertCell(expected, t) => FatInstruction::AssertCell(*expected, token(t)),
+        FatInstruction::UntilZero { head, body, tail } => FatInstruction::UntilZero {
+            head: token(head),
+            body: translate_instructions(body, target),
+            tail: token(tail),
+        },
+    }
+}
+
+fn translate_keeping_layout(fat: &FatProgram, target: &RenderSpec, source: &str) -> String {
+    // (line number, indentation of the line) of each character
+    let mut lines = Vec::new();
+    for (line_number, line) in source.split('\n').enumerate() {
+        let indent = line
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect::<String>();
+        for _ in line.chars() {
+            lines.push((line_number, indent.clone()));
+        }
+        // the line feed
+        lines.push((line_number, indent));
+    }
+
+    let mut result = String::new();
+    let mut current_line = None;
+    for token in fat.tokens() {
+        match token.token_type {
+            TokenType::Breakpoint if target.breakpoint.is_none() => continue,
+            TokenType::AssertCell => continue,
+            _ => {}
+        }
+        let (line_number, indent) = &lines[token.pos_in_chars];
+        match current_line {
+            Some(current) if current == *line_number => result.push_str(&target.token_separator),
+            _ => {
+                let breaks = current_line.map_or(0, |current| line_number - current);
+                result.push_str(&"\n".repeat(breaks));
+                result.push_str(indent);
+            }
+        }
+        result.push_str(target.token_str(token.token_type));
+        current_line = Some(*line_number);
+    }
+    if current_line.is_some() {
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(all(test, feature = "bf", feature = "ook"))]
+mod test {
+    use super::*;
+    use crate::predefined::{bf, ook};
+
+    const HELLO_BF: &str = include_str!("../../tests/data/hello.bf");
+
+    #[test]
+    fn test_translate_round_trip() {
+        let fat = bf::parser().parse_fat_str(HELLO_BF).unwrap();
+        let program = fat.to_program();
+
+        let ook = translate(&fat, &ook::render_spec(), &TranslateOptions::default());
+        let ook_fat = ook::parser().parse_fat_str(&ook).unwrap();
+        assert_eq!(ook_fat.to_program().instructions(), program.instructions());
+        assert_eq!(ook_fat.tokens().len(), fat.tokens().len());
+
+        let opts = TranslateOptions {
+            source: Some(&ook),
+            ..TranslateOptions::default()
+        };
+        let bf = translate(&ook_fat, &bf::render_spec(), &opts);
+        assert_eq!(
+            bf::parser().parse_str(&bf).unwrap().instructions(),
+            program.instructions()
+        );
+    }
+
+    #[test]
+    fn test_translate_keeping_layout() {
+        let source = "+++ add three\n\n  [->+<] move\n";
+        let fat = bf::parser().parse_fat_str(source).unwrap();
+        let opts = TranslateOptions {
+            source: Some(source),
+            ..TranslateOptions::default()
+        };
+        assert_eq!(
+            translate(&fat, &ook::render_spec(), &opts),
+            "Ook. Ook. Ook. Ook. Ook. Ook.\n\
+             \n  \
+             Ook! Ook? Ook! Ook! Ook. Ook? Ook. Ook. Ook? Ook. Ook? Ook!\n",
+        );
+    }
+}
@@ -35,18 +35,38 @@
 //! ```
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod analysis;
+#[cfg(feature = "capi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "capi")))]
+pub mod capi;
+pub mod codegen;
+pub mod coverage;
 pub mod error;
+pub mod fmt;
+pub mod optimize;
 pub mod parser;
-#[cfg(any(feature = "bf", feature = "ook"))]
+#[cfg(any(feature = "asm", feature = "bf", feature = "ook"))]
 pub mod predefined;
+mod prng;
 pub mod program;
 pub mod runtime;
+#[cfg(any(test, feature = "testing"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
 pub mod token;
+pub mod visual;
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use libbf_macros::bf_str as __bf_str;
 
 /// `use libbf::prelude::*` is easy way to use this library;
 pub mod prelude {
     pub use crate::error::*;
     pub use crate::parser::*;
+    #[cfg(feature = "asm")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "asm")))]
+    pub use crate::predefined::asm;
     #[cfg(feature = "bf")]
     #[cfg_attr(docsrs, doc(cfg(feature = "bf")))]
     pub use crate::predefined::bf;
@@ -55,7 +75,11 @@ pub mod prelude {
     pub use crate::predefined::ook;
     pub use crate::program::*;
     // exclude functions in runtime::*
-    pub use crate::runtime::{self, MemorySize, Runner, StepRunner, DEFAULT_MEMSIZE};
+    pub use crate::runtime::{
+        self, CellModulus, CellWidth, CostModel, EofBehavior, MachineState, MemorySize,
+        MeteredOutcome, Profile, Runner, RuntimeConfig, StepOutcome, StepRunner, WouldBlockPolicy,
+        DEFAULT_MEMSIZE,
+    };
     pub use crate::token::simple::*;
     pub use crate::token::*;
 }
@@ -0,0 +1,1003 @@
+//! Program optimizations.
+//!
+//! Each pass takes a program and returns a new program which behaves equivalently
+//! but runs faster.
+//!
+//! # Note
+//!
+//! Passes assume that cells wrap at a power of two (i.e. no
+//! [`CellModulus`](crate::runtime::CellModulus) is configured). For example, a loop `[--]` is
+//! regarded as terminating for any cell value, which is not true for other moduli.
+//! The values set by [`Instruction::DSet`] and [`Instruction::Fill`] are reduced by the modulus
+//! when they are run, so the cells stay in its range.
+//!
+//! Passes do not depend on the [`CellWidth`](crate::runtime::CellWidth): they fold or unroll
+//! only values in `[0, 255]`, which are the same for any width.
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use crate::program::{Instruction, Program, ProgramIndex};
+
+/// An optimization level, which determines the passes to be applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    /// No passes are applied.
+    None,
+    /// [`clear_loops`] is applied.
+    Basic,
+    /// [`clear_loops`], [`fill_clears`] and [`fold_sets`] are applied.
+    #[default]
+    Full,
+    /// The passes of [`OptLevel::Full`], [`unroll_loops`] with [`DEFAULT_UNROLL_BUDGET`] and
+    /// [`fuse_adds`] are applied.
+    Aggressive,
+}
+
+/// The budget of [`unroll_loops`] applied by [`OptLevel::Aggressive`].
+pub const DEFAULT_UNROLL_BUDGET: usize = 64;
+
+/// An optimization pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Pass {
+    /// [`clear_loops`]
+    ClearLoops,
+    /// [`fill_clears`]
+    FillClears,
+    /// [`fold_sets`]
+    FoldSets,
+    /// [`unroll_loops`]
+    UnrollLoops,
+    /// [`fuse_adds`]
+    FuseAdds,
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClearLoops => write!(f, "clear-loops"),
+            Self::FillClears => write!(f, "fill-clears"),
+            Self::FoldSets => write!(f, "fold-sets"),
+            Self::UnrollLoops => write!(f, "unroll-loops"),
+            Self::FuseAdds => write!(f, "fuse-adds"),
+        }
+    }
+}
+
+/// A rewrite applied by an optimization pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    /// The pass which applied the rewrite.
+    pub pass: Pass,
+    /// The index of the first rewritten instruction in the program which the pass was applied to.
+    pub index: ProgramIndex,
+    /// The rewritten instructions.
+    pub before: Vec<Instruction>,
+    /// The instructions which replaced `before`.
+    pub after: Vec<Instruction>,
+}
+
+impl fmt::Display for Rewrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {:?} -> {:?}",
+            self.index, self.pass, self.before, self.after
+        )
+    }
+}
+
+/// A report of rewrites applied by [`optimize_with_report`].
+///
+/// Its [`Display`](fmt::Display) lists one rewrite per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptReport {
+    /// The applied rewrites, in the order of application.
+    pub rewrites: Vec<Rewrite>,
+}
+
+impl fmt::Display for OptReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rewrite in &self.rewrites {
+            writeln!(f, "{rewrite}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Apply all optimization passes to the program.
+pub fn optimize(program: &Program) -> Program {
+    optimize_with_report(program, OptLevel::Full).0
+}
+
+/// Apply optimization passes for `level` to the program and report the applied rewrites.
+pub fn optimize_with_report(program: &Program, level: OptLevel) -> (Program, OptReport) {
+    let mut report = OptReport::default();
+    let mut instructions = program.instructions().to_vec();
+    if level >= OptLevel::Basic {
+        instructions = clear_loops_internal(&instructions, &mut vec![], &mut report.rewrites);
+    }
+    if level >= OptLevel::Full {
+        instructions = fill_clears_internal(&instructions, &mut vec![], &mut report.rewrites);
+        instructions = fold_sets_internal(&instructions, &mut vec![], &mut report.rewrites);
+    }
+    if level >= OptLevel::Aggressive {
+        instructions = unroll_loops_internal(
+            &instructions,
+            DEFAULT_UNROLL_BUDGET,
+            KnownCells::initial(),
+            &mut vec![],
+            &mut report.rewrites,
+        );
+        if needs_fusing(&instructions) {
+            instructions = fuse_adds_internal(&instructions, &mut vec![], &mut report.rewrites);
+        }
+    }
+    (Program::new(instructions), report)
+}
+
+/// Rewrite clear loops (e.g. `[-]`) into [`Instruction::SetZero`].
+pub fn clear_loops(program: &Program) -> Program {
+    Program::new(clear_loops_internal(
+        program.instructions(),
+        &mut vec![],
+        &mut vec![],
+    ))
+}
+
+fn clear_loops_internal(
+    instructions: &[Instruction],
+    path: &mut Vec<usize>,
+    rewrites: &mut Vec<Rewrite>,
+) -> Vec<Instruction> {
+    let mut result = Vec::with_capacity(instructions.len());
+    for (i, instruction) in instructions.iter().enumerate() {
+        path.push(i);
+        result.push(match instruction {
+            _ if is_clear_loop(instruction) => {
+                rewrites.push(Rewrite {
+                    pass: Pass::ClearLoops,
+                    index: ProgramIndex::from_path(path.as_slice()),
+                    before: vec![instruction.clone()],
+                    after: vec![Instruction::SetZero],
+                });
+                Instruction::SetZero
+            }
+            Instruction::UntilZero(sub) => {
+                Instruction::UntilZero(clear_loops_internal(sub, path, rewrites))
+            }
+            other => other.clone(),
+        });
+        path.pop();
+    }
+    result
+}
+
+/// Rewrite runs of clear-and-move (`[-]>[-]>...`) into [`Instruction::Fill`].
+///
+/// A run of `n` (at least 2) clear loops (or [`Instruction::SetZero`]) each followed by `>`
+/// is rewritten into `Fill { len: n, value: 0 }` and a pointer move.
+/// The `>` after the last clear loop is optional.
+pub fn fill_clears(program: &Program) -> Program {
+    Program::new(fill_clears_internal(
+        program.instructions(),
+        &mut vec![],
+        &mut vec![],
+    ))
+}
+
+fn fill_clears_internal(
+    instructions: &[Instruction],
+    path: &mut Vec<usize>,
+    rewrites: &mut Vec<Rewrite>,
+) -> Vec<Instruction> {
+    let mut result = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+    while i < instructions.len() {
+        path.push(i);
+        let (len, consumed, moved) = clear_run(&instructions[i..]);
+        if len >= 2 {
+            let after = vec![
+                Instruction::Fill { len, value: 0 },
+                Instruction::PAdd(moved as isize),
+            ];
+            rewrites.push(Rewrite {
+                pass: Pass::FillClears,
+                index: ProgramIndex::from_path(path.as_slice()),
+                before: instructions[i..i + consumed].to_vec(),
+                after: after.clone(),
+            });
+            result.extend(after);
+            i += consumed;
+        } else {
+            result.push(match &instructions[i] {
+                Instruction::UntilZero(sub) => {
+                    Instruction::UntilZero(fill_clears_internal(sub, path, rewrites))
+                }
+                other => other.clone(),
+            });
+            i += 1;
+        }
+        path.pop();
+    }
+    result
+}
+
+/// Fold [`Instruction::SetZero`] immediately followed by [`Instruction::DAdd`] into
+/// [`Instruction::DSet`] (e.g. `[-]+++` into `DSet(3)`).
+///
+/// An addition out of `[0, 255]` is not folded, since its result depends on the cell width.
+pub fn fold_sets(program: &Program) -> Program {
+    Program::new(fold_sets_internal(
+        program.instructions(),
+        &mut vec![],
+        &mut vec![],
+    ))
+}
+
+fn fold_sets_internal(
+    instructions: &[Instruction],
+    path: &mut Vec<usize>,
+    rewrites: &mut Vec<Rewrite>,
+) -> Vec<Instruction> {
+    let mut result = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+    while i < instructions.len() {
+        path.push(i);
+        match &instructions[i..] {
+            [Instruction::SetZero, Instruction::DAdd(n), ..] if (0..=255).contains(n) => {
+                let after = Instruction::DSet(*n as u8);
+                rewrites.push(Rewrite {
+                    pass: Pass::FoldSets,
+                    index: ProgramIndex::from_path(path.as_slice()),
+                    before: instructions[i..i + 2].to_vec(),
+                    after: vec![after.clone()],
+                });
+                result.push(after);
+                i += 2;
+            }
+            [Instruction::UntilZero(sub), ..] => {
+                result.push(Instruction::UntilZero(fold_sets_internal(
+                    sub, path, rewrites,
+                )));
+                i += 1;
+            }
+            [other, ..] => {
+                result.push(other.clone());
+                i += 1;
+            }
+            [] => unreachable!(),
+        }
+        path.pop();
+    }
+    result
+}
+
+/// Fully unroll loops whose trip counts are statically known and small.
+///
+/// A loop is unrolled if
+///
+///  - the cell governing it has a known value `n`, e.g. set by [`Instruction::DSet`] or
+///    [`Instruction::DAdd`] to a cell which is untouched since the program started,
+///  - its body consists of instructions other than loops and [`Instruction::Input`],
+///    and moves the pointer back to where it started,
+///  - the body changes the governing cell only by [`Instruction::DAdd`], by -1 in total, and
+///  - `n` times the length of the body is at most `budget`.
+///
+/// Such a loop is replaced with `n` copies of its body, where adjacent pointer and data
+/// increments are merged. A loop with `n` = 0 is removed. Any uncertainty (e.g. after a loop
+/// which is not unrolled, or after an input) makes the cells unknown, so the loops after it
+/// are kept until the cells are set again.
+///
+/// It is useful after [`fold_sets`], which turns clear-and-add sequences into known values.
+pub fn unroll_loops(program: &Program, budget: usize) -> Program {
+    Program::new(unroll_loops_internal(
+        program.instructions(),
+        budget,
+        KnownCells::initial(),
+        &mut vec![],
+        &mut vec![],
+    ))
+}
+
+fn unroll_loops_internal(
+    instructions: &[Instruction],
+    budget: usize,
+    mut known: KnownCells,
+    path: &mut Vec<usize>,
+    rewrites: &mut Vec<Rewrite>,
+) -> Vec<Instruction> {
+    let mut result = Vec::with_capacity(instructions.len());
+    for (i, instruction) in instructions.iter().enumerate() {
+        path.push(i);
+        match instruction {
+            Instruction::UntilZero(body) => match known.current() {
+                Some(n) if is_countdown_body(body) && n as usize * body.len() <= budget => {
+                    let mut after = Vec::with_capacity(n as usize * body.len());
+                    for instruction in body.iter().cycle().take(n as usize * body.len()) {
+                        push_merged(&mut after, instruction.clone());
+                    }
+                    for instruction in &after {
+                        known.apply(instruction);
+                    }
+                    rewrites.push(Rewrite {
+                        pass: Pass::UnrollLoops,
+                        index: ProgramIndex::from_path(path.as_slice()),
+                        before: vec![instruction.clone()],
+                        after: after.clone(),
+                    });
+                    result.extend(after);
+                }
+                _ => {
+                    let body =
+                        unroll_loops_internal(body, budget, KnownCells::unknown(), path, rewrites);
+                    known.apply(instruction);
+                    result.push(Instruction::UntilZero(body));
+                }
+            },
+            other => {
+                known.apply(other);
+                result.push(other.clone());
+            }
+        }
+        path.pop();
+    }
+    result
+}
+
+/// Fuse adjacent pointer or data increments (e.g. `PAdd(2), PAdd(-1)` into `PAdd(1)`),
+/// and remove the ones which cancel out.
+///
+/// Returns the program and `true` if it is changed. If there is nothing to fuse, the program is
+/// returned as is without rebuilding it, which makes repeated optimization cheap.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{optimize::fuse_adds, program::{Instruction::*, Program}};
+///
+/// let program = Program::new([DAdd(1), DAdd(2), PAdd(1), PAdd(-1)]);
+/// let (fused, changed) = fuse_adds(&program);
+/// assert!(changed);
+/// assert_eq!(fused.instructions(), [DAdd(3)]);
+///
+/// let (_, changed) = fuse_adds(&fused);
+/// assert!(!changed);
+/// ```
+pub fn fuse_adds(program: &Program) -> (Cow<'_, Program>, bool) {
+    if !needs_fusing(program.instructions()) {
+        return (Cow::Borrowed(program), false);
+    }
+    let instructions = fuse_adds_internal(program.instructions(), &mut vec![], &mut vec![]);
+    (Cow::Owned(Program::new(instructions)), true)
+}
+
+fn fuse_adds_internal(
+    instructions: &[Instruction],
+    path: &mut Vec<usize>,
+    rewrites: &mut Vec<Rewrite>,
+) -> Vec<Instruction> {
+    let mut result = Vec::with_capacity(instructions.len());
+    let mut i = 0;
+    while i < instructions.len() {
+        path.push(i);
+        let run = instructions[i..]
+            .iter()
+            .take_while(|instruction| same_add_kind(instruction, &instructions[i]))
+            .count();
+        if run >= 2 || is_zero_add(&instructions[i]) {
+            let mut after = vec![];
+            for instruction in &instructions[i..i + run] {
+                push_merged(&mut after, instruction.clone());
+            }
+            after.retain(|instruction| !is_zero_add(instruction));
+            rewrites.push(Rewrite {
+                pass: Pass::FuseAdds,
+                index: ProgramIndex::from_path(path.as_slice()),
+                before: instructions[i..i + run].to_vec(),
+                after: after.clone(),
+            });
+            result.extend(after);
+            i += run;
+        } else {
+            result.push(match &instructions[i] {
+                Instruction::UntilZero(sub) if needs_fusing(sub) => {
+                    Instruction::UntilZero(fuse_adds_internal(sub, path, rewrites))
+                }
+                other => other.clone(),
+            });
+            i += 1;
+        }
+        path.pop();
+    }
+    result
+}
+
+// Returns `true` if `fuse_adds` changes the instructions.
+fn needs_fusing(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instruction| match instruction {
+        Instruction::UntilZero(sub) => needs_fusing(sub),
+        other => is_zero_add(other),
+    }) || instructions
+        .windows(2)
+        .any(|pair| same_add_kind(&pair[0], &pair[1]))
+}
+
+// Returns `true` if both are pointer increments or both are data increments.
+fn same_add_kind(a: &Instruction, b: &Instruction) -> bool {
+    matches!(
+        (a, b),
+        (Instruction::PAdd(_), Instruction::PAdd(_)) | (Instruction::DAdd(_), Instruction::DAdd(_))
+    )
+}
+
+fn is_zero_add(instruction: &Instruction) -> bool {
+    matches!(instruction, Instruction::PAdd(0) | Instruction::DAdd(0))
+}
+
+// Cell values known at compile time.
+struct KnownCells {
+    // the pointer relative to where the tracking started
+    pointer: isize,
+    // values of touched cells without wrapping around, `None` for unknown ones
+    cells: HashMap<isize, Option<isize>>,
+    // `true` if untouched cells are 0, i.e. the tracking started with the program
+    untouched_zero: bool,
+}
+
+impl KnownCells {
+    fn initial() -> Self {
+        Self {
+            pointer: 0,
+            cells: HashMap::new(),
+            untouched_zero: true,
+        }
+    }
+
+    fn unknown() -> Self {
+        Self {
+            untouched_zero: false,
+            ..Self::initial()
+        }
+    }
+
+    // The value of the current cell if it is known and the cell has been touched.
+    //
+    // A value out of [0, 255] is regarded as unknown since it depends on the cell width.
+    fn current(&self) -> Option<u8> {
+        self.cells
+            .get(&self.pointer)
+            .copied()
+            .flatten()
+            .and_then(|value| u8::try_from(value).ok())
+    }
+
+    fn apply(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::PAdd(n) => self.pointer += n,
+            Instruction::DAdd(n) => {
+                let old = match self.cells.get(&self.pointer) {
+                    Some(value) => *value,
+                    None if self.untouched_zero => Some(0),
+                    None => None,
+                };
+                let new = old.map(|old| old.wrapping_add(*n));
+                self.cells.insert(self.pointer, new);
+            }
+            Instruction::SetZero => {
+                self.cells.insert(self.pointer, Some(0));
+            }
+            Instruction::DSet(value) => {
+                self.cells.insert(self.pointer, Some((*value).into()));
+            }
+            Instruction::Fill { len, value } => {
+                for offset in 0..*len as isize {
+                    self.cells
+                        .insert(self.pointer + offset, Some((*value).into()));
+                }
+            }
+            Instruction::Input => {
+                self.cells.insert(self.pointer, None);
+            }
+            Instruction::UntilZero(_) => {
+                // The body may change any cell and move the pointer anywhere,
+                // but the loop ends at a zero cell.
+                *self = Self::unknown();
+                self.cells.insert(0, Some(0));
+            }
+            Instruction::Output | Instruction::Breakpoint | Instruction::AssertCell { .. } => {}
+        }
+    }
+}
+
+// Returns `true` if `body` has no loops and no inputs, moves the pointer back to where it started,
+// and changes the cell there only by decrementing it by 1 in total (without wrapping around).
+fn is_countdown_body(body: &[Instruction]) -> bool {
+    let mut offset = 0;
+    let mut delta = 0;
+    for instruction in body {
+        match instruction {
+            Instruction::PAdd(n) => offset += n,
+            Instruction::DAdd(n) if offset == 0 => delta += n,
+            Instruction::SetZero | Instruction::DSet(_) if offset == 0 => return false,
+            Instruction::Fill { len, .. } if (offset..offset + *len as isize).contains(&0) => {
+                return false
+            }
+            Instruction::Input | Instruction::UntilZero(_) => return false,
+            _ => {}
+        }
+    }
+    offset == 0 && delta == -1
+}
+
+// Push an instruction, merging it into the last one if both are pointer or data increments.
+fn push_merged(instructions: &mut Vec<Instruction>, instruction: Instruction) {
+    match (instructions.last_mut(), &instruction) {
+        (Some(Instruction::PAdd(last)), Instruction::PAdd(n))
+        | (Some(Instruction::DAdd(last)), Instruction::DAdd(n)) => {
+            *last += n;
+            if *last == 0 {
+                instructions.pop();
+            }
+        }
+        _ => instructions.push(instruction),
+    }
+}
+
+// Find a run of clear-and-move at the head of instructions.
+//
+// Returns the number of cleared cells, the number of consumed instructions
+// and the distance the pointer moves.
+fn clear_run(instructions: &[Instruction]) -> (usize, usize, usize) {
+    let mut len = 0;
+    let mut consumed = 0;
+    let mut moved = 0;
+    while consumed < instructions.len() && clears(&instructions[consumed]) {
+        len += 1;
+        consumed += 1;
+        if let Some(Instruction::PAdd(1)) = instructions.get(consumed) {
+            moved += 1;
+            consumed += 1;
+        } else {
+            break;
+        }
+    }
+    (len, consumed, moved)
+}
+
+// Returns `true` if the instruction just clears the current cell.
+fn clears(instruction: &Instruction) -> bool {
+    *instruction == Instruction::SetZero || is_clear_loop(instruction)
+}
+
+// Returns `true` if the instruction is a loop which just clears the current cell (e.g. `[-]`).
+fn is_clear_loop(instruction: &Instruction) -> bool {
+    if let Instruction::UntilZero(sub) = instruction {
+        matches!(sub[..], [Instruction::DAdd(n)] if n % 2 != 0)
+    } else {
+        false
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime;
+    use Instruction::*;
+
+    fn clear() -> Instruction {
+        UntilZero(vec![DAdd(-1)])
+    }
+
+    fn run(program: &Program, input: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        if let Err(e) = runtime::run(program, input, &mut output) {
+            panic!("unexpected error: {e}");
+        }
+        output
+    }
+
+    #[test]
+    fn test_fill_clears() {
+        let program = Program::new([
+            clear(),
+            PAdd(1),
+            clear(),
+            PAdd(1),
+            clear(),
+            PAdd(1),
+            UntilZero(vec![clear(), PAdd(1), UntilZero(vec![DAdd(1)])]),
+            clear(),
+            PAdd(1),
+        ]);
+        assert_eq!(
+            fill_clears(&program).instructions(),
+            [
+                Fill { len: 3, value: 0 },
+                PAdd(3),
+                UntilZero(vec![Fill { len: 2, value: 0 }, PAdd(1)]),
+                clear(),
+                PAdd(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_clears_equivalent_memory() {
+        // read 5 cells, clear cells 1..=3, then write all 5 cells.
+        let program = Program::new([
+            Input,
+            PAdd(1),
+            Input,
+            PAdd(1),
+            Input,
+            PAdd(1),
+            Input,
+            PAdd(1),
+            Input,
+            PAdd(-3),
+            clear(),
+            PAdd(1),
+            UntilZero(vec![DAdd(3)]),
+            PAdd(1),
+            clear(),
+            PAdd(-3),
+            Output,
+            PAdd(1),
+            Output,
+            PAdd(1),
+            Output,
+            PAdd(1),
+            Output,
+            PAdd(1),
+            Output,
+        ]);
+        let optimized = fill_clears(&program);
+        assert!(optimized
+            .instructions()
+            .contains(&Fill { len: 3, value: 0 }));
+
+        let input = [1, 2, 3, 4, 5];
+        assert_eq!(run(&optimized, &input), run(&program, &input));
+        assert_eq!(run(&optimized, &input), [1, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_fill_out_of_memory_bounds() {
+        let program = Program::new([PAdd(29998), clear(), PAdd(1), clear(), PAdd(1), clear()]);
+        let optimized = fill_clears(&program);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        match runtime::run(&optimized, input, &mut output) {
+            Err(crate::error::RuntimeError::OutOfMemoryBounds { address, .. }) => {
+                assert_eq!(address, 30000)
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        // a length which exceeds the address space
+        for len in [isize::MAX as usize, usize::MAX] {
+            let program = Program::new([PAdd(1), Fill { len, value: 0 }]);
+            match runtime::run(&program, input, &mut output) {
+                Err(crate::error::RuntimeError::OutOfMemoryBounds { address, .. }) => {
+                    assert_eq!(address, 1)
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_clear_loops() {
+        let program = Program::new([
+            DAdd(3),
+            clear(),
+            UntilZero(vec![PAdd(1), UntilZero(vec![DAdd(1)])]),
+            UntilZero(vec![DAdd(-2)]),
+            UntilZero(vec![PAdd(1), DAdd(-1)]),
+        ]);
+        assert_eq!(
+            clear_loops(&program).instructions(),
+            [
+                DAdd(3),
+                SetZero,
+                UntilZero(vec![PAdd(1), SetZero]),
+                UntilZero(vec![DAdd(-2)]),
+                UntilZero(vec![PAdd(1), DAdd(-1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optimize_with_report() {
+        // +++[-+-][-]. (the first loop is not a clear loop)
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![DAdd(-1), DAdd(1), DAdd(-1)]),
+            clear(),
+            Output,
+        ]);
+        let (optimized, report) = optimize_with_report(&program, OptLevel::Basic);
+        assert_eq!(
+            optimized.instructions(),
+            [DAdd(3), program.instructions()[1].clone(), SetZero, Output]
+        );
+        assert_eq!(
+            report.rewrites,
+            [Rewrite {
+                pass: Pass::ClearLoops,
+                index: ProgramIndex::new_for_test([2]),
+                before: vec![clear()],
+                after: vec![SetZero],
+            }]
+        );
+        assert_eq!(
+            report.to_string(),
+            "[2]: clear-loops: [UntilZero([DAdd(-1)])] -> [SetZero]\n"
+        );
+
+        let (optimized, report) = optimize_with_report(&program, OptLevel::None);
+        assert_eq!(optimized.instructions(), program.instructions());
+        assert!(report.rewrites.is_empty());
+
+        let program = Program::new([clear(), PAdd(1), clear(), Output]);
+        let (optimized, report) = optimize_with_report(&program, OptLevel::Full);
+        assert_eq!(
+            optimized.instructions(),
+            [Fill { len: 2, value: 0 }, PAdd(1), Output]
+        );
+        let passes = report.rewrites.iter().map(|r| r.pass).collect::<Vec<_>>();
+        assert_eq!(
+            passes,
+            [Pass::ClearLoops, Pass::ClearLoops, Pass::FillClears]
+        );
+        assert_eq!(run(&optimized, &[]), run(&program, &[]));
+    }
+
+    #[test]
+    fn test_fold_sets() {
+        let program = Program::new([
+            SetZero,
+            DAdd(65),
+            Output,
+            PAdd(1),
+            SetZero,
+            DAdd(-1),
+            Output,
+        ]);
+        let folded = fold_sets(&program);
+        assert_eq!(
+            folded.instructions(),
+            [DSet(65), Output, PAdd(1), SetZero, DAdd(-1), Output]
+        );
+        assert_eq!(run(&folded, &[]), b"A\xff");
+
+        // a pointer move in between prevents folding.
+        let program = Program::new([SetZero, PAdd(1), DAdd(65)]);
+        assert_eq!(fold_sets(&program).instructions(), program.instructions());
+
+        // ++++++++[>++++++++<-]>+[-] followed by 65 `+` and `.`
+        let program = Program::new([
+            DAdd(8),
+            UntilZero(vec![PAdd(1), DAdd(8), PAdd(-1), DAdd(-1)]),
+            PAdd(1),
+            DAdd(1),
+            clear(),
+            DAdd(65),
+            Output,
+        ]);
+        let optimized = optimize(&program);
+        assert!(optimized.instructions().contains(&DSet(65)));
+        assert_eq!(run(&optimized, &[]), b"A");
+    }
+
+    #[test]
+    fn test_unroll_loops() {
+        // +++[>++<-]>.
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![PAdd(1), DAdd(2), PAdd(-1), DAdd(-1)]),
+            PAdd(1),
+            Output,
+        ]);
+        let unrolled = unroll_loops(&program, 12);
+        assert!(!unrolled
+            .instructions()
+            .iter()
+            .any(|instruction| matches!(instruction, UntilZero(_))));
+        assert_eq!(unrolled.instructions().len(), 1 + 3 * 4 + 2);
+        assert_eq!(run(&unrolled, &[]), run(&program, &[]));
+        assert_eq!(run(&unrolled, &[]), [6]);
+
+        // over the budget
+        assert_eq!(
+            unroll_loops(&program, 11).instructions(),
+            program.instructions()
+        );
+
+        // a loop which is never entered is removed.
+        let program = Program::new([DSet(0), UntilZero(vec![Output, DAdd(-1)]), DAdd(1)]);
+        assert_eq!(unroll_loops(&program, 0).instructions(), [DSet(0), DAdd(1)]);
+
+        // nested in a loop, with the value set in the loop body
+        let program = Program::new([
+            Input,
+            UntilZero(vec![
+                PAdd(1),
+                DSet(2),
+                UntilZero(vec![PAdd(1), DAdd(1), Output, PAdd(-1), DAdd(-1)]),
+                PAdd(-1),
+                DAdd(-1),
+            ]),
+        ]);
+        let unrolled = unroll_loops(&program, 64);
+        let UntilZero(body) = &unrolled.instructions()[1] else {
+            panic!("not a loop: {:?}", unrolled.instructions()[1]);
+        };
+        assert!(!body
+            .iter()
+            .any(|instruction| matches!(instruction, UntilZero(_))));
+        for input in [[0], [1], [3]] {
+            assert_eq!(run(&unrolled, &input), run(&program, &input));
+        }
+    }
+
+    #[test]
+    fn test_unroll_loops_bails() {
+        let loops = [
+            // the governing cell is read from the input
+            vec![Input, UntilZero(vec![Output, DAdd(-1)])],
+            // the governing cell is not decremented by 1
+            vec![DAdd(4), UntilZero(vec![Output, DAdd(-2)])],
+            // the body overwrites the governing cell
+            vec![DAdd(4), UntilZero(vec![DSet(1), DAdd(-1)])],
+            vec![
+                DAdd(4),
+                UntilZero(vec![PAdd(-1), Fill { len: 2, value: 1 }, PAdd(1), DAdd(-1)]),
+            ],
+            // the body moves the pointer
+            vec![DAdd(4), UntilZero(vec![PAdd(1), DAdd(-1)])],
+            // the body has an inner loop or an input
+            vec![
+                DAdd(4),
+                UntilZero(vec![PAdd(1), UntilZero(vec![PAdd(1)]), PAdd(-1), DAdd(-1)]),
+            ],
+            vec![DAdd(4), UntilZero(vec![PAdd(1), Input, PAdd(-1), DAdd(-1)])],
+            // a previous loop makes the cells unknown
+            vec![
+                DAdd(1),
+                PAdd(1),
+                DAdd(4),
+                PAdd(-1),
+                UntilZero(vec![PAdd(1)]),
+                PAdd(-1),
+                UntilZero(vec![Output, DAdd(-1)]),
+            ],
+        ];
+        for instructions in loops {
+            let program = Program::new(instructions);
+            assert_eq!(
+                unroll_loops(&program, 1000).instructions(),
+                program.instructions()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cell_width_independent() {
+        use runtime::{CellWidth, RuntimeConfig};
+        // +[-] +*300 -*44 [-*128 >+<] >.
+        let program = Program::new([
+            DAdd(1),
+            clear(),
+            DAdd(300),
+            DAdd(-44),
+            UntilZero(vec![DAdd(-128), PAdd(1), DAdd(1), PAdd(-1)]),
+            PAdd(1),
+            Output,
+        ]);
+        let run_with_width = |program: &Program, cell_width| {
+            let mut output = vec![];
+            let config = RuntimeConfig::default().with_cell_width(cell_width);
+            runtime::run_with_config(program, &[][..], &mut output, config).unwrap();
+            output
+        };
+        for level in [OptLevel::Basic, OptLevel::Full, OptLevel::Aggressive] {
+            let (optimized, _) = optimize_with_report(&program, level);
+            for cell_width in [CellWidth::U8, CellWidth::U16] {
+                assert_eq!(
+                    run_with_width(&optimized, cell_width),
+                    run_with_width(&program, cell_width),
+                    "{level:?}, {cell_width:?}"
+                );
+            }
+        }
+        assert_eq!(run_with_width(&program, CellWidth::U16), [2]);
+
+        // a value out of [0, 255] is neither folded nor unrolled.
+        let program = Program::new([SetZero, DAdd(-1), UntilZero(vec![DAdd(-1)])]);
+        assert_eq!(fold_sets(&program).instructions(), program.instructions());
+        let program = Program::new([DAdd(256), UntilZero(vec![PAdd(1), PAdd(-1), DAdd(-1)])]);
+        assert_eq!(
+            unroll_loops(&program, 10000).instructions(),
+            program.instructions()
+        );
+    }
+
+    #[test]
+    fn test_unroll_loops_after_fold_sets() {
+        use crate::runtime::{Runner, UnitCost};
+
+        let steps = |program: &Program| {
+            Runner::new(program, &[][..], std::io::sink())
+                .run_metered(UnitCost, u64::MAX)
+                .unwrap()
+                .spent()
+        };
+        // >[-]+++++[<++++++++++>-]<+++++.>[-]++++[<+>-]<.
+        let program = Program::new([
+            PAdd(1),
+            clear(),
+            DAdd(5),
+            UntilZero(vec![PAdd(-1), DAdd(10), PAdd(1), DAdd(-1)]),
+            PAdd(-1),
+            DAdd(5),
+            Output,
+            PAdd(1),
+            clear(),
+            DAdd(4),
+            UntilZero(vec![PAdd(-1), DAdd(1), PAdd(1), DAdd(-1)]),
+            PAdd(-1),
+            Output,
+        ]);
+        let (full, _) = optimize_with_report(&program, OptLevel::Full);
+        let (aggressive, report) = optimize_with_report(&program, OptLevel::Aggressive);
+        assert_eq!(run(&aggressive, &[]), run(&program, &[]));
+        assert_eq!(run(&aggressive, &[]), b"7;");
+
+        // Both multiplication loops are unrolled after their counters are set by `DSet`.
+        assert!(!aggressive
+            .instructions()
+            .iter()
+            .any(|instruction| matches!(instruction, UntilZero(_))));
+        let unrolled = report
+            .rewrites
+            .iter()
+            .filter(|rewrite| rewrite.pass == Pass::UnrollLoops)
+            .count();
+        assert_eq!(unrolled, 2);
+        assert!(steps(&aggressive) < steps(&full));
+        assert!(steps(&full) < steps(&program));
+    }
+
+    #[test]
+    fn test_fuse_adds() {
+        let program = Program::new([
+            DAdd(1),
+            DAdd(2),
+            PAdd(1),
+            UntilZero(vec![PAdd(1), PAdd(-1), DAdd(0), Output, DAdd(-1)]),
+            PAdd(-1),
+            PAdd(0),
+            Input,
+        ]);
+        let (fused, changed) = fuse_adds(&program);
+        assert!(changed);
+        assert_eq!(
+            fused.instructions(),
+            [
+                DAdd(3),
+                PAdd(1),
+                UntilZero(vec![Output, DAdd(-1)]),
+                PAdd(-1),
+                Input
+            ]
+        );
+        for input in [[0], [7]] {
+            assert_eq!(run(&fused, &input), run(&program, &input));
+        }
+
+        // nothing to fuse in the second time
+        let (again, changed) = fuse_adds(&fused);
+        assert!(!changed);
+        assert!(matches!(again, Cow::Borrowed(p) if std::ptr::eq(p, fused.as_ref())));
+    }
+}
@@ -0,0 +1,100 @@
+//! Bracket matching.
+use crate::{
+    error::ParseError,
+    token::{TokenStream, TokenType, Tokenizer},
+};
+
+/// Finds the matching loop head and tail tokens in a source.
+///
+/// Returns the pairs of positions (in Unicode scalar units) of each loop head and its loop tail,
+/// sorted by the loop head position. Unlike [`Parser::parse_str`](super::Parser::parse_str),
+/// no program is built.
+///
+/// # Errors
+///
+/// The same errors as [`Parser::parse_str`](super::Parser::parse_str) are returned for unbalanced loops.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{parser::bracket_pairs, token::simple::SimpleTokenSpec};
+///
+/// let tokenizer = SimpleTokenSpec {
+///     ptr_inc: '>',
+///     ptr_dec: '<',
+///     data_inc: '+',
+///     data_dec: '-',
+///     output: '.',
+///     input: ',',
+///     loop_head: '[',
+///     loop_tail: ']',
+/// }
+/// .to_tokenizer();
+///
+/// assert_eq!(bracket_pairs("+[-]", &tokenizer).unwrap(), [(1, 3)]);
+/// ```
+pub fn bracket_pairs<'a>(
+    source: &'a str,
+    tokenizer: &'a impl Tokenizer<'a>,
+) -> Result<Vec<(usize, usize)>, ParseError> {
+    let mut stream = tokenizer.token_stream(source);
+    let mut pairs = vec![];
+    // indices of unclosed pairs in `pairs`
+    let mut open: Vec<usize> = vec![];
+    loop {
+        let info = stream.next()?;
+        match info.token_type() {
+            None => {
+                return match open.last() {
+                    None => Ok(pairs),
+                    Some(&index) => Err(ParseError::UnexpectedEndOfFile {
+                        pos_in_chars: info.pos_in_chars,
+                        loop_head_pos_in_chars: Some(pairs[index].0),
+                    }),
+                };
+            }
+            Some(TokenType::LoopHead) => {
+                open.push(pairs.len());
+                pairs.push((info.pos_in_chars, 0));
+            }
+            Some(TokenType::LoopTail) => match open.pop() {
+                Some(index) => pairs[index].1 = info.pos_in_chars,
+                None => {
+                    return Err(ParseError::UnexpectedEndOfLoop {
+                        pos_in_chars: info.pos_in_chars,
+                    })
+                }
+            },
+            Some(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_spec;
+
+    #[test]
+    fn test_bracket_pairs() {
+        let tokenizer = bf_spec().to_tokenizer();
+        assert_eq!(bracket_pairs("[[]]", &tokenizer).unwrap(), [(0, 3), (1, 2)]);
+        assert_eq!(
+            bracket_pairs("い[-] [>[+]]", &tokenizer).unwrap(),
+            [(1, 3), (5, 10), (7, 9)]
+        );
+        assert!(bracket_pairs("no loops", &tokenizer).unwrap().is_empty());
+
+        assert!(matches!(
+            bracket_pairs("[[]", &tokenizer),
+            Err(ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 3,
+                loop_head_pos_in_chars: Some(0)
+            })
+        ));
+        assert!(matches!(
+            bracket_pairs("[]]", &tokenizer),
+            Err(ParseError::UnexpectedEndOfLoop { pos_in_chars: 2 })
+        ));
+    }
+}
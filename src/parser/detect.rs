@@ -0,0 +1,49 @@
+//! Dialect detection.
+use crate::predefined::Dialect;
+
+/// Detects the dialect of a source among `candidates`.
+///
+/// Each candidate is tried in order, and the first one which parses the source without error
+/// into a non-empty program is returned. If no candidates match, this returns `None`.
+///
+/// Since a source may be valid in several dialects, the order of `candidates` matters.
+/// [`Dialect::ALL`] lists the dialects in a suitable order.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{parser::detect_dialect, predefined::Dialect};
+///
+/// assert_eq!(detect_dialect("Ook. Ook! Ook! Ook.", Dialect::ALL), Some(Dialect::Ook));
+/// assert_eq!(detect_dialect(",.", Dialect::ALL), Some(Dialect::Bf));
+/// assert_eq!(detect_dialect("no tokens", Dialect::ALL), None);
+/// ```
+pub fn detect_dialect(source: &str, candidates: &[Dialect]) -> Option<Dialect> {
+    candidates.iter().copied().find(|dialect| {
+        dialect
+            .parse_str(source)
+            .is_ok_and(|program| !program.instructions().is_empty())
+    })
+}
+
+#[cfg(all(test, feature = "bf", feature = "ook"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect_dialect() {
+        let bf = include_str!("../../tests/data/hello.bf");
+        let ook = include_str!("../../tests/data/hello.ook");
+
+        assert_eq!(detect_dialect(bf, Dialect::ALL), Some(Dialect::Bf));
+        assert_eq!(detect_dialect(ook, Dialect::ALL), Some(Dialect::Ook));
+        // an Ook! source is also a Brainfuck source.
+        assert_eq!(
+            detect_dialect(ook, &[Dialect::Bf, Dialect::Ook]),
+            Some(Dialect::Bf)
+        );
+        assert_eq!(detect_dialect(bf, &[Dialect::Ook]), None);
+        // unbalanced loops
+        assert_eq!(detect_dialect("+[", Dialect::ALL), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Include directive preprocessor.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::Parser;
+use crate::{error::ParseOrIoError, program::Program, token::Tokenizer};
+
+/// Parses a program from the file at `path`, splicing files referenced by include directives.
+///
+/// An include directive is a line of the form `#include "file"` (surrounding whitespaces are allowed).
+/// The line is replaced with the contents of `file` before tokenizing.
+/// A relative path is resolved from the directory of the file which contains the directive.
+/// Included files may also contain include directives.
+///
+/// Since the directive is processed before tokenizing, it is recognized even if `#` is
+/// not a comment in the language. Positions in parse errors refer to the combined source.
+///
+/// # Errors
+///
+///  - [`ParseOrIoError::IoError`] if reading a file failed. It has the path of the file.
+///  - [`ParseOrIoError::IncludeCycle`] if a file includes itself directly or indirectly.
+///  - [`ParseOrIoError::ParseError`] if the combined source has a syntax error.
+///
+/// # Examples
+///
+/// Given `main.bf`:
+///
+/// ```text
+/// ++
+/// #include "move.bf"
+/// ```
+///
+/// and `move.bf`:
+///
+/// ```text
+/// [>+<-]
+/// ```
+///
+/// `parse_with_includes("main.bf", tokenizer)` parses `++[>+<-]`.
+pub fn parse_with_includes<T>(
+    path: impl AsRef<Path>,
+    tokenizer: T,
+) -> Result<Program, ParseOrIoError>
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    let mut source = String::new();
+    splice(path.as_ref(), &mut vec![], &mut source)?;
+    Ok(Parser::new(tokenizer).parse_str(&source)?)
+}
+
+// Append the contents of the file at `path` to `source`, expanding include directives.
+//
+// `stack` holds the canonical paths of the files being expanded, to detect cycles.
+fn splice(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    source: &mut String,
+) -> Result<(), ParseOrIoError> {
+    let canonical = fs::canonicalize(path).map_err(|e| ParseOrIoError::from(e).with_path(path))?;
+    if stack.contains(&canonical) {
+        return Err(ParseOrIoError::IncludeCycle {
+            path: path.to_path_buf(),
+        });
+    }
+    let contents = fs::read_to_string(path).map_err(|e| ParseOrIoError::from(e).with_path(path))?;
+
+    stack.push(canonical);
+    for line in contents.split_inclusive('\n') {
+        match include_target(line) {
+            Some(target) => {
+                let base = path.parent().unwrap_or(Path::new(""));
+                splice(&base.join(target), stack, source)?;
+                if !source.ends_with('\n') {
+                    source.push('\n');
+                }
+            }
+            None => source.push_str(line),
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+// Returns the target path if the line is an include directive.
+fn include_target(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#include")?
+        .trim_start()
+        .strip_prefix('"')?
+        .strip_suffix('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{program::Instruction::*, testing::bf_spec};
+
+    // Create an empty directory for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("libbf-include-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_include_target() {
+        assert_eq!(include_target("#include \"a.bf\"\n"), Some("a.bf"));
+        assert_eq!(
+            include_target("  #include   \"sub/a.bf\"  "),
+            Some("sub/a.bf")
+        );
+        assert_eq!(include_target("#include a.bf"), None);
+        assert_eq!(include_target("+ #include \"a.bf\""), None);
+    }
+
+    #[test]
+    fn test_parse_with_includes() {
+        let dir = test_dir("splice");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("main.bf"), "++\n#include \"lib/move.bf\"\n>.\n").unwrap();
+        fs::write(dir.join("lib/move.bf"), "[>+<-]").unwrap();
+
+        let program = parse_with_includes(dir.join("main.bf"), bf_spec().to_tokenizer()).unwrap();
+        assert_eq!(
+            program.instructions(),
+            [
+                DAdd(2),
+                UntilZero(vec![PAdd(1), DAdd(1), PAdd(-1), DAdd(-1)]),
+                PAdd(1),
+                Output
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_with_includes_cycle() {
+        let dir = test_dir("cycle");
+        fs::write(dir.join("a.bf"), "+\n#include \"b.bf\"\n").unwrap();
+        fs::write(dir.join("b.bf"), "-\n#include \"a.bf\"\n").unwrap();
+
+        match parse_with_includes(dir.join("a.bf"), bf_spec().to_tokenizer()) {
+            Err(ParseOrIoError::IncludeCycle { path }) => assert_eq!(path, dir.join("a.bf")),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+//! An incremental parser for editors.
+use std::ops::Range;
+
+use super::{Edit, Parser};
+use crate::{error::ParseError, program::FatProgram, token::Tokenizer};
+
+/// A parser which keeps a source and its parsed program up to date with edits.
+///
+/// Each edit is parsed by [`Parser::reparse`], so only the instructions around the edit
+/// are usually reparsed.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{parser::{IncrementalParser, Parser}, token::simple::SimpleTokenSpec};
+///
+/// let parser = Parser::new(
+///     SimpleTokenSpec {
+///         ptr_inc: '>',
+///         ptr_dec: '<',
+///         data_inc: '+',
+///         data_dec: '-',
+///         output: '.',
+///         input: ',',
+///         loop_head: '[',
+///         loop_tail: ']',
+///     }
+///     .to_tokenizer(),
+/// );
+/// let mut incremental = IncrementalParser::new(parser, "+[->+<]");
+/// incremental.apply_edit(7..7, ">.").unwrap();
+///
+/// assert_eq!(incremental.source(), "+[->+<]>.");
+/// assert_eq!(incremental.program().unwrap().instructions().len(), 4);
+/// assert!(incremental.apply_edit(0..1, "]").is_err());
+/// assert!(incremental.apply_edit(0..1, "").is_ok());
+/// ```
+pub struct IncrementalParser<T> {
+    parser: Parser<T>,
+    source: String,
+    program: Result<FatProgram, ParseError>,
+}
+
+impl<T> IncrementalParser<T>
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    /// Create a new incremental parser and parse `source`.
+    pub fn new(parser: Parser<T>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let program = parser.parse_fat_str(&source);
+        Self {
+            parser,
+            source,
+            program,
+        }
+    }
+
+    /// Get the current source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Get the program parsed from the current source, or the error if it failed to parse.
+    pub fn program(&self) -> Result<&FatProgram, &ParseError> {
+        self.program.as_ref()
+    }
+
+    /// Replace the characters in `range_in_chars` of the source with `replacement`,
+    /// and reparse it.
+    ///
+    /// Positions are counted in Unicode scalar units. The source is updated even if it fails
+    /// to parse. In that case, the next edit parses the whole source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range_in_chars` is out of the source or its start is greater than its end.
+    pub fn apply_edit(
+        &mut self,
+        range_in_chars: Range<usize>,
+        replacement: &str,
+    ) -> Result<(), ParseError> {
+        let byte_at = |pos: usize| {
+            self.source
+                .char_indices()
+                .map(|(offset, _)| offset)
+                .chain([self.source.len()])
+                .nth(pos)
+                .expect("edit range out of the source")
+        };
+        let start = byte_at(range_in_chars.start);
+        let end = byte_at(range_in_chars.end);
+        self.source.replace_range(start..end, replacement);
+
+        let edit = Edit::new(range_in_chars, replacement.chars().count());
+        self.program = match &self.program {
+            Ok(old) => self.parser.reparse(old, &self.source, &edit),
+            Err(_) => self.parser.parse_fat_str(&self.source),
+        };
+        self.program.as_ref().map(|_| ()).map_err(Clone::clone)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_parser;
+
+    #[test]
+    fn test_random_edit_sequences() {
+        let full = bf_parser();
+        let mut generator = crate::testing::Generator::new(0x9e37_79b9_7f4a_7c15);
+        let mut next = || generator.next_u64();
+        const CHARS: &[char] = &['>', '<', '+', '-', '.', ',', '[', ']', ' ', 'x', 'い'];
+        for _ in 0..50 {
+            let mut incremental = IncrementalParser::new(bf_parser(), "+[->+<]>.");
+            for _ in 0..100 {
+                let len = incremental.source().chars().count() as u64;
+                let start = (next() % (len + 1)) as usize;
+                let end = start + (next() % (len + 1 - start as u64)).min(3) as usize;
+                let replacement = (0..next() % 4)
+                    .map(|_| CHARS[(next() % CHARS.len() as u64) as usize])
+                    .collect::<String>();
+                let result = incremental.apply_edit(start..end, &replacement);
+
+                let expected = full.parse_fat_str(incremental.source());
+                assert_eq!(result.is_ok(), expected.is_ok());
+                match (incremental.program(), expected) {
+                    (Ok(program), Ok(expected)) => {
+                        assert_eq!(program, &expected, "{:?}", incremental.source())
+                    }
+                    (Err(e), Err(expected)) => assert_eq!(e.to_string(), expected.to_string()),
+                    (actual, expected) => panic!("{actual:?} != {expected:?}"),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Inline input.
+use super::Parser;
+use crate::{
+    error::ParseOrRuntimeError,
+    runtime,
+    token::{TokenStream, TokenType, Tokenizer},
+};
+
+use std::io::Write;
+
+/// Splits a source into the program text and the inline input which follows the first `bang`
+/// outside loops.
+///
+/// Many published programs put their input after a `!` in the same file (e.g. `,[.,]!hello`).
+/// The source is tokenized, and the separator is the first `bang` which is neither a part of
+/// a token nor inside a loop. The separator is included in neither part. If there is no
+/// separator, the whole source is the program text and the input is empty.
+///
+/// Tokenizing stops at the first error (e.g. at a `bang` which a strict tokenizer rejects);
+/// the rest of the source is searched for `bang` at the loop depth so far.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{parser::split_inline_input, token::simple::SimpleTokenSpec};
+///
+/// let tokenizer = SimpleTokenSpec {
+///     ptr_inc: '>',
+///     ptr_dec: '<',
+///     data_inc: '+',
+///     data_dec: '-',
+///     output: '.',
+///     input: ',',
+///     loop_head: '[',
+///     loop_tail: ']',
+/// }
+/// .to_tokenizer();
+///
+/// assert_eq!(split_inline_input(",[.,]!hello", '!', &tokenizer), (",[.,]", "hello"));
+/// // a `!` in a loop is a comment.
+/// assert_eq!(split_inline_input("[wow!],!x!y", '!', &tokenizer), ("[wow!],", "x!y"));
+/// ```
+pub fn split_inline_input<'a>(
+    source: &'a str,
+    bang: char,
+    tokenizer: &'a impl Tokenizer<'a>,
+) -> (&'a str, &'a str) {
+    let mut stream = tokenizer.token_stream(source);
+    let mut chars = source.char_indices();
+    let mut pos_in_chars = 0;
+    let mut depth = 0usize;
+    loop {
+        let (gap_end, token) = match stream.next() {
+            Ok(info) => (info.pos_in_chars, info.token),
+            Err(_) => (usize::MAX, None),
+        };
+        // search the comment before the token.
+        while pos_in_chars < gap_end {
+            let Some((index, c)) = chars.next() else {
+                return (source, "");
+            };
+            pos_in_chars += 1;
+            if depth == 0 && c == bang {
+                return (&source[..index], &source[index + c.len_utf8()..]);
+            }
+        }
+        let Some(token) = token else {
+            return (source, "");
+        };
+        match token.token_type {
+            TokenType::LoopHead => depth += 1,
+            TokenType::LoopTail => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let len = token.token_str.chars().count();
+        chars.by_ref().take(len).for_each(drop);
+        pos_in_chars += len;
+    }
+}
+
+/// Parse a source with inline input and run it.
+///
+/// The source is split by [`split_inline_input`] at the first `!` outside loops. The text before
+/// it is parsed by `parser`, and the text after it is the input of the program. Reading past
+/// the inline input fails with [`RuntimeError::Eof`](crate::error::RuntimeError::Eof) as usual.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{parser::{self, Parser}, token::simple::SimpleTokenSpec};
+///
+/// let parser = Parser::new(
+///     SimpleTokenSpec {
+///         ptr_inc: '>',
+///         ptr_dec: '<',
+///         data_inc: '+',
+///         data_dec: '-',
+///         output: '.',
+///         input: ',',
+///         loop_head: '[',
+///         loop_tail: ']',
+///     }
+///     .to_tokenizer(),
+/// );
+///
+/// let mut output = vec![];
+/// parser::run_source_with_inline_input(&parser, ",.,.!hi", &mut output).unwrap();
+/// assert_eq!(output, b"hi");
+/// ```
+pub fn run_source_with_inline_input<T, W>(
+    parser: &Parser<T>,
+    source: &str,
+    output: W,
+) -> Result<(), ParseOrRuntimeError>
+where
+    for<'x> T: Tokenizer<'x>,
+    W: Write,
+{
+    let (text, input) = split_inline_input(source, '!', parser.tokenizer());
+    let program = parser.parse_str(text)?;
+    runtime::run(&program, input.as_bytes(), output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        error::{ParseError, RuntimeError},
+        testing::{bf_parser, bf_spec},
+        token::simple::SimpleTokenSpec,
+    };
+
+    #[test]
+    fn test_split_inline_input() {
+        let tokenizer = SimpleTokenSpec {
+            ptr_inc: "ptr_inc",
+            ptr_dec: "ptr_dec",
+            data_inc: "inc!",
+            data_dec: "dec!",
+            output: "out",
+            input: "in",
+            loop_head: "loop",
+            loop_tail: "end",
+        }
+        .to_tokenizer();
+        // `!` in a token or a loop is not the separator.
+        assert_eq!(
+            split_inline_input("inc! loop dec! ! end in ! はい!", '!', &tokenizer),
+            ("inc! loop dec! ! end in ", " はい!")
+        );
+        assert_eq!(
+            split_inline_input("in out", '!', &tokenizer),
+            ("in out", "")
+        );
+        assert_eq!(
+            split_inline_input("loop ! in", '!', &tokenizer),
+            ("loop ! in", "")
+        );
+        assert_eq!(split_inline_input("!", '!', &tokenizer), ("", ""));
+
+        // a strict tokenizer fails at `!`.
+        let strict = bf_spec().to_tokenizer().with_strict(true);
+        assert_eq!(split_inline_input(",.!ab", '!', &strict), (",.", "ab"));
+    }
+
+    #[test]
+    fn test_run_source_with_inline_input() {
+        let parser = bf_parser();
+        let mut output = vec![];
+        let result = run_source_with_inline_input(&parser, ",[.,]!hello", &mut output);
+        assert_eq!(output, b"hello");
+        // the cat reads past the inline input.
+        assert!(matches!(
+            result,
+            Err(ParseOrRuntimeError::RuntimeError(RuntimeError::Eof))
+        ));
+
+        let mut output = vec![];
+        let result = run_source_with_inline_input(&parser, ",[.,!]]!x", &mut output);
+        assert!(matches!(
+            result,
+            Err(ParseOrRuntimeError::ParseError(
+                ParseError::UnexpectedEndOfLoop { pos_in_chars: 6 }
+            ))
+        ));
+        assert!(output.is_empty());
+    }
+}
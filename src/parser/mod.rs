@@ -1,13 +1,55 @@
 //! This module provides a parser for the program.
 //!
-use std::io::Read;
+mod brackets;
+#[cfg(any(feature = "asm", feature = "bf", feature = "ook"))]
+mod detect;
+mod include;
+mod incremental;
+mod inline_input;
+mod reparse;
+
+pub use self::brackets::bracket_pairs;
+#[cfg(any(feature = "asm", feature = "bf", feature = "ook"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(feature = "asm", feature = "bf", feature = "ook")))
+)]
+pub use self::detect::detect_dialect;
+pub use self::include::parse_with_includes;
+pub use self::incremental::IncrementalParser;
+pub use self::inline_input::{run_source_with_inline_input, split_inline_input};
+pub use self::reparse::Edit;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    sync::Arc,
+};
 
 use crate::{
     error::{ParseError, ParseOrIoError},
-    program::{Instruction, Program},
+    program::{FatInstruction, FatProgram, Instruction, Program, SourceToken},
     token::{TokenInfo, TokenStream, TokenType, Tokenizer},
 };
 
+// A reader which counts the number of bytes read successfully.
+struct CountingReader<R> {
+    inner: R,
+    count: usize,
+}
+
+impl<R> Read for CountingReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count += len;
+        Ok(len)
+    }
+}
+
 // A context for parsing.
 //
 // This struct holds a token stream and token unget buffer.
@@ -42,6 +84,111 @@ where
     }
 }
 
+/// How consecutive pointer or data increments/decrements are merged into one instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FoldMode {
+    /// Increments and decrements are merged and cancel each other out (e.g. `++-` is `DAdd(1)`).
+    ///
+    /// A run which cancels out completely (e.g. `+-`) generates no instructions.
+    #[default]
+    Cancel,
+    /// Only the same direction is merged (e.g. `++--` is `DAdd(2), DAdd(-2)`).
+    SameDirection,
+}
+
+/// A limit of the operand magnitude of a merged run of increments/decrements.
+///
+/// A limit of 0 is treated as 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OperandLimit {
+    /// No limit other than the range of `isize`.
+    #[default]
+    Unbounded,
+    /// A run which exceeds the limit is split into multiple instructions
+    /// (e.g. `+++++` is `DAdd(2), DAdd(2), DAdd(1)` with the limit 2).
+    Split(usize),
+    /// A run which exceeds the limit is an error ([`ParseError::OperandTooLarge`]).
+    Error(usize),
+}
+
+impl OperandLimit {
+    // Check whether the operand `next` of a run exceeds the limit.
+    //
+    // Returns `Ok(true)` if the run should be split before the token at `pos_in_chars`.
+    fn exceeds(self, next: isize, pos_in_chars: usize) -> Result<bool, ParseError> {
+        match self {
+            Self::Unbounded => Ok(false),
+            Self::Split(limit) => Ok(next.unsigned_abs() > limit.max(1)),
+            Self::Error(limit) if next.unsigned_abs() > limit.max(1) => {
+                Err(ParseError::OperandTooLarge {
+                    pos_in_chars,
+                    limit: limit.max(1),
+                })
+            }
+            Self::Error(_) => Ok(false),
+        }
+    }
+}
+
+/// How loop head and tail tokens are distinguished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopTokenMode {
+    /// A [`TokenType::LoopHead`] token opens a loop and a [`TokenType::LoopTail`] token closes it.
+    #[default]
+    Distinct,
+    /// Either token opens a loop outside a loop and closes the loop inside it,
+    /// regardless of its token type.
+    ///
+    /// This is for dialects which use the same token string for both (e.g. `|`).
+    /// Loops cannot be nested in this mode.
+    /// A token in a [`FatProgram`] has the resolved token type.
+    Toggle,
+}
+
+impl LoopTokenMode {
+    // Resolve the token type of a loop token by whether it is in a loop.
+    fn resolve(self, token_type: TokenType, in_loop: bool) -> TokenType {
+        match (self, token_type) {
+            (Self::Toggle, TokenType::LoopHead | TokenType::LoopTail) if in_loop => {
+                TokenType::LoopTail
+            }
+            (Self::Toggle, TokenType::LoopHead | TokenType::LoopTail) => TokenType::LoopHead,
+            _ => token_type,
+        }
+    }
+}
+
+/// A parser configuration.
+#[derive(Debug, Clone, Default)]
+pub struct ParserConfig {
+    /// How consecutive increments/decrements are merged.
+    pub fold_mode: FoldMode,
+    /// The limit of the operand magnitude of a merged run.
+    pub operand_limit: OperandLimit,
+    /// How loop head and tail tokens are distinguished.
+    pub loop_tokens: LoopTokenMode,
+}
+
+impl ParserConfig {
+    /// Set how consecutive increments/decrements are merged.
+    pub fn with_fold_mode(mut self, fold_mode: FoldMode) -> Self {
+        self.fold_mode = fold_mode;
+        self
+    }
+
+    /// Set the limit of the operand magnitude of a merged run.
+    pub fn with_operand_limit(mut self, operand_limit: OperandLimit) -> Self {
+        self.operand_limit = operand_limit;
+        self
+    }
+
+    /// Set how loop head and tail tokens are distinguished.
+    pub fn with_loop_token_mode(mut self, loop_tokens: LoopTokenMode) -> Self {
+        self.loop_tokens = loop_tokens;
+        self
+    }
+}
+
 /// A parser for the program.
 ///
 /// `Parser` parses program tokens which are provided by [`Tokenizer`] and generates [`Program`]
@@ -73,6 +220,7 @@ where
 /// ```
 pub struct Parser<T> {
     tokenizer: T,
+    config: ParserConfig,
 }
 
 impl<T> Parser<T>
@@ -85,22 +233,81 @@ where
     ///
     ///  - `tokenizer`: A tokenizer which provides tokens.
     pub fn new(tokenizer: T) -> Self {
-        Self { tokenizer }
+        Self::with_config(tokenizer, ParserConfig::default())
+    }
+
+    /// Creates a new parser with the given configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{
+    ///     parser::{FoldMode, Parser, ParserConfig},
+    ///     program::Instruction::*,
+    ///     token::simple::SimpleTokenSpec,
+    /// };
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer();
+    /// let config = ParserConfig::default().with_fold_mode(FoldMode::SameDirection);
+    /// let parser = Parser::with_config(tokenizer, config);
+    ///
+    /// assert_eq!(parser.parse_str("++--").unwrap().instructions(), [DAdd(2), DAdd(-2)]);
+    /// ```
+    pub fn with_config(tokenizer: T, config: ParserConfig) -> Self {
+        Self { tokenizer, config }
+    }
+
+    /// Get the tokenizer.
+    pub fn tokenizer(&self) -> &T {
+        &self.tokenizer
     }
 
     /// Parses a program from a [`Read`] object.
     ///
     /// # Errors
     ///
-    ///  - [`ParseOrIoError::IoError'](enum.ParseOrIoError.html#variant.IoError) aaa
-    ///  - [`ParseOrIoError'] bbb
-    pub fn parse(&self, mut reader: impl Read) -> Result<Program, ParseOrIoError> {
+    ///  - [`ParseOrIoError::IoError`] if reading the source failed.
+    ///    It has the number of bytes which were read before the error.
+    ///  - [`ParseOrIoError::ParseError`] if the source has a syntax error.
+    pub fn parse(&self, reader: impl Read) -> Result<Program, ParseOrIoError> {
+        let mut reader = CountingReader {
+            inner: reader,
+            count: 0,
+        };
         let mut source = String::new();
-        let _ = reader.read_to_string(&mut source)?;
+        if let Err(error) = reader.read_to_string(&mut source) {
+            return Err(ParseOrIoError::IoError {
+                error: Arc::new(error),
+                bytes_read: reader.count,
+                path: None,
+            });
+        }
         let program = self.parse_str(&source)?;
         Ok(program)
     }
 
+    /// Parses a program from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Parser::parse`], except that [`ParseOrIoError::IoError`] also has `path`.
+    pub fn parse_file(&self, path: impl AsRef<Path>) -> Result<Program, ParseOrIoError> {
+        let path = path.as_ref();
+        File::open(path)
+            .map_err(ParseOrIoError::from)
+            .and_then(|file| self.parse(file))
+            .map_err(|e| e.with_path(path))
+    }
+
     /// Parses a program from a string.
     ///
     /// # Arguments
@@ -112,58 +319,316 @@ where
     /// A program or a parse error.
     pub fn parse_str<'a>(&'a self, source: &'a str) -> Result<Program, ParseError> {
         let mut context = ParseContext::new(self.tokenizer.token_stream(source));
-        Ok(Program::new(Self::parse_internal(&mut context, true)?))
+        Ok(Program::new(Self::parse_internal(
+            &mut context,
+            &self.config,
+            None,
+        )?))
     }
 
-    fn parse_internal<'a>(
-        context: &mut ParseContext<'a, impl TokenStream<'a>>,
-        top_level: bool,
-    ) -> Result<Vec<Instruction>, ParseError> {
+    /// Checks whether a string is a valid program without building it.
+    ///
+    /// Returns the same error as [`Parser::parse_str`] does. The instructions are discarded
+    /// as soon as they are parsed, so only the positions of the open loop heads are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{error::ParseError, parser::Parser, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer();
+    /// let parser = Parser::new(tokenizer);
+    ///
+    /// assert!(parser.check_str(",[.,]").is_ok());
+    /// assert!(matches!(
+    ///     parser.check_str(",[.,]]"),
+    ///     Err(ParseError::UnexpectedEndOfLoop { pos_in_chars: 5 })
+    /// ));
+    /// ```
+    pub fn check_str(&self, source: &str) -> Result<(), ParseError> {
+        let mut context = ParseContext::new(self.tokenizer.token_stream(source));
+        let mut loop_heads = Vec::new();
         let mut instructions = Vec::new();
-
         loop {
             let info = context.next_token_info()?;
-            let token_type = info.token_type();
+            let token_type = info.token_type().map(|token_type| {
+                self.config
+                    .loop_tokens
+                    .resolve(token_type, !loop_heads.is_empty())
+            });
             match token_type {
-                Some(TokenType::PInc) => Self::push_padd(context, &mut instructions, 1)?,
-                Some(TokenType::PDec) => Self::push_padd(context, &mut instructions, -1)?,
-                Some(TokenType::DInc) => Self::push_dadd(context, &mut instructions, 1)?,
-                Some(TokenType::DDec) => Self::push_dadd(context, &mut instructions, -1)?,
-                Some(TokenType::Output) => instructions.push(Instruction::Output),
-                Some(TokenType::Input) => instructions.push(Instruction::Input),
-                Some(TokenType::LoopHead) => instructions.push(Instruction::UntilZero(
-                    Self::parse_internal(context, false)?,
-                )),
+                Some(TokenType::LoopHead) => loop_heads.push(info.pos_in_chars),
                 Some(TokenType::LoopTail) => {
-                    if top_level {
+                    if loop_heads.pop().is_none() {
                         return Err(ParseError::UnexpectedEndOfLoop {
                             pos_in_chars: info.pos_in_chars,
                         });
-                    } else {
-                        return Ok(instructions);
                     }
                 }
-
+                Some(_) => {
+                    context.unget_token_info(info);
+                    Self::parse_next(
+                        &mut context,
+                        &self.config,
+                        loop_heads.last().copied(),
+                        &mut instructions,
+                    )?;
+                    instructions.clear();
+                }
                 None => {
-                    return if top_level {
-                        Ok(instructions)
-                    } else {
-                        Err(ParseError::UnexpectedEndOfFile {
+                    return match loop_heads.last() {
+                        None => Ok(()),
+                        Some(&head) => Err(ParseError::UnexpectedEndOfFile {
                             pos_in_chars: info.pos_in_chars,
-                        })
+                            loop_head_pos_in_chars: Some(head),
+                        }),
                     }
                 }
             }
         }
     }
 
+    /// Returns `true` if a string is a valid program (see [`Parser::check_str`]).
+    pub fn is_valid(&self, source: &str) -> bool {
+        self.check_str(source).is_ok()
+    }
+
+    /// Parses a program from a string incrementally, yielding each top-level instruction as soon
+    /// as it is parsed.
+    ///
+    /// A loop is yielded after its tail is parsed, and a run of increments/decrements after
+    /// the token following it is read. The instructions are the same as the ones of
+    /// [`Parser::parse_str`] up to the first error, which is the last item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{error::ParseError, parser::Parser, program::Instruction::*, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer();
+    /// let parser = Parser::new(tokenizer);
+    /// let mut iter = parser.parse_iter("++.[-]]");
+    ///
+    /// assert_eq!(iter.next().unwrap().unwrap(), DAdd(2));
+    /// assert_eq!(iter.next().unwrap().unwrap(), Output);
+    /// assert_eq!(iter.next().unwrap().unwrap(), UntilZero(vec![DAdd(-1)]));
+    /// assert!(matches!(iter.next(), Some(Err(ParseError::UnexpectedEndOfLoop { .. }))));
+    /// assert!(iter.next().is_none());
+    /// ```
+    pub fn parse_iter<'a>(
+        &'a self,
+        source: &'a str,
+    ) -> impl Iterator<Item = Result<Instruction, ParseError>> + 'a {
+        let mut context = ParseContext::new(self.tokenizer.token_stream(source));
+        let mut pending = Vec::new();
+        let mut finished = false;
+        std::iter::from_fn(move || loop {
+            if !pending.is_empty() {
+                return Some(Ok(pending.remove(0)));
+            }
+            if finished {
+                return None;
+            }
+            match Self::parse_next(&mut context, &self.config, None, &mut pending) {
+                Ok(true) => {}
+                Ok(false) => finished = true,
+                Err(e) => {
+                    finished = true;
+                    return Some(Err(e));
+                }
+            }
+        })
+    }
+
+    /// Parses a program from a string, keeping the source tokens of each instruction.
+    ///
+    /// The result is converted into the same program as [`Parser::parse_str`] returns
+    /// by [`FatProgram::to_program`].
+    pub fn parse_fat_str<'a>(&'a self, source: &'a str) -> Result<FatProgram, ParseError> {
+        let mut context = ParseContext::new(self.tokenizer.token_stream(source));
+        let (instructions, _) = Self::parse_fat_internal(&mut context, &self.config, None)?;
+        Ok(FatProgram::new(instructions))
+    }
+
+    // Parse fat instructions until the end of the loop or EOF.
+    //
+    // Returns the instructions and the loop tail token (`None` for the top level).
+    fn parse_fat_internal<'a>(
+        context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
+        loop_head_pos_in_chars: Option<usize>,
+    ) -> Result<(Vec<FatInstruction>, Option<SourceToken>), ParseError> {
+        let mut instructions: Vec<FatInstruction> = Vec::new();
+
+        loop {
+            let info = context.next_token_info()?;
+            let Some(token) = &info.token else {
+                return match loop_head_pos_in_chars {
+                    None => Ok((instructions, None)),
+                    Some(_) => Err(ParseError::UnexpectedEndOfFile {
+                        pos_in_chars: info.pos_in_chars,
+                        loop_head_pos_in_chars,
+                    }),
+                };
+            };
+            let token_type = config
+                .loop_tokens
+                .resolve(token.token_type, loop_head_pos_in_chars.is_some());
+            let source_token = SourceToken {
+                token_type,
+                token_str: token.token_str.to_string(),
+                pos_in_chars: info.pos_in_chars,
+            };
+            let foldable = |operand: isize| match config.fold_mode {
+                FoldMode::Cancel => true,
+                FoldMode::SameDirection => operand.signum() == direction(token_type),
+            };
+            match (token_type, instructions.last_mut()) {
+                (TokenType::PInc | TokenType::PDec, Some(FatInstruction::PAdd(operand, run)))
+                | (TokenType::DInc | TokenType::DDec, Some(FatInstruction::DAdd(operand, run)))
+                    if foldable(*operand)
+                        && !config
+                            .operand_limit
+                            .exceeds(*operand + direction(token_type), info.pos_in_chars)? =>
+                {
+                    *operand += direction(token_type);
+                    run.push(source_token);
+                }
+                (TokenType::PInc | TokenType::PDec, _) => instructions.push(FatInstruction::PAdd(
+                    direction(token_type),
+                    vec![source_token],
+                )),
+                (TokenType::DInc | TokenType::DDec, _) => instructions.push(FatInstruction::DAdd(
+                    direction(token_type),
+                    vec![source_token],
+                )),
+                (TokenType::Output, _) => instructions.push(FatInstruction::Output(source_token)),
+                (TokenType::Input, _) => instructions.push(FatInstruction::Input(source_token)),
+                (TokenType::Breakpoint, _) => {
+                    instructions.push(FatInstruction::Breakpoint(source_token))
+                }
+                (TokenType::AssertCell, _) => instructions.push(FatInstruction::AssertCell(
+                    assertion_operand(token.token_str, info.pos_in_chars)?,
+                    source_token,
+                )),
+                (TokenType::LoopHead, _) => {
+                    let (body, tail) =
+                        Self::parse_fat_internal(context, config, Some(info.pos_in_chars))?;
+                    instructions.push(FatInstruction::UntilZero {
+                        head: source_token,
+                        body,
+                        tail: tail.expect("a loop body ends with its tail"),
+                    });
+                }
+                (TokenType::LoopTail, _) => {
+                    return match loop_head_pos_in_chars {
+                        None => Err(ParseError::UnexpectedEndOfLoop {
+                            pos_in_chars: info.pos_in_chars,
+                        }),
+                        Some(_) => Ok((instructions, Some(source_token))),
+                    };
+                }
+            }
+        }
+    }
+
+    // Parse instructions until the end of the loop or EOF.
+    //
+    // `loop_head_pos_in_chars` is the position of the head of the loop being parsed,
+    // or `None` for the top level.
+    fn parse_internal<'a>(
+        context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
+        loop_head_pos_in_chars: Option<usize>,
+    ) -> Result<Vec<Instruction>, ParseError> {
+        let mut instructions = Vec::new();
+        while Self::parse_next(context, config, loop_head_pos_in_chars, &mut instructions)? {}
+        Ok(instructions)
+    }
+
+    // Parse the next token, with the following tokens merged into it, and push the instructions
+    // generated from them.
+    //
+    // Returns `false` at the end of the loop or EOF.
+    fn parse_next<'a>(
+        context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
+        loop_head_pos_in_chars: Option<usize>,
+        instructions: &mut Vec<Instruction>,
+    ) -> Result<bool, ParseError> {
+        let top_level = loop_head_pos_in_chars.is_none();
+        let info = context.next_token_info()?;
+        let token_type = info
+            .token_type()
+            .map(|token_type| config.loop_tokens.resolve(token_type, !top_level));
+        match token_type {
+            Some(TokenType::PInc) => Self::push_padd(context, config, instructions, 1)?,
+            Some(TokenType::PDec) => Self::push_padd(context, config, instructions, -1)?,
+            Some(TokenType::DInc) => Self::push_dadd(context, config, instructions, 1)?,
+            Some(TokenType::DDec) => Self::push_dadd(context, config, instructions, -1)?,
+            Some(TokenType::Output) => instructions.push(Instruction::Output),
+            Some(TokenType::Input) => instructions.push(Instruction::Input),
+            Some(TokenType::Breakpoint) => instructions.push(Instruction::Breakpoint),
+            Some(TokenType::AssertCell) => instructions.push(Instruction::AssertCell {
+                expected: assertion_operand(
+                    info.token_str().unwrap_or_default(),
+                    info.pos_in_chars,
+                )?,
+            }),
+            Some(TokenType::LoopHead) => instructions.push(Instruction::UntilZero(
+                Self::parse_internal(context, config, Some(info.pos_in_chars))?,
+            )),
+            Some(TokenType::LoopTail) => {
+                if top_level {
+                    return Err(ParseError::UnexpectedEndOfLoop {
+                        pos_in_chars: info.pos_in_chars,
+                    });
+                } else {
+                    return Ok(false);
+                }
+            }
+
+            None => {
+                return if top_level {
+                    Ok(false)
+                } else {
+                    Err(ParseError::UnexpectedEndOfFile {
+                        pos_in_chars: info.pos_in_chars,
+                        loop_head_pos_in_chars,
+                    })
+                }
+            }
+        }
+        Ok(true)
+    }
+
     fn push_padd<'a>(
         context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
         instructions: &mut Vec<Instruction>,
         initial_operand: isize,
     ) -> Result<(), ParseError> {
         Self::push_xadd(
             context,
+            config,
             instructions,
             initial_operand,
             TokenType::PInc,
@@ -174,11 +639,13 @@ where
 
     fn push_dadd<'a>(
         context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
         instructions: &mut Vec<Instruction>,
         initial_operand: isize,
     ) -> Result<(), ParseError> {
         Self::push_xadd(
             context,
+            config,
             instructions,
             initial_operand,
             TokenType::DInc,
@@ -189,6 +656,7 @@ where
 
     fn push_xadd<'a>(
         context: &mut ParseContext<'a, impl TokenStream<'a>>,
+        config: &ParserConfig,
         instructions: &mut Vec<Instruction>,
         initial_operand: isize,
         inc: TokenType,
@@ -196,19 +664,29 @@ where
         gen: fn(isize) -> Instruction,
     ) -> Result<(), ParseError> {
         let mut operand = initial_operand;
+        let same_direction_only = config.fold_mode == FoldMode::SameDirection;
 
         loop {
             let info = context.next_token_info()?;
             let token_type = info.token_type();
-            if token_type == Some(inc) {
-                operand += 1;
-            } else if token_type == Some(dec) {
-                operand -= 1;
+            let delta = if token_type == Some(inc) && !(same_direction_only && initial_operand < 0)
+            {
+                1
+            } else if token_type == Some(dec) && !(same_direction_only && initial_operand > 0) {
+                -1
             } else {
                 // unget token other than inc or dec (including EOF.)
                 context.unget_token_info(info);
                 break;
+            };
+            if config
+                .operand_limit
+                .exceeds(operand + delta, info.pos_in_chars)?
+            {
+                instructions.push(gen(operand));
+                operand = 0;
             }
+            operand += delta;
         }
 
         if operand != 0 {
@@ -217,3 +695,459 @@ where
         Ok(())
     }
 }
+
+// The expected value of an assertion token, which is the decimal number at the end of the token
+// string, or 0 if there is none.
+fn assertion_operand(token_str: &str, pos_in_chars: usize) -> Result<u8, ParseError> {
+    let digits = token_str.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &token_str[digits.len()..];
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    digits.parse().map_err(|_| ParseError::MiscError {
+        pos_in_chars,
+        message: format!("assertion value {digits} is out of range"),
+    })
+}
+
+// The operand of a pointer or data increment/decrement token.
+fn direction(token_type: TokenType) -> isize {
+    match token_type {
+        TokenType::PInc | TokenType::DInc => 1,
+        _ => -1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        testing::{bf_parser, bf_spec},
+        token::simple::SimpleTokenSpec,
+    };
+
+    #[test]
+    fn test_unexpected_end_of_file() {
+        let source = "+[[>[]";
+        match bf_parser().parse_str(source) {
+            Err(ParseError::UnexpectedEndOfFile {
+                pos_in_chars,
+                loop_head_pos_in_chars,
+            }) => {
+                assert_eq!(pos_in_chars, 6);
+                assert_eq!(loop_head_pos_in_chars, Some(2));
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_end_of_loop() {
+        let source = "+[]]";
+        match bf_parser().parse_str(source) {
+            Err(ParseError::UnexpectedEndOfLoop { pos_in_chars }) => {
+                assert_eq!(pos_in_chars, 3);
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_check_str() {
+        let parser = bf_parser();
+        let config = ParserConfig::default().with_operand_limit(OperandLimit::Error(3));
+        let limited = Parser::with_config(bf_parser().tokenizer, config);
+        let sources = [
+            "",
+            ",[.,]",
+            "+[[>[]",
+            "+[]]",
+            "]",
+            "[",
+            "++[->+<]>.",
+            "[[[]]]x[]",
+            "++++",
+            "[+++[-]]+++",
+        ];
+        for source in sources {
+            for parser in [&parser, &limited] {
+                let expected = parser.parse_str(source).err();
+                let actual = parser.check_str(source).err();
+                assert_eq!(format!("{actual:?}"), format!("{expected:?}"), "{source}");
+                assert_eq!(parser.is_valid(source), expected.is_none(), "{source}");
+            }
+        }
+    }
+
+    // A reader which fails after providing `data`.
+    struct FailingReader<'a> {
+        data: &'a [u8],
+    }
+
+    impl Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return Err(io::Error::other("disk error"));
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_parse_read_error() {
+        let parser = bf_parser();
+        let source = "+".repeat(8192);
+        match parser.parse(FailingReader {
+            data: source.as_bytes(),
+        }) {
+            Err(e @ ParseOrIoError::IoError { bytes_read, .. }) => {
+                assert_eq!(bytes_read, 8192);
+                assert_eq!(
+                    e.to_string(),
+                    "failed reading source after 8192 bytes: disk error"
+                );
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_file_error() {
+        let parser = bf_parser();
+        let path = Path::new("no/such/file.bf");
+        match parser.parse_file(path) {
+            Err(ParseOrIoError::IoError {
+                bytes_read,
+                path: error_path,
+                ..
+            }) => {
+                assert_eq!(bytes_read, 0);
+                assert_eq!(error_path.as_deref(), Some(path));
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_fat_str() {
+        let parser = bf_parser();
+        let source = "+x+-[>.]<<,";
+        let fat = parser.parse_fat_str(source).unwrap();
+        assert_eq!(
+            fat.to_program().instructions(),
+            parser.parse_str(source).unwrap().instructions()
+        );
+
+        let tokens = fat
+            .tokens()
+            .iter()
+            .map(|token| (token.token_str.as_str(), token.pos_in_chars))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [
+                ("+", 0),
+                ("+", 2),
+                ("-", 3),
+                ("[", 4),
+                (">", 5),
+                (".", 6),
+                ("]", 7),
+                ("<", 8),
+                ("<", 9),
+                (",", 10)
+            ]
+        );
+        assert!(
+            matches!(fat.instructions()[0], FatInstruction::DAdd(1, ref run) if run.len() == 3)
+        );
+
+        // a zero-sum run is kept in the fat program but dropped from the program.
+        let fat = parser.parse_fat_str("+-").unwrap();
+        assert!(matches!(fat.instructions(), [FatInstruction::DAdd(0, _)]));
+        assert!(fat.to_program().instructions().is_empty());
+
+        assert!(matches!(
+            parser.parse_fat_str("[+"),
+            Err(ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 2,
+                loop_head_pos_in_chars: Some(0)
+            })
+        ));
+        assert!(matches!(
+            parser.parse_fat_str("+]"),
+            Err(ParseError::UnexpectedEndOfLoop { pos_in_chars: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_strip_token_strings() {
+        let parser = bf_parser();
+        let source = "+x+-[>.]<<,";
+        let fat = parser.parse_fat_str(source).unwrap();
+        let mut stripped = fat.clone();
+        stripped.strip_token_strings();
+
+        assert_eq!(
+            stripped.to_program().instructions(),
+            fat.to_program().instructions()
+        );
+        assert_eq!(stripped.tokens().len(), fat.tokens().len());
+        for (stripped, token) in stripped.tokens().iter().zip(fat.tokens()) {
+            assert_eq!(stripped.token_type, token.token_type);
+            assert_eq!(stripped.pos_in_chars, token.pos_in_chars);
+            assert!(stripped.token_str.is_empty());
+            assert_eq!(stripped.token_str.capacity(), 0);
+        }
+    }
+
+    #[test]
+    fn test_fold_mode() {
+        use crate::program::Instruction::*;
+
+        let parser = bf_parser();
+        assert!(parser.parse_str("++--").unwrap().instructions().is_empty());
+        assert_eq!(
+            parser.parse_str("+>><-").unwrap().instructions(),
+            [DAdd(1), PAdd(1), DAdd(-1)]
+        );
+
+        let parser = Parser::with_config(
+            parser.tokenizer,
+            ParserConfig::default().with_fold_mode(FoldMode::SameDirection),
+        );
+        assert_eq!(
+            parser.parse_str("++--").unwrap().instructions(),
+            [DAdd(2), DAdd(-2)]
+        );
+        assert_eq!(
+            parser.parse_str("+>><-[<<>]").unwrap().instructions(),
+            [
+                DAdd(1),
+                PAdd(2),
+                PAdd(-1),
+                DAdd(-1),
+                UntilZero(vec![PAdd(-2), PAdd(1)])
+            ]
+        );
+
+        let fat = parser.parse_fat_str("++--[<<>]").unwrap();
+        assert_eq!(
+            fat.to_program().instructions(),
+            parser.parse_str("++--[<<>]").unwrap().instructions()
+        );
+        assert!(matches!(
+            fat.instructions(),
+            [
+                FatInstruction::DAdd(2, _),
+                FatInstruction::DAdd(-2, _),
+                FatInstruction::UntilZero { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_operand_limit() {
+        use crate::program::Instruction::*;
+
+        let tokenizer = || bf_parser().tokenizer;
+        let huge = "+".repeat(1_000_000);
+        assert_eq!(
+            bf_parser().parse_str(&huge).unwrap().instructions(),
+            [DAdd(1_000_000)]
+        );
+
+        let parser = Parser::with_config(
+            tokenizer(),
+            ParserConfig::default().with_operand_limit(OperandLimit::Split(255)),
+        );
+        let program = parser.parse_str(&huge).unwrap();
+        assert_eq!(program.instructions().len(), 1_000_000usize.div_ceil(255));
+        assert!(program.instructions()[..3921]
+            .iter()
+            .all(|inst| *inst == DAdd(255)));
+        assert_eq!(program.instructions()[3921], DAdd(1_000_000 % 255));
+        assert_eq!(
+            parser
+                .parse_fat_str(&huge)
+                .unwrap()
+                .to_program()
+                .instructions(),
+            program.instructions()
+        );
+
+        let parser = Parser::with_config(
+            tokenizer(),
+            ParserConfig::default().with_operand_limit(OperandLimit::Split(2)),
+        );
+        for source in ["+++--->>><<<<", "-----+", "++x+[>>>]"] {
+            assert_eq!(
+                parser
+                    .parse_fat_str(source)
+                    .unwrap()
+                    .to_program()
+                    .instructions(),
+                parser.parse_str(source).unwrap().instructions(),
+                "{source}"
+            );
+        }
+        assert_eq!(
+            parser.parse_str("+++--->>><<<<").unwrap().instructions(),
+            [DAdd(2), DAdd(-2), PAdd(2), PAdd(-2), PAdd(-1)]
+        );
+        assert_eq!(
+            parser.parse_str("++x+[>>>]").unwrap().instructions(),
+            [DAdd(2), DAdd(1), UntilZero(vec![PAdd(2), PAdd(1)])]
+        );
+
+        let parser = Parser::with_config(
+            tokenizer(),
+            ParserConfig::default().with_operand_limit(OperandLimit::Error(255)),
+        );
+        assert!(parser.parse_str(&"+".repeat(255)).is_ok());
+        for result in [
+            parser.parse_str(&huge).map(|_| ()),
+            parser.parse_fat_str(&huge).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(ParseError::OperandTooLarge {
+                    pos_in_chars: 255,
+                    limit: 255
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_loop_token_mode() {
+        use crate::program::Instruction::*;
+
+        let tokenizer = || {
+            SimpleTokenSpec {
+                ptr_inc: '>',
+                ptr_dec: '<',
+                data_inc: '+',
+                data_dec: '-',
+                output: '.',
+                input: ',',
+                loop_head: '|',
+                loop_tail: '|',
+            }
+            .to_tokenizer()
+        };
+        let parser = Parser::with_config(
+            tokenizer(),
+            ParserConfig::default().with_loop_token_mode(LoopTokenMode::Toggle),
+        );
+        assert_eq!(
+            parser.parse_str("+|+|").unwrap().instructions(),
+            [DAdd(1), UntilZero(vec![DAdd(1)])]
+        );
+        assert_eq!(
+            parser.parse_str("|-||.|").unwrap().instructions(),
+            [UntilZero(vec![DAdd(-1)]), UntilZero(vec![Output])]
+        );
+        assert!(matches!(
+            parser.parse_str("+|+"),
+            Err(ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 3,
+                loop_head_pos_in_chars: Some(1)
+            })
+        ));
+
+        let fat = parser.parse_fat_str("+|+|").unwrap();
+        assert_eq!(
+            fat.to_program().instructions(),
+            parser.parse_str("+|+|").unwrap().instructions()
+        );
+        let FatInstruction::UntilZero { head, tail, .. } = &fat.instructions()[1] else {
+            panic!("not a loop: {:?}", fat.instructions()[1]);
+        };
+        assert_eq!(head.token_type, TokenType::LoopHead);
+        assert_eq!(tail.token_type, TokenType::LoopTail);
+
+        // An inserted loop token changes the meaning of the following tokens.
+        let old = parser.parse_fat_str("|+|-").unwrap();
+        let new = parser.reparse(&old, "|+||-", &Edit::new(3..3, 1));
+        assert!(new.is_err());
+        let new = parser.reparse(&old, "|+||-|", &Edit::new(3..4, 3)).unwrap();
+        assert_eq!(new, parser.parse_fat_str("|+||-|").unwrap());
+
+        // Without the mode, the shared token is always a loop head.
+        assert!(matches!(
+            Parser::new(tokenizer()).parse_str("+|+|"),
+            Err(ParseError::UnexpectedEndOfFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_iter() {
+        let parser = Parser::with_config(
+            bf_spec().to_tokenizer(),
+            ParserConfig::default().with_operand_limit(OperandLimit::Split(3)),
+        );
+        for source in ["", "+-", "++++++>.<,[->+<[.]]x.", "+[[-]>>>>]<<"] {
+            let instructions = parser
+                .parse_iter(source)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(
+                instructions,
+                parser.parse_str(source).unwrap().instructions(),
+                "{source:?}"
+            );
+        }
+
+        let results = parser.parse_iter("+.[-").collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            results[2],
+            Err(ParseError::UnexpectedEndOfFile {
+                pos_in_chars: 4,
+                loop_head_pos_in_chars: Some(2)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_assertion_token() {
+        use crate::program::Instruction::*;
+
+        let parser = Parser::new(
+            bf_parser()
+                .tokenizer
+                .with_assertion("=")
+                .with_assertion("=65"),
+        );
+        let source = "=+[-]=65=";
+        assert_eq!(
+            parser.parse_str(source).unwrap().instructions(),
+            [
+                AssertCell { expected: 0 },
+                DAdd(1),
+                UntilZero(vec![DAdd(-1)]),
+                AssertCell { expected: 65 },
+                AssertCell { expected: 0 }
+            ]
+        );
+        assert_eq!(
+            parser
+                .parse_fat_str(source)
+                .unwrap()
+                .to_program()
+                .instructions(),
+            parser.parse_str(source).unwrap().instructions()
+        );
+
+        let parser = Parser::new(bf_parser().tokenizer.with_assertion("=256"));
+        assert!(matches!(
+            parser.parse_str("+=256"),
+            Err(ParseError::MiscError {
+                pos_in_chars: 1,
+                ..
+            })
+        ));
+        assert!(parser.parse_fat_str("+=256").is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Incremental reparse.
+use std::ops::Range;
+
+use super::{LoopTokenMode, ParseContext, Parser};
+use crate::{
+    error::ParseError,
+    program::{FatInstruction, FatProgram, SourceToken},
+    token::{TokenStream, Tokenizer},
+};
+
+/// A single-range edit of a source.
+///
+/// The characters in `range_in_chars` of the old source were replaced with
+/// `new_len_in_chars` characters. Positions are counted in Unicode scalar units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The replaced range in the old source.
+    pub range_in_chars: Range<usize>,
+    /// The length of the replacement.
+    pub new_len_in_chars: usize,
+}
+
+impl Edit {
+    /// Create a new edit which replaces `range_in_chars` with `new_len_in_chars` characters.
+    pub fn new(range_in_chars: Range<usize>, new_len_in_chars: usize) -> Self {
+        Self {
+            range_in_chars,
+            new_len_in_chars,
+        }
+    }
+
+    // The change of the source length.
+    fn delta(&self) -> isize {
+        self.new_len_in_chars as isize - self.range_in_chars.len() as isize
+    }
+}
+
+impl<T> Parser<T>
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    /// Parses an edited source, reusing the program parsed from the source before the edit.
+    ///
+    /// `old` must be the result of [`Parser::parse_fat_str`] (or this function) for the old source,
+    /// and `source` must be the old source with `edit` applied.
+    /// The result is the same as `parse_fat_str(source)`.
+    ///
+    /// Only the instructions around the edit in the innermost loop body (or the top level)
+    /// containing the edit are reparsed. If the edit touches the head or tail of a loop, or
+    /// the edited region does not parse by itself, the whole source is parsed.
+    /// With [`LoopTokenMode::Toggle`](super::LoopTokenMode::Toggle), an edit may change
+    /// the meaning of every following loop token, so the whole source is always parsed.
+    ///
+    /// The tokenizer is assumed to tokenize the rest of a source in the same way
+    /// when it restarts at the end of a token, which holds for the tokenizers in this crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::{Edit, Parser}, token::simple::SimpleTokenSpec};
+    ///
+    /// let parser = Parser::new(
+    ///     SimpleTokenSpec {
+    ///         ptr_inc: '>',
+    ///         ptr_dec: '<',
+    ///         data_inc: '+',
+    ///         data_dec: '-',
+    ///         output: '.',
+    ///         input: ',',
+    ///         loop_head: '[',
+    ///         loop_tail: ']',
+    ///     }
+    ///     .to_tokenizer(),
+    /// );
+    /// let old = parser.parse_fat_str("[-]>++.").unwrap();
+    /// // "++" -> "+++"
+    /// let source = "[-]>+++.";
+    /// let new = parser.reparse(&old, source, &Edit::new(5..5, 1)).unwrap();
+    ///
+    /// assert_eq!(new, parser.parse_fat_str(source).unwrap());
+    /// ```
+    pub fn reparse(
+        &self,
+        old: &FatProgram,
+        source: &str,
+        edit: &Edit,
+    ) -> Result<FatProgram, ParseError> {
+        if self.config.loop_tokens == LoopTokenMode::Toggle {
+            return self.parse_fat_str(source);
+        }
+        match self.reparse_region(old, source, edit) {
+            Some(program) => Ok(program),
+            None => self.parse_fat_str(source),
+        }
+    }
+
+    // Reparse the region around the edit.
+    //
+    // Returns `None` if the region cannot be reparsed by itself.
+    fn reparse_region(&self, old: &FatProgram, source: &str, edit: &Edit) -> Option<FatProgram> {
+        let source_len = source.chars().count();
+        self.reparse_in(old.instructions(), 0..source_len, source, edit)
+            .map(FatProgram::new)
+    }
+
+    // Reparse the region around the edit in `instructions`, which are at the top level or
+    // in a loop body. `bounds` is the range of the new source between the loop head and tail,
+    // or the whole new source for the top level.
+    fn reparse_in(
+        &self,
+        instructions: &[FatInstruction],
+        bounds: Range<usize>,
+        source: &str,
+        edit: &Edit,
+    ) -> Option<Vec<FatInstruction>> {
+        let spans = instructions.iter().map(span).collect::<Vec<_>>();
+        let Range {
+            start: edit_start,
+            end: edit_end,
+        } = edit.range_in_chars;
+        let delta = edit.delta();
+        let shifted = |instructions: &[FatInstruction]| {
+            instructions
+                .iter()
+                .cloned()
+                .map(|mut inst| {
+                    shift_tokens(&mut inst, delta);
+                    inst
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // instructions touching the edit
+        let first = spans.partition_point(|span| span.end <= edit_start);
+        let last = spans.partition_point(|span| span.start < edit_end);
+
+        // An edit inside a loop body is reparsed in the body.
+        if let [FatInstruction::UntilZero { head, body, tail }] = &instructions[first..last] {
+            let head_end = head.pos_in_chars + head.token_str.chars().count();
+            if head_end <= edit_start && edit_end <= tail.pos_in_chars {
+                let body_bounds = head_end..shift(tail.pos_in_chars, delta);
+                let body = self.reparse_in(body, body_bounds, source, edit)?;
+                let mut tail = tail.clone();
+                tail.pos_in_chars = shift(tail.pos_in_chars, delta);
+                let mut result = instructions[..first].to_vec();
+                result.push(FatInstruction::UntilZero {
+                    head: head.clone(),
+                    body,
+                    tail,
+                });
+                result.extend(shifted(&instructions[last..]));
+                return Some(result);
+            }
+        }
+        if instructions[first..last].iter().any(is_loop) {
+            return None;
+        }
+
+        // Also reparse the neighbors, since new tokens may merge into them across comments.
+        // A loop never merges, so the region stops at its boundary.
+        let (lo, region_start) = match first.checked_sub(1) {
+            None => (0, bounds.start),
+            Some(prev) if is_loop(&instructions[prev]) => (first, spans[prev].end),
+            Some(prev) => (prev, spans[prev].start),
+        };
+        let source_len = source.chars().count();
+        let (hi, region_end) = match spans.get(last) {
+            None => (instructions.len(), bounds.end),
+            Some(next) if is_loop(&instructions[last]) => (last, shift(next.start, delta)),
+            Some(next) => (last + 1, shift(next.end, delta)),
+        };
+        if region_end > bounds.end || region_start > region_end {
+            return None;
+        }
+
+        let start_byte = byte_offset(source, 0, region_start);
+        let end_byte = byte_offset(source, start_byte, region_end - region_start);
+        let region = &source[start_byte..end_byte];
+        let mut context = ParseContext::new(self.tokenizer.token_stream(region));
+        let (mut region_instructions, _) =
+            Self::parse_fat_internal(&mut context, &self.config, None).ok()?;
+        region_instructions
+            .iter_mut()
+            .for_each(|inst| shift_tokens(inst, region_start as isize));
+
+        // The rest must not be tokenized differently, e.g. by a token across the region end.
+        if region_end < source_len {
+            let tokens_end = region_instructions
+                .last()
+                .map_or(region_start, |inst| span(inst).end);
+            let tokens_end_byte = byte_offset(source, 0, tokens_end);
+            let info = self
+                .tokenizer
+                .token_stream(&source[tokens_end_byte..])
+                .next()
+                .ok()?;
+            if tokens_end + info.pos_in_chars != region_end {
+                return None;
+            }
+        }
+
+        let mut result = instructions[..lo].to_vec();
+        result.extend(region_instructions);
+        result.extend(shifted(&instructions[hi..]));
+        Some(result)
+    }
+}
+
+fn is_loop(inst: &FatInstruction) -> bool {
+    matches!(inst, FatInstruction::UntilZero { .. })
+}
+
+// The range of the source which an instruction was built from.
+fn span(inst: &FatInstruction) -> Range<usize> {
+    let (first, last) = match inst {
+        FatInstruction::PAdd(_, run) | FatInstruction::DAdd(_, run) => {
+            (&run[0], &run[run.len() - 1])
+        }
+        FatInstruction::Output(token)
+        | FatInstruction::Input(token)
+        | FatInstruction::Breakpoint(token)
+        | FatInstruction::AssertCell(_, token) => (token, token),
+        FatInstruction::UntilZero { head, tail, .. } => (head, tail),
+    };
+    first.pos_in_chars..last.pos_in_chars + last.token_str.chars().count()
+}
+
+fn shift(pos_in_chars: usize, delta: isize) -> usize {
+    (pos_in_chars as isize + delta) as usize
+}
+
+fn shift_tokens(inst: &mut FatInstruction, delta: isize) {
+    let shift_token = |token: &mut SourceToken| {
+        token.pos_in_chars = shift(token.pos_in_chars, delta);
+    };
+    match inst {
+        FatInstruction::PAdd(_, run) | FatInstruction::DAdd(_, run) => {
+            run.iter_mut().for_each(shift_token)
+        }
+        FatInstruction::Output(token)
+        | FatInstruction::Input(token)
+        | FatInstruction::Breakpoint(token)
+        | FatInstruction::AssertCell(_, token) => shift_token(token),
+        FatInstruction::UntilZero { head, body, tail } => {
+            shift_token(head);
+            shift_token(tail);
+            body.iter_mut().for_each(|inst| shift_tokens(inst, delta));
+        }
+    }
+}
+
+// The byte offset of the position `len_in_chars` characters after `start_byte`.
+fn byte_offset(source: &str, start_byte: usize, len_in_chars: usize) -> usize {
+    source[start_byte..]
+        .char_indices()
+        .nth(len_in_chars)
+        .map_or(source.len(), |(offset, _)| start_byte + offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{testing::bf_parser, token::simple::SimpleTokenizer};
+
+    // Apply an edit to `old`, returning the new source.
+    fn apply(old: &str, edit: &Edit, replacement: &str) -> String {
+        let chars = old.chars().collect::<Vec<_>>();
+        let mut new = chars[..edit.range_in_chars.start]
+            .iter()
+            .collect::<String>();
+        new.push_str(replacement);
+        new.extend(&chars[edit.range_in_chars.end..]);
+        new
+    }
+
+    fn assert_reparse(parser: &Parser<SimpleTokenizer>, old: &str, edit: Edit, replacement: &str) {
+        assert_eq!(edit.new_len_in_chars, replacement.chars().count());
+        let old_fat = parser.parse_fat_str(old).unwrap();
+        let source = apply(old, &edit, replacement);
+        let full = parser.parse_fat_str(&source);
+        match parser.reparse(&old_fat, &source, &edit) {
+            Ok(fat) => assert_eq!(
+                Ok(&fat),
+                full.as_ref().map_err(|_| ()),
+                "{old:?} -> {source:?}"
+            ),
+            Err(e) => assert!(full.is_err(), "{old:?} -> {source:?}: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_reparse_straight_line() {
+        let parser = bf_parser();
+        let old = "[-]>++ comment >>.<\n,[+]";
+        // "comment" -> "+"
+        let edit = Edit::new(7..14, 1);
+        let source = apply(old, &edit, "+");
+        let old_fat = parser.parse_fat_str(old).unwrap();
+        assert!(parser.reparse_region(&old_fat, &source, &edit).is_some());
+        assert_reparse(&parser, old, edit, "+");
+
+        // merges into the neighbors
+        assert_reparse(&parser, old, Edit::new(15..17, 2), "<<");
+        assert_reparse(&parser, old, Edit::new(3..4, 3), "ｘ+>");
+        // delete a whole instruction
+        assert_reparse(&parser, old, Edit::new(17..18, 0), "");
+        // at the both ends
+        assert_reparse(&parser, old, Edit::new(0..0, 1), "+");
+        assert_reparse(&parser, old, Edit::new(24..24, 1), ".");
+    }
+
+    #[test]
+    fn test_reparse_in_loop() {
+        let parser = bf_parser();
+        let old = "+[->[+<]x>+<]>.";
+        let old_fat = parser.parse_fat_str(old).unwrap();
+        // in the inner loop
+        let edit = Edit::new(5..6, 2);
+        let source = apply(old, &edit, "--");
+        assert!(parser.reparse_region(&old_fat, &source, &edit).is_some());
+        assert_reparse(&parser, old, edit, "--");
+        // in the outer loop
+        let edit = Edit::new(8..9, 3);
+        let source = apply(old, &edit, ">>>");
+        assert!(parser.reparse_region(&old_fat, &source, &edit).is_some());
+        assert_reparse(&parser, old, edit, ">>>");
+        // just after the head and just before the tail
+        assert_reparse(&parser, old, Edit::new(2..2, 1), "-");
+        assert_reparse(&parser, old, Edit::new(12..12, 1), "<");
+        // a new loop in a loop body
+        assert_reparse(&parser, old, Edit::new(8..9, 3), "[-]");
+    }
+
+    #[test]
+    fn test_reparse_fallback() {
+        let parser = bf_parser();
+        let old = "+[->+<]>.";
+        let old_fat = parser.parse_fat_str(old).unwrap();
+        // the loop head
+        let edit = Edit::new(1..2, 2);
+        let source = apply(old, &edit, "[[");
+        assert!(parser.reparse_region(&old_fat, &source, &edit).is_none());
+        assert_reparse(&parser, old, edit, "[[");
+
+        // unbalanced loops in the region
+        assert_reparse(&parser, old, Edit::new(8..8, 1), "]");
+        assert_reparse(&parser, old, Edit::new(8..8, 1), "[");
+        assert_reparse(&parser, old, Edit::new(7..8, 3), "[-]");
+    }
+
+    #[test]
+    fn test_reparse_random_edits() {
+        let parser = bf_parser();
+        let mut generator = crate::testing::Generator::new(0x2545_f491_4f6c_dd1d);
+        let mut next = || generator.next_u64();
+        const CHARS: &[char] = &['>', '<', '+', '-', '.', ',', '[', ']', ' ', 'x', 'い'];
+        for _ in 0..500 {
+            let mut old = String::new();
+            let mut depth = 0;
+            for _ in 0..next() % 40 {
+                match CHARS[(next() % CHARS.len() as u64) as usize] {
+                    ']' if depth == 0 => {}
+                    c => {
+                        depth += (c == '[') as usize;
+                        depth -= (c == ']') as usize;
+                        old.push(c);
+                    }
+                }
+            }
+            old.push_str(&"]".repeat(depth));
+
+            let len = old.chars().count() as u64;
+            let start = (next() % (len + 1)) as usize;
+            let end = start + (next() % (len + 1 - start as u64)) as usize;
+            let replacement = (0..next() % 4)
+                .map(|_| CHARS[(next() % CHARS.len() as u64) as usize])
+                .collect::<String>();
+            let edit = Edit::new(start..end, replacement.chars().count());
+            assert_reparse(&parser, &old, edit, &replacement);
+        }
+    }
+}
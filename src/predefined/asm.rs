@@ -0,0 +1,307 @@
+//! Predefined mnemonic (assembly-style) implementations.
+//!
+//! This module is enabled when feature `asm` is enabled.
+//!
+//! A source consists of lines and each line has at most one mnemonic.
+//! Everything after `;` in a line is a comment.
+//!
+//! | mnemonic    | Brainfuck equivalent           |
+//! |-------------|--------------------------------|
+//! | `right [n]` | `>` repeated `n` times         |
+//! | `left [n]`  | `<` repeated `n` times         |
+//! | `add [n]`   | `+` repeated `n` times         |
+//! | `sub [n]`   | `-` repeated `n` times         |
+//! | `out [n]`   | `.` repeated `n` times         |
+//! | `in [n]`    | `,` repeated `n` times         |
+//! | `loop`      | `[`                            |
+//! | `end`       | `]`                            |
+//!
+//! `n` defaults to 1. For `right`, `left`, `add` and `sub`, a negative `n` means the opposite
+//! direction (e.g. `add -2` is equivalent to `sub 2`).
+//!
+//! # Examples
+//!
+//! ```
+//! use libbf::{predefined::asm, runtime};
+//! use std::io;
+//!
+//! let source = r#"
+//! ; prints "Hi"
+//! add 8
+//! loop        ; repeat 8 times
+//!   right
+//!   add 9     ; cell[1] += 9
+//!   left
+//!   sub
+//! end
+//! right
+//! out         ; 'H' (72)
+//! add 33
+//! out         ; 'i' (105)
+//! "#;
+//! let program = asm::parser().parse_str(source).expect("Failed to parse");
+//! let mut output = Vec::new();
+//! runtime::run(&program, io::stdin(), &mut output).expect("Failed to run");
+//!
+//! assert_eq!(output, b"Hi");
+//! ```
+use crate::{
+    error::ParseError,
+    prelude::Parser,
+    token::{Token, TokenInfo, TokenStream, TokenType, Tokenizer},
+};
+
+/// The character which starts a comment.
+const COMMENT_START: char = ';';
+
+/// Create a parser for the mnemonic language.
+pub fn parser() -> Parser<MnemonicTokenizer> {
+    Parser::new(MnemonicTokenizer)
+}
+
+/// A tokenizer for the mnemonic language.
+pub struct MnemonicTokenizer;
+
+impl<'a> Tokenizer<'a> for MnemonicTokenizer {
+    type Stream = MnemonicTokenStream<'a>;
+
+    fn token_stream(&'a self, source: &'a str) -> Self::Stream {
+        MnemonicTokenStream::new(source)
+    }
+}
+
+/// A token stream for the mnemonic language.
+///
+/// A line with a repetition count `n` generates `n` tokens.
+/// All of them have the same token string (the line without the comment)
+/// and the same position (the start of the line).
+pub struct MnemonicTokenStream<'a> {
+    source: &'a str,
+    pos: usize,
+    pos_in_chars: usize,
+    pending: Option<PendingToken<'a>>,
+}
+
+// Tokens which are generated by a line and not yet returned.
+struct PendingToken<'a> {
+    token_type: TokenType,
+    token_str: &'a str,
+    pos_in_chars: usize,
+    remaining: usize,
+}
+
+// The kind of the argument a mnemonic takes.
+enum ArgumentKind {
+    // A signed count. A negative count means the token type of the opposite direction.
+    Signed(TokenType),
+    // A non-negative count.
+    Count,
+    // No argument.
+    None,
+}
+
+impl<'a> MnemonicTokenStream<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            pos: 0,
+            pos_in_chars: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<'a> TokenStream<'a> for MnemonicTokenStream<'a> {
+    fn next(&mut self) -> Result<TokenInfo<'a>, ParseError> {
+        loop {
+            if let Some(pending) = &mut self.pending {
+                if pending.remaining > 0 {
+                    pending.remaining -= 1;
+                    return Ok(TokenInfo {
+                        token: Some(Token {
+                            token_type: pending.token_type,
+                            token_str: pending.token_str,
+                        }),
+                        pos_in_chars: pending.pos_in_chars,
+                    });
+                }
+                self.pending = None;
+            }
+
+            if self.pos >= self.source.len() {
+                return Ok(TokenInfo {
+                    token: None,
+                    pos_in_chars: self.pos_in_chars,
+                });
+            }
+
+            let line_pos_in_chars = self.pos_in_chars;
+            let rest = &self.source[self.pos..];
+            let line_len = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            let line = &rest[..line_len];
+            // next line
+            self.pos += line_len;
+            self.pos_in_chars += line.chars().count();
+
+            let code = match line.split_once(COMMENT_START) {
+                Some((code, _comment)) => code,
+                None => line,
+            }
+            .trim();
+            if !code.is_empty() {
+                self.pending = Some(parse_line(code, line_pos_in_chars)?);
+            }
+        }
+    }
+}
+
+fn parse_line(code: &str, pos_in_chars: usize) -> Result<PendingToken<'_>, ParseError> {
+    let error = |message: String| ParseError::MiscError {
+        pos_in_chars,
+        message,
+    };
+
+    let mut words = code.split_whitespace();
+    let mnemonic = words.next().expect("code must not be empty");
+    let argument = words.next();
+    if let Some(extra) = words.next() {
+        return Err(error(format!("unexpected argument `{extra}`")));
+    }
+
+    let (token_type, argument_kind) = match mnemonic {
+        "right" => (TokenType::PInc, ArgumentKind::Signed(TokenType::PDec)),
+        "left" => (TokenType::PDec, ArgumentKind::Signed(TokenType::PInc)),
+        "add" => (TokenType::DInc, ArgumentKind::Signed(TokenType::DDec)),
+        "sub" => (TokenType::DDec, ArgumentKind::Signed(TokenType::DInc)),
+        "out" => (TokenType::Output, ArgumentKind::Count),
+        "in" => (TokenType::Input, ArgumentKind::Count),
+        "loop" => (TokenType::LoopHead, ArgumentKind::None),
+        "end" => (TokenType::LoopTail, ArgumentKind::None),
+        _ => return Err(error(format!("unknown mnemonic `{mnemonic}`"))),
+    };
+
+    let (token_type, count) = match (argument_kind, argument) {
+        (_, None) => (token_type, 1),
+        (ArgumentKind::Signed(opposite), Some(argument)) => match argument.parse::<isize>() {
+            Ok(n) if n < 0 => (opposite, n.unsigned_abs()),
+            Ok(n) => (token_type, n as usize),
+            Err(_) => return Err(error(format!("invalid argument `{argument}`"))),
+        },
+        (ArgumentKind::Count, Some(argument)) => match argument.parse::<usize>() {
+            Ok(n) => (token_type, n),
+            Err(_) => return Err(error(format!("invalid argument `{argument}`"))),
+        },
+        (ArgumentKind::None, Some(argument)) => {
+            return Err(error(format!("unexpected argument `{argument}`")))
+        }
+    };
+
+    Ok(PendingToken {
+        token_type,
+        token_str: code,
+        pos_in_chars,
+        remaining: count,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::program::Instruction::*;
+
+    fn token(token_type: TokenType, token_str: &str, pos_in_chars: usize) -> TokenInfo<'_> {
+        TokenInfo {
+            token: Some(Token {
+                token_type,
+                token_str,
+            }),
+            pos_in_chars,
+        }
+    }
+
+    #[test]
+    fn test_token_stream() {
+        // line 0 (char 0): "right 2"
+        // line 1 (char 8): comment only
+        // line 2 (char 18): "  out ; right"
+        // EOF (char 32)
+        let mut stream = MnemonicTokenizer.token_stream("right 2\n; comment\n  out ; right\n");
+        assert_eq!(stream.next().unwrap(), token(TokenType::PInc, "right 2", 0));
+        assert_eq!(stream.next().unwrap(), token(TokenType::PInc, "right 2", 0));
+        assert_eq!(stream.next().unwrap(), token(TokenType::Output, "out", 18));
+        assert_eq!(
+            stream.next().unwrap(),
+            TokenInfo {
+                token: None,
+                pos_in_chars: 32,
+            },
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        let source = "right 3\nleft\nadd -2\nsub +4\nout 0\nin 2\nloop\nend";
+        let program = match parser().parse_str(source) {
+            Ok(program) => program,
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+        assert_eq!(
+            program.instructions(),
+            [PAdd(2), DAdd(-6), Input, Input, UntilZero(vec![])]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "; header\nadd ; sub\n\n   ; indented comment\nout;loop\n";
+        let program = match parser().parse_str(source) {
+            Ok(program) => program,
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+        assert_eq!(program.instructions(), [DAdd(1), Output]);
+    }
+
+    #[test]
+    fn test_unknown_mnemonic() {
+        let source = "add\n  jump 3\n";
+        if let Err(err) = parser().parse_str(source) {
+            if let ParseError::MiscError {
+                pos_in_chars,
+                message,
+            } = err
+            {
+                assert_eq!(pos_in_chars, 4);
+                assert_eq!(message, "unknown mnemonic `jump`");
+            } else {
+                panic!("unexpected error: {err}");
+            }
+        } else {
+            panic!("unexpectedly succeeded");
+        }
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        for (source, expected_message) in [
+            ("add x", "invalid argument `x`"),
+            ("out -1", "invalid argument `-1`"),
+            ("loop 2", "unexpected argument `2`"),
+            ("right 1 2", "unexpected argument `2`"),
+        ] {
+            if let Err(err) = parser().parse_str(source) {
+                if let ParseError::MiscError {
+                    pos_in_chars,
+                    message,
+                } = err
+                {
+                    assert_eq!(pos_in_chars, 0, "{source}");
+                    assert_eq!(message, expected_message, "{source}");
+                } else {
+                    panic!("unexpected error: {err}");
+                }
+            } else {
+                panic!("unexpectedly succeeded: {source}");
+            }
+        }
+    }
+}
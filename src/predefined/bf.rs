@@ -2,6 +2,7 @@
 //!
 //! This module is enabled when feature `bf` is enabled.
 use crate::{
+    fmt::RenderSpec,
     prelude::Parser,
     token::simple::{SimpleTokenSpec1, SimpleTokenizer},
 };
@@ -32,6 +33,13 @@ pub fn parser() -> Parser<SimpleTokenizer> {
     Parser::new(tokenizer())
 }
 
+/// Create a render specification for Brainfuck.
+///
+/// This is equivalent to call of `RenderSpec::new(&TOKEN_SPEC, "")`
+pub fn render_spec() -> RenderSpec {
+    RenderSpec::new(&TOKEN_SPEC, "")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
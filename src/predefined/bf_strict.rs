@@ -0,0 +1,57 @@
+//! Predefined strict Brainfuck implementations.
+//!
+//! Unlike [`bf`](super::bf), only the Brainfuck tokens (`><+-.,[]`) and whitespace are allowed.
+//! Any other character is a parse error instead of a comment.
+//!
+//! This module is enabled when feature `bf` is enabled.
+use super::bf::TOKEN_SPEC;
+use crate::{prelude::Parser, token::simple::SimpleTokenizer};
+
+/// Create a strict tokenizer for Brainfuck.
+///
+/// This is equivalent to call of `TOKEN_SPEC.to_tokenizer().with_strict(true)`
+pub fn tokenizer() -> SimpleTokenizer {
+    TOKEN_SPEC.to_tokenizer().with_strict(true)
+}
+
+/// Create a strict parser for Brainfuck.
+///
+/// This is equivalent to call of `Parser::new(tokenizer())`
+pub fn parser() -> Parser<SimpleTokenizer> {
+    Parser::new(tokenizer())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::ParseError, predefined::bf};
+
+    #[test]
+    fn test_reject_comments() {
+        assert!(bf::parser().parse_str("a+").is_ok());
+        assert!(matches!(
+            parser().parse_str("a+"),
+            Err(ParseError::MiscError {
+                pos_in_chars: 0,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parser().parse_str("+\n[-] ＋"),
+            Err(ParseError::MiscError {
+                pos_in_chars: 6,
+                ..
+            })
+        ));
+
+        let program = parser().parse_str(" +\n[\t-]\r\n").unwrap();
+        assert_eq!(
+            program.instructions(),
+            bf::parser().parse_str("+[-]").unwrap().instructions()
+        );
+        assert!(matches!(
+            parser().parse_str("[+"),
+            Err(ParseError::UnexpectedEndOfFile { .. })
+        ));
+    }
+}
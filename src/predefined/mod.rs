@@ -3,10 +3,94 @@
 //! This module is enabled when predefined related features are enabled.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+#[cfg(feature = "asm")]
+#[cfg_attr(docsrs, doc(cfg(feature = "asm")))]
+pub mod asm;
+
 #[cfg(feature = "bf")]
 #[cfg_attr(docsrs, doc(cfg(feature = "bf")))]
 pub mod bf;
 
+#[cfg(feature = "bf")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bf")))]
+pub mod bf_strict;
+
 #[cfg(feature = "ook")]
 #[cfg_attr(docsrs, doc(cfg(feature = "ook")))]
 pub mod ook;
+
+use crate::{
+    error::ParseError,
+    program::{FatProgram, Program},
+};
+
+/// A predefined dialect.
+///
+/// Only the dialects whose features are enabled are available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// [`asm`]
+    #[cfg(feature = "asm")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "asm")))]
+    Asm,
+    /// [`bf`]
+    #[cfg(feature = "bf")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "bf")))]
+    Bf,
+    /// [`ook`]
+    #[cfg(feature = "ook")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ook")))]
+    Ook,
+}
+
+impl Dialect {
+    /// All available dialects, in the order from the strictest to the most lenient.
+    ///
+    /// This order is suitable for [`detect_dialect`](crate::parser::detect_dialect).
+    /// For example, an Ook! source is also a valid Brainfuck source (`.` is an output),
+    /// so Ook! must be tried before Brainfuck.
+    pub const ALL: &'static [Self] = &[
+        #[cfg(feature = "asm")]
+        Self::Asm,
+        #[cfg(feature = "ook")]
+        Self::Ook,
+        #[cfg(feature = "bf")]
+        Self::Bf,
+    ];
+
+    /// Get the name of the dialect (e.g. `"bf"`).
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "asm")]
+            Self::Asm => "asm",
+            #[cfg(feature = "bf")]
+            Self::Bf => "bf",
+            #[cfg(feature = "ook")]
+            Self::Ook => "ook",
+        }
+    }
+
+    /// Parse a source with the parser of the dialect.
+    pub fn parse_str(self, source: &str) -> Result<Program, ParseError> {
+        match self {
+            #[cfg(feature = "asm")]
+            Self::Asm => asm::parser().parse_str(source),
+            #[cfg(feature = "bf")]
+            Self::Bf => bf::parser().parse_str(source),
+            #[cfg(feature = "ook")]
+            Self::Ook => ook::parser().parse_str(source),
+        }
+    }
+
+    /// Parse a source into a [`FatProgram`] with the parser of the dialect.
+    pub fn parse_fat_str(self, source: &str) -> Result<FatProgram, ParseError> {
+        match self {
+            #[cfg(feature = "asm")]
+            Self::Asm => asm::parser().parse_fat_str(source),
+            #[cfg(feature = "bf")]
+            Self::Bf => bf::parser().parse_fat_str(source),
+            #[cfg(feature = "ook")]
+            Self::Ook => ook::parser().parse_fat_str(source),
+        }
+    }
+}
@@ -3,8 +3,9 @@
 //! This module is enabled when feature `ook` is enabled.
 use crate::{
     error::ParseError,
+    fmt::RenderSpec,
     prelude::Parser,
-    token::{Token, TokenInfo, TokenStream, TokenType, Tokenizer},
+    token::{simple::SimpleTokenSpec1, Token, TokenInfo, TokenStream, TokenType, Tokenizer},
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -29,6 +30,25 @@ pub fn parser() -> Parser<OokTokenizer> {
     Parser::new(OokTokenizer)
 }
 
+/// Create a render specification for Ook!
+///
+/// Each token is rendered as a pair of Ook words, and tokens are separated by a space.
+pub fn render_spec() -> RenderSpec {
+    RenderSpec::new(
+        &SimpleTokenSpec1 {
+            ptr_inc: "Ook. Ook?",
+            ptr_dec: "Ook? Ook.",
+            data_inc: "Ook. Ook.",
+            data_dec: "Ook! Ook!",
+            output: "Ook! Ook.",
+            input: "Ook. Ook!",
+            loop_head: "Ook! Ook?",
+            loop_tail: "Ook? Ook!",
+        },
+        " ",
+    )
+}
+
 /// A tokenizer for Ook!
 pub struct OokTokenizer;
 
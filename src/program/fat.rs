@@ -0,0 +1,157 @@
+//! Programs which keep their source tokens.
+use crate::token::TokenType;
+
+use super::{Instruction, Program};
+
+/// A source token which an instruction was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceToken {
+    /// The token type.
+    pub token_type: TokenType,
+    /// The token string.
+    pub token_str: String,
+    /// The position of the token in the source string which is counted in Unicode scalar units.
+    pub pos_in_chars: usize,
+}
+
+/// An instruction with the source tokens which it was built from.
+///
+/// Unlike [`Instruction`], a run of pointer or data increments/decrements whose sum is zero
+/// (e.g. `+-`) is kept as an instruction with operand 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FatInstruction {
+    /// Unified pointer increments/decrements and their tokens
+    PAdd(isize, Vec<SourceToken>),
+
+    /// Unified data increments/decrements and their tokens
+    DAdd(isize, Vec<SourceToken>),
+
+    /// Write one byte at the current pointer
+    Output(SourceToken),
+
+    /// Read one byte and store it at the current pointer
+    Input(SourceToken),
+
+    /// Breakpoint
+    Breakpoint(SourceToken),
+
+    /// Assert that the value at the current pointer is the operand
+    AssertCell(u8, SourceToken),
+
+    /// loop until the value at the current pointer is non-zero
+    UntilZero {
+        /// The loop head token.
+        head: SourceToken,
+        /// The loop body.
+        body: Vec<FatInstruction>,
+        /// The loop tail token.
+        tail: SourceToken,
+    },
+}
+
+impl FatInstruction {
+    /// Get the source tokens of the instruction in source order, including those of the loop body.
+    pub fn tokens(&self) -> Vec<&SourceToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SourceToken>) {
+        match self {
+            Self::PAdd(_, run) | Self::DAdd(_, run) => tokens.extend(run),
+            Self::Output(token)
+            | Self::Input(token)
+            | Self::Breakpoint(token)
+            | Self::AssertCell(_, token) => tokens.push(token),
+            Self::UntilZero { head, body, tail } => {
+                tokens.push(head);
+                body.iter().for_each(|inst| inst.collect_tokens(tokens));
+                tokens.push(tail);
+            }
+        }
+    }
+
+    fn for_each_token_mut(&mut self, f: &mut impl FnMut(&mut SourceToken)) {
+        match self {
+            Self::PAdd(_, run) | Self::DAdd(_, run) => run.iter_mut().for_each(f),
+            Self::Output(token)
+            | Self::Input(token)
+            | Self::Breakpoint(token)
+            | Self::AssertCell(_, token) => f(token),
+            Self::UntilZero { head, body, tail } => {
+                f(head);
+                body.iter_mut().for_each(|inst| inst.for_each_token_mut(f));
+                f(tail);
+            }
+        }
+    }
+}
+
+/// A parsed program which keeps the source tokens of each instruction.
+///
+/// It is obtained by [`Parser::parse_fat_str`](crate::parser::Parser::parse_fat_str) and
+/// is useful for source-level tools such as formatters.
+/// [`FatProgram::to_program`] converts it into a [`Program`] to run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FatProgram(Vec<FatInstruction>);
+
+impl FatProgram {
+    /// Create a new program from a [`FatInstruction`] vector.
+    pub fn new(instructions: impl Into<Vec<FatInstruction>>) -> Self {
+        Self(instructions.into())
+    }
+
+    /// Get the instructions of the program.
+    pub fn instructions(&self) -> &[FatInstruction] {
+        &self.0
+    }
+
+    /// Get all source tokens of the program in source order.
+    pub fn tokens(&self) -> Vec<&SourceToken> {
+        let mut tokens = Vec::new();
+        self.0
+            .iter()
+            .for_each(|inst| inst.collect_tokens(&mut tokens));
+        tokens
+    }
+
+    /// Drop the token strings to save memory, keeping the token types and positions.
+    ///
+    /// Every [`SourceToken::token_str`] becomes empty. Functions which need the token strings,
+    /// such as [`Parser::reparse`](crate::parser::Parser::reparse), no longer work correctly
+    /// with the stripped program.
+    pub fn strip_token_strings(&mut self) {
+        self.0
+            .iter_mut()
+            .for_each(|inst| inst.for_each_token_mut(&mut |token| token.token_str = String::new()));
+    }
+
+    /// Convert into a [`Program`], dropping the source tokens.
+    ///
+    /// Instructions with operand 0 are dropped, so the result is the same as
+    /// [`Parser::parse_str`](crate::parser::Parser::parse_str) returns.
+    pub fn to_program(&self) -> Program {
+        Program::new(to_instructions(&self.0))
+    }
+}
+
+fn to_instructions(instructions: &[FatInstruction]) -> Vec<Instruction> {
+    instructions
+        .iter()
+        .filter_map(|inst| match inst {
+            FatInstruction::PAdd(0, _) | FatInstruction::DAdd(0, _) => None,
+            FatInstruction::PAdd(operand, _) => Some(Instruction::PAdd(*operand)),
+            FatInstruction::DAdd(operand, _) => Some(Instruction::DAdd(*operand)),
+            FatInstruction::Output(_) => Some(Instruction::Output),
+            FatInstruction::Input(_) => Some(Instruction::Input),
+            FatInstruction::Breakpoint(_) => Some(Instruction::Breakpoint),
+            FatInstruction::AssertCell(expected, _) => Some(Instruction::AssertCell {
+                expected: *expected,
+            }),
+            FatInstruction::UntilZero { body, .. } => {
+                Some(Instruction::UntilZero(to_instructions(body)))
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,402 @@
+//! Flat representation of programs.
+use std::fmt::Write;
+
+use super::{FatInstruction, FatProgram, Instruction, Program, ProgramIndex};
+
+/// An instruction of [`FlatProgram`].
+///
+/// Loops are represented by a pair of jumps, whose operands are offsets in the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlatInstruction {
+    /// [`Instruction::PAdd`]
+    PAdd(isize),
+    /// [`Instruction::DAdd`]
+    DAdd(isize),
+    /// [`Instruction::Output`]
+    Output,
+    /// [`Instruction::Input`]
+    Input,
+    /// The head of a loop: jump to the offset if the value at the current pointer is zero.
+    ///
+    /// The offset is the one just after the matching [`FlatInstruction::JumpIfNonZero`].
+    JumpIfZero(usize),
+    /// The tail of a loop: jump to the offset if the value at the current pointer is non-zero.
+    ///
+    /// The offset is the one just after the matching [`FlatInstruction::JumpIfZero`].
+    JumpIfNonZero(usize),
+    /// [`Instruction::SetZero`]
+    SetZero,
+    /// [`Instruction::DSet`]
+    DSet(u8),
+    /// [`Instruction::Fill`]
+    Fill { len: usize, value: u8 },
+    /// [`Instruction::Breakpoint`]
+    Breakpoint,
+    /// [`Instruction::AssertCell`]
+    AssertCell { expected: u8 },
+}
+
+/// A program whose loops are flattened into jumps.
+///
+/// Each instruction is identified by its offset, which can be converted from and into
+/// [`ProgramIndex`] of the original program.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::program::{FlatProgram, Instruction::*, Program};
+///
+/// // ++[>+<-]
+/// let program = Program::new([
+///     DAdd(2),
+///     UntilZero(vec![PAdd(1), DAdd(1), PAdd(-1), DAdd(-1)]),
+/// ]);
+/// let flat = FlatProgram::new(&program);
+///
+/// assert_eq!(
+///     flat.disassemble(),
+///     "\
+/// 0000  dadd  2
+/// 0001  jz    0007      +
+/// 0002  padd  1         |
+/// 0003  dadd  1         |
+/// 0004  padd  -1        |
+/// 0005  dadd  -1        |
+/// 0006  jnz   0002      +
+/// ",
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlatProgram {
+    instructions: Vec<FlatInstruction>,
+    // the index of the original instruction at each offset.
+    // Both jumps of a loop have the index of the loop.
+    indices: Vec<ProgramIndex>,
+    // the position in chars of the first source token at each offset, if built from a fat program.
+    positions: Option<Vec<usize>>,
+}
+
+impl FlatProgram {
+    /// Create a new flat program from a program.
+    pub fn new(program: &Program) -> Self {
+        let mut flat = Self {
+            instructions: Vec::new(),
+            indices: Vec::new(),
+            positions: None,
+        };
+        flat.push_instructions(program.instructions(), &mut vec![]);
+        flat
+    }
+
+    /// Create a new flat program from a fat program, keeping the source positions.
+    ///
+    /// The offsets and indices are those of [`FatProgram::to_program`].
+    /// [`FlatProgram::disassemble_with_source`] uses the source positions.
+    pub fn from_fat(fat: &FatProgram) -> Self {
+        let mut flat = Self {
+            instructions: Vec::new(),
+            indices: Vec::new(),
+            positions: Some(Vec::new()),
+        };
+        flat.push_fat_instructions(fat.instructions(), &mut vec![]);
+        flat
+    }
+
+    /// Get the instructions of the program.
+    pub fn instructions(&self) -> &[FlatInstruction] {
+        &self.instructions
+    }
+
+    /// Get the offset of the instruction pointed by `index`.
+    ///
+    /// For a loop, this is the offset of its [`FlatInstruction::JumpIfZero`].
+    /// Returns `None` if `index` does not point to an instruction.
+    pub fn offset_of(&self, index: &ProgramIndex) -> Option<usize> {
+        self.indices.iter().position(|i| i == index)
+    }
+
+    /// Get the index of the original instruction at `offset`.
+    ///
+    /// Both jumps of a loop are converted into the index of the loop.
+    /// Returns `None` if `offset` is out of range.
+    pub fn index_of(&self, offset: usize) -> Option<&ProgramIndex> {
+        self.indices.get(offset)
+    }
+
+    /// Disassemble the program.
+    ///
+    /// Each line consists of the offset, the mnemonic, the operand and a column which shows loops:
+    /// the jumps of a loop are marked with `+` and connected with `|`, one lane per depth.
+    ///
+    /// | mnemonic | instruction                         |
+    /// |----------|-------------------------------------|
+    /// | `padd`   | [`FlatInstruction::PAdd`]           |
+    /// | `dadd`   | [`FlatInstruction::DAdd`]           |
+    /// | `out`    | [`FlatInstruction::Output`]         |
+    /// | `in`     | [`FlatInstruction::Input`]          |
+    /// | `jz`     | [`FlatInstruction::JumpIfZero`]     |
+    /// | `jnz`    | [`FlatInstruction::JumpIfNonZero`]  |
+    /// | `clr`    | [`FlatInstruction::SetZero`]        |
+    /// | `set`    | [`FlatInstruction::DSet`]           |
+    /// | `fill`   | [`FlatInstruction::Fill`]           |
+    /// | `brk`    | [`FlatInstruction::Breakpoint`]     |
+    /// | `asrt`   | [`FlatInstruction::AssertCell`]     |
+    pub fn disassemble(&self) -> String {
+        self.disassemble_internal(None)
+    }
+
+    /// Disassemble the program, interleaving the source lines.
+    ///
+    /// `source` must be the source string which the fat program was parsed from.
+    /// Each source line is put as a comment starting with `;` before the first instruction built
+    /// from it. If the program was not created by [`FlatProgram::from_fat`], this is equivalent to
+    /// [`FlatProgram::disassemble`].
+    pub fn disassemble_with_source(&self, source: &str) -> String {
+        self.disassemble_internal(Some(source))
+    }
+
+    fn disassemble_internal(&self, source: Option<&str>) -> String {
+        // (line number, line) of each character
+        let lines = source.map(|source| {
+            source
+                .split('\n')
+                .enumerate()
+                .flat_map(|(number, line)| {
+                    let line = line.strip_suffix('\r').unwrap_or(line);
+                    std::iter::repeat_n((number, line), line.chars().count() + 1)
+                })
+                .collect::<Vec<_>>()
+        });
+        let digits = self
+            .instructions
+            .len()
+            .saturating_sub(1)
+            .to_string()
+            .len()
+            .max(4);
+        let mut last_line = None;
+        let mut result = String::new();
+        for (offset, inst) in self.instructions.iter().enumerate() {
+            if let (Some(lines), Some(positions)) = (&lines, &self.positions) {
+                if let Some(&(number, line)) = lines.get(positions[offset]) {
+                    if last_line.is_none_or(|last| last < number) {
+                        writeln!(result, "; {}", line.trim_end()).unwrap();
+                        last_line = Some(number);
+                    }
+                }
+            }
+
+            let (mnemonic, operand) = match inst {
+                FlatInstruction::PAdd(operand) => ("padd", operand.to_string()),
+                FlatInstruction::DAdd(operand) => ("dadd", operand.to_string()),
+                FlatInstruction::Output => ("out", String::new()),
+                FlatInstruction::Input => ("in", String::new()),
+                FlatInstruction::JumpIfZero(target) => ("jz", format!("{target:0digits$}")),
+                FlatInstruction::JumpIfNonZero(target) => ("jnz", format!("{target:0digits$}")),
+                FlatInstruction::SetZero => ("clr", String::new()),
+                FlatInstruction::DSet(value) => ("set", value.to_string()),
+                FlatInstruction::Fill { len, value } => ("fill", format!("{len}, {value}")),
+                FlatInstruction::Breakpoint => ("brk", String::new()),
+                FlatInstruction::AssertCell { expected } => ("asrt", expected.to_string()),
+            };
+            let lanes = self.lanes(offset);
+            let line = format!("{offset:0digits$}  {mnemonic:<5} {operand:<9} {lanes}");
+            writeln!(result, "{}", line.trim_end()).unwrap();
+        }
+        result
+    }
+
+    // The loop column at `offset`.
+    fn lanes(&self, offset: usize) -> String {
+        let index = &self.indices[offset];
+        let is_jump = matches!(
+            self.instructions[offset],
+            FlatInstruction::JumpIfZero(_) | FlatInstruction::JumpIfNonZero(_)
+        );
+        // an index at depth `d` is in `d - 1` loops, excluding the loop itself for jumps.
+        let mut lanes = vec!["|"; index.depth() - 1];
+        if is_jump {
+            lanes.push("+");
+        }
+        lanes.join(" ")
+    }
+
+    fn push(&mut self, inst: FlatInstruction, path: &[usize], position: Option<usize>) {
+        self.instructions.push(inst);
+        self.indices.push(ProgramIndex::from_path(path));
+        if let (Some(positions), Some(position)) = (&mut self.positions, position) {
+            positions.push(position);
+        }
+    }
+
+    fn push_instructions(&mut self, instructions: &[Instruction], path: &mut Vec<usize>) {
+        for (i, inst) in instructions.iter().enumerate() {
+            path.push(i);
+            let flat = match inst {
+                Instruction::PAdd(operand) => FlatInstruction::PAdd(*operand),
+                Instruction::DAdd(operand) => FlatInstruction::DAdd(*operand),
+                Instruction::Output => FlatInstruction::Output,
+                Instruction::Input => FlatInstruction::Input,
+                Instruction::Breakpoint => FlatInstruction::Breakpoint,
+                Instruction::AssertCell { expected } => FlatInstruction::AssertCell {
+                    expected: *expected,
+                },
+                Instruction::SetZero => FlatInstruction::SetZero,
+                Instruction::DSet(value) => FlatInstruction::DSet(*value),
+                Instruction::Fill { len, value } => FlatInstruction::Fill {
+                    len: *len,
+                    value: *value,
+                },
+                Instruction::UntilZero(sub) => {
+                    let head = self.instructions.len();
+                    self.push(FlatInstruction::JumpIfZero(0), path, None);
+                    self.push_instructions(sub, path);
+                    self.close_loop(head, path, None);
+                    path.pop();
+                    continue;
+                }
+            };
+            self.push(flat, path, None);
+            path.pop();
+        }
+    }
+
+    fn push_fat_instructions(&mut self, instructions: &[FatInstruction], path: &mut Vec<usize>) {
+        let mut i = 0;
+        for inst in instructions {
+            let (flat, position) = match inst {
+                FatInstruction::PAdd(0, _) | FatInstruction::DAdd(0, _) => continue,
+                FatInstruction::PAdd(operand, tokens) => {
+                    (FlatInstruction::PAdd(*operand), tokens[0].pos_in_chars)
+                }
+                FatInstruction::DAdd(operand, tokens) => {
+                    (FlatInstruction::DAdd(*operand), tokens[0].pos_in_chars)
+                }
+                FatInstruction::Output(token) => (FlatInstruction::Output, token.pos_in_chars),
+                FatInstruction::Input(token) => (FlatInstruction::Input, token.pos_in_chars),
+                FatInstruction::Breakpoint(token) => {
+                    (FlatInstruction::Breakpoint, token.pos_in_chars)
+                }
+                FatInstruction::AssertCell(expected, token) => (
+                    FlatInstruction::AssertCell {
+                        expected: *expected,
+                    },
+                    token.pos_in_chars,
+                ),
+                FatInstruction::UntilZero { head, body, tail } => {
+                    path.push(i);
+                    let head_offset = self.instructions.len();
+                    self.push(
+                        FlatInstruction::JumpIfZero(0),
+                        path,
+                        Some(head.pos_in_chars),
+                    );
+                    self.push_fat_instructions(body, path);
+                    self.close_loop(head_offset, path, Some(tail.pos_in_chars));
+                    path.pop();
+                    i += 1;
+                    continue;
+                }
+            };
+            path.push(i);
+            self.push(flat, path, Some(position));
+            path.pop();
+            i += 1;
+        }
+    }
+
+    fn close_loop(&mut self, head: usize, path: &[usize], position: Option<usize>) {
+        self.push(FlatInstruction::JumpIfNonZero(head + 1), path, position);
+        self.instructions[head] = FlatInstruction::JumpIfZero(self.instructions.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use Instruction::*;
+
+    fn nested_program() -> Program {
+        // +[>[-]<-],
+        Program::new([
+            DAdd(1),
+            UntilZero(vec![PAdd(1), UntilZero(vec![DAdd(-1)]), PAdd(-1), DAdd(-1)]),
+            Input,
+        ])
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let flat = FlatProgram::new(&nested_program());
+        assert_eq!(
+            flat.disassemble(),
+            "\
+0000  dadd  1
+0001  jz    0009      +
+0002  padd  1         |
+0003  jz    0006      | +
+0004  dadd  -1        | |
+0005  jnz   0004      | +
+0006  padd  -1        |
+0007  dadd  -1        |
+0008  jnz   0002      +
+0009  in
+"
+        );
+    }
+
+    #[test]
+    fn test_offset_and_index() {
+        let program = nested_program();
+        let flat = FlatProgram::new(&program);
+        for (offset, inst) in flat.instructions().iter().enumerate() {
+            let index = flat.index_of(offset).unwrap();
+            match inst {
+                FlatInstruction::JumpIfNonZero(target) => {
+                    assert_eq!(flat.offset_of(index), Some(target - 1));
+                }
+                FlatInstruction::JumpIfZero(_) => {
+                    assert!(matches!(program[index], UntilZero(_)));
+                    assert_eq!(flat.offset_of(index), Some(offset));
+                }
+                _ => assert_eq!(flat.offset_of(index), Some(offset)),
+            }
+        }
+        assert_eq!(flat.index_of(5), Some(&ProgramIndex::new_for_test([1, 1])));
+        assert_eq!(flat.index_of(10), None);
+        assert_eq!(flat.offset_of(&ProgramIndex::new_for_test([3])), None);
+    }
+
+    #[cfg(feature = "bf")]
+    #[test]
+    fn test_disassemble_with_source() {
+        let source = "+ init\n[>[-]<-\n]  loop\n+-,\n";
+        let fat = crate::predefined::bf::parser()
+            .parse_fat_str(source)
+            .unwrap();
+        let flat = FlatProgram::from_fat(&fat);
+        assert_eq!(
+            flat.instructions(),
+            FlatProgram::new(&fat.to_program()).instructions()
+        );
+        assert_eq!(
+            flat.disassemble_with_source(source),
+            "\
+; + init
+0000  dadd  1
+; [>[-]<-
+0001  jz    0009      +
+0002  padd  1         |
+0003  jz    0006      | +
+0004  dadd  -1        | |
+0005  jnz   0004      | +
+0006  padd  -1        |
+0007  dadd  -1        |
+; ]  loop
+0008  jnz   0002      +
+; +-,
+0009  in
+"
+        );
+    }
+}
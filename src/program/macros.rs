@@ -0,0 +1,221 @@
+//! Macros for compile-time program construction.
+
+/// Build a [`Program`](crate::program::Program) from a Brainfuck literal at compile time.
+///
+/// The literal consists of the eight Brainfuck characters `><+-.,[]` only.
+/// Other characters, including comments, are not allowed.
+/// Consecutive `>`/`<` and `+`/`-` are folded into one instruction like the parser does,
+/// so the resulting program equals the one parsed from the same source.
+///
+/// Since the literal is processed token by token, a long literal may exceed the recursion limit.
+/// Increase it by `#![recursion_limit = "..."]` if needed.
+///
+/// With the `macros` feature, the code can also be a string literal, which may contain comments
+/// and is parsed by a procedural macro without the recursion limit. A syntax error in the string
+/// is reported as a compile error at the literal.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{bf, program::Instruction::*};
+///
+/// let program = bf!(++[>+<-]);
+/// assert_eq!(
+///     program.instructions(),
+///     [DAdd(2), UntilZero(vec![PAdd(1), DAdd(1), PAdd(-1), DAdd(-1)])],
+/// );
+/// ```
+///
+/// A string literal:
+///
+/// ```
+/// # #[cfg(feature = "macros")]
+/// # {
+/// use libbf::{bf, program::Instruction::*};
+///
+/// let program = bf!("++ copy [>+<-]");
+/// assert_eq!(
+///     program.instructions(),
+///     [DAdd(2), UntilZero(vec![PAdd(1), DAdd(1), PAdd(-1), DAdd(-1)])],
+/// );
+/// # }
+/// ```
+///
+/// An unmatched loop tail is a compile error:
+///
+/// ```compile_fail
+/// let program = libbf::bf!("+[-]]");
+/// ```
+#[macro_export]
+macro_rules! bf {
+    // `-` may start a negative literal, which is not a string.
+    (- $($code:tt)*) => {
+        $crate::program::Program::new($crate::__bf_instructions!(- $($code)*))
+    };
+    ($source:literal) => {
+        $crate::__bf_str!($crate, $source)
+    };
+    ($($code:tt)*) => {
+        $crate::program::Program::new($crate::__bf_instructions!($($code)*))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bf_instructions {
+    ($($code:tt)*) => {{
+        let mut instructions = ::std::vec::Vec::<$crate::program::Instruction>::new();
+        #[allow(unused_variables)]
+        let v = &mut instructions;
+        $crate::__bf_munch!(v [] $($code)*);
+        instructions
+    }};
+}
+
+#[cfg(not(feature = "macros"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bf_str {
+    ($krate:path, $source:literal) => {
+        ::std::compile_error!("`bf!` with a string literal requires the `macros` feature")
+    };
+}
+
+// `[]` holds a pending `PAdd`/`DAdd` and its operand, which is pushed
+// when a token of another kind comes.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bf_munch {
+    // end of the code
+    ($v:ident []) => {};
+    ($v:ident [$kind:ident $operand:expr]) => {
+        $crate::__bf_push!($v $kind $operand);
+    };
+
+    // split compound tokens
+    ($v:ident [$($pending:tt)*] >> $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] > > $($rest)*);
+    };
+    ($v:ident [$($pending:tt)*] << $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] < < $($rest)*);
+    };
+    ($v:ident [$($pending:tt)*] -> $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] - > $($rest)*);
+    };
+    ($v:ident [$($pending:tt)*] <- $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] < - $($rest)*);
+    };
+    ($v:ident [$($pending:tt)*] ... $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] . . . $($rest)*);
+    };
+    ($v:ident [$($pending:tt)*] .. $($rest:tt)*) => {
+        $crate::__bf_munch!($v [$($pending)*] . . $($rest)*);
+    };
+
+    // fold into the pending instruction
+    ($v:ident [PAdd $operand:expr] > $($rest:tt)*) => {
+        $crate::__bf_munch!($v [PAdd $operand + 1] $($rest)*);
+    };
+    ($v:ident [PAdd $operand:expr] < $($rest:tt)*) => {
+        $crate::__bf_munch!($v [PAdd $operand - 1] $($rest)*);
+    };
+    ($v:ident [DAdd $operand:expr] + $($rest:tt)*) => {
+        $crate::__bf_munch!($v [DAdd $operand + 1] $($rest)*);
+    };
+    ($v:ident [DAdd $operand:expr] - $($rest:tt)*) => {
+        $crate::__bf_munch!($v [DAdd $operand - 1] $($rest)*);
+    };
+
+    // push the pending instruction before a token of another kind
+    ($v:ident [$kind:ident $operand:expr] $($rest:tt)*) => {
+        $crate::__bf_push!($v $kind $operand);
+        $crate::__bf_munch!($v [] $($rest)*);
+    };
+
+    ($v:ident [] > $($rest:tt)*) => {
+        $crate::__bf_munch!($v [PAdd 1] $($rest)*);
+    };
+    ($v:ident [] < $($rest:tt)*) => {
+        $crate::__bf_munch!($v [PAdd -1] $($rest)*);
+    };
+    ($v:ident [] + $($rest:tt)*) => {
+        $crate::__bf_munch!($v [DAdd 1] $($rest)*);
+    };
+    ($v:ident [] - $($rest:tt)*) => {
+        $crate::__bf_munch!($v [DAdd -1] $($rest)*);
+    };
+    ($v:ident [] . $($rest:tt)*) => {
+        $v.push($crate::program::Instruction::Output);
+        $crate::__bf_munch!($v [] $($rest)*);
+    };
+    ($v:ident [] , $($rest:tt)*) => {
+        $v.push($crate::program::Instruction::Input);
+        $crate::__bf_munch!($v [] $($rest)*);
+    };
+    ($v:ident [] [$($body:tt)*] $($rest:tt)*) => {
+        $v.push($crate::program::Instruction::UntilZero(
+            $crate::__bf_instructions!($($body)*),
+        ));
+        $crate::__bf_munch!($v [] $($rest)*);
+    };
+}
+
+// Push `PAdd`/`DAdd` unless its operand is 0, like the parser does.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bf_push {
+    ($v:ident $kind:ident $operand:expr) => {
+        let operand: isize = $operand;
+        if operand != 0 {
+            $v.push($crate::program::Instruction::$kind(operand));
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::bf_parser;
+
+    fn parse(source: &str) -> crate::program::Program {
+        bf_parser().parse_str(source).unwrap()
+    }
+
+    #[test]
+    fn test_bf() {
+        assert_eq!(
+            bf!(++[>+<-]).instructions(),
+            parse("++[>+<-]").instructions()
+        );
+        assert_eq!(
+            bf!(,[.,]>>+-<-->...<<[[]]).instructions(),
+            parse(",[.,]>>+-<-->...<<[[]]").instructions()
+        );
+        assert!(bf!().instructions().is_empty());
+        assert_eq!(bf!(-).instructions(), parse("-").instructions());
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_bf_str() {
+        assert_eq!(
+            bf!("++ copy [>+<-]").instructions(),
+            parse("++[>+<-]").instructions()
+        );
+        assert_eq!(
+            bf!(",[.,]>>+-<-->...<<[[]]").instructions(),
+            parse(",[.,]>>+-<-->...<<[[]]").instructions()
+        );
+        assert!(bf!("").instructions().is_empty());
+
+        let program = bf!(
+            "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++."
+        );
+        assert_eq!(
+            program.instructions(),
+            parse(include_str!("../../tests/data/hello.bf")).instructions()
+        );
+        let mut output = vec![];
+        crate::runtime::run(&program, &[][..], &mut output).unwrap();
+        assert_eq!(output, b"Hello World!\n");
+    }
+}
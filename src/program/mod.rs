@@ -1,14 +1,29 @@
 //! Parsed program of Brainfuck-like language and related definitions.
-use std::ops::Index;
+mod fat;
+mod flat;
+mod macros;
+
+pub use self::fat::{FatInstruction, FatProgram, SourceToken};
+pub use self::flat::{FlatInstruction, FlatProgram};
+
+use std::{fmt, ops::Index, str::FromStr};
+
+use crate::{
+    error::{ParseIndexError, ValidationError},
+    runtime::MemorySize,
+};
 
 /// A parsed program of Brainfuck-link language.
 ///
 /// Each instruction can be acceseed by [`ProgramIndex`].
-#[derive(Debug)]
+///
+/// A program is `Send` and `Sync`, so it can be parsed once and shared between threads
+/// (e.g. in an [`Arc`](std::sync::Arc)).
+#[derive(Debug, Clone)]
 pub struct Program(Vec<Instruction>);
 
 /// An intermediate instruction of Brainfuck-like language.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     /// Unified pointer increments/decrements
     PAdd(isize),
@@ -24,10 +39,117 @@ pub enum Instruction {
 
     /// loop until the value at the current pointer is non-zero
     UntilZero(Vec<Instruction>),
+
+    /// Set the value at the current pointer to zero
+    ///
+    /// This instruction is generated by optimizations (see [`crate::optimize`]).
+    SetZero,
+
+    /// Set the value at the current pointer to the operand
+    ///
+    /// This instruction is generated by optimizations (see [`crate::optimize`]).
+    DSet(u8),
+
+    /// Set `len` cells from the current pointer to `value` without moving the pointer
+    ///
+    /// This instruction is generated by optimizations (see [`crate::optimize`]).
+    Fill { len: usize, value: u8 },
+
+    /// Do nothing, but pause a [`StepRunner`](crate::runtime::StepRunner)
+    ///
+    /// [`Runner`](crate::runtime::Runner) ignores this instruction.
+    Breakpoint,
+
+    /// Assert that the value at the current pointer is `expected`
+    ///
+    /// If it is not, the run fails with
+    /// [`RuntimeError::AssertionFailed`](crate::error::RuntimeError::AssertionFailed).
+    /// This instruction is for testing programs, and can be injected by tools or parsed from
+    /// [`TokenType::AssertCell`](crate::token::TokenType::AssertCell) tokens.
+    AssertCell { expected: u8 },
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction in a C-like arithmetic form (e.g. `ptr += 3`, `*ptr -= 1`).
+    ///
+    /// A loop is formatted as `while *ptr { ... }` with its body separated by `; `.
+    /// The alternate form (`{:#}`) omits the body as `...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = |operand: isize| if operand < 0 { '-' } else { '+' };
+        match self {
+            Self::PAdd(operand) => write!(f, "ptr {}= {}", sign(*operand), operand.unsigned_abs()),
+            Self::DAdd(operand) => {
+                write!(f, "*ptr {}= {}", sign(*operand), operand.unsigned_abs())
+            }
+            Self::Output => write!(f, "output"),
+            Self::Input => write!(f, "input"),
+            Self::UntilZero(body) if body.is_empty() => write!(f, "while *ptr {{}}"),
+            Self::UntilZero(_) if f.alternate() => write!(f, "while *ptr {{ ... }}"),
+            Self::UntilZero(body) => {
+                write!(f, "while *ptr {{ ")?;
+                for (i, inst) in body.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{inst}")?;
+                }
+                write!(f, " }}")
+            }
+            Self::SetZero => write!(f, "*ptr = 0"),
+            Self::DSet(value) => write!(f, "*ptr = {value}"),
+            Self::Fill { len, value } => write!(f, "ptr[0..{len}] = {value}"),
+            Self::Breakpoint => write!(f, "breakpoint"),
+            Self::AssertCell { expected } => write!(f, "assert *ptr == {expected}"),
+        }
+    }
+}
+
+/// Static counts of instructions in a [`Program`] by kind.
+///
+/// It is obtained by [`Program::instruction_counts`].
+/// Instructions nested within loops are counted once regardless of how many times they are executed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstructionCounts {
+    /// The number of [`Instruction::PAdd`]
+    pub p_add: usize,
+    /// The number of [`Instruction::DAdd`]
+    pub d_add: usize,
+    /// The number of [`Instruction::Output`]
+    pub output: usize,
+    /// The number of [`Instruction::Input`]
+    pub input: usize,
+    /// The number of [`Instruction::UntilZero`]
+    pub loops: usize,
+    /// The number of [`Instruction::SetZero`]
+    pub set_zero: usize,
+    /// The number of [`Instruction::DSet`]
+    pub d_set: usize,
+    /// The number of [`Instruction::Fill`]
+    pub fill: usize,
+    /// The number of [`Instruction::Breakpoint`]
+    pub breakpoints: usize,
+    /// The number of [`Instruction::AssertCell`]
+    pub assertions: usize,
+}
+
+impl InstructionCounts {
+    /// Get the total number of instructions.
+    pub fn total(&self) -> usize {
+        self.p_add
+            + self.d_add
+            + self.output
+            + self.input
+            + self.loops
+            + self.set_zero
+            + self.d_set
+            + self.fill
+            + self.breakpoints
+            + self.assertions
+    }
 }
 
 /// An itdex for [`Program`]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ProgramIndex(Vec<usize>);
 
 impl ProgramIndex {
@@ -36,6 +158,19 @@ impl ProgramIndex {
         Self(index.into())
     }
 
+    /// Create an index from the path of positions, from the top-level instruction to
+    /// the innermost one.
+    ///
+    /// The index is not validated. Use [`Program::is_valid_index`] to check it against a program.
+    pub fn from_path(path: impl Into<Vec<usize>>) -> Self {
+        Self(path.into())
+    }
+
+    /// Get the path of positions, from the top-level instruction to the innermost one.
+    pub fn path(&self) -> &[usize] {
+        &self.0
+    }
+
     /// Set the index to point to the first instruction of the next depth.
     pub fn step_in(&mut self) {
         self.0.push(0);
@@ -48,6 +183,23 @@ impl ProgramIndex {
         self.0.pop();
         !self.0.is_empty()
     }
+
+    /// Get the index of the loop instruction which contains the instruction pointed by this index.
+    ///
+    /// Returns `None` if the index points to a top-level instruction.
+    pub fn parent(&self) -> Option<ProgramIndex> {
+        match self.0.split_last() {
+            Some((_, parent)) if !parent.is_empty() => Some(Self(parent.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Get the depth of the index.
+    ///
+    /// An index which points to a top-level instruction has depth 1.
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl Program {
@@ -56,6 +208,30 @@ impl Program {
         Self(instructions.into())
     }
 
+    /// Create a new program which consists of the single instruction.
+    pub fn single(instruction: Instruction) -> Self {
+        Self(vec![instruction])
+    }
+
+    /// Create a builder of an empty program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::program::{Instruction::*, Program};
+    ///
+    /// let program = Program::builder()
+    ///     .push(DAdd(3))
+    ///     .push(UntilZero(vec![DAdd(-1)]))
+    ///     .extend([PAdd(1), Output])
+    ///     .build();
+    ///
+    /// assert_eq!(program.instructions(), [DAdd(3), UntilZero(vec![DAdd(-1)]), PAdd(1), Output]);
+    /// ```
+    pub fn builder() -> ProgramBuilder {
+        ProgramBuilder::default()
+    }
+
     /// Get the instructions of the program.
     pub fn instructions(&self) -> &[Instruction] {
         &self.0
@@ -72,6 +248,22 @@ impl Program {
         }
     }
 
+    /// Get the instruction pointed by `index`.
+    ///
+    /// Unlike indexing (`program[&index]`), this returns `None` instead of panicking if `index` is
+    /// invalid (see [`Program::is_valid_index`]).
+    pub fn get(&self, index: &ProgramIndex) -> Option<&Instruction> {
+        get_instruction_at(&self.0, &index.0)
+    }
+
+    /// Returns `true` if `index` points to an instruction of the program.
+    ///
+    /// An index is invalid if it is empty, out of range, or recurses into an instruction other than
+    /// [`Instruction::UntilZero`]. Indexing (`program[&index]`) panics with an invalid index.
+    pub fn is_valid_index(&self, index: &ProgramIndex) -> bool {
+        self.get(index).is_some()
+    }
+
     /// Step `index` to the next instruction.
     ///
     /// If the index already points to the last instruction of the program or
@@ -85,6 +277,165 @@ impl Program {
         Self::next_index_internal(self.instructions(), &mut index.0)
     }
 
+    /// Step `index` to the next instruction in pre-order, stepping into loop bodies.
+    ///
+    /// If the index points to an [`Instruction::UntilZero`] instruction with a non-empty body,
+    /// the index is changed to point to the first instruction of the body. Otherwise, the index is
+    /// changed to point to the next instruction, stepping out of loop bodies which end.
+    /// Unlike [`Program::step_index`], this follows the static order of the instructions,
+    /// not the execution order, so starting from [`Program::first_index`] visits every
+    /// instruction once.
+    ///
+    /// If the index already points to the last instruction in pre-order, the index is not changed
+    /// and this function returns `false`.
+    pub fn advance_preorder(&self, index: &mut ProgramIndex) -> bool {
+        if let Instruction::UntilZero(sub) = &self[index] {
+            if !sub.is_empty() {
+                index.step_in();
+                return true;
+            }
+        }
+        let mut next = index.clone();
+        loop {
+            if self.step_index(&mut next) {
+                *index = next;
+                return true;
+            }
+            if !next.step_out() {
+                return false;
+            }
+        }
+    }
+
+    /// Get the number of [`Instruction::Input`] instructions which the program executes,
+    /// if it is statically determinable.
+    ///
+    /// The number is determinable if no input instruction is nested within loops.
+    /// Otherwise, this function returns `None`.
+    pub fn static_input_count(&self) -> Option<usize> {
+        let mut count = 0;
+        for instruction in self.instructions() {
+            match instruction {
+                Instruction::Input => count += 1,
+                Instruction::UntilZero(sub) if contains_input(sub) => return None,
+                _ => {}
+            }
+        }
+        Some(count)
+    }
+
+    /// Count the instructions by kind, including those nested within loops.
+    pub fn instruction_counts(&self) -> InstructionCounts {
+        let mut counts = InstructionCounts::default();
+        count_instructions(self.instructions(), &mut counts);
+        counts
+    }
+
+    /// List the indices of [`Instruction::Output`] and [`Instruction::Input`] instructions
+    /// which are nested within loops.
+    ///
+    /// This is an advisory lint: byte-by-byte I/O in a loop body may be slow unless
+    /// the input or output is buffered.
+    pub fn io_in_loops(&self) -> Vec<ProgramIndex> {
+        let mut result = Vec::new();
+        Self::io_in_loops_internal(self.instructions(), &mut vec![], &mut result);
+        result
+    }
+
+    fn io_in_loops_internal(
+        instructions: &[Instruction],
+        path: &mut Vec<usize>,
+        result: &mut Vec<ProgramIndex>,
+    ) {
+        for (i, instruction) in instructions.iter().enumerate() {
+            path.push(i);
+            match instruction {
+                Instruction::Output | Instruction::Input if path.len() > 1 => {
+                    result.push(ProgramIndex(path.clone()))
+                }
+                Instruction::UntilZero(sub) => Self::io_in_loops_internal(sub, path, result),
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+
+    /// Check statically-detectable issues which make the program fail or hang
+    /// when it runs with the memory size `memsize`.
+    ///
+    /// This is a best-effort pre-flight check. Passing it does not guarantee that the program runs
+    /// successfully, and the first issue found in program order is reported.
+    ///
+    /// Loops which are never entered are not checked, such as a loop at the start of the program
+    /// (a common idiom of a header comment) or a loop right after another loop or
+    /// [`Instruction::SetZero`], where the current cell is always 0.
+    ///
+    /// # Errors
+    ///
+    /// - [`ValidationError::PointerMoveOutOfBounds`] if `memsize` is [`MemorySize::Fixed`] or
+    ///   [`MemorySize::LeftRightBounded`] and a pointer move is not smaller than the memory size.
+    /// - [`ValidationError::InfiniteLoop`] if a loop body never changes the memory or the pointer.
+    pub fn validate_runnable(&self, memsize: MemorySize) -> Result<(), ValidationError> {
+        Self::validate_runnable_internal(self.instructions(), memsize, &mut vec![])
+    }
+
+    fn validate_runnable_internal(
+        instructions: &[Instruction],
+        memsize: MemorySize,
+        path: &mut Vec<usize>,
+    ) -> Result<(), ValidationError> {
+        for (i, instruction) in instructions.iter().enumerate() {
+            path.push(i);
+            match instruction {
+                // the current cell is 0 at the start of the program and after these instructions.
+                Instruction::UntilZero(_)
+                    if *path == [0]
+                        || i.checked_sub(1).is_some_and(|prev| {
+                            matches!(
+                                instructions[prev],
+                                Instruction::UntilZero(_) | Instruction::SetZero
+                            )
+                        }) => {}
+                Instruction::PAdd(operand) => {
+                    let len = match memsize {
+                        MemorySize::Fixed(len) => Some(len),
+                        MemorySize::LeftRightBounded { left, right } => left.checked_add(right),
+                        MemorySize::RightInfinite | MemorySize::BothInfinite => None,
+                    };
+                    if let Some(len) = len {
+                        if operand.unsigned_abs() >= len {
+                            return Err(ValidationError::PointerMoveOutOfBounds {
+                                index: ProgramIndex(path.clone()),
+                                operand: *operand,
+                                memsize,
+                            });
+                        }
+                    }
+                }
+                Instruction::UntilZero(sub) => {
+                    if sub.iter().all(|inst| {
+                        matches!(
+                            inst,
+                            Instruction::Output
+                                | Instruction::Breakpoint
+                                | Instruction::AssertCell { .. }
+                                | Instruction::PAdd(0)
+                                | Instruction::DAdd(0)
+                        )
+                    }) {
+                        return Err(ValidationError::InfiniteLoop {
+                            index: ProgramIndex(path.clone()),
+                        });
+                    }
+                    Self::validate_runnable_internal(sub, memsize, path)?;
+                }
+                _ => {}
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+
     fn next_index_internal(instructions: &[Instruction], index: &mut [usize]) -> bool {
         let (head, tail) = index.split_first_mut().expect("index must not be empty");
         if tail.is_empty() {
@@ -102,6 +453,79 @@ impl Program {
     }
 }
 
+/// A builder of [`Program`], created by [`Program::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder(Vec<Instruction>);
+
+impl ProgramBuilder {
+    /// Append an instruction.
+    pub fn push(mut self, instruction: Instruction) -> Self {
+        self.0.push(instruction);
+        self
+    }
+
+    /// Append instructions.
+    pub fn extend(mut self, instructions: impl IntoIterator<Item = Instruction>) -> Self {
+        self.0.extend(instructions);
+        self
+    }
+
+    /// Build the program.
+    pub fn build(self) -> Program {
+        Program(self.0)
+    }
+}
+
+impl fmt::Display for ProgramIndex {
+    /// Formats the index as `[i, j, ...]`, from the top-level instruction to the innermost one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl FromStr for ProgramIndex {
+    type Err = ParseIndexError;
+
+    /// Parses an index formatted by [`Display`](fmt::Display) (e.g. `[3, 1, 0]`)
+    /// or the dotted form (e.g. `3.1.0`).
+    ///
+    /// Whitespace around each position is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::program::ProgramIndex;
+    ///
+    /// let index: ProgramIndex = "3.1.0".parse().unwrap();
+    /// assert_eq!(index.path(), [3, 1, 0]);
+    /// assert_eq!(index.to_string().parse::<ProgramIndex>().unwrap(), index);
+    /// assert!("3..0".parse::<ProgramIndex>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (body, separator) = match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(body) => (body, ','),
+            None => (s, '.'),
+        };
+        if body.trim().is_empty() {
+            return Err(ParseIndexError::Empty);
+        }
+        body.split(separator)
+            .map(|segment| {
+                let segment = segment.trim();
+                match segment.bytes().all(|b| b.is_ascii_digit()) {
+                    true => segment.parse().ok(),
+                    false => None,
+                }
+                .ok_or_else(|| ParseIndexError::InvalidSegment {
+                    segment: segment.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
 impl Index<&ProgramIndex> for Program {
     type Output = Instruction;
 
@@ -110,6 +534,34 @@ impl Index<&ProgramIndex> for Program {
     }
 }
 
+fn contains_input(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instruction| match instruction {
+        Instruction::Input => true,
+        Instruction::UntilZero(sub) => contains_input(sub),
+        _ => false,
+    })
+}
+
+fn count_instructions(instructions: &[Instruction], counts: &mut InstructionCounts) {
+    for instruction in instructions {
+        match instruction {
+            Instruction::PAdd(_) => counts.p_add += 1,
+            Instruction::DAdd(_) => counts.d_add += 1,
+            Instruction::Output => counts.output += 1,
+            Instruction::Input => counts.input += 1,
+            Instruction::UntilZero(sub) => {
+                counts.loops += 1;
+                count_instructions(sub, counts);
+            }
+            Instruction::SetZero => counts.set_zero += 1,
+            Instruction::DSet(_) => counts.d_set += 1,
+            Instruction::Breakpoint => counts.breakpoints += 1,
+            Instruction::AssertCell { .. } => counts.assertions += 1,
+            Instruction::Fill { .. } => counts.fill += 1,
+        }
+    }
+}
+
 fn instruction_at<'a>(instructions: &'a [Instruction], index: &[usize]) -> &'a Instruction {
     assert!(!index.is_empty());
     let (head, tail) = index.split_first().expect("index must not be empty");
@@ -123,9 +575,36 @@ fn instruction_at<'a>(instructions: &'a [Instruction], index: &[usize]) -> &'a I
     }
 }
 
+fn get_instruction_at<'a>(
+    instructions: &'a [Instruction],
+    index: &[usize],
+) -> Option<&'a Instruction> {
+    let (head, tail) = index.split_first()?;
+    let instruction = instructions.get(*head)?;
+    match instruction {
+        _ if tail.is_empty() => Some(instruction),
+        Instruction::UntilZero(sub) => get_instruction_at(sub, tail),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::testing::bf_parser;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<Program>();
+        assert_send_sync::<ProgramIndex>();
+        assert_send_sync::<FatProgram>();
+        assert_send_sync::<FlatProgram>();
+        assert_send_sync::<crate::parser::Parser<crate::token::simple::SimpleTokenizer>>();
+        #[cfg(feature = "regex")]
+        assert_send_sync::<crate::parser::Parser<crate::token::regex::RegexTokenizer>>();
+    }
 
     #[test]
     fn empty_first_index() {
@@ -142,6 +621,87 @@ mod test {
         );
     }
 
+    #[test]
+    fn parent_and_depth() {
+        let index = ProgramIndex::new_for_test([1, 0, 2]);
+        assert_eq!(index.parent(), Some(ProgramIndex::new_for_test([1, 0])));
+        assert_eq!(index.depth(), 3);
+
+        let top = ProgramIndex::new_for_test([1]);
+        assert_eq!(top.parent(), None);
+        assert_eq!(top.depth(), 1);
+    }
+
+    #[test]
+    fn advance_preorder() {
+        use Instruction::*;
+        let program = Program::new([
+            PAdd(1),
+            UntilZero(vec![
+                DAdd(1),
+                UntilZero(vec![]),
+                UntilZero(vec![UntilZero(vec![Output])]),
+            ]),
+            Input,
+        ]);
+
+        let mut index = program.first_index().unwrap();
+        let mut visited = vec![index.clone()];
+        while program.advance_preorder(&mut index) {
+            visited.push(index.clone());
+        }
+        let expected: [&[usize]; 8] = [
+            &[0],
+            &[1],
+            &[1, 0],
+            &[1, 1],
+            &[1, 2],
+            &[1, 2, 0],
+            &[1, 2, 0, 0],
+            &[2],
+        ];
+        assert_eq!(visited, expected.map(ProgramIndex::new_for_test).to_vec());
+        assert_eq!(index, ProgramIndex::new_for_test([2]));
+
+        let program = Program::new([UntilZero(vec![Output])]);
+        let mut index = ProgramIndex::new_for_test([0, 0]);
+        assert!(!program.advance_preorder(&mut index));
+        assert_eq!(index, ProgramIndex::new_for_test([0, 0]));
+    }
+
+    #[test]
+    fn static_input_count() {
+        use Instruction::*;
+        assert_eq!(Program::new([Input, Input]).static_input_count(), Some(2));
+        assert_eq!(
+            Program::new([Input, UntilZero(vec![Input])]).static_input_count(),
+            None
+        );
+        assert_eq!(
+            Program::new([Input, UntilZero(vec![UntilZero(vec![Output])]), Input])
+                .static_input_count(),
+            Some(2)
+        );
+        assert_eq!(Program::new([]).static_input_count(), Some(0));
+    }
+
+    #[test]
+    fn io_in_loops() {
+        use Instruction::*;
+        let program = Program::new([
+            Output,
+            UntilZero(vec![Output]),
+            UntilZero(vec![PAdd(1), UntilZero(vec![Input])]),
+        ]);
+        assert_eq!(
+            program.io_in_loops(),
+            [
+                ProgramIndex::new_for_test([1, 0]),
+                ProgramIndex::new_for_test([2, 1, 0]),
+            ]
+        );
+    }
+
     #[test]
     fn index() {
         use Instruction::*;
@@ -185,4 +745,215 @@ mod test {
         let program = Program::new([PAdd(1), UntilZero(vec![PAdd(2), Input, PAdd(-2)])]);
         let _ = program[&ProgramIndex::new_for_test([0, 0])];
     }
+
+    #[test]
+    fn is_valid_index() {
+        use Instruction::*;
+        let program = Program::new([PAdd(1), UntilZero(vec![PAdd(2), Input, PAdd(-2)])]);
+
+        for index in [&[0][..], &[1], &[1, 0], &[1, 2]] {
+            let index = ProgramIndex::new_for_test(index);
+            assert!(program.is_valid_index(&index), "{index}");
+            assert_eq!(program.get(&index), Some(&program[&index]));
+        }
+        for index in [&[][..], &[2], &[1, 3], &[0, 0], &[1, 0, 0]] {
+            let index = ProgramIndex::new_for_test(index);
+            assert!(!program.is_valid_index(&index), "{index}");
+            assert_eq!(program.get(&index), None);
+        }
+        assert!(!Program::new([]).is_valid_index(&ProgramIndex::new_for_test([0])));
+    }
+
+    #[test]
+    fn test_validate_runnable() {
+        use Instruction::*;
+
+        let program = Program::new([DAdd(1), UntilZero(vec![PAdd(40000), DAdd(-1)])]);
+        match program.validate_runnable(MemorySize::Fixed(30000)) {
+            Err(e @ ValidationError::PointerMoveOutOfBounds { .. }) => assert_eq!(
+                e.to_string(),
+                "[1, 0]: pointer move 40000 exceeds the memory (valid range: [0, 30000))"
+            ),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert!(program.validate_runnable(MemorySize::RightInfinite).is_ok());
+
+        let program = Program::new([Input, UntilZero(vec![Output])]);
+        match program.validate_runnable(MemorySize::Fixed(30000)) {
+            Err(ValidationError::InfiniteLoop { index }) => {
+                assert_eq!(index, ProgramIndex(vec![1]))
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        // ,[.,]
+        let program = Program::new([Input, UntilZero(vec![Output, Input])]);
+        assert!(program.validate_runnable(MemorySize::Fixed(30000)).is_ok());
+
+        let program = Program::new([
+            Input,
+            UntilZero(vec![Breakpoint, AssertCell { expected: 1 }]),
+        ]);
+        assert!(matches!(
+            program.validate_runnable(MemorySize::Fixed(30000)),
+            Err(ValidationError::InfiniteLoop { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_runnable_dead_loops() {
+        use Instruction::*;
+
+        // [This is a header comment.]+++.
+        let program = Program::new([UntilZero(vec![Output]), DAdd(3), Output]);
+        assert!(program.validate_runnable(MemorySize::Fixed(30000)).is_ok());
+
+        // ,[-][] and ,[-][>>>>]
+        let program = Program::new([Input, UntilZero(vec![DAdd(-1)]), UntilZero(vec![])]);
+        assert!(program.validate_runnable(MemorySize::Fixed(30000)).is_ok());
+        let program = Program::new([Input, SetZero, UntilZero(vec![PAdd(40000)])]);
+        assert!(program.validate_runnable(MemorySize::Fixed(30000)).is_ok());
+
+        // a loop which may be entered is still checked.
+        let program = Program::new([Input, UntilZero(vec![DAdd(-1)]), DAdd(1), UntilZero(vec![])]);
+        match program.validate_runnable(MemorySize::Fixed(30000)) {
+            Err(ValidationError::InfiniteLoop { index }) => {
+                assert_eq!(index, ProgramIndex(vec![3]))
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_instruction_display() {
+        use Instruction::*;
+        let cases = [
+            (PAdd(3), "ptr += 3"),
+            (PAdd(-2), "ptr -= 2"),
+            (DAdd(2), "*ptr += 2"),
+            (DAdd(-1), "*ptr -= 1"),
+            (Output, "output"),
+            (Input, "input"),
+            (UntilZero(vec![]), "while *ptr {}"),
+            (
+                UntilZero(vec![DAdd(-1), PAdd(1), UntilZero(vec![Output])]),
+                "while *ptr { *ptr -= 1; ptr += 1; while *ptr { output } }",
+            ),
+            (SetZero, "*ptr = 0"),
+            (DSet(42), "*ptr = 42"),
+            (Fill { len: 3, value: 0 }, "ptr[0..3] = 0"),
+            (Breakpoint, "breakpoint"),
+            (AssertCell { expected: 3 }, "assert *ptr == 3"),
+        ];
+        for (inst, expected) in cases {
+            assert_eq!(inst.to_string(), expected);
+        }
+        assert_eq!(
+            format!("{:#}", UntilZero(vec![DAdd(-1)])),
+            "while *ptr { ... }"
+        );
+        assert_eq!(format!("{:#}", UntilZero(vec![])), "while *ptr {}");
+    }
+
+    #[test]
+    fn test_instruction_counts() {
+        let parser = bf_parser();
+        let program = parser
+            .parse_str(include_str!("../../tests/data/hello.bf"))
+            .unwrap();
+        let counts = program.instruction_counts();
+        assert_eq!(
+            counts,
+            InstructionCounts {
+                p_add: 19,
+                d_add: 21,
+                output: 13,
+                input: 0,
+                loops: 3,
+                ..InstructionCounts::default()
+            }
+        );
+        assert_eq!(counts.total(), 56);
+
+        let program = Program::new([
+            Instruction::SetZero,
+            Instruction::UntilZero(vec![Instruction::DSet(1), Instruction::Input]),
+            Instruction::Fill { len: 2, value: 0 },
+        ]);
+        assert_eq!(
+            program.instruction_counts(),
+            InstructionCounts {
+                input: 1,
+                loops: 1,
+                set_zero: 1,
+                d_set: 1,
+                fill: 1,
+                ..InstructionCounts::default()
+            }
+        );
+    }
+
+    #[test]
+    fn builder() {
+        use crate::runtime;
+        use Instruction::*;
+
+        let run = |program: &Program| {
+            let mut output = vec![];
+            runtime::run(program, &[][..], &mut output).unwrap();
+            output
+        };
+
+        let program = Program::single(DSet(b'A'));
+        assert_eq!(program.instructions(), [DSet(b'A')]);
+        assert!(run(&program).is_empty());
+
+        let program = Program::builder()
+            .push(DSet(b'A'))
+            .push(Output)
+            .extend([DAdd(1), Output])
+            .build();
+        assert_eq!(
+            program.instructions(),
+            [DSet(b'A'), Output, DAdd(1), Output]
+        );
+        assert_eq!(run(&program), b"AB");
+
+        assert!(Program::builder().build().instructions().is_empty());
+    }
+
+    #[test]
+    fn program_index_from_str() {
+        for path in [vec![0], vec![3, 1, 0], vec![12, 0, 7, 100]] {
+            let index = ProgramIndex::from_path(path.clone());
+            assert_eq!(index.path(), path);
+            assert_eq!(index.to_string().parse::<ProgramIndex>().unwrap(), index);
+            let dotted = path
+                .iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+                .join(".");
+            assert_eq!(dotted.parse::<ProgramIndex>().unwrap(), index);
+        }
+        assert_eq!(" [ 1,2 ] ".parse::<ProgramIndex>().unwrap().path(), [1, 2]);
+
+        for s in ["", " ", "[]"] {
+            assert_eq!(s.parse::<ProgramIndex>(), Err(ParseIndexError::Empty));
+        }
+        for (s, segment) in [
+            ("1..2", ""),
+            ("1.x", "x"),
+            ("[1.2]", "1.2"),
+            ("-1", "-1"),
+            ("+1", "+1"),
+        ] {
+            assert_eq!(
+                s.parse::<ProgramIndex>(),
+                Err(ParseIndexError::InvalidSegment {
+                    segment: segment.to_string()
+                }),
+                "{s:?}"
+            );
+        }
+    }
 }
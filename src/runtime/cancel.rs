@@ -0,0 +1,57 @@
+//! Cooperative cancellation.
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A shared flag to cancel a running program.
+///
+/// Clones of a flag share the same state, so a flag can be cancelled from another thread
+/// while a runner which has its clone is running.
+/// The runner checks the flag each time it checks a loop condition and each time it retries
+/// an input which is not ready, and stops with [`RuntimeError::Cancelled`](crate::error::RuntimeError::Cancelled)
+/// once the flag is set.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     error::RuntimeError,
+///     program::{Instruction::*, Program},
+///     runtime::{CancelFlag, Runner},
+/// };
+///
+/// let program = Program::new(vec![DAdd(1), UntilZero(vec![])]);
+/// let flag = CancelFlag::new();
+/// let canceller = flag.clone();
+/// std::thread::spawn(move || canceller.cancel());
+///
+/// let result = Runner::new(&program, std::io::empty(), std::io::sink())
+///     .with_cancel(flag)
+///     .run();
+/// assert!(matches!(result, Err(RuntimeError::Cancelled)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    /// Create a new flag which is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the flag.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Clear the flag, so that runners with the flag can run again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the flag is set.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,100 @@
+//! Cost models for metered runs.
+use super::*;
+
+/// A read-only view of the machine state which is passed to [`CostModel::cost`].
+#[derive(Debug, Clone, Copy)]
+pub struct MachineView<'a> {
+    machine: &'a internal::Machine,
+}
+
+impl<'a> MachineView<'a> {
+    pub(super) fn new(machine: &'a internal::Machine) -> Self {
+        Self { machine }
+    }
+
+    /// Get the pointer.
+    pub fn pointer(&self) -> isize {
+        self.machine.pointer()
+    }
+
+    /// Get the memory data at `address`.
+    ///
+    /// Returns `None` if the address is out of memory bounds.
+    pub fn data_at(&self, address: isize) -> Option<u8> {
+        self.machine.data_at(address)
+    }
+}
+
+/// A cost model which determines the cost of executing an instruction.
+///
+/// It is used by [`Runner::run_metered`].
+pub trait CostModel {
+    /// Get the cost of executing `instruction` in the state `machine`.
+    ///
+    /// For [`Instruction::UntilZero`], this is the cost of checking the loop condition,
+    /// which is charged each time the condition is checked. The instructions in the loop body
+    /// are charged separately.
+    fn cost(&self, instruction: &Instruction, machine: MachineView<'_>) -> u64;
+}
+
+impl<F> CostModel for F
+where
+    F: Fn(&Instruction, MachineView<'_>) -> u64,
+{
+    fn cost(&self, instruction: &Instruction, machine: MachineView<'_>) -> u64 {
+        self(instruction, machine)
+    }
+}
+
+/// A cost model in which every instruction costs 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnitCost;
+
+impl CostModel for UnitCost {
+    fn cost(&self, _instruction: &Instruction, _machine: MachineView<'_>) -> u64 {
+        1
+    }
+}
+
+/// A cost model in which an instruction costs the number of cells it touches.
+///
+/// [`Instruction::Fill`] costs its length (at least 1) and every other instruction costs 1.
+/// This reflects the work done by instructions which the optimizer merges,
+/// so that optimized and unoptimized programs are metered more fairly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorkCost;
+
+impl CostModel for WorkCost {
+    fn cost(&self, instruction: &Instruction, _machine: MachineView<'_>) -> u64 {
+        match instruction {
+            Instruction::Fill { len, .. } => (*len as u64).max(1),
+            _ => 1,
+        }
+    }
+}
+
+/// The outcome of [`Runner::run_metered`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeteredOutcome {
+    /// The program finished within the budget.
+    Finished {
+        /// The total cost spent.
+        spent: u64,
+    },
+    /// The program was stopped because the next instruction would exceed the budget.
+    ///
+    /// That instruction is not executed.
+    BudgetExhausted {
+        /// The total cost spent before stopping.
+        spent: u64,
+    },
+}
+
+impl MeteredOutcome {
+    /// Get the total cost spent.
+    pub fn spent(&self) -> u64 {
+        match self {
+            Self::Finished { spent } | Self::BudgetExhausted { spent } => *spent,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Input decoding.
+use std::{
+    fmt,
+    io::{self, Read},
+    sync::{Arc, Mutex},
+};
+
+/// A policy for input text which cannot be mapped to a cell value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappablePolicy {
+    /// Store the given value instead.
+    Replace(u8),
+    /// Skip it and decode the next character.
+    Skip,
+    /// Return [`RuntimeError::IoError`](crate::error::RuntimeError::IoError) with
+    /// [`io::ErrorKind::InvalidData`].
+    Error,
+}
+
+/// A decoding of the input into cell values, applied by each input instruction.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     program::{Instruction::*, Program},
+///     runtime::{self, InputDecode, RuntimeConfig, UnmappablePolicy},
+/// };
+///
+/// let program = Program::new([Input, Output]);
+/// let config = RuntimeConfig::default()
+///     .with_input_decode(InputDecode::utf8_to_latin1(UnmappablePolicy::Error));
+/// let mut output = vec![];
+/// runtime::run_with_config(&program, "é".as_bytes(), &mut output, config).unwrap();
+///
+/// assert_eq!(output, [0xe9]);
+/// ```
+#[derive(Clone, Default)]
+pub struct InputDecode(pub(super) Kind);
+
+// A decoding function, which reads the input and returns the next cell value or `None` at EOF.
+type DecodeFn = dyn FnMut(&mut dyn Read) -> io::Result<Option<u8>> + Send;
+
+#[derive(Clone, Default)]
+pub(super) enum Kind {
+    #[default]
+    Bytes,
+    Utf8ToLatin1(UnmappablePolicy),
+    Fn(Arc<Mutex<DecodeFn>>),
+}
+
+impl InputDecode {
+    /// Create a decoding which stores each input byte as is.
+    pub fn bytes() -> Self {
+        Self(Kind::Bytes)
+    }
+
+    /// Create a decoding which reads the input as UTF-8 and stores each character as its Latin-1
+    /// value (i.e. its code point).
+    ///
+    /// Characters above U+00FF, invalid sequences and a sequence truncated by an end-of-file are
+    /// handled by `on_unmappable`.
+    pub fn utf8_to_latin1(on_unmappable: UnmappablePolicy) -> Self {
+        Self(Kind::Utf8ToLatin1(on_unmappable))
+    }
+
+    /// Create a decoding which calls `f` with the input for each input instruction.
+    ///
+    /// `f` returns the cell value, or `None` at an end-of-file. An error returned by `f` is
+    /// handled in the same way as an error of the input: [`io::ErrorKind::WouldBlock`] follows
+    /// [`WouldBlockPolicy`](super::WouldBlockPolicy), in which case `f` should keep the bytes
+    /// of an incomplete character it has read for the next call.
+    ///
+    /// Clones of the decoding share `f`.
+    pub fn from_fn(
+        f: impl FnMut(&mut dyn Read) -> io::Result<Option<u8>> + Send + 'static,
+    ) -> Self {
+        Self(Kind::Fn(Arc::new(Mutex::new(f))))
+    }
+}
+
+impl fmt::Debug for InputDecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Kind::Bytes => write!(f, "InputDecode::Bytes"),
+            Kind::Utf8ToLatin1(policy) => write!(f, "InputDecode::Utf8ToLatin1({policy:?})"),
+            Kind::Fn(_) => write!(f, "InputDecode::Fn(..)"),
+        }
+    }
+}
+
+/// A result of [`Utf8Decoder::push`].
+pub(super) enum Decoded {
+    /// A complete character.
+    Char(char),
+    /// More bytes are needed.
+    Pending,
+    /// An invalid sequence.
+    Invalid,
+}
+
+/// An incremental UTF-8 decoder which keeps an incomplete sequence across input instructions.
+#[derive(Default)]
+pub(super) struct Utf8Decoder {
+    buf: Vec<u8>,
+    // a byte which ended an invalid sequence and starts the next one
+    retry: Option<u8>,
+}
+
+impl Utf8Decoder {
+    /// Take the byte to be decoded before reading the input.
+    pub(super) fn take_retry(&mut self) -> Option<u8> {
+        self.retry.take()
+    }
+
+    /// Decode the next byte.
+    pub(super) fn push(&mut self, byte: u8) -> Decoded {
+        if self.buf.is_empty() {
+            return match byte {
+                0x00..=0x7f => Decoded::Char(byte as char),
+                0xc2..=0xf4 => {
+                    self.buf.push(byte);
+                    Decoded::Pending
+                }
+                _ => Decoded::Invalid,
+            };
+        }
+        if byte & 0xc0 != 0x80 {
+            self.buf.clear();
+            self.retry = Some(byte);
+            return Decoded::Invalid;
+        }
+        self.buf.push(byte);
+        let len = match self.buf[0] {
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        if self.buf.len() < len {
+            return Decoded::Pending;
+        }
+        let decoded = match std::str::from_utf8(&self.buf) {
+            Ok(s) => Decoded::Char(s.chars().next().expect("a complete sequence")),
+            Err(_) => Decoded::Invalid,
+        };
+        self.buf.clear();
+        decoded
+    }
+
+    /// Discard an incomplete sequence at an end-of-file.
+    ///
+    /// Returns `true` if there was one.
+    pub(super) fn finish(&mut self) -> bool {
+        let incomplete = !self.buf.is_empty();
+        self.buf.clear();
+        incomplete
+    }
+}
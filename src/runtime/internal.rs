@@ -1,8 +1,15 @@
+use super::input_decode::{self, Decoded, Utf8Decoder};
 use super::*;
+use crate::error::AccessKind;
+use std::{io, sync::PoisonError};
 
 /// A runtime memory.
+///
+/// Each cell is stored as [`CellWidth::bytes`] bytes in little-endian order.
+#[derive(Debug, Clone)]
 pub struct Memory {
     size: MemorySize,
+    width: CellWidth,
     /// memory data for [0..]
     right_data: Vec<u8>,
     /// memory data for [..-1]
@@ -10,13 +17,13 @@ pub struct Memory {
 }
 
 impl Memory {
-    /// Creates a new memory with the given size.
-    fn new(size: MemorySize) -> Self {
+    /// Creates a new memory with the given size and cell width.
+    fn new(size: MemorySize, width: CellWidth) -> Self {
         let right_data = if let MemorySize::Fixed(len) = size {
-            if len > isize::MAX as usize {
-                panic!("memory size larger han isize::MAX is not supported.");
+            match len.checked_mul(width.bytes()) {
+                Some(bytes) if bytes <= isize::MAX as usize => vec![0; bytes],
+                _ => panic!("memory size larger han isize::MAX is not supported."),
             }
-            vec![0; len]
         } else {
             vec![]
         };
@@ -24,32 +31,241 @@ impl Memory {
 
         Self {
             size,
+            width,
             right_data,
             left_data,
         }
     }
 
-    /// Get the mutable reference of the memory data at the given address.
+    /// Get the memory data at the given address.
     ///
-    /// If the address is out of range, this function returns error [`RuntimeError::OutOfMemoryBounds`].
-    fn get_mut(&mut self, address: isize) -> Result<&mut u8, RuntimeError> {
+    /// For a cell wider than 8 bits, this is the lowest byte of the cell (see [`Memory::get_cell`]).
+    pub fn get(&self, address: isize) -> Option<u8> {
+        self.get_cell(address).map(|value| value as u8)
+    }
+
+    /// Get the value of the cell at the given address.
+    ///
+    /// Unlike [`Memory::get_mut`], this function does not extend the memory.
+    /// An address which is in range but not yet allocated has value 0.
+    /// If the address is out of range, this function returns `None`.
+    pub fn get_cell(&self, address: isize) -> Option<u32> {
+        let (data, offset, limit) = self.locate(address);
+        if limit.is_some_and(|limit| offset >= limit) {
+            return None;
+        }
+        let (start, end) = self.byte_range(offset)?;
+        Some(data.get(start..end).map_or(0, decode_cell))
+    }
+
+    // Get the range of the bytes of the cell at `offset` in a tape, or `None` on overflow.
+    fn byte_range(&self, offset: usize) -> Option<(usize, usize)> {
+        let bytes = self.width.bytes();
+        let start = offset.checked_mul(bytes)?;
+        Some((start, start.checked_add(bytes)?))
+    }
+
+    // Get the tape, the offset in it and its limit of the given address.
+    fn locate(&self, address: isize) -> (&Vec<u8>, usize, Option<usize>) {
         if address >= 0 {
-            if (address as usize) >= self.right_data.len() {
-                if let MemorySize::Fixed(_) = self.size {
-                    return Err(RuntimeError::OutOfMemoryBounds { address });
-                }
-                self.right_data.resize(address as usize + 1, 0);
-            }
-            Ok(&mut self.right_data[address as usize])
-        } else if let MemorySize::BothInfinite = self.size {
+            (&self.right_data, address as usize, self.size.right_limit())
+        } else {
             let left_address = (-(address + 1)) as usize;
-            if left_address >= self.left_data.len() {
-                self.left_data.resize(left_address + 1, 0);
-            }
-            Ok(&mut self.left_data[left_address])
+            (&self.left_data, left_address, self.size.left_limit())
+        }
+    }
+
+    /// Get the mutable reference of the bytes of the cell at the given address.
+    ///
+    /// If the address is out of range, this function returns error [`RuntimeError::OutOfMemoryBounds`]
+    /// with `access`.
+    fn get_mut(&mut self, address: isize, access: AccessKind) -> Result<&mut [u8], RuntimeError> {
+        let (_, offset, limit) = self.locate(address);
+        let range = self
+            .byte_range(offset)
+            .filter(|_| limit.is_none_or(|limit| offset < limit));
+        let Some((start, end)) = range else {
+            return Err(self.out_of_bounds(address, access));
+        };
+        let data = if address >= 0 {
+            &mut self.right_data
         } else {
-            Err(RuntimeError::OutOfMemoryBounds { address })
+            &mut self.left_data
+        };
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        Ok(&mut data[start..end])
+    }
+
+    /// Set the value of the cell at the given address, truncated to the cell width.
+    fn store(&mut self, address: isize, value: u32) -> Result<(), RuntimeError> {
+        encode_cell(self.get_mut(address, AccessKind::Data)?, value);
+        Ok(())
+    }
+
+    /// Grow the memory to cover addresses from 0 to `up_to` (inclusive).
+    ///
+    /// Cells which are already allocated are kept as they are.
+    /// If `up_to` is out of range, this function returns error [`RuntimeError::OutOfMemoryBounds`].
+    fn reserve(&mut self, up_to: isize) -> Result<(), RuntimeError> {
+        self.get_mut(up_to, AccessKind::Data)?;
+        Ok(())
+    }
+
+    /// Returns `true` if the zero cells at the ends of the allocated memory are more than
+    /// `threshold` in total.
+    ///
+    /// At most `threshold + 1` cells are examined.
+    fn zero_tail_exceeds(&self, threshold: usize) -> bool {
+        let mut rest = threshold;
+        let bytes = self.width.bytes();
+        for data in self.tapes() {
+            let zeros = data
+                .chunks(bytes)
+                .rev()
+                .take(rest + 1)
+                .take_while(|cell| cell.iter().all(|&data| data == 0))
+                .count();
+            if zeros > rest {
+                return true;
+            }
+            rest -= zeros;
         }
+        false
+    }
+
+    /// Release the zero cells at the ends of the allocated memory.
+    ///
+    /// Released cells are allocated again with 0 on demand, so the data at any address is not
+    /// changed. The memory with [`MemorySize::Fixed`] is not released since it is allocated
+    /// in advance.
+    pub fn compact(&mut self) {
+        let bytes = self.width.bytes();
+        for data in self.tapes_mut() {
+            let len = data
+                .iter()
+                .rposition(|&data| data != 0)
+                .map_or(0, |i| (i / bytes + 1) * bytes);
+            data.truncate(len);
+            data.shrink_to_fit();
+        }
+    }
+
+    // The allocated memory which can be released, i.e. [0..] and [..-1] except for a fixed size.
+    fn tapes(&self) -> impl Iterator<Item = &Vec<u8>> {
+        let releasable = !matches!(self.size, MemorySize::Fixed(_));
+        [&self.right_data, &self.left_data]
+            .into_iter()
+            .filter(move |_| releasable)
+    }
+
+    fn tapes_mut(&mut self) -> impl Iterator<Item = &mut Vec<u8>> {
+        let releasable = !matches!(self.size, MemorySize::Fixed(_));
+        [&mut self.right_data, &mut self.left_data]
+            .into_iter()
+            .filter(move |_| releasable)
+    }
+
+    /// Get the allocated capacities of the memory for [0..] and [..-1], in cells.
+    #[cfg(test)]
+    pub fn capacities(&self) -> (usize, usize) {
+        let bytes = self.width.bytes();
+        (
+            self.right_data.capacity() / bytes,
+            self.left_data.capacity() / bytes,
+        )
+    }
+
+    fn out_of_bounds(&self, address: isize, access: AccessKind) -> RuntimeError {
+        RuntimeError::OutOfMemoryBounds {
+            address,
+            memsize: self.size,
+            access,
+        }
+    }
+
+    /// Set `len` cells from `start` to `value`.
+    ///
+    /// If some of the cells are out of range, the cells before the first out-of-range cell are set
+    /// and this function returns error [`RuntimeError::OutOfMemoryBounds`] for that cell.
+    /// If the cells exceed the address space, no cells are set and the error is for `start`.
+    fn fill(&mut self, start: isize, len: usize, value: u8) -> Result<(), RuntimeError> {
+        let bytes = self.width.bytes();
+        let end = isize::try_from(len)
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .filter(|&end| (end.max(0) as usize).checked_mul(bytes).is_some())
+            .ok_or_else(|| self.out_of_bounds(start, AccessKind::Data))?;
+        let mut address = start;
+        // negative addresses
+        while address < end.min(0) {
+            self.store(address, value.into())?;
+            address += 1;
+        }
+        if address >= end {
+            return Ok(());
+        }
+
+        let (from, to) = (address as usize, end as usize);
+        let limit = self.size.right_limit().unwrap_or(usize::MAX);
+        if to * bytes > self.right_data.len() {
+            self.right_data.resize(to.min(limit) * bytes, 0);
+        }
+        let available = to.min(self.right_data.len() / bytes);
+        if from < available {
+            let cells = &mut self.right_data[from * bytes..available * bytes];
+            if bytes == 1 {
+                cells.fill(value);
+            } else {
+                for cell in cells.chunks_mut(bytes) {
+                    cell.fill(0);
+                    cell[0] = value;
+                }
+            }
+        }
+        if available < to {
+            return Err(self.out_of_bounds(available.max(from) as isize, AccessKind::Data));
+        }
+        Ok(())
+    }
+}
+
+// Decode the little-endian bytes of a cell.
+fn decode_cell(cell: &[u8]) -> u32 {
+    cell.iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u32::from(byte))
+}
+
+// Encode a value into the little-endian bytes of a cell, truncating it to the cell width.
+fn encode_cell(cell: &mut [u8], value: u32) {
+    let len = cell.len();
+    cell.copy_from_slice(&value.to_le_bytes()[..len]);
+}
+
+// Read a byte, or `None` at EOF.
+fn read_byte(input: &mut dyn Read) -> io::Result<Option<u8>> {
+    let mut buf = 0;
+    match input.read(std::slice::from_mut(&mut buf))? {
+        0 => Ok(None),
+        _ => Ok(Some(buf)),
+    }
+}
+
+fn unmappable_error() -> RuntimeError {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "input is not a Latin-1 character in UTF-8",
+    )
+    .into()
+}
+
+// Returns an error if the run is cancelled.
+fn check_cancel(cancel: &Option<CancelFlag>) -> Result<(), RuntimeError> {
+    match cancel {
+        Some(cancel) if cancel.is_cancelled() => Err(RuntimeError::Cancelled),
+        _ => Ok(()),
     }
 }
 
@@ -59,12 +275,54 @@ pub enum NextAction<'a> {
     StepIn(&'a [Instruction]),
 }
 
+/// A machine state of the runtime, which consists of everything except the input and the output.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    memory: Memory,
+    pointer: isize,
+    cell_modulus: Option<CellModulus>,
+    would_block: WouldBlockPolicy,
+    eof: EofBehavior,
+    input_decode: InputDecode,
+    output_map: OutputMap,
+    auto_compact: Option<usize>,
+}
+
+impl Machine {
+    /// Get the pointer.
+    pub fn pointer(&self) -> isize {
+        self.pointer
+    }
+
+    /// Get the memory data at the given address without modifying the memory.
+    pub fn data_at(&self, address: isize) -> Option<u8> {
+        self.memory.get(address)
+    }
+
+    /// Get the value of the cell at the given address without modifying the memory.
+    pub fn cell_at(&self, address: isize) -> Option<u32> {
+        self.memory.get_cell(address)
+    }
+
+    // Reduce a value to be stored into a cell by the cell modulus.
+    fn reduce(&self, value: u32) -> u32 {
+        match self.cell_modulus {
+            Some(CellModulus(m)) => value % m,
+            None => value,
+        }
+    }
+}
+
 /// A program runtime.
 pub struct Runtime<R, W> {
     input: R,
     output: W,
-    memory: Memory,
-    pointer: isize,
+    machine: Machine,
+    cancel: Option<CancelFlag>,
+    // the state of `InputDecode::utf8_to_latin1`
+    utf8_decoder: Utf8Decoder,
+    // a buffer for translated output bytes
+    output_buf: Vec<u8>,
 }
 
 impl<R, W> Runtime<R, W>
@@ -72,43 +330,208 @@ where
     R: Read,
     W: Write,
 {
-    /// Create a new runtime with the given input, output and memory size.
-    pub fn new(input: R, output: W, memsize: MemorySize) -> Self {
+    /// Create a new runtime with the given input, output and configuration.
+    pub fn new(input: R, output: W, config: RuntimeConfig) -> Self {
+        // a modulus which is not less than the range of a cell has no effect.
+        let cell_range = 1u64 << (8 * config.cell_width.bytes());
+        let cell_modulus = config
+            .cell_modulus
+            .filter(|&CellModulus(m)| u64::from(m) < cell_range);
+        let machine = Machine {
+            memory: Memory::new(config.memsize, config.cell_width),
+            pointer: 0,
+            cell_modulus,
+            would_block: config.would_block,
+            eof: config.eof,
+            input_decode: config.input_decode,
+            output_map: config.output_map,
+            auto_compact: config.auto_compact,
+        };
+        Self::with_machine(input, output, machine)
+    }
+
+    /// Create a new runtime with the given input, output and machine state.
+    pub fn with_machine(input: R, output: W, machine: Machine) -> Self {
         Self {
             input,
             output,
-            memory: Memory::new(memsize),
-            pointer: 0,
+            machine,
+            cancel: None,
+            utf8_decoder: Utf8Decoder::default(),
+            output_buf: vec![],
+        }
+    }
+
+    /// Replace the output with the one made from it by `f`, keeping the other states.
+    pub fn map_output<W2>(self, f: impl FnOnce(W) -> W2) -> Runtime<R, W2> {
+        Runtime {
+            input: self.input,
+            output: f(self.output),
+            machine: self.machine,
+            cancel: self.cancel,
+            utf8_decoder: self.utf8_decoder,
+            output_buf: self.output_buf,
         }
     }
 
+    /// Release the zero cells at the ends of the allocated memory (see [`Memory::compact`]).
+    pub fn compact(&mut self) {
+        self.machine.memory.compact();
+    }
+
+    /// Set the flag to cancel the run.
+    pub fn set_cancel(&mut self, cancel: CancelFlag) {
+        self.cancel = Some(cancel);
+    }
+
+    /// Set the behavior of the input instruction at the end of the input.
+    pub fn set_eof_behavior(&mut self, eof: EofBehavior) {
+        self.machine.eof = eof;
+    }
+
+    /// Get the machine state.
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Get the input.
+    pub fn input_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Grow the memory to cover addresses from 0 to `up_to` (inclusive).
+    pub fn reserve_memory(&mut self, up_to: isize) -> Result<(), RuntimeError> {
+        self.machine.memory.reserve(up_to)
+    }
+
+    /// Get the memory.
+    #[cfg(test)]
+    pub fn memory(&self) -> &Memory {
+        &self.machine.memory
+    }
+
+    // Get the mutable reference of the bytes of the cell which is pointed by the pointer.
+    fn current_data(&mut self, access: AccessKind) -> Result<&mut [u8], RuntimeError> {
+        self.machine.memory.get_mut(self.machine.pointer, access)
+    }
+
     // Add operand to the pointer.
     fn add_pointer(&mut self, operand: isize) -> Result<(), RuntimeError> {
-        self.pointer += operand;
+        self.machine.pointer += operand;
         Ok(())
     }
 
     // Add operand to the data which is pointed by the pointer.
     fn add_data(&mut self, operand: isize) -> Result<(), RuntimeError> {
-        let data = self.memory.get_mut(self.pointer)?;
-        *data = (*data as isize).wrapping_add(operand) as u8;
+        let cell_modulus = self.machine.cell_modulus;
+        let data = self.current_data(AccessKind::Data)?;
+        let value = i64::from(decode_cell(data));
+        let value = match cell_modulus {
+            Some(CellModulus(m)) => {
+                let m = i64::from(m);
+                (value + (operand as i64).rem_euclid(m)) % m
+            }
+            None => value.wrapping_add(operand as i64),
+        };
+        encode_cell(data, value as u32);
         Ok(())
     }
 
-    // Read a byte from the input and store it to the data which is pointed by the pointer.
+    // Read a value from the input, decoded by the input decoding, and store it to the data which
+    // is pointed by the pointer.
+    //
+    // If the input is not ready, the data is not changed. At the end of the input, the data is
+    // handled by the EOF behavior.
     fn input(&mut self) -> Result<(), RuntimeError> {
-        let data = self.memory.get_mut(self.pointer)?;
-        if self.input.read(std::slice::from_mut(data))? == 0 {
-            Err(RuntimeError::Eof)
-        } else {
-            Ok(())
+        // Check the bounds before consuming the input.
+        self.current_data(AccessKind::Data)?;
+        let result = match &self.machine.input_decode.0 {
+            input_decode::Kind::Bytes => self.read_with(read_byte),
+            input_decode::Kind::Utf8ToLatin1(policy) => self.read_latin1(*policy),
+            input_decode::Kind::Fn(f) => {
+                let f = f.clone();
+                self.read_with(|input| f.lock().unwrap_or_else(PoisonError::into_inner)(input))
+            }
+        };
+        let value = match (result, self.machine.eof) {
+            (Ok(value), _) => value,
+            (Err(RuntimeError::Eof), EofBehavior::Unchanged) => return Ok(()),
+            (Err(RuntimeError::Eof), EofBehavior::SetZero) => 0,
+            (Err(RuntimeError::Eof), EofBehavior::SetValue(value)) => value,
+            (Err(e), _) => return Err(e),
+        };
+        let value = self.machine.reduce(value.into());
+        encode_cell(self.current_data(AccessKind::Data)?, value);
+        Ok(())
+    }
+
+    // Read a value by `read`, which returns `None` at EOF, following the would-block policy.
+    fn read_with(
+        &mut self,
+        mut read: impl FnMut(&mut dyn Read) -> io::Result<Option<u8>>,
+    ) -> Result<u8, RuntimeError> {
+        loop {
+            match read(&mut self.input) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => return Err(RuntimeError::Eof),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => match self.machine.would_block {
+                    WouldBlockPolicy::Retry(backoff) => {
+                        check_cancel(&self.cancel)?;
+                        std::thread::sleep(backoff)
+                    }
+                    WouldBlockPolicy::Report => return Err(RuntimeError::InputNotReady),
+                    WouldBlockPolicy::Eof => return Err(RuntimeError::Eof),
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Read a UTF-8 character as a Latin-1 value.
+    fn read_latin1(&mut self, on_unmappable: UnmappablePolicy) -> Result<u8, RuntimeError> {
+        loop {
+            let byte = match self.utf8_decoder.take_retry() {
+                Some(byte) => byte,
+                None => match self.read_with(read_byte) {
+                    Err(RuntimeError::Eof) if self.utf8_decoder.finish() => {
+                        return match on_unmappable {
+                            UnmappablePolicy::Replace(value) => Ok(value),
+                            UnmappablePolicy::Skip => Err(RuntimeError::Eof),
+                            UnmappablePolicy::Error => Err(unmappable_error()),
+                        };
+                    }
+                    result => result?,
+                },
+            };
+            match self.utf8_decoder.push(byte) {
+                Decoded::Char(c) if c as u32 <= 0xff => return Ok(c as u8),
+                Decoded::Pending => {}
+                Decoded::Char(_) | Decoded::Invalid => match on_unmappable {
+                    UnmappablePolicy::Replace(value) => return Ok(value),
+                    UnmappablePolicy::Skip => {}
+                    UnmappablePolicy::Error => return Err(unmappable_error()),
+                },
+            }
         }
     }
 
-    // Write a byte which is pointed by the pointer to the output.
+    // Write a byte which is pointed by the pointer to the output, translated by the output map.
+    //
+    // A cell wider than 8 bits is truncated to its lowest byte.
     fn output(&mut self) -> Result<(), RuntimeError> {
-        let data = self.memory.get_mut(self.pointer)?;
-        self.output.write_all(std::slice::from_ref(data))?;
+        let data = self
+            .machine
+            .memory
+            .get_mut(self.machine.pointer, AccessKind::Data)?[0];
+        if self.machine.output_map.is_identity() {
+            self.output.write_all(&[data])?;
+        } else {
+            self.output_buf.clear();
+            self.machine
+                .output_map
+                .translate(data, &mut self.output_buf);
+            self.output.write_all(&self.output_buf)?;
+        }
         Ok(())
     }
 
@@ -121,16 +544,49 @@ where
     /// `inst` is other instruction, this function returns [`NextAction::Next`].
     ///
     /// In any case, if an error occurred, this function returns that error.
+    /// If `inst` is [`Instruction::UntilZero`] and the run is cancelled, this function returns
+    /// [`RuntimeError::Cancelled`] without checking the loop condition.
     pub fn exec_one<'a>(&mut self, inst: &'a Instruction) -> Result<NextAction<'a>, RuntimeError> {
         match inst {
             Instruction::PAdd(operand) => self.add_pointer(*operand)?,
             Instruction::DAdd(operand) => self.add_data(*operand)?,
             Instruction::Output => self.output()?,
             Instruction::Input => self.input()?,
+            Instruction::Breakpoint => {}
+            Instruction::AssertCell { expected } => {
+                let actual = decode_cell(self.current_data(AccessKind::Data)?);
+                let expected = u32::from(*expected);
+                if actual != expected {
+                    return Err(RuntimeError::AssertionFailed {
+                        address: self.machine.pointer,
+                        expected,
+                        actual,
+                    });
+                }
+            }
             Instruction::UntilZero(sub) => {
-                if *self.memory.get_mut(self.pointer)? != 0 {
+                check_cancel(&self.cancel)?;
+                let data = self.current_data(AccessKind::LoopCondition)?;
+                if data.iter().any(|&data| data != 0) {
                     return Ok(NextAction::StepIn(sub));
                 }
+                if let Some(threshold) = self.machine.auto_compact {
+                    if self.machine.memory.zero_tail_exceeds(threshold) {
+                        self.machine.memory.compact();
+                    }
+                }
+            }
+            Instruction::SetZero => self.current_data(AccessKind::Data)?.fill(0),
+            Instruction::DSet(value) => {
+                let value = self.machine.reduce((*value).into());
+                encode_cell(self.current_data(AccessKind::Data)?, value)
+            }
+            Instruction::Fill { len, value } => {
+                // the reduced value is not greater than `value`, so it fits in `u8`.
+                let value = self.machine.reduce((*value).into()) as u8;
+                self.machine
+                    .memory
+                    .fill(self.machine.pointer, *len, value)?
             }
         }
         Ok(NextAction::Next)
@@ -140,13 +596,18 @@ where
 
     /// Get the pointer of the runtime.
     pub fn get_pointer(&self) -> isize {
-        self.pointer
+        self.machine.pointer
     }
 
     /// Get the memory data which is pointed by the pointer.
     ///
+    /// For a cell wider than 8 bits, this is the lowest byte of the cell.
     /// Returns `None` if the address is out of memory bounds.
     pub fn get_data_at_mut(&mut self, address: isize) -> Option<&mut u8> {
-        self.memory.get_mut(address).ok()
+        self.machine
+            .memory
+            .get_mut(address, AccessKind::Data)
+            .ok()
+            .map(|cell| &mut cell[0])
     }
 }
@@ -0,0 +1,789 @@
+//! Input and output adapters for running programs.
+use crate::prng::SplitMix64;
+
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A writer adapter which normalizes line endings.
+///
+/// Both `\n` and `\r\n` written to this writer are rewritten into the chosen line ending.
+/// A `\r` at the end of written data is held until the next write or flush
+/// to see whether it is a part of `\r\n`.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime::{self, io::LineEndingNormalizer}};
+/// use std::io::Write;
+///
+/// // outputs "A\nB"
+/// let program = Program::new([DAdd(65), Output, DAdd(-55), Output, DAdd(56), Output]);
+/// let mut output = LineEndingNormalizer::crlf(Vec::new());
+/// runtime::run(&program, std::io::stdin(), &mut output).unwrap();
+/// output.flush().unwrap();
+///
+/// assert_eq!(output.into_inner(), b"A\r\nB");
+/// ```
+pub struct LineEndingNormalizer<W> {
+    inner: W,
+    line_ending: Vec<u8>,
+    pending_cr: bool,
+}
+
+impl<W> LineEndingNormalizer<W>
+where
+    W: Write,
+{
+    /// Create a new normalizer which rewrites line endings into `line_ending`.
+    pub fn new(inner: W, line_ending: impl Into<Vec<u8>>) -> Self {
+        Self {
+            inner,
+            line_ending: line_ending.into(),
+            pending_cr: false,
+        }
+    }
+
+    /// Create a new normalizer which rewrites line endings into `\r\n`.
+    pub fn crlf(inner: W) -> Self {
+        Self::new(inner, *b"\r\n")
+    }
+
+    /// Create a new normalizer which rewrites line endings into `\n`.
+    pub fn lf(inner: W) -> Self {
+        Self::new(inner, *b"\n")
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap this normalizer, returning the underlying writer.
+    ///
+    /// A held `\r` is discarded. Call [`Write::flush`] before this to write it out.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for LineEndingNormalizer<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut normalized = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if byte == b'\n' {
+                    normalized.extend_from_slice(&self.line_ending);
+                    continue;
+                }
+                normalized.push(b'\r');
+            }
+            match byte {
+                b'\r' => self.pending_cr = true,
+                b'\n' => normalized.extend_from_slice(&self.line_ending),
+                _ => normalized.push(byte),
+            }
+        }
+        self.inner.write_all(&normalized)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending_cr {
+            self.inner.write_all(b"\r")?;
+            self.pending_cr = false;
+        }
+        self.inner.flush()
+    }
+}
+
+/// A writer which writes the same data to two writers.
+///
+/// Each write is first written to `a`, then the part `a` accepted is written entirely to `b`.
+/// Errors fail fast: an error from `a` is returned without writing to `b`, and an error from `b`
+/// is returned as is even though `a` has already accepted the data.
+/// Flushing flushes `a` and then `b`, stopping at the first error.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::runtime::io::TeeWriter;
+/// use std::io::Write;
+///
+/// let mut writer = TeeWriter::new(vec![], vec![]);
+/// writer.write_all(b"tee").unwrap();
+/// let (a, b) = writer.into_inner();
+///
+/// assert_eq!(a, b"tee");
+/// assert_eq!(b, b"tee");
+/// ```
+#[derive(Debug)]
+pub struct TeeWriter<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> TeeWriter<A, B>
+where
+    A: Write,
+    B: Write,
+{
+    /// Create a new writer which writes to both `a` and `b`.
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+
+    /// Get references to the underlying writers.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.a, &self.b)
+    }
+
+    /// Get mutable references to the underlying writers.
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a, &mut self.b)
+    }
+
+    /// Unwrap this writer, returning the underlying writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A, B> Write for TeeWriter<A, B>
+where
+    A: Write,
+    B: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.a.write(buf)?;
+        self.b.write_all(&buf[..len])?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.a.flush()?;
+        self.b.flush()
+    }
+}
+
+/// A writer which records everything written to the underlying writer.
+///
+/// Only the data the underlying writer actually accepted is recorded.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime::{self, io::CaptureWriter}};
+///
+/// // outputs "A"
+/// let program = Program::new([DAdd(65), Output]);
+/// let mut output = CaptureWriter::new(std::io::sink());
+/// runtime::run(&program, std::io::empty(), &mut output).unwrap();
+///
+/// assert_eq!(output.captured(), b"A");
+/// ```
+#[derive(Debug)]
+pub struct CaptureWriter<W> {
+    inner: W,
+    captured: Vec<u8>,
+}
+
+impl<W> CaptureWriter<W>
+where
+    W: Write,
+{
+    /// Create a new writer which records everything written to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            captured: vec![],
+        }
+    }
+
+    /// Get the recorded data.
+    pub fn captured(&self) -> &[u8] {
+        &self.captured
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    ///
+    /// Data written directly to the underlying writer is not recorded.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap this writer, returning the underlying writer and the recorded data.
+    pub fn into_inner(self) -> (W, Vec<u8>) {
+        (self.inner, self.captured)
+    }
+}
+
+impl<W> Write for CaptureWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.captured.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer which caps the throughput by sleeping before writes.
+///
+/// The `n`-th byte (counted from 0) is written no earlier than `n / bytes_per_sec` seconds
+/// after the first write. A write is split so that each part is written on time.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::runtime::io::RateLimitedWriter;
+/// use std::{io::Write, time::Instant};
+///
+/// let start = Instant::now();
+/// let mut writer = RateLimitedWriter::new(vec![], 100);
+/// writer.write_all(b"abc").unwrap();
+///
+/// assert!(start.elapsed().as_millis() >= 20);
+/// assert_eq!(writer.into_inner(), b"abc");
+/// ```
+#[derive(Debug)]
+pub struct RateLimitedWriter<W> {
+    inner: W,
+    bytes_per_sec: u32,
+    start: Option<Instant>,
+    written: u64,
+}
+
+impl<W> RateLimitedWriter<W>
+where
+    W: Write,
+{
+    /// Create a new writer which writes at most `bytes_per_sec` bytes per second to `inner`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is 0.
+    pub fn new(inner: W, bytes_per_sec: u32) -> Self {
+        assert!(bytes_per_sec > 0, "rate must be positive.");
+        Self {
+            inner,
+            bytes_per_sec,
+            start: None,
+            written: 0,
+        }
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwrap this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for RateLimitedWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let due = start
+            + Duration::from_secs(self.written / self.bytes_per_sec as u64)
+            + Duration::from_secs(self.written % self.bytes_per_sec as u64) / self.bytes_per_sec;
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        // Write the bytes due by now, at least one.
+        let elapsed = start.elapsed().as_secs_f64();
+        let allowed = (elapsed * self.bytes_per_sec as f64) as u64 + 1;
+        let len = (allowed.saturating_sub(self.written) as usize).clamp(1, buf.len());
+        let len = self.inner.write(&buf[..len])?;
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer which discards everything, e.g. to keep output out of benchmarks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullWriter;
+
+impl Write for NullWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A reader which is always at end-of-file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullReader;
+
+impl Read for NullReader {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+/// A reader which records every byte the underlying reader delivered.
+///
+/// The recorded bytes can be replayed by [`ReplayReader`] to run a program again
+/// with the same input.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime::{self, io::RecordingReader}};
+///
+/// let program = Program::new([Input, Output, Input, Output]);
+/// let mut input = RecordingReader::new(&b"abc"[..]);
+/// let output = runtime::run_collect(&program, &mut input).unwrap();
+///
+/// let replayed = runtime::run_collect(&program, input.into_replay()).unwrap();
+/// assert_eq!(replayed, output);
+/// ```
+#[derive(Debug)]
+pub struct RecordingReader<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R> RecordingReader<R>
+where
+    R: Read,
+{
+    /// Create a new reader which records everything read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            recorded: vec![],
+        }
+    }
+
+    /// Get the recorded data.
+    pub fn recorded(&self) -> &[u8] {
+        &self.recorded
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap this reader, returning the underlying reader and the recorded data.
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.inner, self.recorded)
+    }
+
+    /// Create a [`ReplayReader`] which replays the recorded data once.
+    pub fn into_replay(self) -> ReplayReader {
+        ReplayReader::new(self.recorded)
+    }
+}
+
+impl<R> Read for RecordingReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// A reader which replays fixed data.
+///
+/// When the reader reaches the end of the data, it reports end-of-file or,
+/// if looping is enabled, starts over from the beginning.
+/// A looping reader with empty data reports end-of-file.
+/// Instead of end-of-file, a read past the end can fail with an error
+/// (see [`ReplayReader::with_over_read_error`]).
+///
+/// # Examples
+///
+/// ```
+/// use libbf::runtime::io::ReplayReader;
+/// use std::io::Read;
+///
+/// let mut reader = ReplayReader::new(*b"ab").with_looping(true);
+/// let mut buf = [0; 5];
+/// reader.read_exact(&mut buf).unwrap();
+///
+/// assert_eq!(&buf, b"ababa");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayReader {
+    data: Vec<u8>,
+    pos: usize,
+    looping: bool,
+    over_read_error: Option<io::ErrorKind>,
+}
+
+impl ReplayReader {
+    /// Create a new reader which replays `data` once.
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: data.into(),
+            pos: 0,
+            looping: false,
+            over_read_error: None,
+        }
+    }
+
+    /// Set whether the reader starts over from the beginning when it reaches the end.
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Set the error to report instead of end-of-file when reading past the end of the data.
+    ///
+    /// `None` (the default) reports end-of-file.
+    /// A read which delivers some bytes before reaching the end succeeds with those bytes.
+    pub fn with_over_read_error(mut self, kind: Option<io::ErrorKind>) -> Self {
+        self.over_read_error = kind;
+        self
+    }
+
+    /// Get the position of the next byte to be read in the data.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Get the data to be replayed.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Read for ReplayReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.pos == self.data.len() {
+                if !self.looping || self.data.is_empty() {
+                    break;
+                }
+                self.pos = 0;
+            }
+            let len = (buf.len() - written).min(self.data.len() - self.pos);
+            buf[written..written + len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+            written += len;
+            self.pos += len;
+        }
+        match self.over_read_error {
+            Some(kind) if written == 0 && !buf.is_empty() => Err(kind.into()),
+            _ => Ok(written),
+        }
+    }
+}
+
+/// A reader which produces a deterministic pseudo-random byte stream.
+///
+/// The stream is generated by [SplitMix64](https://prng.di.unimi.it/splitmix64.c)
+/// seeded with `seed`; each byte is the most significant 8 bits of one output.
+/// The same seed always produces the same stream, so a failing run can be
+/// reproduced from the seed alone.
+///
+/// The stream is infinite when `len` is `None`,
+/// otherwise the reader reports end-of-file after `len` bytes.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     error::RuntimeError,
+///     program::{Instruction::*, Program},
+///     runtime::{self, io::SeededReader},
+/// };
+///
+/// // echoes the input until a zero byte is read
+/// let program = Program::new([Input, UntilZero(vec![Output, Input])]);
+///
+/// let seed = 0x5eed;
+/// let result = runtime::run_collect(&program, SeededReader::new(seed, Some(64)));
+/// if let Err(RuntimeError::Eof) = result {
+///     // the same seed reproduces the same input
+///     let mut input = vec![];
+///     std::io::Read::read_to_end(&mut SeededReader::new(seed, Some(64)), &mut input).unwrap();
+///     assert!(!input.contains(&0));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SeededReader {
+    rng: SplitMix64,
+    remaining: Option<usize>,
+}
+
+impl SeededReader {
+    /// Create a new reader producing `len` bytes (or infinitely many if `None`) from `seed`.
+    pub fn new(seed: u64, len: Option<usize>) -> Self {
+        Self {
+            rng: SplitMix64::new(seed),
+            remaining: len,
+        }
+    }
+
+    /// Get the number of bytes left before end-of-file, or `None` if the stream is infinite.
+    pub fn remaining(&self) -> Option<usize> {
+        self.remaining
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        (self.rng.next_u64() >> 56) as u8
+    }
+}
+
+impl Read for SeededReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = match self.remaining {
+            Some(remaining) => buf.len().min(remaining),
+            None => buf.len(),
+        };
+        for byte in &mut buf[..len] {
+            *byte = self.next_byte();
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= len;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        program::{Instruction::*, Program},
+        runtime,
+    };
+
+    #[test]
+    fn test_run_with_crlf_normalizer() {
+        // outputs "A\nB"
+        let program = Program::new([DAdd(65), Output, DAdd(-55), Output, DAdd(56), Output]);
+        let input: &[u8] = &[];
+        let mut output = LineEndingNormalizer::crlf(vec![]);
+        if let Err(e) = runtime::run(&program, input, &mut output) {
+            panic!("unexpected error: {e}");
+        }
+        output.flush().unwrap();
+        assert_eq!(output.into_inner(), b"A\r\nB");
+    }
+
+    #[test]
+    fn test_lf_normalizer() {
+        let mut output = LineEndingNormalizer::lf(vec![]);
+        // "\r\n" split across writes
+        output.write_all(b"A\r").unwrap();
+        output.write_all(b"\nB\rC\n\r").unwrap();
+        output.flush().unwrap();
+        assert_eq!(output.into_inner(), b"A\nB\rC\n\r");
+    }
+
+    #[test]
+    fn test_run_with_replay_reader() {
+        let program = Program::new([Input, Output, Input, Output, Input, Output, Input, Output]);
+
+        let input = ReplayReader::new(*b"ab").with_looping(true);
+        let mut output = vec![];
+        if let Err(e) = runtime::run(&program, input, &mut output) {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(output, b"abab");
+
+        let input = ReplayReader::new(*b"ab");
+        let mut output = vec![];
+        let result = runtime::run(&program, input, &mut output);
+        assert!(matches!(result, Err(crate::error::RuntimeError::Eof)));
+        assert_eq!(output, b"ab");
+
+        let mut input = ReplayReader::new(vec![]).with_looping(true);
+        assert_eq!(input.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_seeded_reader() {
+        let mut first = [0; 8];
+        SeededReader::new(0, None).read_exact(&mut first).unwrap();
+        assert_eq!(first, [0xe2, 0x6e, 0x06, 0xf8, 0x1b, 0x53, 0x2c, 0xc5]);
+
+        // reads split at arbitrary points produce the same stream
+        let mut reader = SeededReader::new(0, None);
+        let mut split = [0; 8];
+        reader.read_exact(&mut split[..3]).unwrap();
+        reader.read_exact(&mut split[3..]).unwrap();
+        assert_eq!(split, first);
+
+        let mut reader = SeededReader::new(0, Some(5));
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(buf[..5], first[..5]);
+        assert_eq!(reader.remaining(), Some(0));
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let program = Program::new([Input, Input, Input]);
+        let result = runtime::run_collect(&program, SeededReader::new(1, Some(2)));
+        assert!(matches!(result, Err(crate::error::RuntimeError::Eof)));
+    }
+
+    /// A writer which accepts at most `limit` bytes per write and counts flushes.
+    struct ShortWriter {
+        data: Vec<u8>,
+        limit: usize,
+        flushed: usize,
+    }
+
+    impl ShortWriter {
+        fn new(limit: usize) -> Self {
+            Self {
+                data: vec![],
+                limit,
+                flushed: 0,
+            }
+        }
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.limit == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let len = buf.len().min(self.limit);
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tee_writer() {
+        // `a` accepts partially, `b` receives only the accepted part
+        let mut writer = TeeWriter::new(ShortWriter::new(2), ShortWriter::new(1));
+        assert_eq!(writer.write(b"abc").unwrap(), 2);
+        writer.write_all(b"de").unwrap();
+        writer.flush().unwrap();
+        let (a, b) = writer.into_inner();
+        assert_eq!(a.data, b"abde");
+        assert_eq!(b.data, b"abde");
+        assert_eq!((a.flushed, b.flushed), (1, 1));
+
+        // fail fast
+        let mut writer = TeeWriter::new(ShortWriter::new(0), ShortWriter::new(1));
+        assert!(writer.write(b"a").is_err());
+        assert!(writer.get_ref().1.data.is_empty());
+        let mut writer = TeeWriter::new(ShortWriter::new(1), ShortWriter::new(0));
+        assert!(writer.write(b"a").is_err());
+        assert_eq!(writer.get_ref().0.data, b"a");
+    }
+
+    #[test]
+    fn test_capture_writer() {
+        let mut writer = CaptureWriter::new(ShortWriter::new(2));
+        assert_eq!(writer.write(b"abc").unwrap(), 2);
+        writer.write_all(b"cde").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.captured(), b"abcde");
+        let (inner, captured) = writer.into_inner();
+        assert_eq!(inner.data, captured);
+        assert_eq!(inner.flushed, 1);
+
+        let mut writer = CaptureWriter::new(ShortWriter::new(0));
+        assert!(writer.write(b"a").is_err());
+        assert!(writer.captured().is_empty());
+    }
+
+    #[test]
+    fn test_null_adapters() {
+        let program = Program::new([DAdd(65), Output, Input]);
+        let result = runtime::run(&program, NullReader, NullWriter);
+        assert!(matches!(result, Err(crate::error::RuntimeError::Eof)));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        // echoes the input until a zero byte is read
+        let program = Program::new([Input, UntilZero(vec![Output, Input])]);
+
+        let source = std::io::Cursor::new(b"hello\0ignored".to_vec());
+        let mut input = RecordingReader::new(source);
+        let output = runtime::run_collect(&program, &mut input).unwrap();
+        assert_eq!(output, b"hello");
+        let (source, recorded) = input.into_inner();
+        assert_eq!(recorded, b"hello\0");
+        drop(source);
+
+        let replayed = runtime::run_collect(&program, ReplayReader::new(recorded)).unwrap();
+        assert_eq!(replayed, output);
+    }
+
+    #[test]
+    fn test_replay_over_read_error() {
+        let mut reader =
+            ReplayReader::new(*b"ab").with_over_read_error(Some(io::ErrorKind::UnexpectedEof));
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut []).unwrap(), 0);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let program = Program::new([Input, Input, Input]);
+        let input = ReplayReader::new(*b"ab").with_over_read_error(Some(io::ErrorKind::Other));
+        let result = runtime::run_collect(&program, input);
+        assert!(matches!(
+            result,
+            Err(crate::error::RuntimeError::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_rate_limited_writer() {
+        let start = Instant::now();
+        let mut writer = RateLimitedWriter::new(ShortWriter::new(8), 200);
+        writer.write_all(b"0123456789").unwrap();
+        writer.flush().unwrap();
+        // the last byte is due at 9 / 200 seconds
+        assert!(start.elapsed() >= Duration::from_millis(45));
+        let inner = writer.into_inner();
+        assert_eq!(inner.data, b"0123456789");
+        assert_eq!(inner.flushed, 1);
+    }
+}
@@ -1,17 +1,47 @@
 //! Program runtime.
+mod cancel;
+mod cost;
+mod input_decode;
 mod internal;
+pub mod io;
+mod observer;
+mod output_map;
+mod pipeline;
+mod profile;
 mod runner;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
 mod step_runner;
+pub mod trace;
 
-use crate::{error::RuntimeError, prelude::Program, program::Instruction};
+use crate::{
+    error::{ParseMemorySizeError, RuntimeError},
+    prelude::Program,
+    program::{Instruction, ProgramIndex},
+};
 
-use std::io::{Read, Write};
+use std::{
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+    time::Duration,
+};
 
+pub use self::cancel::CancelFlag;
+pub use self::cost::{CostModel, MachineView, MeteredOutcome, UnitCost, WorkCost};
+pub use self::input_decode::{InputDecode, UnmappablePolicy};
+pub use self::observer::{ExecObserver, NoObserver};
+pub use self::output_map::OutputMap;
+pub use self::pipeline::{pipeline, ExecutionStats, PIPE_CAPACITY};
+pub use self::profile::Profile;
 pub use self::runner::Runner;
-pub use self::step_runner::StepRunner;
+#[cfg(all(unix, feature = "signal"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "signal"))))]
+pub use self::signal::{ctrlc_guard, CtrlcGuard};
+pub use self::step_runner::{Budget, BudgetOutcome, StepOutcome, StepRunner};
 
 /// A runtime memory size.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemorySize {
     /// Fixed size (range: [0, self.0)). Access to memory out of bounds will cause runtime error.
     Fixed(usize),
@@ -19,11 +49,313 @@ pub enum MemorySize {
     RightInfinite,
     /// Infinite to both (positive and negative) directions
     BothInfinite,
+    /// Bounded on both sides (range: [-left, right)). Access to memory out of bounds will cause
+    /// runtime error.
+    ///
+    /// Unlike [`MemorySize::Fixed`], the memory is allocated on demand.
+    LeftRightBounded {
+        /// The number of cells at negative addresses.
+        left: usize,
+        /// The number of cells at non-negative addresses.
+        right: usize,
+    },
+}
+
+impl MemorySize {
+    // The number of cells at non-negative addresses, or `None` if infinite.
+    fn right_limit(&self) -> Option<usize> {
+        match self {
+            Self::Fixed(len) => Some(*len),
+            Self::LeftRightBounded { right, .. } => Some(*right),
+            Self::RightInfinite | Self::BothInfinite => None,
+        }
+    }
+
+    // The number of cells at negative addresses, or `None` if infinite.
+    fn left_limit(&self) -> Option<usize> {
+        match self {
+            Self::Fixed(_) | Self::RightInfinite => Some(0),
+            Self::LeftRightBounded { left, .. } => Some(*left),
+            Self::BothInfinite => None,
+        }
+    }
+}
+
+impl fmt::Display for MemorySize {
+    /// Formats the size as `fixed:N`, `right-infinite`, `both-infinite` or `bounded:L:R`,
+    /// which can be parsed by [`FromStr`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(len) => write!(f, "fixed:{len}"),
+            Self::RightInfinite => write!(f, "right-infinite"),
+            Self::BothInfinite => write!(f, "both-infinite"),
+            Self::LeftRightBounded { left, right } => write!(f, "bounded:{left}:{right}"),
+        }
+    }
+}
+
+impl FromStr for MemorySize {
+    type Err = ParseMemorySizeError;
+
+    /// Parses a memory size.
+    ///
+    /// The accepted forms are `N` and `fixed:N` for [`MemorySize::Fixed`],
+    /// `right-infinite` (or `right-inf`) for [`MemorySize::RightInfinite`],
+    /// `both-infinite` (or `both-inf`) for [`MemorySize::BothInfinite`], and
+    /// `bounded:L:R` for [`MemorySize::LeftRightBounded`] with `left: L` and `right: R`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::runtime::MemorySize;
+    ///
+    /// assert_eq!("30000".parse(), Ok(MemorySize::Fixed(30000)));
+    /// assert_eq!("both-infinite".parse(), Ok(MemorySize::BothInfinite));
+    /// assert_eq!(MemorySize::Fixed(10).to_string().parse(), Ok(MemorySize::Fixed(10)));
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMemorySizeError {
+            input: s.to_string(),
+        };
+        let parse_len = |len: &str| match len.bytes().all(|b| b.is_ascii_digit()) {
+            true => len.parse::<usize>().map_err(|_| error()),
+            false => Err(error()),
+        };
+        if let Some(bounds) = s.strip_prefix("bounded:") {
+            let (left, right) = bounds.split_once(':').ok_or_else(error)?;
+            return Ok(Self::LeftRightBounded {
+                left: parse_len(left)?,
+                right: parse_len(right)?,
+            });
+        }
+        match s {
+            "right-infinite" | "right-inf" => Ok(Self::RightInfinite),
+            "both-infinite" | "both-inf" => Ok(Self::BothInfinite),
+            _ => parse_len(s.strip_prefix("fixed:").unwrap_or(s)).map(Self::Fixed),
+        }
+    }
 }
 
 /// Default memory size.
 pub const DEFAULT_MEMSIZE: MemorySize = MemorySize::Fixed(30000);
 
+/// A modulus at which cell values wrap around.
+///
+/// By default, a cell wraps at its [`CellWidth`]. With a modulus `m` (see [`CellModulus::new`]),
+/// data increments/decrements wrap at `m` instead, so every cell holds a value in `[0, m)`.
+/// A modulus which is not less than the range of a cell (e.g. 256 for [`CellWidth::U8`])
+/// has no effect.
+///
+/// Input and output are still byte based:
+///
+///  - An input byte `b` is stored as `b % m`.
+///  - An output writes the lowest byte of the cell value, as without a modulus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellModulus(u32);
+
+impl CellModulus {
+    /// Create a modulus `m`, or `None` if `m` is 0.
+    pub fn new(m: u32) -> Option<Self> {
+        (m != 0).then_some(Self(m))
+    }
+
+    /// Get the modulus.
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+/// A width of a cell.
+///
+/// Input and output are still byte based:
+///
+///  - An input byte is zero-extended to the cell width.
+///  - An output writes the lowest byte of the cell.
+///
+/// Accessors which return a `u8`, such as [`MachineState::data_at`], also see the lowest byte of
+/// a cell. Use [`MachineState::cell_at`] to get the whole value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellWidth {
+    /// 8-bit cells.
+    #[default]
+    U8,
+    /// 16-bit cells.
+    U16,
+    /// 32-bit cells.
+    U32,
+}
+
+impl CellWidth {
+    /// Get the number of bytes of a cell.
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+}
+
+/// A policy for an input which is not ready yet.
+///
+/// An input is not ready when reading it fails with [`std::io::ErrorKind::WouldBlock`],
+/// which typically happens with non-blocking sockets and pipes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WouldBlockPolicy {
+    /// Sleep for the given duration and retry reading until the input gets ready.
+    Retry(Duration),
+    /// Return [`RuntimeError::InputNotReady`].
+    ///
+    /// The input instruction is not completed, so [`StepRunner::step`] can retry it later.
+    #[default]
+    Report,
+    /// Treat it as an end-of-file (see [`EofBehavior`]).
+    Eof,
+}
+
+/// A behavior of the input instruction at the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofBehavior {
+    /// Return [`RuntimeError::Eof`].
+    #[default]
+    Error,
+    /// Leave the current cell unchanged.
+    Unchanged,
+    /// Set the current cell to 0.
+    SetZero,
+    /// Set the current cell to the given value, which is wrapped at the cell modulus.
+    SetValue(u8),
+}
+
+/// A runtime configuration.
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    /// The memory size.
+    pub memsize: MemorySize,
+    /// The cell width.
+    pub cell_width: CellWidth,
+    /// The cell modulus. `None` means that cells wrap at the cell width.
+    pub cell_modulus: Option<CellModulus>,
+    /// The policy for an input which is not ready yet.
+    pub would_block: WouldBlockPolicy,
+    /// The behavior of the input instruction at the end of the input.
+    pub eof: EofBehavior,
+    /// The decoding of the input.
+    pub input_decode: InputDecode,
+    /// The translation of output bytes.
+    pub output_map: OutputMap,
+    /// The threshold of automatic memory compaction. `None` disables it.
+    ///
+    /// When a loop is exited and the zero cells at the ends of the allocated memory are more than
+    /// the threshold, they are released. Checking it examines up to `threshold + 1` cells.
+    pub auto_compact: Option<usize>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            memsize: DEFAULT_MEMSIZE,
+            cell_width: CellWidth::default(),
+            cell_modulus: None,
+            would_block: WouldBlockPolicy::default(),
+            eof: EofBehavior::default(),
+            input_decode: InputDecode::bytes(),
+            output_map: OutputMap::identity(),
+            auto_compact: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Set the memory size.
+    pub fn with_memsize(mut self, memsize: MemorySize) -> Self {
+        self.memsize = memsize;
+        self
+    }
+
+    /// Set the cell width.
+    pub fn with_cell_width(mut self, cell_width: CellWidth) -> Self {
+        self.cell_width = cell_width;
+        self
+    }
+
+    /// Set the cell modulus.
+    pub fn with_cell_modulus(mut self, cell_modulus: CellModulus) -> Self {
+        self.cell_modulus = Some(cell_modulus);
+        self
+    }
+
+    /// Set the policy for an input which is not ready yet.
+    pub fn with_would_block_policy(mut self, would_block: WouldBlockPolicy) -> Self {
+        self.would_block = would_block;
+        self
+    }
+
+    /// Set the behavior of the input instruction at the end of the input.
+    pub fn with_eof_behavior(mut self, eof: EofBehavior) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Set the decoding of the input.
+    pub fn with_input_decode(mut self, input_decode: InputDecode) -> Self {
+        self.input_decode = input_decode;
+        self
+    }
+
+    /// Set the translation of output bytes.
+    pub fn with_output_map(mut self, output_map: OutputMap) -> Self {
+        self.output_map = output_map;
+        self
+    }
+
+    /// Set the threshold of automatic memory compaction.
+    pub fn with_auto_compact(mut self, threshold: usize) -> Self {
+        self.auto_compact = Some(threshold);
+        self
+    }
+}
+
+/// A snapshot of the state of a runner, excluding its input and output.
+///
+/// It is obtained by [`StepRunner::fork_state`] and can be resumed by [`StepRunner::from_state`].
+#[derive(Debug, Clone)]
+pub struct MachineState {
+    machine: internal::Machine,
+    index: Option<ProgramIndex>,
+    // `true` if the loop at `index` has completed an iteration
+    reentering: bool,
+}
+
+impl MachineState {
+    /// Get the pointer.
+    pub fn pointer(&self) -> isize {
+        self.machine.pointer()
+    }
+
+    /// Get the memory data at `address`.
+    ///
+    /// For a cell wider than 8 bits, this is the lowest byte of the cell.
+    /// Returns `None` if the address is out of memory bounds.
+    pub fn data_at(&self, address: isize) -> Option<u8> {
+        self.machine.data_at(address)
+    }
+
+    /// Get the value of the cell at `address`.
+    ///
+    /// Returns `None` if the address is out of memory bounds.
+    pub fn cell_at(&self, address: isize) -> Option<u32> {
+        self.machine.cell_at(address)
+    }
+
+    /// Get the index of the instruction to be executed next.
+    ///
+    /// If the program was finished, this returns `None`.
+    pub fn index(&self) -> Option<&ProgramIndex> {
+        self.index.as_ref()
+    }
+}
+
 /// Run a program with the given input and output.
 ///
 /// It is equivalent to `Runner::new(input, output).run()`.
@@ -51,9 +383,186 @@ where
     Runner::with_memsize(program, input, output, memsize).run()
 }
 
+/// Run a program with the given input, output and runtime configuration.
+///
+/// It is equivalent to `Runner::with_config(input, output, config).run()`.
+pub fn run_with_config<R, W>(
+    program: &Program,
+    input: R,
+    output: W,
+    config: RuntimeConfig,
+) -> Result<(), RuntimeError>
+where
+    R: Read,
+    W: Write,
+{
+    Runner::with_config(program, input, output, config).run()
+}
+
+/// Run a program with the given input and return its output.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime};
+///
+/// let program = Program::new([Input, DAdd(1), Output]);
+///
+/// assert_eq!(runtime::run_collect(&program, &b"a"[..]).unwrap(), b"b");
+/// ```
+pub fn run_collect<R>(program: &Program, input: R) -> Result<Vec<u8>, RuntimeError>
+where
+    R: Read,
+{
+    let mut output = vec![];
+    run(program, input, &mut output)?;
+    Ok(output)
+}
+
+/// Run a program with the given input and return its output, keeping at most `max_bytes` bytes
+/// in memory.
+///
+/// If the program outputs more than `max_bytes` bytes, the run stops with
+/// [`RuntimeError::OutputLimitExceeded`], which has the first `max_bytes` bytes of the output.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{error::RuntimeError, program::{Instruction::*, Program}, runtime};
+///
+/// // outputs "A" forever
+/// let program = Program::new([DAdd(65), UntilZero(vec![Output])]);
+/// let result = runtime::run_to_capped_vec(&program, &[][..], 3);
+///
+/// assert!(matches!(result, Err(RuntimeError::OutputLimitExceeded { output, .. }) if output == b"AAA"));
+/// ```
+pub fn run_to_capped_vec<R>(
+    program: &Program,
+    input: R,
+    max_bytes: usize,
+) -> Result<Vec<u8>, RuntimeError>
+where
+    R: Read,
+{
+    let mut output = CappedVec {
+        buf: vec![],
+        max_bytes,
+        exceeded: false,
+    };
+    let result = run(program, input, &mut output);
+    if output.exceeded {
+        return Err(RuntimeError::OutputLimitExceeded {
+            limit: max_bytes,
+            output: output.buf,
+        });
+    }
+    result.map(|()| output.buf)
+}
+
+// A vector which fails writing more than `max_bytes` bytes.
+struct CappedVec {
+    buf: Vec<u8>,
+    max_bytes: usize,
+    exceeded: bool,
+}
+
+impl Write for CappedVec {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.max_bytes - self.buf.len());
+        if len == 0 && !buf.is_empty() {
+            self.exceeded = true;
+            return Err(std::io::Error::other("output limit exceeded"));
+        }
+        self.buf.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run a program with the given input and output, writing at most `bytes_per_sec` bytes per second.
+///
+/// Output is delayed by sleeping so that it appears gradually, e.g. for live demonstrations.
+/// See [`io::RateLimitedWriter`].
+///
+/// # Panics
+///
+/// Panics if `bytes_per_sec` is 0.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime};
+/// use std::time::Instant;
+///
+/// // outputs "AAAAA"
+/// let program = Program::new([DAdd(65), Output, Output, Output, Output, Output]);
+/// let start = Instant::now();
+/// let mut output = vec![];
+/// runtime::run_rate_limited(&program, &[][..], &mut output, 100).unwrap();
+///
+/// assert!(start.elapsed().as_millis() >= 40);
+/// assert_eq!(output, b"AAAAA");
+/// ```
+pub fn run_rate_limited<R, W>(
+    program: &Program,
+    input: R,
+    output: W,
+    bytes_per_sec: u32,
+) -> Result<(), RuntimeError>
+where
+    R: Read,
+    W: Write,
+{
+    run(
+        program,
+        input,
+        io::RateLimitedWriter::new(output, bytes_per_sec),
+    )
+}
+
+/// Run a program with the given input and return the display width of its output.
+///
+/// The output is decoded as UTF-8 (invalid sequences are replaced with U+FFFD) and
+/// the number of terminal columns is summed up. Control characters, including line feeds,
+/// occupy no columns. Wide characters (e.g. CJK ideographs) occupy 2 columns.
+///
+/// The width of each character is a simple approximation of East Asian Width and
+/// does not cover all characters.
+pub fn run_to_display_width<R>(program: &Program, input: R) -> Result<usize, RuntimeError>
+where
+    R: Read,
+{
+    let output = run_collect(program, input)?;
+    Ok(String::from_utf8_lossy(&output)
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(crate::error::char_width)
+        .sum())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{error::AccessKind, testing::bf_parser};
+
+    /// A reader which is not ready for the first `not_ready` reads.
+    pub(super) struct NotReadyReader<'a> {
+        pub(super) not_ready: usize,
+        pub(super) data: &'a [u8],
+    }
+
+    impl Read for NotReadyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.not_ready > 0 {
+                self.not_ready -= 1;
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            self.data.read(buf)
+        }
+    }
 
     #[test]
     fn test_run_empty_program() {
@@ -102,8 +611,15 @@ mod test {
         let mut output = vec![];
         let result = run(&program, input, &mut output);
         if let Err(e) = result {
-            if let RuntimeError::OutOfMemoryBounds { address } = e {
+            if let RuntimeError::OutOfMemoryBounds {
+                address,
+                memsize,
+                access,
+            } = e
+            {
                 assert_eq!(address, -1);
+                assert_eq!(memsize, DEFAULT_MEMSIZE);
+                assert_eq!(access, AccessKind::Data);
             } else {
                 panic!("unexpected error: {e}");
             }
@@ -120,8 +636,15 @@ mod test {
         let mut output = vec![];
         let result = run_with_memsize(&program, input, &mut output, MemorySize::RightInfinite);
         if let Err(e) = result {
-            if let RuntimeError::OutOfMemoryBounds { address } = e {
+            if let RuntimeError::OutOfMemoryBounds {
+                address,
+                memsize,
+                access,
+            } = e
+            {
                 assert_eq!(address, -1);
+                assert_eq!(memsize, MemorySize::RightInfinite);
+                assert_eq!(access, AccessKind::Data);
             } else {
                 panic!("unexpected error: {e}");
             }
@@ -150,8 +673,41 @@ mod test {
         let mut output = vec![];
         let result = run(&program, input, &mut output);
         if let Err(e) = result {
-            if let RuntimeError::OutOfMemoryBounds { address } = e {
+            if let RuntimeError::OutOfMemoryBounds {
+                address,
+                memsize,
+                access,
+            } = e
+            {
                 assert_eq!(address, 30000);
+                assert_eq!(memsize, MemorySize::Fixed(30000));
+                assert_eq!(access, AccessKind::Data);
+            } else {
+                panic!("unexpected error: {e}");
+            }
+        } else {
+            panic!("unexpectedly succeeded");
+        }
+    }
+
+    #[test]
+    fn test_run_out_of_memory_bounds_loop_condition() {
+        use Instruction::*;
+        let program = Program::new([PAdd(-1), UntilZero(vec![DAdd(-1)])]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let result = run(&program, input, &mut output);
+        if let Err(e) = result {
+            if let RuntimeError::OutOfMemoryBounds {
+                address, access, ..
+            } = e
+            {
+                assert_eq!(address, -1);
+                assert_eq!(access, AccessKind::LoopCondition);
+                assert_eq!(
+                    e.to_string(),
+                    "out of memory bounds [-1] (loop condition read, valid range: [0, 30000))"
+                );
             } else {
                 panic!("unexpected error: {e}");
             }
@@ -160,6 +716,50 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_run_left_right_bounded() {
+        use Instruction::*;
+        let memsize = MemorySize::LeftRightBounded { left: 3, right: 5 };
+        // the extreme left and right cells
+        let program = Program::new([PAdd(-3), DAdd(1), Output, PAdd(7), DAdd(2), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        run_with_memsize(&program, input, &mut output, memsize).unwrap();
+        assert_eq!(output, [1, 2]);
+
+        // just beyond them
+        for (operand, address) in [(-4, -4), (5, 5)] {
+            let program = Program::new([PAdd(operand), DAdd(1)]);
+            let input: &[u8] = &[];
+            let mut output = vec![];
+            let result = run_with_memsize(&program, input, &mut output, memsize);
+            match result {
+                Err(
+                    e @ RuntimeError::OutOfMemoryBounds {
+                        address: a,
+                        memsize: m,
+                        access: AccessKind::Data,
+                    },
+                ) => {
+                    assert_eq!(a, address);
+                    assert_eq!(m, memsize);
+                    assert!(e.to_string().ends_with("valid range: [-3, 5))"), "{e}");
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+
+        // fill stops at the right bound
+        let program = Program::new([PAdd(3), Fill { len: 3, value: 7 }]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let result = run_with_memsize(&program, input, &mut output, memsize);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::OutOfMemoryBounds { address: 5, .. })
+        ));
+    }
+
     #[test]
     fn test_run_positive_memory_address_access_for_right_inifinite() {
         use Instruction::*;
@@ -184,11 +784,101 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_run_cell_modulus_wraps_up() {
+        use Instruction::*;
+        let program = Program::new([DAdd(9), Output, DAdd(1), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_cell_modulus(CellModulus::new(10).unwrap());
+        let result = run_with_config(&program, input, &mut output, config);
+        if let Err(e) = result {
+            panic!("unexpected error: {e}");
+        } else {
+            assert_eq!(output, &[9, 0]);
+        }
+    }
+
+    #[test]
+    fn test_run_cell_modulus_wraps_down() {
+        use Instruction::*;
+        let program = Program::new([DAdd(-1), Output, DAdd(-25), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_cell_modulus(CellModulus::new(10).unwrap());
+        let result = run_with_config(&program, input, &mut output, config);
+        if let Err(e) = result {
+            panic!("unexpected error: {e}");
+        } else {
+            assert_eq!(output, &[9, 4]);
+        }
+    }
+
+    #[test]
+    fn test_run_cell_modulus_input() {
+        use Instruction::*;
+        let program = Program::new([Input, Output]);
+        let input: &[u8] = &[25];
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_cell_modulus(CellModulus::new(10).unwrap());
+        let result = run_with_config(&program, input, &mut output, config);
+        if let Err(e) = result {
+            panic!("unexpected error: {e}");
+        } else {
+            assert_eq!(output, &[5]);
+        }
+    }
+
+    #[test]
+    fn test_run_cell_modulus_set() {
+        use Instruction::*;
+        let program = Program::new([DSet(42), Output, DSet(9), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_cell_modulus(CellModulus::new(10).unwrap());
+        let result = run_with_config(&program, input, &mut output, config);
+        if let Err(e) = result {
+            panic!("unexpected error: {e}");
+        } else {
+            assert_eq!(output, &[2, 9]);
+        }
+
+        let program = Program::new([Fill { len: 2, value: 42 }, Output, PAdd(1), Output]);
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_cell_modulus(CellModulus::new(10).unwrap());
+        run_with_config(&program, input, &mut output, config).unwrap();
+        assert_eq!(output, &[2, 2]);
+    }
+
+    #[test]
+    fn test_cell_modulus_new() {
+        assert_eq!(CellModulus::new(0), None);
+        assert_eq!(CellModulus::new(10).map(CellModulus::get), Some(10));
+    }
+
+    #[test]
+    fn test_run_cell_modulus_too_large() {
+        use Instruction::*;
+        let program = Program::new([DAdd(-1), Output]);
+        let run_with_width = |cell_width| {
+            let mut output = vec![];
+            let config = RuntimeConfig::default()
+                .with_cell_width(cell_width)
+                .with_cell_modulus(CellModulus::new(1000).unwrap());
+            run_with_config(&program, &[][..], &mut output, config).unwrap();
+            output
+        };
+        // the modulus has no effect on 8-bit cells.
+        assert_eq!(run_with_width(CellWidth::U8), [255]);
+        // 999 % 256
+        assert_eq!(run_with_width(CellWidth::U16), [231]);
+    }
+
     struct TestErrorReader;
 
     impl Read for TestErrorReader {
         fn read(&mut self, _buf: &mut [u8]) -> Result<usize, std::io::Error> {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "test error"))
+            Err(std::io::Error::other("test error"))
         }
     }
 
@@ -233,8 +923,8 @@ mod test {
         use Instruction::*;
         let program = Program::new([Output]);
         let input: &[u8] = &[];
-        let mut output: &mut [u8] = &mut [];
-        let result = run(&program, input, &mut output);
+        let output: &mut [u8] = &mut [];
+        let result = run(&program, input, output);
         if let Err(e) = result {
             if let RuntimeError::IoError(_) = e {
                 // OK
@@ -246,6 +936,51 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_memsize_from_str() {
+        for (s, memsize) in [
+            ("30000", MemorySize::Fixed(30000)),
+            ("fixed:0", MemorySize::Fixed(0)),
+            ("fixed:30000", MemorySize::Fixed(30000)),
+            ("right-infinite", MemorySize::RightInfinite),
+            ("right-inf", MemorySize::RightInfinite),
+            ("both-infinite", MemorySize::BothInfinite),
+            ("both-inf", MemorySize::BothInfinite),
+            (
+                "bounded:100:30000",
+                MemorySize::LeftRightBounded {
+                    left: 100,
+                    right: 30000,
+                },
+            ),
+        ] {
+            assert_eq!(s.parse(), Ok(memsize), "{s:?}");
+            assert_eq!(memsize.to_string().parse(), Ok(memsize));
+        }
+
+        for s in [
+            "",
+            "fixed:",
+            "fixed:-1",
+            "+5",
+            "many",
+            "Fixed:1",
+            "bounded:1",
+            "bounded:1:",
+            "bounded:-1:1",
+            "99999999999999999999999",
+        ] {
+            let error = s.parse::<MemorySize>().unwrap_err();
+            assert_eq!(error.input, s);
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "invalid memory size `{s}` (expected `N`, `fixed:N`, `right-infinite`, `both-infinite` or `bounded:L:R`)"
+                )
+            );
+        }
+    }
+
     #[test]
     fn test_run_hello_world() {
         use Instruction::*;
@@ -318,4 +1053,440 @@ mod test {
             assert_eq!(output, b"Hello World!\n");
         }
     }
+
+    #[test]
+    fn test_run_would_block_policy() {
+        use Instruction::*;
+        let program = Program::new([Input, Output]);
+
+        let input = NotReadyReader {
+            not_ready: 2,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let config = RuntimeConfig::default()
+            .with_would_block_policy(WouldBlockPolicy::Retry(Duration::ZERO));
+        if let Err(e) = run_with_config(&program, input, &mut output, config) {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(output, b"A");
+
+        let input = NotReadyReader {
+            not_ready: 1,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let result = run(&program, input, &mut output);
+        assert!(matches!(result, Err(RuntimeError::InputNotReady)));
+
+        let input = NotReadyReader {
+            not_ready: 1,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_would_block_policy(WouldBlockPolicy::Eof);
+        let result = run_with_config(&program, input, &mut output, config);
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+    }
+
+    #[test]
+    fn test_run_cell_width() {
+        use Instruction::*;
+        // enters the loop only if 256 does not wrap around
+        let program = Program::new([
+            DAdd(256),
+            UntilZero(vec![SetZero, DAdd(65), Output, SetZero]),
+            Input,
+            DAdd(1),
+            PAdd(1),
+            DAdd(-1),
+            Output,
+            AssertCell { expected: 255 },
+        ]);
+        let run_with_width = |cell_width| {
+            let mut output = vec![];
+            let config = RuntimeConfig::default().with_cell_width(cell_width);
+            let mut runner = StepRunner::with_config(&program, &[0xff][..], &mut output, config);
+            let mut result = Ok(());
+            while runner.is_running() && result.is_ok() {
+                result = runner.step().map(drop);
+            }
+            let state = runner.fork_state();
+            drop(runner);
+            let cells = (state.cell_at(0).unwrap(), state.cell_at(1).unwrap());
+            (result, output, cells)
+        };
+
+        let (result, output, cells) = run_with_width(CellWidth::U8);
+        assert!(result.is_ok());
+        assert_eq!(output, [0xff]);
+        assert_eq!(cells, (0, 0xff));
+
+        let (result, output, cells) = run_with_width(CellWidth::U16);
+        assert!(matches!(
+            result,
+            Err(RuntimeError::AssertionFailed {
+                address: 1,
+                expected: 255,
+                actual: 0xffff,
+            })
+        ));
+        assert_eq!(output, b"A\xff");
+        assert_eq!(cells, (0x100, 0xffff));
+
+        let (_, output, cells) = run_with_width(CellWidth::U32);
+        assert_eq!(output, b"A\xff");
+        assert_eq!(cells, (0x100, u32::MAX));
+
+        // fill clears the whole cells
+        let program = Program::new([
+            DAdd(-1),
+            PAdd(1),
+            DAdd(-1),
+            PAdd(-1),
+            Fill { len: 2, value: 3 },
+        ]);
+        let config = RuntimeConfig::default().with_cell_width(CellWidth::U16);
+        let mut runner = StepRunner::with_config(&program, &[][..], vec![], config);
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        let state = runner.fork_state();
+        assert_eq!((state.cell_at(0), state.cell_at(1)), (Some(3), Some(3)));
+    }
+
+    #[test]
+    fn test_run_cell_width_far_address() {
+        use Instruction::*;
+        // the byte offset of the cell overflows `usize`.
+        let address = isize::MAX / 2;
+        let program = Program::new([PAdd(address), DAdd(1)]);
+        let config = RuntimeConfig::default()
+            .with_memsize(MemorySize::RightInfinite)
+            .with_cell_width(CellWidth::U32);
+        let mut runner = StepRunner::with_config(&program, &[][..], vec![], config);
+        runner.step().unwrap();
+        assert!(matches!(
+            runner.step(),
+            Err(RuntimeError::OutOfMemoryBounds { address: a, .. }) if a == address
+        ));
+        assert_eq!(runner.fork_state().cell_at(address), None);
+    }
+
+    #[test]
+    fn test_run_cell_width_wrapping() {
+        use Instruction::*;
+        let cell_after = |instructions: &[Instruction], cell_width| {
+            let program = Program::new(instructions.to_vec());
+            let config = RuntimeConfig::default().with_cell_width(cell_width);
+            let mut runner = StepRunner::with_config(&program, &[][..], vec![], config);
+            while runner.is_running() {
+                runner.step().unwrap();
+            }
+            runner.fork_state().cell_at(0).unwrap()
+        };
+
+        assert_eq!(cell_after(&[DAdd(300)], CellWidth::U8), 44);
+        assert_eq!(cell_after(&[DAdd(300)], CellWidth::U16), 300);
+        assert_eq!(cell_after(&[DAdd(65536 + 300)], CellWidth::U16), 300);
+        assert_eq!(cell_after(&[DAdd(-1)], CellWidth::U16), 0xffff);
+        assert_eq!(
+            cell_after(&[DAdd(300), DAdd(-301)], CellWidth::U32),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_run_eof_behavior() {
+        use Instruction::*;
+        let program = Program::new([DAdd(7), Input, Output]);
+
+        let mut output = vec![];
+        let result = run(&program, &[][..], &mut output);
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+        assert!(output.is_empty());
+
+        for (eof, expected) in [
+            (EofBehavior::Unchanged, 7),
+            (EofBehavior::SetZero, 0),
+            (EofBehavior::SetValue(255), 255),
+        ] {
+            let mut output = vec![];
+            let config = RuntimeConfig::default().with_eof_behavior(eof);
+            if let Err(e) = run_with_config(&program, &[][..], &mut output, config) {
+                panic!("unexpected error: {e}");
+            }
+            assert_eq!(output, [expected], "{eof:?}");
+        }
+
+        // the value is wrapped at the cell modulus
+        let mut output = vec![];
+        let config = RuntimeConfig::default()
+            .with_cell_modulus(CellModulus::new(100).unwrap())
+            .with_eof_behavior(EofBehavior::SetValue(255));
+        run_with_config(&program, &[][..], &mut output, config).unwrap();
+        assert_eq!(output, [55]);
+
+        // the would-block policy treating a not-ready input as an end-of-file
+        let input = NotReadyReader {
+            not_ready: 1,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_would_block_policy(WouldBlockPolicy::Eof);
+        Runner::with_config(&program, input, &mut output, config)
+            .with_eof_behavior(EofBehavior::SetZero)
+            .run()
+            .unwrap();
+        assert_eq!(output, [0]);
+    }
+
+    #[test]
+    fn test_run_cancel() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), Output, UntilZero(vec![]), Output]);
+
+        let flag = CancelFlag::new();
+        flag.cancel();
+        let mut output = vec![];
+        let result = Runner::new(&program, std::io::empty(), &mut output)
+            .with_cancel(flag.clone())
+            .run();
+        assert!(matches!(result, Err(RuntimeError::Cancelled)));
+        assert_eq!(output, [1]);
+
+        let mut output = vec![];
+        let mut runner =
+            StepRunner::new(&program, std::io::empty(), &mut output).with_cancel(flag.clone());
+        assert!(matches!(runner.step(), Ok(StepOutcome::Stepped)));
+        assert!(matches!(runner.step(), Ok(StepOutcome::Stepped)));
+        assert!(matches!(runner.step(), Err(RuntimeError::Cancelled)));
+        assert!(matches!(runner.step(), Err(RuntimeError::Cancelled)));
+        flag.reset();
+        assert!(matches!(runner.step(), Ok(StepOutcome::Stepped)));
+        assert!(runner.is_running());
+
+        let input = NotReadyReader {
+            not_ready: usize::MAX,
+            data: b"",
+        };
+        let flag = CancelFlag::new();
+        flag.cancel();
+        let config = RuntimeConfig::default()
+            .with_would_block_policy(WouldBlockPolicy::Retry(Duration::ZERO));
+        let result = Runner::with_config(&Program::new([Input]), input, std::io::sink(), config)
+            .with_cancel(flag)
+            .run();
+        assert!(matches!(result, Err(RuntimeError::Cancelled)));
+    }
+
+    #[test]
+    fn test_run_output_map() {
+        use Instruction::*;
+        let program = Program::new([
+            DSet(b'A'),
+            Output,
+            DSet(0xe9),
+            Output,
+            DSet(0xff),
+            Output,
+            DSet(0x80),
+            Output,
+        ]);
+
+        let map = OutputMap::from_fn(|value, output| {
+            output.extend_from_slice(char::from(value).encode_utf8(&mut [0; 4]).as_bytes())
+        });
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_output_map(map);
+        run_with_config(&program, &[][..], &mut output, config).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Aéÿ\u{80}");
+
+        let mut table = [0; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = if i < 128 { i as u8 } else { b'?' };
+        }
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_output_map(OutputMap::table(table));
+        run_with_config(&program, &[][..], &mut output, config).unwrap();
+        assert_eq!(output, b"A???");
+
+        let map = OutputMap::from_fn(|value, output| {
+            if value.is_ascii() {
+                output.push(value);
+            }
+        });
+        let mut output = vec![];
+        let mut runner = StepRunner::with_config(
+            &program,
+            &[][..],
+            &mut output,
+            RuntimeConfig::default().with_output_map(map),
+        );
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        assert_eq!(output, b"A");
+    }
+
+    #[test]
+    fn test_run_input_decode() {
+        use Instruction::*;
+        let program = Program::new([Input, Output, Input, Output]);
+        let run_decoded = |input: &[u8], policy: UnmappablePolicy| {
+            let mut output = vec![];
+            let config =
+                RuntimeConfig::default().with_input_decode(InputDecode::utf8_to_latin1(policy));
+            let result = run_with_config(&program, input, &mut output, config);
+            (result, output)
+        };
+
+        let (result, output) = run_decoded("éA".as_bytes(), UnmappablePolicy::Error);
+        assert!(result.is_ok());
+        assert_eq!(output, [0xe9, b'A']);
+
+        let (result, output) = run_decoded("あA".as_bytes(), UnmappablePolicy::Error);
+        assert!(
+            matches!(result, Err(RuntimeError::IoError(e)) if e.kind() == std::io::ErrorKind::InvalidData)
+        );
+        assert_eq!(output, []);
+        let (result, output) = run_decoded("あA".as_bytes(), UnmappablePolicy::Replace(b'?'));
+        assert!(result.is_ok());
+        assert_eq!(output, b"?A");
+        let (result, output) = run_decoded("あA".as_bytes(), UnmappablePolicy::Skip);
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+        assert_eq!(output, b"A");
+
+        // An invalid sequence ends at the byte which cannot continue it.
+        let (result, output) = run_decoded(b"\xe9A", UnmappablePolicy::Replace(b'?'));
+        assert!(result.is_ok());
+        assert_eq!(output, b"?A");
+        // A truncated sequence at EOF
+        let (result, output) = run_decoded(b"\xc3", UnmappablePolicy::Replace(b'?'));
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+        assert_eq!(output, b"?");
+        let (result, output) = run_decoded(b"\xc3", UnmappablePolicy::Skip);
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+        assert_eq!(output, []);
+
+        // An incomplete sequence is kept while the input is not ready.
+        struct ChunkReader(Vec<Option<u8>>);
+        impl Read for ChunkReader {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Ok(0);
+                }
+                match self.0.remove(0) {
+                    Some(byte) => {
+                        buf[0] = byte;
+                        Ok(1)
+                    }
+                    None => Err(std::io::ErrorKind::WouldBlock.into()),
+                }
+            }
+        }
+        let input = ChunkReader(vec![Some(0xc3), None, Some(0xa9)]);
+        let mut output = vec![];
+        let config = RuntimeConfig::default()
+            .with_input_decode(InputDecode::utf8_to_latin1(UnmappablePolicy::Error));
+        let mut runner = StepRunner::with_config(&program, input, &mut output, config);
+        assert!(matches!(runner.step(), Err(RuntimeError::InputNotReady)));
+        assert!(matches!(runner.step(), Ok(StepOutcome::Stepped)));
+        assert!(matches!(runner.step(), Ok(StepOutcome::Stepped)));
+        assert!(matches!(runner.step(), Err(RuntimeError::Eof)));
+        assert_eq!(output, [0xe9]);
+
+        // a custom decoding which skips whitespaces
+        let decode = InputDecode::from_fn(|input| loop {
+            let mut buf = [0];
+            match input.read(&mut buf)? {
+                0 => return Ok(None),
+                _ if buf[0].is_ascii_whitespace() => {}
+                _ => return Ok(Some(buf[0])),
+            }
+        });
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_input_decode(decode);
+        let result = run_with_config(&program, &b" a\n b "[..], &mut output, config);
+        assert!(result.is_ok());
+        assert_eq!(output, b"ab");
+    }
+
+    #[test]
+    fn test_run_to_capped_vec() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), UntilZero(vec![Output, DAdd(1), Output, DAdd(-1)])]);
+        let result = run_to_capped_vec(&program, &[][..], 100);
+        match result {
+            Err(RuntimeError::OutputLimitExceeded { limit, output }) => {
+                assert_eq!(limit, 100);
+                assert_eq!(output, [1, 2].repeat(50));
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        let program = Program::new([DAdd(1), Output, Output]);
+        assert_eq!(run_to_capped_vec(&program, &[][..], 2).unwrap(), [1, 1]);
+        assert!(matches!(
+            run_to_capped_vec(&program, &[][..], 0),
+            Err(RuntimeError::OutputLimitExceeded { output, .. }) if output.is_empty()
+        ));
+        assert!(matches!(
+            run_to_capped_vec(&Program::new([Input]), &[][..], 2),
+            Err(RuntimeError::Eof)
+        ));
+    }
+
+    #[test]
+    fn test_run_assert_cell() {
+        use Instruction::*;
+        // Inject an assertion after each loop that the loop cleared the cell.
+        fn inject(instructions: &[Instruction]) -> Vec<Instruction> {
+            instructions
+                .iter()
+                .flat_map(|inst| match inst {
+                    UntilZero(body) => vec![UntilZero(inject(body)), AssertCell { expected: 0 }],
+                    inst => vec![inst.clone()],
+                })
+                .collect()
+        }
+        let program = Program::new(inject(&[
+            DAdd(3),
+            AssertCell { expected: 3 },
+            UntilZero(vec![PAdd(1), DAdd(2), PAdd(-1), DAdd(-1)]),
+            PAdd(1),
+            AssertCell { expected: 6 },
+            Output,
+        ]));
+        assert_eq!(program.instruction_counts().assertions, 3);
+        assert_eq!(run_collect(&program, &[][..]).unwrap(), [6]);
+
+        let program = Program::new([DAdd(1), PAdd(2), AssertCell { expected: 2 }, Output]);
+        let mut output = vec![];
+        match run(&program, &[][..], &mut output) {
+            Err(e @ RuntimeError::AssertionFailed { .. }) => {
+                assert_eq!(
+                    e.to_string(),
+                    "assertion failed at [2]: expected 2, but got 0"
+                );
+                assert_eq!(e.kind().code(), "E0106");
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(output, []);
+    }
+
+    #[test]
+    fn test_run_to_display_width() {
+        use Instruction::*;
+        // Hello World!\n
+        let source = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let program = bf_parser().parse_str(source).unwrap();
+        let input: &[u8] = &[];
+        assert_eq!(run_to_display_width(&program, input).unwrap(), 12);
+
+        // echoes "あa" (3 bytes + 1 byte)
+        let program = Program::new([Input, Output, Input, Output, Input, Output, Input, Output]);
+        assert_eq!(run_to_display_width(&program, "あa".as_bytes()).unwrap(), 3);
+    }
 }
@@ -0,0 +1,78 @@
+//! Control-flow observers.
+use crate::program::ProgramIndex;
+
+/// An observer of control-flow events of a [`StepRunner`](super::StepRunner).
+///
+/// All methods do nothing by default, so an observer implements only the events it needs.
+/// `index` is the index of the loop ([`Instruction::UntilZero`](crate::program::Instruction::UntilZero)).
+///
+/// For a loop whose body runs twice, the events are `loop_entered`, `loop_iterated`,
+/// `loop_iterated` and `loop_exited`.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     program::{Instruction::*, Program, ProgramIndex},
+///     runtime::{ExecObserver, StepRunner},
+/// };
+///
+/// #[derive(Default)]
+/// struct Iterations(u64);
+///
+/// impl ExecObserver for Iterations {
+///     fn loop_iterated(&mut self, _index: &ProgramIndex) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let program = Program::new([DAdd(3), UntilZero(vec![DAdd(-1)])]);
+/// let mut runner = StepRunner::new(&program, &[][..], vec![]).with_observer(Iterations::default());
+/// while runner.is_running() {
+///     runner.step().unwrap();
+/// }
+/// assert_eq!(runner.observer().0, 3);
+/// ```
+pub trait ExecObserver {
+    /// Called when a loop is reached, i.e. its condition is checked for the first time.
+    ///
+    /// `cell` is the cell value which the condition checks.
+    fn loop_entered(&mut self, _index: &ProgramIndex, _cell: u8) {}
+
+    /// Called when an iteration of a loop is completed, before its condition is checked again.
+    fn loop_iterated(&mut self, _index: &ProgramIndex) {}
+
+    /// Called when a loop is exited because its condition is zero.
+    fn loop_exited(&mut self, _index: &ProgramIndex) {}
+
+    /// Called when the program is finished by executing its last instruction.
+    fn finished(&mut self) {}
+}
+
+impl<O> ExecObserver for &mut O
+where
+    O: ExecObserver + ?Sized,
+{
+    fn loop_entered(&mut self, index: &ProgramIndex, cell: u8) {
+        (**self).loop_entered(index, cell)
+    }
+
+    fn loop_iterated(&mut self, index: &ProgramIndex) {
+        (**self).loop_iterated(index)
+    }
+
+    fn loop_exited(&mut self, index: &ProgramIndex) {
+        (**self).loop_exited(index)
+    }
+
+    fn finished(&mut self) {
+        (**self).finished()
+    }
+}
+
+/// An observer which ignores all events, used by a [`StepRunner`](super::StepRunner)
+/// without an observer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObserver;
+
+impl ExecObserver for NoObserver {}
@@ -0,0 +1,83 @@
+//! Output byte translation.
+use std::{fmt, sync::Arc};
+
+/// A translation of output bytes, applied to each output cell value before it is written.
+///
+/// A cell value may be translated into a sequence of bytes, e.g. to encode a code page or
+/// a custom glyph table as UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     program::{Instruction::*, Program},
+///     runtime::{self, OutputMap, RuntimeConfig},
+/// };
+///
+/// // Latin-1 to UTF-8
+/// let map = OutputMap::from_fn(|value, output| {
+///     output.extend_from_slice(char::from(value).encode_utf8(&mut [0; 4]).as_bytes())
+/// });
+/// let program = Program::new([DSet(0xe9), Output]);
+/// let mut output = vec![];
+/// let config = RuntimeConfig::default().with_output_map(map);
+/// runtime::run_with_config(&program, &[][..], &mut output, config).unwrap();
+///
+/// assert_eq!(String::from_utf8(output).unwrap(), "é");
+/// ```
+#[derive(Clone, Default)]
+pub struct OutputMap(Kind);
+
+// A translation function, which appends the translated bytes of a value to a buffer.
+type TranslateFn = dyn Fn(u8, &mut Vec<u8>) + Send + Sync;
+
+#[derive(Clone, Default)]
+enum Kind {
+    #[default]
+    Identity,
+    Table(Box<[u8; 256]>),
+    Fn(Arc<TranslateFn>),
+}
+
+impl OutputMap {
+    /// Create a map which writes each value as is.
+    pub fn identity() -> Self {
+        Self(Kind::Identity)
+    }
+
+    /// Create a map which writes `table[value]` for each value.
+    pub fn table(table: [u8; 256]) -> Self {
+        Self(Kind::Table(Box::new(table)))
+    }
+
+    /// Create a map which calls `f` with each value and a buffer to append the translated bytes to.
+    ///
+    /// The buffer is empty when `f` is called. If `f` appends nothing, the value is not written.
+    pub fn from_fn(f: impl Fn(u8, &mut Vec<u8>) + Send + Sync + 'static) -> Self {
+        Self(Kind::Fn(Arc::new(f)))
+    }
+
+    /// Returns `true` if the map writes each value as is.
+    pub fn is_identity(&self) -> bool {
+        matches!(self.0, Kind::Identity)
+    }
+
+    // Translate `value` and append the result to `output`.
+    pub(super) fn translate(&self, value: u8, output: &mut Vec<u8>) {
+        match &self.0 {
+            Kind::Identity => output.push(value),
+            Kind::Table(table) => output.push(table[value as usize]),
+            Kind::Fn(f) => f(value, output),
+        }
+    }
+}
+
+impl fmt::Debug for OutputMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Kind::Identity => write!(f, "OutputMap::Identity"),
+            Kind::Table(_) => write!(f, "OutputMap::Table(..)"),
+            Kind::Fn(_) => write!(f, "OutputMap::Fn(..)"),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Pipelines of programs.
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    io,
+    rc::Rc,
+};
+
+use super::*;
+use crate::error::PipelineError;
+
+/// The capacity of the buffer between two stages of a [`pipeline`].
+///
+/// A stage whose next instruction is an output waits while the buffer holds this many bytes.
+pub const PIPE_CAPACITY: usize = 4096;
+
+// The maximum number of steps which a stage runs before the next stage runs.
+const QUANTUM: u64 = 1024;
+
+/// Statistics of a stage of a [`pipeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecutionStats {
+    /// The number of executed steps (see [`StepRunner::step`]).
+    pub steps: u64,
+    /// The number of bytes read from the input.
+    pub input_bytes: u64,
+    /// The number of bytes written to the output.
+    pub output_bytes: u64,
+    /// `true` if the stage ended by reading past the end of its input.
+    pub reached_eof: bool,
+}
+
+/// Run programs connecting the output of each program to the input of the next one,
+/// like a shell pipeline.
+///
+/// The first program reads `input` and the last program writes `output`. The stages run in turn
+/// on the current thread, passing data through in-memory buffers of [`PIPE_CAPACITY`] bytes:
+///
+///  - A stage waits while its input buffer is empty, or while its output buffer is full.
+///  - When a stage ends, the next stage reads an end-of-file after the buffered data.
+///    A stage which reads past the end of its input ends there, as a filter does, instead of
+///    failing with [`RuntimeError::Eof`].
+///  - When a stage ends, the previous stage ends at its next output, since nothing reads it.
+///
+/// Every stage runs with `config`, except that the stages other than the first one use
+/// [`WouldBlockPolicy::Report`] to wait for their input.
+/// If the first stage uses [`WouldBlockPolicy::Report`] and `input` is not ready, the stages keep
+/// polling it.
+///
+/// Returns the statistics of each stage. If `programs` is empty, nothing is read or written.
+///
+/// # Errors
+///
+/// Returns [`PipelineError`] with the index of the first stage which fails. The stages are
+/// not run any further. An error in flushing `output` is attributed to the last stage.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     bf,
+///     runtime::{self, RuntimeConfig},
+/// };
+///
+/// // outputs "ABC", and then increments each input byte.
+/// let generator = bf!(++++++++[>++++++++<-]>+.+.+.);
+/// let increment = bf!(,[+.,]);
+///
+/// let mut output = vec![];
+/// let stats = runtime::pipeline(
+///     &[&generator, &increment],
+///     &[][..],
+///     &mut output,
+///     RuntimeConfig::default(),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(output, b"BCD");
+/// assert!(stats[1].reached_eof);
+/// ```
+pub fn pipeline<R, W>(
+    programs: &[&Program],
+    mut input: R,
+    mut output: W,
+    config: RuntimeConfig,
+) -> Result<Vec<ExecutionStats>, PipelineError>
+where
+    R: Read,
+    W: Write,
+{
+    let mut stages = vec![];
+    {
+        let mut stage_input: Option<Box<dyn Read + '_>> = Some(Box::new(&mut input));
+        let mut last_output = Some(&mut output);
+        for (i, program) in programs.iter().enumerate() {
+            let input_bytes = Rc::new(Cell::new(0));
+            let output_bytes = Rc::new(Cell::new(0));
+            let reader = Counted {
+                inner: stage_input.take().expect("input of each stage is set"),
+                count: input_bytes.clone(),
+            };
+            let (writer, pipe): (Box<dyn Write + '_>, _) = if i + 1 < programs.len() {
+                let pipe = Rc::new(RefCell::new(Pipe::default()));
+                stage_input = Some(Box::new(PipeReader(pipe.clone())));
+                (Box::new(PipeWriter(pipe.clone())), Some(pipe))
+            } else {
+                let output = last_output
+                    .take()
+                    .expect("only the last stage writes the output");
+                (Box::new(output), None)
+            };
+            let writer = Counted {
+                inner: writer,
+                count: output_bytes.clone(),
+            };
+            let config = match i {
+                0 => config.clone(),
+                _ => config
+                    .clone()
+                    .with_would_block_policy(WouldBlockPolicy::Report),
+            };
+            stages.push(Stage {
+                runner: StepRunner::with_config(
+                    program,
+                    Box::new(reader) as Box<dyn Read + '_>,
+                    Box::new(writer) as Box<dyn Write + '_>,
+                    config,
+                ),
+                input_bytes,
+                output_bytes,
+                pipe,
+                stats: ExecutionStats::default(),
+                done: false,
+            });
+        }
+
+        while stages.iter().any(|stage| !stage.done) {
+            for i in 0..stages.len() {
+                let consumer_done = stages.get(i + 1).is_some_and(|stage| stage.done);
+                stages[i]
+                    .run(consumer_done)
+                    .map_err(|error| PipelineError { stage: i, error })?;
+            }
+        }
+    }
+
+    let stats = stages
+        .into_iter()
+        .map(|stage| ExecutionStats {
+            input_bytes: stage.input_bytes.get(),
+            output_bytes: stage.output_bytes.get(),
+            ..stage.stats
+        })
+        .collect::<Vec<_>>();
+    output.flush().map_err(|e| PipelineError {
+        stage: stats.len().saturating_sub(1),
+        error: e.into(),
+    })?;
+    Ok(stats)
+}
+
+struct Stage<'a> {
+    runner: StepRunner<'a, Box<dyn Read + 'a>, Box<dyn Write + 'a>>,
+    input_bytes: Rc<Cell<u64>>,
+    output_bytes: Rc<Cell<u64>>,
+    // the buffer to the next stage, or `None` for the last stage
+    pipe: Option<Rc<RefCell<Pipe>>>,
+    stats: ExecutionStats,
+    done: bool,
+}
+
+impl Stage<'_> {
+    // Run the stage until it waits, ends, or runs `QUANTUM` steps.
+    fn run(&mut self, consumer_done: bool) -> Result<(), RuntimeError> {
+        for _ in 0..QUANTUM {
+            if self.done {
+                return Ok(());
+            }
+            if !self.runner.is_running() {
+                self.finish(false);
+                return Ok(());
+            }
+            if let (Some(Instruction::Output), Some(pipe)) =
+                (self.runner.get_current_instruction(), &self.pipe)
+            {
+                if consumer_done {
+                    self.finish(false);
+                    return Ok(());
+                }
+                if pipe.borrow().buffer.len() >= PIPE_CAPACITY {
+                    return Ok(());
+                }
+            }
+            match self.runner.step() {
+                Ok(_) => self.stats.steps += 1,
+                Err(RuntimeError::InputNotReady) => return Ok(()),
+                Err(RuntimeError::Eof) => self.finish(true),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, reached_eof: bool) {
+        self.done = true;
+        self.stats.reached_eof = reached_eof;
+        if let Some(pipe) = &self.pipe {
+            pipe.borrow_mut().closed = true;
+        }
+    }
+}
+
+// A buffer between two stages.
+#[derive(Default)]
+struct Pipe {
+    buffer: VecDeque<u8>,
+    // `true` if the writing stage has ended
+    closed: bool,
+}
+
+struct PipeReader(Rc<RefCell<Pipe>>);
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut pipe = self.0.borrow_mut();
+        if pipe.buffer.is_empty() && !pipe.closed {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        pipe.buffer.read(buf)
+    }
+}
+
+struct PipeWriter(Rc<RefCell<Pipe>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().buffer.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// A reader or writer which counts the bytes passed through.
+struct Counted<T> {
+    inner: T,
+    count: Rc<Cell<u64>>,
+}
+
+impl<T: Read> Read for Counted<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for Counted<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use Instruction::*;
+
+    // Outputs 'A' 10000 times.
+    fn generator() -> Program {
+        Program::new([
+            DAdd(65),
+            PAdd(1),
+            DAdd(100),
+            UntilZero(vec![
+                PAdd(1),
+                DAdd(100),
+                UntilZero(vec![PAdd(-2), Output, PAdd(2), DAdd(-1)]),
+                PAdd(-1),
+                DAdd(-1),
+            ]),
+        ])
+    }
+
+    // Shifts each input byte by 13, like rot13 without wrapping around the alphabet.
+    fn shift13() -> Program {
+        crate::bf!(,[+++++++++++++.,])
+    }
+
+    fn run_manually(programs: &[&Program], input: &[u8]) -> Vec<u8> {
+        let mut data = input.to_vec();
+        for program in programs {
+            let mut output = vec![];
+            match run(program, &data[..], &mut output) {
+                Ok(()) | Err(RuntimeError::Eof) => {}
+                Err(e) => panic!("unexpected error: {e}"),
+            }
+            data = output;
+        }
+        data
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let (generator, shift13) = (generator(), shift13());
+        let programs = [&generator, &shift13, &shift13];
+        let mut output = vec![];
+        let stats = pipeline(&programs, &[][..], &mut output, RuntimeConfig::default()).unwrap();
+        assert_eq!(output, run_manually(&programs, &[]));
+        assert_eq!(output, [b'A' + 26; 10000]);
+
+        assert_eq!(stats.len(), 3);
+        assert!(!stats[0].reached_eof);
+        assert!(stats[1].reached_eof);
+        assert!(stats[2].reached_eof);
+        assert_eq!(stats[0].input_bytes, 0);
+        assert!(stats.iter().all(|stats| stats.output_bytes == 10000));
+        assert_eq!(stats[1].input_bytes, 10000);
+        assert_eq!(stats[2].input_bytes, 10000);
+
+        // the input of the first stage
+        let mut output = vec![];
+        pipeline(
+            &[&shift13],
+            &b"AB"[..],
+            &mut output,
+            RuntimeConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(output, b"NO");
+
+        assert!(
+            pipeline(&[], &b"AB"[..], &mut output, RuntimeConfig::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_pipeline_error() {
+        let generator = generator();
+        let failing = Program::new([Input, PAdd(-1), DAdd(1)]);
+        let mut output = vec![];
+        let result = pipeline(
+            &[&generator, &failing],
+            &[][..],
+            &mut output,
+            RuntimeConfig::default(),
+        );
+        match result {
+            Err(PipelineError {
+                stage: 1,
+                error: RuntimeError::OutOfMemoryBounds { address: -1, .. },
+            }) => {}
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_pipeline_consumer_ends_first() {
+        // outputs 1 forever
+        let infinite = crate::bf!(+[.]);
+        let head = crate::bf!(,.);
+        let mut output = vec![];
+        let stats = pipeline(
+            &[&infinite, &head],
+            &[][..],
+            &mut output,
+            RuntimeConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(output, [1]);
+        assert!(!stats[0].reached_eof);
+        assert!(!stats[1].reached_eof);
+    }
+}
@@ -0,0 +1,46 @@
+//! Execution profiles.
+use std::collections::HashMap;
+
+use crate::program::ProgramIndex;
+
+/// Execution counts of the instructions of a program.
+///
+/// It is recorded by [`Runner::run_profiled`](super::Runner::run_profiled).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    counts: HashMap<ProgramIndex, u64>,
+    loop_entries: HashMap<ProgramIndex, u64>,
+}
+
+impl Profile {
+    /// Create an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of times the instruction pointed by `index` was executed.
+    ///
+    /// For [`Instruction::UntilZero`](crate::program::Instruction::UntilZero),
+    /// this is the number of times its condition was checked.
+    pub fn count(&self, index: &ProgramIndex) -> u64 {
+        self.counts.get(index).copied().unwrap_or(0)
+    }
+
+    /// Get the number of times the loop pointed by `index` entered its body.
+    pub fn loop_entries(&self, index: &ProgramIndex) -> u64 {
+        self.loop_entries.get(index).copied().unwrap_or(0)
+    }
+
+    /// Get an iterator over the executed instructions and their counts, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&ProgramIndex, u64)> {
+        self.counts.iter().map(|(index, count)| (index, *count))
+    }
+
+    pub(super) fn count_mut(&mut self, index: &ProgramIndex) -> &mut u64 {
+        self.counts.entry(index.clone()).or_insert(0)
+    }
+
+    pub(super) fn loop_entries_mut(&mut self, index: &ProgramIndex) -> &mut u64 {
+        self.loop_entries.entry(index.clone()).or_insert(0)
+    }
+}
@@ -1,6 +1,46 @@
-///! Basic program runner.
+//! Basic program runner.
 use super::internal::NextAction;
+use super::trace::{TraceHook, TraceRecord};
 use super::*;
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, Seek, SeekFrom},
+    sync::Arc,
+};
+
+// A writer which passes each byte to `f` with the underlying output.
+struct MappedWriter<W, F> {
+    output: W,
+    f: F,
+}
+
+impl<W, F> Write for MappedWriter<W, F>
+where
+    W: Write,
+    F: FnMut(u8, &mut dyn Write) -> io::Result<()>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            (self.f)(byte, &mut self.output)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+// An execution of an instruction, passed to the hook of `Runner::walk`.
+//
+// Each one has the path of the instruction from the root (see `ProgramIndex::from_path`).
+enum Step<'a> {
+    // The instruction is about to be executed.
+    Before(&'a [usize], &'a Instruction),
+    // The instruction has been executed. If it is a loop which enters its body, this has the
+    // number of the iterations of the loop done before, in this execution of the loop.
+    After(&'a [usize], Option<u64>),
+}
 
 /// A basic program runner.
 ///
@@ -22,15 +62,227 @@ where
 
     /// Create a new runner with the given input, output and memory size.
     pub fn with_memsize(program: &'a Program, input: R, output: W, memsize: MemorySize) -> Self {
-        let runtime = internal::Runtime::new(input, output, memsize);
+        Self::with_config(
+            program,
+            input,
+            output,
+            RuntimeConfig::default().with_memsize(memsize),
+        )
+    }
+
+    /// Create a new runner with the given input, output and runtime configuration.
+    pub fn with_config(program: &'a Program, input: R, output: W, config: RuntimeConfig) -> Self {
+        let runtime = internal::Runtime::new(input, output, config);
         Self { program, runtime }
     }
 
+    /// Set the flag to cancel the run (see [`CancelFlag`]).
+    pub fn with_cancel(mut self, cancel: CancelFlag) -> Self {
+        self.runtime.set_cancel(cancel);
+        self
+    }
+
+    /// Set the behavior of the input instruction at the end of the input (see [`EofBehavior`]).
+    pub fn with_eof_behavior(mut self, eof: EofBehavior) -> Self {
+        self.runtime.set_eof_behavior(eof);
+        self
+    }
+
+    /// Grow the memory in advance to cover addresses from 0 to `up_to` (inclusive).
+    ///
+    /// The memory is grown on demand while running, which may reallocate it. Reserving the memory
+    /// before running avoids such reallocations, e.g. to keep them out of benchmark timings.
+    /// A negative `up_to` grows the memory to the left, which is possible only with
+    /// [`MemorySize::BothInfinite`].
+    ///
+    /// If `up_to` is out of the memory bounds, this function returns
+    /// [`RuntimeError::OutOfMemoryBounds`] and the memory is not changed.
+    pub fn reserve_memory(&mut self, up_to: isize) -> Result<(), RuntimeError> {
+        self.runtime.reserve_memory(up_to)
+    }
+
     /// Run the program.
     pub fn run(mut self) -> Result<(), RuntimeError> {
         self.run_internal(self.program.instructions())
     }
 
+    /// Run the program, passing each output byte with the output to `f`, which writes zero or more
+    /// bytes in place of it.
+    ///
+    /// The bytes are the ones translated by [`OutputMap`] if it is configured.
+    /// If `f` returns an error, the run stops with [`RuntimeError::IoError`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{program::{Instruction::*, Program}, runtime::Runner};
+    ///
+    /// let program = Program::new([DSet(b'h'), Output, DAdd(1), Output]);
+    /// let mut output = vec![];
+    /// Runner::new(&program, &[][..], &mut output)
+    ///     .run_with_output_map(|byte, output| write!(output, "{byte:02x} "))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(output, b"68 69 ");
+    /// ```
+    pub fn run_with_output_map(
+        self,
+        f: impl FnMut(u8, &mut dyn Write) -> io::Result<()>,
+    ) -> Result<(), RuntimeError> {
+        let mut runner = Runner {
+            program: self.program,
+            runtime: self.runtime.map_output(|output| MappedWriter { output, f }),
+        };
+        runner.run_internal(runner.program.instructions())
+    }
+
+    /// Run the program, calling `hook` each time a loop enters its body.
+    ///
+    /// `hook` receives the index of the loop instruction ([`Instruction::UntilZero`]) and
+    /// the cumulative number of times the loop has entered its body so far, including this one.
+    /// The count is not reset when the loop is executed again from the beginning.
+    pub fn run_with_loop_hook(
+        mut self,
+        mut hook: impl FnMut(&ProgramIndex, u64),
+    ) -> Result<(), RuntimeError> {
+        let mut counts = HashMap::new();
+        self.walk_program(|_, step| {
+            if let Step::After(path, Some(_)) = step {
+                let index = ProgramIndex::from_path(path);
+                let count = counts.entry(index.clone()).or_insert(0);
+                *count += 1;
+                hook(&index, *count);
+            }
+            Ok(true)
+        })
+        .map(drop)
+    }
+
+    /// Run the program, charging the cost of each instruction by `model` until `budget` is exhausted.
+    ///
+    /// Before an instruction is executed, its cost is added to the total. If the total would exceed
+    /// `budget`, the run stops without executing the instruction and
+    /// [`MeteredOutcome::BudgetExhausted`] is returned.
+    pub fn run_metered(
+        mut self,
+        model: impl CostModel,
+        budget: u64,
+    ) -> Result<MeteredOutcome, RuntimeError> {
+        let mut spent = 0u64;
+        let finished = self.walk_program(|runtime, step| {
+            let Step::Before(_, inst) = step else {
+                return Ok(true);
+            };
+            let cost = model.cost(inst, MachineView::new(runtime.machine()));
+            match spent.checked_add(cost) {
+                Some(total) if total <= budget => {
+                    spent = total;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })?;
+        if finished {
+            Ok(MeteredOutcome::Finished { spent })
+        } else {
+            Ok(MeteredOutcome::BudgetExhausted { spent })
+        }
+    }
+
+    /// Run the program, recording the execution count of each instruction into `profile`.
+    ///
+    /// The counts are recorded even if the program fails. An instruction which fails is counted.
+    pub fn run_profiled(mut self, profile: &mut Profile) -> Result<(), RuntimeError> {
+        self.walk_program(|_, step| {
+            match step {
+                Step::Before(path, _) => *profile.count_mut(&ProgramIndex::from_path(path)) += 1,
+                Step::After(path, Some(_)) => {
+                    *profile.loop_entries_mut(&ProgramIndex::from_path(path)) += 1
+                }
+                Step::After(_, None) => {}
+            }
+            Ok(true)
+        })
+        .map(drop)
+    }
+
+    /// Run the program, passing a record of each instruction to `hook` before executing it.
+    ///
+    /// Each check of a loop condition is also an instruction. So the number of records of an
+    /// instruction equals its count in the profile recorded by [`Runner::run_profiled`].
+    /// If `hook` returns an error, the run stops with [`RuntimeError::IoError`].
+    pub fn run_traced(mut self, hook: &mut impl TraceHook) -> Result<(), RuntimeError> {
+        let mut steps = 0;
+        self.walk_program(|runtime, step| {
+            let Step::Before(path, inst) = step else {
+                return Ok(true);
+            };
+            let machine = runtime.machine();
+            let record = TraceRecord {
+                step: steps,
+                index: &ProgramIndex::from_path(path),
+                instruction: inst,
+                pointer: machine.pointer(),
+                cell: machine.data_at(machine.pointer()),
+            };
+            hook.record(&record)
+                .map_err(|e| RuntimeError::IoError(Arc::new(e)))?;
+            steps += 1;
+            Ok(true)
+        })
+        .map(drop)
+    }
+
+    /// Run the program, collecting the addresses of the cells written by it.
+    ///
+    /// A cell is written by a data increment/decrement, an input, and an instruction which sets
+    /// the cell (e.g. [`Instruction::SetZero`]), even if its value is not changed.
+    /// The addresses are collected even if the program fails. An instruction which fails writes
+    /// nothing, except that [`Instruction::Fill`] writes the cells before the failing one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{program::{Instruction::*, Program}, runtime::Runner};
+    ///
+    /// let program = Program::new([DAdd(1), PAdd(2), Input, PAdd(-1), Output]);
+    /// let (writes, result) = Runner::new(&program, &b"A"[..], vec![]).run_with_write_set();
+    ///
+    /// assert!(result.is_ok());
+    /// assert_eq!(writes.into_iter().collect::<Vec<_>>(), [0, 2]);
+    /// ```
+    pub fn run_with_write_set(mut self) -> (BTreeSet<isize>, Result<(), RuntimeError>) {
+        let mut writes = BTreeSet::new();
+        // the cells to be written by the instruction being executed
+        let mut pending = 0..0;
+        let result = self.walk_program(|runtime, step| {
+            match step {
+                Step::Before(_, inst) => {
+                    let pointer = runtime.get_pointer();
+                    pending = match inst {
+                        Instruction::DAdd(_)
+                        | Instruction::Input
+                        | Instruction::SetZero
+                        | Instruction::DSet(_) => pointer..pointer.saturating_add(1),
+                        // a range beyond the address space is reported by `exec_one`.
+                        Instruction::Fill { len, .. } => isize::try_from(*len)
+                            .ok()
+                            .and_then(|len| pointer.checked_add(len))
+                            .map_or(pointer..pointer, |end| pointer..end),
+                        _ => pointer..pointer,
+                    };
+                }
+                Step::After(..) => writes.extend(pending.clone()),
+            }
+            Ok(true)
+        });
+        // a failing fill has written the cells before the failing one.
+        if let Err(RuntimeError::OutOfMemoryBounds { address, .. }) = &result {
+            writes.extend(pending.start..*address);
+        }
+        (writes, result.map(drop))
+    }
+
     fn run_internal(&mut self, instructions: &[Instruction]) -> Result<(), RuntimeError> {
         for inst in instructions {
             while let NextAction::StepIn(sub) = self.runtime.exec_one(inst)? {
@@ -40,4 +292,386 @@ where
 
         Ok(())
     }
+
+    // Run the program by `walk`.
+    fn walk_program(
+        &mut self,
+        mut hook: impl FnMut(&mut internal::Runtime<R, W>, Step) -> Result<bool, RuntimeError>,
+    ) -> Result<bool, RuntimeError> {
+        let program = self.program;
+        self.walk(program.instructions(), &mut vec![], &mut hook)
+    }
+
+    // Run `instructions`, passing each execution of an instruction to `hook` before and after it.
+    //
+    // Each check of a loop condition is also an execution. If `hook` returns `false`, the run
+    // stops and this function returns `false`.
+    fn walk(
+        &mut self,
+        instructions: &[Instruction],
+        path: &mut Vec<usize>,
+        hook: &mut impl FnMut(&mut internal::Runtime<R, W>, Step) -> Result<bool, RuntimeError>,
+    ) -> Result<bool, RuntimeError> {
+        for (i, inst) in instructions.iter().enumerate() {
+            path.push(i);
+            let mut iteration = 0;
+            loop {
+                if !hook(&mut self.runtime, Step::Before(path, inst))? {
+                    return Ok(false);
+                }
+                let next = self.runtime.exec_one(inst)?;
+                let entered = matches!(next, NextAction::StepIn(_)).then_some(iteration);
+                if !hook(&mut self.runtime, Step::After(path, entered))? {
+                    return Ok(false);
+                }
+                match next {
+                    NextAction::StepIn(sub) => {
+                        if !self.walk(sub, path, hook)? {
+                            return Ok(false);
+                        }
+                        iteration += 1;
+                    }
+                    NextAction::Next => break,
+                }
+            }
+            path.pop();
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R, W> Runner<'_, R, W>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    /// Run the program, rewinding the input each time a loop enters its body again.
+    ///
+    /// The input position is saved when a loop is reached, and restored at the beginning of
+    /// each iteration except the first one. So every iteration of a loop reads the same bytes.
+    /// After the loop, the input continues from the position where the last iteration left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use libbf::{program::{Instruction::*, Program}, runtime::Runner};
+    ///
+    /// // +++[>,.<-]
+    /// let program = Program::new([
+    ///     DAdd(3),
+    ///     UntilZero(vec![PAdd(1), Input, Output, PAdd(-1), DAdd(-1)]),
+    /// ]);
+    /// let mut output = vec![];
+    /// Runner::new(&program, Cursor::new(b"ab"), &mut output)
+    ///     .run_rewinding_input_on_loop()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(output, b"aaa");
+    /// ```
+    pub fn run_rewinding_input_on_loop(mut self) -> Result<(), RuntimeError> {
+        // the input positions of the loops being executed, outermost first
+        let mut starts = vec![];
+        self.walk_program(|runtime, step| {
+            if let Step::After(path, Some(iteration)) = step {
+                let depth = path.len() - 1;
+                if iteration == 0 {
+                    starts.truncate(depth);
+                    starts.push(runtime.input_mut().stream_position()?);
+                } else {
+                    runtime.input_mut().seek(SeekFrom::Start(starts[depth]))?;
+                }
+            }
+            Ok(true)
+        })
+        .map(drop)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_parser;
+    use Instruction::*;
+
+    #[test]
+    fn test_run_with_loop_hook() {
+        // +++[-]
+        let program = Program::new([DAdd(3), UntilZero(vec![DAdd(-1)])]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let mut hooked = vec![];
+        let runner = Runner::new(&program, input, &mut output);
+        if let Err(e) =
+            runner.run_with_loop_hook(|index, count| hooked.push((index.clone(), count)))
+        {
+            panic!("unexpected error: {e}");
+        }
+        let loop_index = ProgramIndex::new_for_test([1]);
+        assert_eq!(
+            hooked,
+            [
+                (loop_index.clone(), 1),
+                (loop_index.clone(), 2),
+                (loop_index, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_with_loop_hook_cumulative() {
+        // ++[>+++[-]<-]: the inner loop enters its body 3 times per outer iteration.
+        let program = Program::new([
+            DAdd(2),
+            UntilZero(vec![
+                PAdd(1),
+                DAdd(3),
+                UntilZero(vec![DAdd(-1)]),
+                PAdd(-1),
+                DAdd(-1),
+            ]),
+        ]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let mut counts = HashMap::new();
+        let runner = Runner::new(&program, input, &mut output);
+        if let Err(e) = runner.run_with_loop_hook(|index, count| {
+            counts.insert(index.clone(), count);
+        }) {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(counts[&ProgramIndex::new_for_test([1])], 2);
+        assert_eq!(counts[&ProgramIndex::new_for_test([1, 2])], 6);
+    }
+
+    #[test]
+    fn test_run_metered() {
+        // a fill over 100 cells costs 100, while a single `+` costs 1.
+        let program = Program::new([Fill { len: 100, value: 1 }]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(WorkCost, 10).unwrap(),
+            MeteredOutcome::BudgetExhausted { spent: 0 }
+        );
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(WorkCost, 100).unwrap(),
+            MeteredOutcome::Finished { spent: 100 }
+        );
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(UnitCost, 10).unwrap(),
+            MeteredOutcome::Finished { spent: 1 }
+        );
+
+        let program = Program::new([DAdd(1)]);
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(WorkCost, 10).unwrap(),
+            MeteredOutcome::Finished { spent: 1 }
+        );
+    }
+
+    #[test]
+    fn test_run_metered_loop() {
+        // +++[-]: the loop condition is checked 4 times.
+        let program = Program::new([DAdd(3), UntilZero(vec![DAdd(-1)])]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(UnitCost, 8).unwrap(),
+            MeteredOutcome::Finished { spent: 8 }
+        );
+
+        // a closure model which charges data changes by the current value: 0 + 3 + 2 + 1.
+        let model = |inst: &Instruction, machine: MachineView<'_>| match inst {
+            DAdd(_) => u64::from(machine.data_at(machine.pointer()).unwrap()),
+            _ => 0,
+        };
+        let runner = Runner::new(&program, input, &mut output);
+        assert_eq!(
+            runner.run_metered(model, 5).unwrap(),
+            MeteredOutcome::BudgetExhausted { spent: 5 }
+        );
+    }
+
+    #[test]
+    fn test_run_profiled() {
+        // +++[-]>[+]
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![DAdd(-1)]),
+            PAdd(1),
+            UntilZero(vec![DAdd(1)]),
+        ]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let mut profile = Profile::new();
+        let runner = Runner::new(&program, input, &mut output);
+        runner.run_profiled(&mut profile).unwrap();
+
+        let index = |path: &[usize]| ProgramIndex::new_for_test(path);
+        assert_eq!(profile.count(&index(&[0])), 1);
+        assert_eq!(profile.count(&index(&[1])), 4);
+        assert_eq!(profile.loop_entries(&index(&[1])), 3);
+        assert_eq!(profile.count(&index(&[1, 0])), 3);
+        assert_eq!(profile.count(&index(&[3])), 1);
+        assert_eq!(profile.loop_entries(&index(&[3])), 0);
+        assert_eq!(profile.count(&index(&[3, 0])), 0);
+    }
+
+    #[test]
+    fn test_run_with_write_set() {
+        let program = Program::new([
+            DAdd(1),
+            PAdd(2),
+            UntilZero(vec![DAdd(-1)]),
+            SetZero,
+            PAdd(3),
+            Input,
+            Output,
+            PAdd(-1),
+            UntilZero(vec![]),
+        ]);
+        let (writes, result) = Runner::new(&program, &b"A"[..], vec![]).run_with_write_set();
+        result.unwrap();
+        assert_eq!(writes, BTreeSet::from([0, 2, 5]));
+
+        // the cells written before the failure are collected.
+        let program = Program::new([PAdd(1), DAdd(1), PAdd(6), Fill { len: 5, value: 1 }]);
+        let (writes, result) =
+            Runner::with_memsize(&program, &[][..], vec![], MemorySize::Fixed(10))
+                .run_with_write_set();
+        assert!(matches!(
+            result,
+            Err(RuntimeError::OutOfMemoryBounds { address: 10, .. })
+        ));
+        assert_eq!(writes, BTreeSet::from([1, 7, 8, 9]));
+
+        // a fill beyond the address space is an error, not an overflow.
+        for len in [usize::MAX, isize::MAX as usize] {
+            let program = Program::new([PAdd(1), Fill { len, value: 1 }]);
+            let (writes, result) =
+                Runner::with_memsize(&program, &[][..], vec![], MemorySize::RightInfinite)
+                    .run_with_write_set();
+            assert!(matches!(
+                result,
+                Err(RuntimeError::OutOfMemoryBounds { address: 1, .. })
+            ));
+            assert!(writes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_reserve_memory() {
+        let program = Program::new([PAdd(99_999), DAdd(1), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let config = RuntimeConfig::default().with_memsize(MemorySize::RightInfinite);
+        let mut runner = Runner::with_config(&program, input, &mut output, config);
+        runner.reserve_memory(99_999).unwrap();
+        let capacities = runner.runtime.memory().capacities();
+        assert!(capacities.0 >= 100_000);
+        runner.run_internal(program.instructions()).unwrap();
+        assert_eq!(runner.runtime.memory().capacities(), capacities);
+        assert_eq!(output, [1]);
+
+        let config = RuntimeConfig::default().with_memsize(MemorySize::BothInfinite);
+        let mut runner = Runner::with_config(&program, input, vec![], config);
+        runner.reserve_memory(-10).unwrap();
+        assert!(runner.runtime.memory().capacities().1 >= 10);
+
+        let mut runner = Runner::with_memsize(&program, input, vec![], MemorySize::Fixed(10));
+        runner.reserve_memory(9).unwrap();
+        assert!(matches!(
+            runner.reserve_memory(10),
+            Err(RuntimeError::OutOfMemoryBounds { address: 10, .. })
+        ));
+        assert!(matches!(
+            runner.reserve_memory(-1),
+            Err(RuntimeError::OutOfMemoryBounds { address: -1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_run_rewinding_input_on_loop() {
+        use std::io::Cursor;
+
+        // +++[>,.<-]>,.
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![PAdd(1), Input, Output, PAdd(-1), DAdd(-1)]),
+            PAdd(1),
+            Input,
+            Output,
+        ]);
+        let mut output = vec![];
+        let runner = Runner::new(&program, Cursor::new(b"ab"), &mut output);
+        if let Err(e) = runner.run_rewinding_input_on_loop() {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(output, b"aaab");
+
+        // without rewinding, the loop reads the input to the end.
+        let mut output = vec![];
+        let result = Runner::new(&program, Cursor::new(b"ab"), &mut output).run();
+        assert!(matches!(result, Err(RuntimeError::Eof)));
+        assert_eq!(output, b"ab");
+
+        // ,[>++[>,.<-]<-]: the inner loop rewinds to where each outer iteration reached it.
+        let program = Program::new([
+            Input,
+            UntilZero(vec![
+                PAdd(1),
+                DAdd(2),
+                UntilZero(vec![PAdd(1), Input, Output, PAdd(-1), DAdd(-1)]),
+                PAdd(-1),
+                DAdd(-1),
+            ]),
+        ]);
+        let mut output = vec![];
+        let runner = Runner::new(&program, Cursor::new(b"\x02xyz"), &mut output);
+        if let Err(e) = runner.run_rewinding_input_on_loop() {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(output, b"xxxx");
+    }
+
+    #[test]
+    fn test_run_with_output_map() {
+        // Hello World!\n
+        let source = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+        let program = bf_parser().parse_str(source).unwrap();
+        let mut output = vec![];
+        let mut first = true;
+        let result =
+            Runner::new(&program, &[][..], &mut output).run_with_output_map(|byte, output| {
+                if !std::mem::take(&mut first) {
+                    output.write_all(b" ")?;
+                }
+                write!(output, "{byte:02x}")
+            });
+        if let Err(e) = result {
+            panic!("unexpected error: {e}");
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "48 65 6c 6c 6f 20 57 6f 72 6c 64 21 0a"
+        );
+
+        // an error of the transformer stops the run.
+        let program = Program::new([Output, Output]);
+        let mut calls = 0;
+        let result = Runner::new(&program, &[][..], vec![]).run_with_output_map(|_, _| {
+            calls += 1;
+            Err(io::Error::other("rejected"))
+        });
+        assert!(matches!(result, Err(RuntimeError::IoError(_))));
+        assert_eq!(calls, 1);
+    }
 }
@@ -0,0 +1,138 @@
+//! Ctrl-C (SIGINT) integration.
+use std::{
+    io,
+    os::raw::c_int,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+use super::CancelFlag;
+
+const SIGINT: c_int = 2;
+const SIG_ERR: usize = !0;
+
+extern "C" {
+    fn signal(signum: c_int, handler: usize) -> usize;
+}
+
+// The flag which the handler sets. It is shared by all guards, since the handler can only touch
+// statics.
+static FLAG: OnceLock<CancelFlag> = OnceLock::new();
+// `true` while a guard is alive.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_sigint(_signum: c_int) {
+    // Only an atomic store, which is async-signal-safe.
+    if let Some(flag) = FLAG.get() {
+        flag.cancel();
+    }
+}
+
+/// A guard of the Ctrl-C handler installed by [`ctrlc_guard`].
+///
+/// The previous handler is restored when the guard is dropped.
+#[derive(Debug)]
+pub struct CtrlcGuard {
+    flag: CancelFlag,
+    previous: usize,
+}
+
+impl CtrlcGuard {
+    /// Get the flag which is set when Ctrl-C is pressed.
+    pub fn flag(&self) -> CancelFlag {
+        self.flag.clone()
+    }
+}
+
+impl Drop for CtrlcGuard {
+    fn drop(&mut self) {
+        // SAFETY: `previous` is the handler returned by `signal`.
+        unsafe { signal(SIGINT, self.previous) };
+        INSTALLED.store(false, Ordering::Release);
+    }
+}
+
+/// Install a Ctrl-C (SIGINT) handler which sets a [`CancelFlag`].
+///
+/// A signal handler cannot touch a runner, so the handler only sets the flag, and a runner with
+/// the flag stops with [`RuntimeError::Cancelled`](crate::error::RuntimeError::Cancelled)
+/// at the next loop condition check. The output written so far is kept, so the caller can report
+/// partial results. A run blocked on reading its input is not stopped until the read returns.
+///
+/// The flag is cleared when the handler is installed. Only one guard can be alive at a time.
+///
+/// This function is available on Unix platforms with feature `signal`.
+///
+/// # Errors
+///
+/// Returns an error with [`io::ErrorKind::AlreadyExists`] if another guard is alive,
+/// or the OS error if installing the handler fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libbf::{
+///     error::RuntimeError,
+///     program::{Instruction::*, Program},
+///     runtime::{self, Runner},
+/// };
+///
+/// let program = Program::new(vec![DAdd(1), UntilZero(vec![Output])]);
+/// let guard = runtime::ctrlc_guard().unwrap();
+/// let mut output = vec![];
+/// let result = Runner::new(&program, std::io::stdin(), &mut output)
+///     .with_cancel(guard.flag())
+///     .run();
+/// match result {
+///     Err(RuntimeError::Cancelled) => eprintln!("interrupted after {} bytes of output", output.len()),
+///     Err(e) => eprintln!("error[{}]: {e}", e.kind().code()),
+///     Ok(()) => {}
+/// }
+/// ```
+pub fn ctrlc_guard() -> io::Result<CtrlcGuard> {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a Ctrl-C guard is already installed",
+        ));
+    }
+    let flag = FLAG.get_or_init(CancelFlag::new).clone();
+    flag.reset();
+    // SAFETY: `handle_sigint` only performs an async-signal-safe operation.
+    let previous = unsafe { signal(SIGINT, handle_sigint as extern "C" fn(c_int) as usize) };
+    if previous == SIG_ERR {
+        let error = io::Error::last_os_error();
+        INSTALLED.store(false, Ordering::Release);
+        return Err(error);
+    }
+    Ok(CtrlcGuard { flag, previous })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    extern "C" {
+        fn raise(signum: c_int) -> c_int;
+    }
+
+    #[test]
+    fn test_ctrlc_guard() {
+        let guard = ctrlc_guard().unwrap();
+        assert_eq!(
+            ctrlc_guard().unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        let flag = guard.flag();
+        assert!(!flag.is_cancelled());
+        assert_eq!(unsafe { raise(SIGINT) }, 0);
+        assert!(flag.is_cancelled());
+        drop(guard);
+
+        // A new guard clears the flag.
+        let guard = ctrlc_guard().unwrap();
+        assert!(!guard.flag().is_cancelled());
+    }
+}
@@ -1,17 +1,95 @@
-///! Step-by-step program runner.
+//! Step-by-step program runner.
+use std::time::Instant;
+
 use crate::prelude::ProgramIndex;
 
 use super::*;
 
+/// An outcome of [`StepRunner::step`] and [`StepRunner::run_to_breakpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction other than [`Instruction::Breakpoint`] was executed.
+    Stepped,
+    /// An [`Instruction::Breakpoint`] was executed.
+    ///
+    /// The runner points to the instruction next to the breakpoint.
+    Breakpoint,
+    /// The program is finished. No instruction was executed.
+    Finished,
+}
+
+/// A budget of [`StepRunner::run_budgeted`].
+///
+/// `None` means no limit. The default budget has no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Budget {
+    /// The maximum number of steps.
+    pub max_steps: Option<u64>,
+    /// The maximum duration.
+    ///
+    /// The clock is checked every [`Budget::CLOCK_CHECK_INTERVAL`] steps,
+    /// so the run may exceed this by the time of those steps.
+    pub max_duration: Option<Duration>,
+}
+
+impl Budget {
+    /// The number of steps between checks of the clock.
+    pub const CLOCK_CHECK_INTERVAL: u64 = 1024;
+}
+
+/// The outcome of [`StepRunner::run_budgeted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetOutcome {
+    /// The budget was exhausted. The program is still running.
+    BudgetExhausted {
+        /// The number of steps executed.
+        steps: u64,
+    },
+    /// An [`Instruction::Breakpoint`] was executed.
+    Breakpoint {
+        /// The number of steps executed, including the breakpoint.
+        steps: u64,
+    },
+    /// The program is finished.
+    Finished {
+        /// The number of steps executed.
+        steps: u64,
+    },
+    /// An input instruction got [`RuntimeError::InputNotReady`] under
+    /// [`WouldBlockPolicy::Report`]. The input instruction is retried by the next run.
+    InputNotReady {
+        /// The number of steps executed, not including the input instruction.
+        steps: u64,
+    },
+}
+
+impl BudgetOutcome {
+    /// Get the number of steps executed.
+    pub fn steps(&self) -> u64 {
+        match self {
+            Self::BudgetExhausted { steps }
+            | Self::Breakpoint { steps }
+            | Self::Finished { steps }
+            | Self::InputNotReady { steps } => *steps,
+        }
+    }
+}
+
 /// A step-by-step program runner.
 ///
 /// This runner runs the program step-by-step.
 ///
 /// It is useful for debugging, visual representation backend and etc,...
-pub struct StepRunner<'a, R, W> {
+///
+/// Control-flow events can be observed by setting an [`ExecObserver`] with
+/// [`StepRunner::with_observer`]. Without it, the runner uses [`NoObserver`], which costs nothing.
+pub struct StepRunner<'a, R, W, O = NoObserver> {
     program: &'a Program,
     runtime: internal::Runtime<R, W>,
     index: Option<ProgramIndex>,
+    // `true` if the loop at `index` has completed an iteration
+    reentering: bool,
+    observer: O,
 }
 
 impl<'a, R, W> StepRunner<'a, R, W>
@@ -26,14 +104,104 @@ where
 
     /// Create a new runner with the given input, output and memory size.
     pub fn with_memsize(program: &'a Program, input: R, output: W, memsize: MemorySize) -> Self {
-        let runtime = internal::Runtime::new(input, output, memsize);
+        Self::with_config(
+            program,
+            input,
+            output,
+            RuntimeConfig::default().with_memsize(memsize),
+        )
+    }
+
+    /// Create a new runner with the given input, output and runtime configuration.
+    pub fn with_config(program: &'a Program, input: R, output: W, config: RuntimeConfig) -> Self {
+        let runtime = internal::Runtime::new(input, output, config);
         Self {
             program,
             runtime,
             index: program.first_index(),
+            reentering: false,
+            observer: NoObserver,
+        }
+    }
+
+    /// Create a new runner which resumes the execution from `state` with the given input and output.
+    ///
+    /// `state` must be obtained from a runner which runs `program`.
+    /// Otherwise, the runner may panic while running.
+    pub fn from_state(program: &'a Program, state: MachineState, input: R, output: W) -> Self {
+        Self {
+            program,
+            runtime: internal::Runtime::with_machine(input, output, state.machine),
+            index: state.index,
+            reentering: state.reentering,
+            observer: NoObserver,
+        }
+    }
+}
+
+impl<'a, R, W, O> StepRunner<'a, R, W, O>
+where
+    R: Read,
+    W: Write,
+    O: ExecObserver,
+{
+    /// Set the flag to cancel the run (see [`CancelFlag`]).
+    ///
+    /// After the run is cancelled, [`StepRunner::step`] keeps returning the error until the flag
+    /// is reset, and then resumes from the cancelled loop.
+    pub fn with_cancel(mut self, cancel: CancelFlag) -> Self {
+        self.runtime.set_cancel(cancel);
+        self
+    }
+
+    /// Set the behavior of the input instruction at the end of the input (see [`EofBehavior`]).
+    pub fn with_eof_behavior(mut self, eof: EofBehavior) -> Self {
+        self.runtime.set_eof_behavior(eof);
+        self
+    }
+
+    /// Set the observer of control-flow events, replacing the current one.
+    ///
+    /// To keep the observer after the runner is dropped, pass a mutable reference to it.
+    pub fn with_observer<P: ExecObserver>(self, observer: P) -> StepRunner<'a, R, W, P> {
+        StepRunner {
+            program: self.program,
+            runtime: self.runtime,
+            index: self.index,
+            reentering: self.reentering,
+            observer,
         }
     }
 
+    /// Get the observer.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Get the mutable reference of the observer.
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Fork the current state of the runner except its input and output.
+    ///
+    /// The returned state is independent of this runner,
+    /// so a runner created by [`StepRunner::from_state`] does not affect this runner and vice versa.
+    pub fn fork_state(&self) -> MachineState {
+        MachineState {
+            machine: self.runtime.machine().clone(),
+            index: self.index.clone(),
+            reentering: self.reentering,
+        }
+    }
+
+    /// Get the program which the runner runs.
+    ///
+    /// It is useful for showing instructions around the current one.
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+
     /// Get the index of the instruction to be executed.
     ///
     /// If the program is finished, this returns `None`.
@@ -46,11 +214,28 @@ where
         self.index.as_ref().map(|index| &self.program[index])
     }
 
+    /// Release the zero cells at the ends of the allocated memory.
+    ///
+    /// The memory grows again on demand, so the data at any address is not changed.
+    /// Memory with [`MemorySize::Fixed`] is not released.
+    pub fn compact(&mut self) {
+        self.runtime.compact();
+    }
+
     /// Get the pointer.
     pub fn get_pointer(&self) -> isize {
         self.runtime.get_pointer()
     }
 
+    /// Get the data at the pointer without modifying the memory.
+    ///
+    /// Returns `None` if the pointer is out of memory bounds. An unallocated cell
+    /// in bounds is 0.
+    pub fn current_cell(&self) -> Option<u8> {
+        let machine = self.runtime.machine();
+        machine.data_at(machine.pointer())
+    }
+
     /// Get mutable reference of data at `addres'.
     pub fn get_data_at_mut(&mut self, address: isize) -> Option<&mut u8> {
         self.runtime.get_data_at_mut(address)
@@ -62,22 +247,487 @@ where
     }
 
     /// Execute the program one step.
-    pub fn step(&mut self) -> Result<(), RuntimeError> {
-        if let Some(index) = &mut self.index {
-            let inst = &self.program[index];
-            match self.runtime.exec_one(inst)? {
-                internal::NextAction::Next => {
-                    if !self.program.step_index(index) && !index.step_out() {
+    ///
+    /// If an error occurred, the index is not advanced. In particular, after
+    /// [`RuntimeError::InputNotReady`], calling this function again retries the same input instruction.
+    pub fn step(&mut self) -> Result<StepOutcome, RuntimeError> {
+        let Some(index) = &mut self.index else {
+            return Ok(StepOutcome::Finished);
+        };
+        let inst = &self.program[index];
+        let action = self.runtime.exec_one(inst)?;
+        if let Instruction::UntilZero(_) = inst {
+            if !self.reentering {
+                let machine = self.runtime.machine();
+                let cell = machine.data_at(machine.pointer()).unwrap_or_default();
+                self.observer.loop_entered(index, cell);
+            }
+            self.reentering = false;
+        }
+        match action {
+            internal::NextAction::Next => {
+                if let Instruction::UntilZero(_) = inst {
+                    self.observer.loop_exited(index);
+                }
+                if !self.program.step_index(index) {
+                    if index.step_out() {
+                        self.observer.loop_iterated(index);
+                        self.reentering = true;
+                    } else {
                         self.index = None;
+                        self.observer.finished();
                     }
                 }
-                internal::NextAction::StepIn(sub) => {
-                    if !sub.is_empty() {
-                        index.step_in();
-                    }
+            }
+            internal::NextAction::StepIn(sub) => {
+                if sub.is_empty() {
+                    self.observer.loop_iterated(index);
+                    self.reentering = true;
+                } else {
+                    index.step_in();
                 }
             }
         }
-        Ok(())
+        match inst {
+            Instruction::Breakpoint => Ok(StepOutcome::Breakpoint),
+            _ => Ok(StepOutcome::Stepped),
+        }
+    }
+
+    /// Execute the program until a breakpoint ([`Instruction::Breakpoint`]) is executed or
+    /// the program is finished.
+    ///
+    /// Returns [`StepOutcome::Breakpoint`] or [`StepOutcome::Finished`] respectively.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{program::{Instruction::*, Program}, runtime::{StepOutcome, StepRunner}};
+    ///
+    /// let program = Program::new([DAdd(1), Breakpoint, DAdd(1)]);
+    /// let mut runner = StepRunner::new(&program, &[][..], vec![]);
+    ///
+    /// assert_eq!(runner.run_to_breakpoint().unwrap(), StepOutcome::Breakpoint);
+    /// assert_eq!(*runner.get_data_at_mut(0).unwrap(), 1);
+    /// assert_eq!(runner.run_to_breakpoint().unwrap(), StepOutcome::Finished);
+    /// assert_eq!(*runner.get_data_at_mut(0).unwrap(), 2);
+    /// ```
+    pub fn run_to_breakpoint(&mut self) -> Result<StepOutcome, RuntimeError> {
+        loop {
+            match self.step()? {
+                StepOutcome::Stepped => {}
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+
+    /// Execute the program until `budget` is exhausted, a breakpoint is executed, the program is
+    /// finished, or the input is not ready.
+    ///
+    /// It is useful for running the program a little in each frame of a GUI.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{
+    ///     program::{Instruction::*, Program},
+    ///     runtime::{Budget, BudgetOutcome, StepRunner},
+    /// };
+    /// use std::time::Duration;
+    ///
+    /// let program = Program::new([DAdd(5), UntilZero(vec![DAdd(-1)])]);
+    /// let mut runner = StepRunner::new(&program, &[][..], vec![]);
+    /// let budget = Budget {
+    ///     max_steps: Some(4),
+    ///     max_duration: Some(Duration::from_millis(2)),
+    /// };
+    ///
+    /// assert_eq!(runner.run_budgeted(budget).unwrap(), BudgetOutcome::BudgetExhausted { steps: 4 });
+    /// while let BudgetOutcome::BudgetExhausted { .. } = runner.run_budgeted(budget).unwrap() {}
+    /// assert!(!runner.is_running());
+    /// ```
+    pub fn run_budgeted(&mut self, budget: Budget) -> Result<BudgetOutcome, RuntimeError> {
+        let start = budget.max_duration.map(|max| (Instant::now(), max));
+        let mut steps = 0;
+        loop {
+            if !self.is_running() {
+                return Ok(BudgetOutcome::Finished { steps });
+            }
+            if budget.max_steps.is_some_and(|max| steps >= max) {
+                return Ok(BudgetOutcome::BudgetExhausted { steps });
+            }
+            if let Some((start, max)) = start {
+                if steps % Budget::CLOCK_CHECK_INTERVAL == 0 && start.elapsed() >= max {
+                    return Ok(BudgetOutcome::BudgetExhausted { steps });
+                }
+            }
+            match self.step() {
+                Ok(StepOutcome::Stepped) => steps += 1,
+                Ok(StepOutcome::Breakpoint) => {
+                    return Ok(BudgetOutcome::Breakpoint { steps: steps + 1 })
+                }
+                Ok(StepOutcome::Finished) => return Ok(BudgetOutcome::Finished { steps }),
+                Err(RuntimeError::InputNotReady) => {
+                    return Ok(BudgetOutcome::InputNotReady { steps })
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_spec;
+
+    #[test]
+    fn test_fork_state() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), PAdd(1), DAdd(2), Output, PAdd(1)]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, input, &mut output);
+        runner.step().unwrap();
+        runner.step().unwrap();
+
+        let state = runner.fork_state();
+        assert_eq!(state.pointer(), 1);
+        assert_eq!(state.index(), Some(&ProgramIndex::new_for_test([2])));
+
+        let mut fork_output = vec![];
+        let mut fork = StepRunner::from_state(&program, state, input, &mut fork_output);
+        while fork.is_running() {
+            fork.step().unwrap();
+        }
+        assert_eq!(fork.get_pointer(), 2);
+        assert_eq!(fork.get_data_at_mut(1).copied(), Some(2));
+        drop(fork);
+        assert_eq!(fork_output, [2]);
+
+        // the original runner is not affected.
+        assert_eq!(runner.get_index(), Some(&ProgramIndex::new_for_test([2])));
+        assert_eq!(runner.get_pointer(), 1);
+        assert_eq!(runner.get_data_at_mut(0).copied(), Some(1));
+        assert_eq!(runner.get_data_at_mut(1).copied(), Some(0));
+        drop(runner);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_current_cell() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), DAdd(2), PAdd(3), PAdd(-4)]);
+        for (memsize, moved) in [
+            (MemorySize::Fixed(2), None),
+            (MemorySize::RightInfinite, Some(0)),
+            (MemorySize::BothInfinite, Some(0)),
+        ] {
+            let mut runner = StepRunner::with_memsize(&program, &[][..], vec![], memsize);
+            assert_eq!(runner.current_cell(), Some(0));
+            runner.step().unwrap();
+            assert_eq!(runner.current_cell(), Some(1));
+            runner.step().unwrap();
+            assert_eq!(runner.current_cell(), Some(3));
+            runner.step().unwrap();
+            assert_eq!(runner.current_cell(), moved);
+            runner.step().unwrap();
+            let left = matches!(memsize, MemorySize::BothInfinite).then_some(0);
+            assert_eq!(runner.current_cell(), left);
+        }
+    }
+
+    #[test]
+    fn test_step_retries_input_not_ready() {
+        use super::super::test::NotReadyReader;
+        use Instruction::*;
+        let program = Program::new([Input, Output]);
+        let input = NotReadyReader {
+            not_ready: 2,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, input, &mut output);
+        for _ in 0..2 {
+            assert!(matches!(runner.step(), Err(RuntimeError::InputNotReady)));
+            assert_eq!(runner.get_index(), Some(&ProgramIndex::new_for_test([0])));
+        }
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        drop(runner);
+        assert_eq!(output, b"A");
+    }
+
+    #[test]
+    fn test_observer() {
+        use Instruction::*;
+
+        #[derive(Debug, PartialEq, Eq)]
+        enum Event {
+            Entered(ProgramIndex, u8),
+            Iterated(ProgramIndex),
+            Exited(ProgramIndex),
+            Finished,
+        }
+
+        #[derive(Default)]
+        struct Recorder(Vec<Event>);
+
+        impl ExecObserver for Recorder {
+            fn loop_entered(&mut self, index: &ProgramIndex, cell: u8) {
+                self.0.push(Event::Entered(index.clone(), cell));
+            }
+
+            fn loop_iterated(&mut self, index: &ProgramIndex) {
+                self.0.push(Event::Iterated(index.clone()));
+            }
+
+            fn loop_exited(&mut self, index: &ProgramIndex) {
+                self.0.push(Event::Exited(index.clone()));
+            }
+
+            fn finished(&mut self) {
+                self.0.push(Event::Finished);
+            }
+        }
+
+        let program = Program::new([DAdd(2), UntilZero(vec![DAdd(-1), Output])]);
+        let mut recorder = Recorder::default();
+        let mut runner = StepRunner::new(&program, &[][..], vec![]).with_observer(&mut recorder);
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        drop(runner);
+        let index = ProgramIndex::new_for_test([1]);
+        assert_eq!(
+            recorder.0,
+            [
+                Event::Entered(index.clone(), 2),
+                Event::Iterated(index.clone()),
+                Event::Iterated(index.clone()),
+                Event::Exited(index),
+                Event::Finished,
+            ]
+        );
+
+        // an empty body and a nested loop
+        let program = Program::new([
+            UntilZero(vec![]),
+            DAdd(1),
+            UntilZero(vec![UntilZero(vec![DAdd(-1)])]),
+        ]);
+        let mut runner =
+            StepRunner::new(&program, &[][..], vec![]).with_observer(Recorder::default());
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        let outer = ProgramIndex::new_for_test([2]);
+        let inner = ProgramIndex::new_for_test([2, 0]);
+        assert_eq!(
+            runner.observer().0,
+            [
+                Event::Entered(ProgramIndex::new_for_test([0]), 0),
+                Event::Exited(ProgramIndex::new_for_test([0])),
+                Event::Entered(outer.clone(), 1),
+                Event::Entered(inner.clone(), 1),
+                Event::Iterated(inner.clone()),
+                Event::Exited(inner),
+                Event::Iterated(outer.clone()),
+                Event::Exited(outer),
+                Event::Finished,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_budgeted() {
+        use super::super::test::NotReadyReader;
+        use Instruction::*;
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![DAdd(-1)]),
+            Breakpoint,
+            Input,
+            Output,
+        ]);
+        let input = NotReadyReader {
+            not_ready: 1,
+            data: b"A",
+        };
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, input, &mut output);
+        let budget = Budget {
+            max_steps: Some(5),
+            max_duration: None,
+        };
+        // DAdd, 4 checks and 3 bodies of the loop, and the breakpoint
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::BudgetExhausted { steps: 5 }
+        );
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::Breakpoint { steps: 4 }
+        );
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::InputNotReady { steps: 0 }
+        );
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::Finished { steps: 2 }
+        );
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::Finished { steps: 0 }
+        );
+        drop(runner);
+        assert_eq!(output, b"A");
+
+        // The program is finished by the last step of the budget.
+        let program = Program::new([DAdd(1), DAdd(1)]);
+        let mut runner = StepRunner::new(&program, &[][..], vec![]);
+        let budget = Budget {
+            max_steps: Some(2),
+            max_duration: None,
+        };
+        assert_eq!(
+            runner.run_budgeted(budget).unwrap(),
+            BudgetOutcome::Finished { steps: 2 }
+        );
+    }
+
+    #[test]
+    fn test_run_budgeted_duration() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), UntilZero(vec![])]);
+        let mut runner = StepRunner::new(&program, &[][..], vec![]);
+        let budget = Budget {
+            max_steps: None,
+            max_duration: Some(Duration::from_millis(10)),
+        };
+        let start = Instant::now();
+        let outcome = runner.run_budgeted(budget).unwrap();
+        let elapsed = start.elapsed();
+        assert!(matches!(outcome, BudgetOutcome::BudgetExhausted { steps } if steps > 0));
+        assert!(elapsed >= Duration::from_millis(10));
+        assert!(elapsed < Duration::from_secs(5));
+        assert!(runner.is_running());
+    }
+
+    #[test]
+    fn test_compact() {
+        use Instruction::*;
+        let program = Program::new([
+            PAdd(100),
+            DSet(7),
+            Output,
+            SetZero,
+            PAdd(-300),
+            DAdd(1),
+            Output,
+            SetZero,
+            PAdd(199),
+            DSet(2),
+            UntilZero(vec![Output, DAdd(-1)]),
+            PAdd(-1),
+            Output,
+            PAdd(100),
+            Output,
+            PAdd(-300),
+            Output,
+        ]);
+        let config = || RuntimeConfig::default().with_memsize(MemorySize::BothInfinite);
+        let mut expected = vec![];
+        let mut runner = StepRunner::with_config(&program, &[][..], &mut expected, config());
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        drop(runner);
+
+        let mut output = vec![];
+        let mut runner = StepRunner::with_config(&program, &[][..], &mut output, config());
+        for _ in 0..8 {
+            runner.step().unwrap();
+        }
+        assert!(runner.runtime.memory().capacities().1 >= 200);
+        runner.compact();
+        assert_eq!(runner.runtime.memory().capacities(), (0, 0));
+        assert_eq!(runner.get_pointer(), -200);
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        drop(runner);
+        assert_eq!(output, expected);
+
+        // automatic compaction after the loop
+        let mut output = vec![];
+        let config = config().with_auto_compact(10);
+        let mut runner = StepRunner::with_config(&program, &[][..], &mut output, config);
+        for _ in 0..10 {
+            runner.step().unwrap();
+        }
+        assert!(runner.runtime.memory().capacities().1 >= 200);
+        while runner.get_index() != Some(&ProgramIndex::new_for_test([11])) {
+            runner.step().unwrap();
+        }
+        assert_eq!(runner.runtime.memory().capacities().1, 0);
+        assert!(runner.runtime.memory().capacities().0 < 100);
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        drop(runner);
+        assert_eq!(output, expected);
+
+        // fixed memory is not released
+        let program = Program::new([PAdd(9), DAdd(1), SetZero]);
+        let config = RuntimeConfig::default().with_memsize(MemorySize::Fixed(10));
+        let mut runner = StepRunner::with_config(&program, &[][..], vec![], config);
+        while runner.is_running() {
+            runner.step().unwrap();
+        }
+        runner.compact();
+        assert!(runner.runtime.memory().capacities().0 >= 10);
+        assert_eq!(runner.get_data_at_mut(9).copied(), Some(0));
+    }
+
+    #[test]
+    fn test_program() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), PAdd(1), Output]);
+        let input: &[u8] = &[];
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, input, &mut output);
+        runner.step().unwrap();
+
+        let mut next = runner.get_index().unwrap().clone();
+        assert!(runner.program().step_index(&mut next));
+        assert_eq!(runner.program()[&next], Output);
+        assert!(std::ptr::eq(runner.program(), &program));
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        use crate::parser::Parser;
+
+        let tokenizer = bf_spec().to_tokenizer().with_breakpoint('#');
+        let program = Parser::new(tokenizer).parse_str("++[-#]+.").unwrap();
+
+        let mut output = vec![];
+        Runner::new(&program, &[][..], &mut output).run().unwrap();
+        assert_eq!(output, [1]);
+
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, &[][..], &mut output);
+        assert_eq!(runner.step().unwrap(), StepOutcome::Stepped);
+        for expected in [1, 0] {
+            assert_eq!(runner.run_to_breakpoint().unwrap(), StepOutcome::Breakpoint);
+            assert_eq!(*runner.get_data_at_mut(0).unwrap(), expected);
+        }
+        assert_eq!(runner.run_to_breakpoint().unwrap(), StepOutcome::Finished);
+        assert_eq!(runner.step().unwrap(), StepOutcome::Finished);
+        drop(runner);
+        assert_eq!(output, [1]);
     }
 }
@@ -0,0 +1,279 @@
+//! Execution traces.
+use std::io::{self, BufWriter};
+
+use super::*;
+
+/// A record of an executed instruction, which is passed to [`TraceHook::record`].
+///
+/// The machine state is the one before the instruction is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord<'a> {
+    /// The number of instructions executed before this one, starting from 0.
+    pub step: u64,
+    /// The index of the instruction.
+    pub index: &'a ProgramIndex,
+    /// The instruction.
+    pub instruction: &'a Instruction,
+    /// The pointer.
+    pub pointer: isize,
+    /// The cell value at the pointer, or `None` if the pointer is out of memory bounds.
+    pub cell: Option<u8>,
+}
+
+impl TraceRecord<'_> {
+    /// Get the kind of the instruction, which is the name of the variant of [`Instruction`]
+    /// (e.g. `"PAdd"`).
+    pub fn kind(&self) -> &'static str {
+        match self.instruction {
+            Instruction::PAdd(_) => "PAdd",
+            Instruction::DAdd(_) => "DAdd",
+            Instruction::Output => "Output",
+            Instruction::Input => "Input",
+            Instruction::UntilZero(_) => "UntilZero",
+            Instruction::SetZero => "SetZero",
+            Instruction::DSet(_) => "DSet",
+            Instruction::Fill { .. } => "Fill",
+            Instruction::Breakpoint => "Breakpoint",
+            Instruction::AssertCell { .. } => "AssertCell",
+        }
+    }
+
+    /// Get the operands of the instruction.
+    ///
+    /// `PAdd` and `DAdd` have the operand, `DSet` has the value, `AssertCell` has the expected value, `Fill` has the length and
+    /// the value, and the others have no operands.
+    pub fn operands(&self) -> Vec<isize> {
+        match self.instruction {
+            Instruction::PAdd(operand) | Instruction::DAdd(operand) => vec![*operand],
+            Instruction::DSet(value) | Instruction::AssertCell { expected: value } => {
+                vec![*value as isize]
+            }
+            Instruction::Fill { len, value } => vec![*len as isize, *value as isize],
+            _ => vec![],
+        }
+    }
+
+    /// Format the record as a JSON object in a single line.
+    ///
+    /// The object has the following members in this order:
+    ///
+    /// | member     | type               | description                                  |
+    /// |------------|--------------------|----------------------------------------------|
+    /// | `step`     | number             | [`TraceRecord::step`]                        |
+    /// | `index`    | array of numbers   | [`TraceRecord::index`]                       |
+    /// | `kind`     | string             | [`TraceRecord::kind`]                        |
+    /// | `operands` | array of numbers   | [`TraceRecord::operands`]                    |
+    /// | `pointer`  | number             | [`TraceRecord::pointer`]                     |
+    /// | `cell`     | number or `null`   | [`TraceRecord::cell`], only if `with_cell`   |
+    pub fn to_json(&self, with_cell: bool) -> String {
+        let operands = self
+            .operands()
+            .iter()
+            .map(|operand| operand.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut json = format!(
+            r#"{{"step":{},"index":{},"kind":"{}","operands":[{}],"pointer":{}"#,
+            self.step,
+            self.index.to_string().replace(' ', ""),
+            self.kind(),
+            operands,
+            self.pointer,
+        );
+        if with_cell {
+            match self.cell {
+                Some(cell) => json.push_str(&format!(r#","cell":{cell}"#)),
+                None => json.push_str(r#","cell":null"#),
+            }
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// A hook which receives a record of each executed instruction.
+///
+/// It is used by [`Runner::run_traced`]. An error returned by the hook stops the run.
+pub trait TraceHook {
+    /// Receive a record of the instruction to be executed.
+    fn record(&mut self, record: &TraceRecord<'_>) -> io::Result<()>;
+}
+
+impl<F> TraceHook for F
+where
+    F: FnMut(&TraceRecord<'_>) -> io::Result<()>,
+{
+    fn record(&mut self, record: &TraceRecord<'_>) -> io::Result<()> {
+        self(record)
+    }
+}
+
+/// A trace hook which writes records as JSON lines.
+///
+/// Each line is a JSON object formatted by [`TraceRecord::to_json`].
+/// The output is buffered; call [`JsonLinesTrace::finish`] to flush it.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{program::{Instruction::*, Program}, runtime::{trace::JsonLinesTrace, Runner}};
+///
+/// let program = Program::new([DAdd(2), UntilZero(vec![DAdd(-1)])]);
+/// let mut trace = JsonLinesTrace::new(vec![]).with_cell_values(true);
+/// Runner::new(&program, &[][..], vec![]).run_traced(&mut trace).unwrap();
+/// let trace = String::from_utf8(trace.finish().unwrap()).unwrap();
+///
+/// assert_eq!(trace.lines().count(), 6);
+/// assert_eq!(
+///     trace.lines().nth(2).unwrap(),
+///     r#"{"step":2,"index":[1,0],"kind":"DAdd","operands":[-1],"pointer":0,"cell":2}"#,
+/// );
+/// ```
+pub struct JsonLinesTrace<W: Write> {
+    output: BufWriter<W>,
+    every: u64,
+    with_cell: bool,
+}
+
+impl<W> JsonLinesTrace<W>
+where
+    W: Write,
+{
+    /// Create a new trace hook which writes every record to `output` without cell values.
+    pub fn new(output: W) -> Self {
+        Self {
+            output: BufWriter::new(output),
+            every: 1,
+            with_cell: false,
+        }
+    }
+
+    /// Set the sampling interval. Only the records whose step is a multiple of `every` are written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is 0.
+    pub fn with_sampling(mut self, every: u64) -> Self {
+        assert!(every > 0, "sampling interval must be positive.");
+        self.every = every;
+        self
+    }
+
+    /// Set whether records include the cell value at the pointer.
+    pub fn with_cell_values(mut self, with_cell: bool) -> Self {
+        self.with_cell = with_cell;
+        self
+    }
+
+    /// Flush the buffer and return the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.output.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl<W> TraceHook for JsonLinesTrace<W>
+where
+    W: Write,
+{
+    fn record(&mut self, record: &TraceRecord<'_>) -> io::Result<()> {
+        if record.step.is_multiple_of(self.every) {
+            writeln!(self.output, "{}", record.to_json(self.with_cell))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::program::Instruction::*;
+    use std::collections::HashMap;
+
+    // Extract the value of `name` from a JSON line written by `TraceRecord::to_json`.
+    fn member<'a>(line: &'a str, name: &str) -> &'a str {
+        let start = line.find(&format!(r#""{name}":"#)).unwrap() + name.len() + 3;
+        let rest = &line[start..];
+        let end = if rest.starts_with('[') {
+            rest.find(']').unwrap() + 1
+        } else {
+            rest.find([',', '}']).unwrap()
+        };
+        &rest[..end]
+    }
+
+    #[test]
+    fn test_trace_counts_match_profile() {
+        // +++[>++[>+<-]<-]>>.
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![
+                PAdd(1),
+                DAdd(2),
+                UntilZero(vec![PAdd(1), DAdd(1), PAdd(-1), DAdd(-1)]),
+                PAdd(-1),
+                DAdd(-1),
+            ]),
+            PAdd(2),
+            Output,
+        ]);
+        let mut profile = Profile::new();
+        Runner::new(&program, &[][..], vec![])
+            .run_profiled(&mut profile)
+            .unwrap();
+
+        let mut trace = JsonLinesTrace::new(vec![]).with_cell_values(true);
+        Runner::new(&program, &[][..], vec![])
+            .run_traced(&mut trace)
+            .unwrap();
+        let trace = String::from_utf8(trace.finish().unwrap()).unwrap();
+
+        let mut counts = HashMap::new();
+        for (step, line) in trace.lines().enumerate() {
+            assert_eq!(member(line, "step"), step.to_string());
+            *counts.entry(member(line, "index").to_string()).or_insert(0) += 1;
+        }
+        let total = profile.iter().map(|(_, count)| count).sum::<u64>();
+        assert_eq!(trace.lines().count() as u64, total);
+        assert_eq!(counts.len(), profile.iter().count());
+        for (index, count) in profile.iter() {
+            assert_eq!(
+                counts[&index.to_string().replace(' ', "")],
+                count,
+                "{index}"
+            );
+        }
+
+        let last = trace.lines().last().unwrap();
+        assert_eq!(member(last, "kind"), r#""Output""#);
+        assert_eq!(member(last, "operands"), "[]");
+        assert_eq!(member(last, "pointer"), "2");
+        assert_eq!(member(last, "cell"), "6");
+
+        let mut sampled = JsonLinesTrace::new(vec![]).with_sampling(10);
+        Runner::new(&program, &[][..], vec![])
+            .run_traced(&mut sampled)
+            .unwrap();
+        let sampled = String::from_utf8(sampled.finish().unwrap()).unwrap();
+        assert_eq!(sampled.lines().count() as u64, total.div_ceil(10));
+        assert!(sampled.lines().all(|line| !line.contains("cell")));
+        assert_eq!(member(sampled.lines().nth(1).unwrap(), "step"), "10");
+    }
+
+    #[test]
+    fn test_trace_hook_error_stops_run() {
+        let program = Program::new([DAdd(1), Output, Output]);
+        let mut output = vec![];
+        let mut records = 0;
+        let result =
+            Runner::new(&program, &[][..], &mut output).run_traced(&mut |_: &TraceRecord| {
+                records += 1;
+                if records == 2 {
+                    return Err(io::ErrorKind::Other.into());
+                }
+                Ok(())
+            });
+        assert!(matches!(result, Err(RuntimeError::IoError(_))));
+        assert_eq!(records, 2);
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! Fixture-driven conformance tests.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{run_with, EquivalenceConfig, Termination};
+use crate::{error::ErrorKind, parser::Parser, token::Tokenizer};
+
+/// The outcome of a fixture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureOutcome {
+    /// The program behaved as expected.
+    Passed,
+    /// The program did not behave as expected. The message describes the differences.
+    Failed(String),
+}
+
+/// The result of a fixture run by [`run_fixture_dir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureResult {
+    /// The path of the program.
+    pub path: PathBuf,
+    /// The outcome.
+    pub outcome: FixtureOutcome,
+}
+
+impl FixtureResult {
+    /// Returns `true` if the fixture passed.
+    pub fn passed(&self) -> bool {
+        self.outcome == FixtureOutcome::Passed
+    }
+}
+
+/// Run the fixtures in the directory `path`.
+///
+/// A fixture is a program file with the extension `.b` and the following optional files
+/// which have the same stem:
+///
+/// | file   | content                                                          |
+/// |--------|------------------------------------------------------------------|
+/// | `.in`  | the input (empty if absent)                                      |
+/// | `.out` | the expected output (empty if absent)                            |
+/// | `.err` | the expected error kind, as its code (e.g. `E0102`) or its name (e.g. `Eof`) |
+///
+/// Without `.err`, the program is expected to finish within `config.step_limit` steps.
+/// With `.err`, the program is expected to fail to parse or run with the error of the kind,
+/// after writing the expected output.
+/// The program is run by `config.runner`.
+///
+/// The results are sorted by the paths. Subdirectories are not searched.
+///
+/// # Errors
+///
+/// Returns an error if reading the directory or a fixture file fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libbf::{parser::Parser, testing::{self, EquivalenceConfig}, token::simple::SimpleTokenSpec};
+///
+/// let parser = Parser::new(
+///     SimpleTokenSpec {
+///         ptr_inc: '>',
+///         ptr_dec: '<',
+///         data_inc: '+',
+///         data_dec: '-',
+///         output: '.',
+///         input: ',',
+///         loop_head: '[',
+///         loop_tail: ']',
+///     }
+///     .to_tokenizer(),
+/// );
+/// let results =
+///     testing::run_fixture_dir("tests/fixtures", &parser, &EquivalenceConfig::default()).unwrap();
+/// for result in results.iter().filter(|result| !result.passed()) {
+///     eprintln!("{}: {:?}", result.path.display(), result.outcome);
+/// }
+/// ```
+pub fn run_fixture_dir<T>(
+    path: impl AsRef<Path>,
+    parser: &Parser<T>,
+    config: &EquivalenceConfig,
+) -> io::Result<Vec<FixtureResult>>
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    let mut programs = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .filter(|path| match path {
+            Ok(path) => path.extension().is_some_and(|ext| ext == "b") && path.is_file(),
+            Err(_) => true,
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    programs.sort();
+
+    programs
+        .into_iter()
+        .map(|path| {
+            let outcome = run_fixture(&path, parser, config)?;
+            Ok(FixtureResult { path, outcome })
+        })
+        .collect()
+}
+
+fn run_fixture<T>(
+    path: &Path,
+    parser: &Parser<T>,
+    config: &EquivalenceConfig,
+) -> io::Result<FixtureOutcome>
+where
+    for<'x> T: Tokenizer<'x>,
+{
+    let source = fs::read_to_string(path)?;
+    let input = read_optional(&path.with_extension("in"))?.unwrap_or_default();
+    let expected_output = read_optional(&path.with_extension("out"))?.unwrap_or_default();
+    let expected_error = match read_optional(&path.with_extension("err"))? {
+        Some(name) => {
+            let name = String::from_utf8_lossy(&name);
+            match parse_kind(name.trim()) {
+                Some(kind) => Some(kind),
+                None => {
+                    return Ok(FixtureOutcome::Failed(format!(
+                        "unknown error kind: {:?}",
+                        name.trim()
+                    )))
+                }
+            }
+        }
+        None => None,
+    };
+
+    let program = match parser.parse_str(&source) {
+        Ok(program) => program,
+        Err(e) if Some(e.kind()) == expected_error => return Ok(FixtureOutcome::Passed),
+        Err(e) => return Ok(FixtureOutcome::Failed(format!("parse error: {e}"))),
+    };
+    let result = run_with(&program, &input, config.runner, config);
+
+    let mut failures = vec![];
+    if let Some(diff) = diff_output(&expected_output, &result.output) {
+        failures.push(diff);
+    }
+    match (&result.termination, expected_error) {
+        (Termination::Finished, None) => {}
+        (Termination::Error(e), Some(kind)) if e.kind() == kind => {}
+        (termination, None) => failures.push(format!("expected to finish, but {termination}")),
+        (termination, Some(kind)) => failures.push(format!(
+            "expected to fail with {kind:?} ({}), but {termination}",
+            kind.code()
+        )),
+    }
+    if failures.is_empty() {
+        Ok(FixtureOutcome::Passed)
+    } else {
+        Ok(FixtureOutcome::Failed(failures.join("\n")))
+    }
+}
+
+fn read_optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_kind(name: &str) -> Option<ErrorKind> {
+    [
+        ErrorKind::UnexpectedEndOfFile,
+        ErrorKind::UnexpectedEndOfLoop,
+        ErrorKind::MiscSyntaxError,
+        ErrorKind::IncludeCycle,
+        ErrorKind::OperandTooLarge,
+        ErrorKind::OutOfMemoryBounds,
+        ErrorKind::Eof,
+        ErrorKind::InputNotReady,
+        ErrorKind::Cancelled,
+        ErrorKind::OutputLimitExceeded,
+        ErrorKind::AssertionFailed,
+        ErrorKind::IoError,
+    ]
+    .into_iter()
+    .find(|kind| kind.code() == name || format!("{kind:?}") == name)
+}
+
+// Describe the first difference of the outputs with some bytes around it.
+fn diff_output(expected: &[u8], actual: &[u8]) -> Option<String> {
+    const CONTEXT: usize = 8;
+    let index = expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .or((expected.len() != actual.len()).then(|| expected.len().min(actual.len())))?;
+    let start = index.saturating_sub(CONTEXT);
+    let snippet = |bytes: &[u8]| {
+        let end = bytes.len().min(index + CONTEXT);
+        bytes
+            .get(start..end)
+            .unwrap_or(&[])
+            .escape_ascii()
+            .to_string()
+    };
+    Some(format!(
+        "output differs at byte {index} (expected {} bytes, got {} bytes)\n  expected: \"{}\"\n  actual:   \"{}\"",
+        expected.len(),
+        actual.len(),
+        snippet(expected),
+        snippet(actual)
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_parser;
+
+    #[test]
+    fn test_run_fixture_dir() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let results = run_fixture_dir(&dir, &bf_parser(), &EquivalenceConfig::default()).unwrap();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert!(
+                result.passed(),
+                "{}: {:?}",
+                result.path.display(),
+                result.outcome
+            );
+        }
+    }
+
+    #[test]
+    fn test_failed_fixtures() {
+        let dir = std::env::temp_dir().join(format!("libbf-fixtures-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files: &[(&str, &[u8])] = &[
+            // wrong output
+            ("a.b", b"+++++++[>++++++++++<-]>-.+.."),
+            ("a.out", b"ABC"),
+            // expected to fail, but finished
+            ("b.b", b",."),
+            ("b.in", b"x"),
+            ("b.out", b"x"),
+            ("b.err", b"Eof\n"),
+            // a parse error
+            ("c.b", b"[+"),
+            ("c.err", b"E0001"),
+            ("d.b", b"+]"),
+            // step limit
+            ("e.b", b"+[]"),
+            ("f.b", b"+"),
+            ("f.err", b"E9999"),
+            ("not-a-fixture.txt", b"+]"),
+        ];
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        let results = run_fixture_dir(
+            &dir,
+            &bf_parser(),
+            &EquivalenceConfig::default().with_step_limit(100),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let outcomes = results
+            .unwrap()
+            .into_iter()
+            .map(|result| {
+                let name = result
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                (name, result.outcome)
+            })
+            .collect::<Vec<_>>();
+        let names = outcomes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.b", "b.b", "c.b", "d.b", "e.b", "f.b"]);
+        let message = |i: usize| match &outcomes[i].1 {
+            FixtureOutcome::Failed(message) => message.as_str(),
+            FixtureOutcome::Passed => panic!("{} unexpectedly passed", outcomes[i].0),
+        };
+        assert_eq!(
+            message(0),
+            "output differs at byte 0 (expected 3 bytes, got 3 bytes)\n  expected: \"ABC\"\n  actual:   \"EFF\""
+        );
+        assert_eq!(
+            message(1),
+            "expected to fail with Eof (E0102), but finished"
+        );
+        assert_eq!(outcomes[2].1, FixtureOutcome::Passed);
+        assert!(message(3).starts_with("parse error: "));
+        assert_eq!(
+            message(4),
+            "expected to finish, but stopped by the step limit"
+        );
+        assert_eq!(message(5), "unknown error kind: \"E9999\"");
+    }
+}
@@ -0,0 +1,273 @@
+//! Random generators of programs, sources and inputs.
+use std::ops::Range;
+
+use crate::{
+    fmt::RenderSpec,
+    prng::SplitMix64,
+    program::{Instruction, Program},
+    token::TokenType,
+};
+
+/// A deterministic random generator of test data.
+///
+/// The same seed always generates the same data, so a failing case can be reproduced
+/// by its seed.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::testing::Generator;
+///
+/// let mut generator = Generator::new(42);
+/// let program = generator.program(20, 3, false);
+///
+/// assert_eq!(program.instructions(), Generator::new(42).program(20, 3, false).instructions());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rng: SplitMix64,
+}
+
+impl Generator {
+    /// Create a new generator with a seed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SplitMix64::new(seed),
+        }
+    }
+
+    /// Generate a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    // A random number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Generate a program with at most `max_len` instructions in total (including those in
+    /// loop bodies) and at most `max_depth` levels of nested loops.
+    ///
+    /// The program consists of [`Instruction::PAdd`], [`Instruction::DAdd`],
+    /// [`Instruction::Output`], [`Instruction::UntilZero`] and, if `allow_input` is `true`,
+    /// [`Instruction::Input`]. Operands are non-zero and in `-3..=3`, so the program can be
+    /// rendered in any dialect. Adjacent instructions may have the same kind, so the program
+    /// is not necessarily what a parser generates from its rendering.
+    pub fn program(&mut self, max_len: usize, max_depth: usize, allow_input: bool) -> Program {
+        let mut budget = self.below(max_len + 1);
+        Program::new(self.instructions(&mut budget, max_depth, allow_input))
+    }
+
+    fn instructions(
+        &mut self,
+        budget: &mut usize,
+        depth: usize,
+        allow_input: bool,
+    ) -> Vec<Instruction> {
+        let mut instructions = vec![];
+        while *budget > 0 {
+            // Stop a loop body early sometimes, so that loops are not always at the end.
+            if self.below(8) == 0 {
+                break;
+            }
+            *budget -= 1;
+            let operand = |generator: &mut Self| {
+                let operand = generator.below(3) as isize + 1;
+                if generator.below(2) == 0 {
+                    operand
+                } else {
+                    -operand
+                }
+            };
+            let inst = match self.below(if allow_input { 6 } else { 5 }) {
+                0 => Instruction::PAdd(operand(self)),
+                1 => Instruction::DAdd(operand(self)),
+                2 => Instruction::Output,
+                3 | 4 if depth > 0 => {
+                    Instruction::UntilZero(self.instructions(budget, depth - 1, allow_input))
+                }
+                3 | 4 => Instruction::DAdd(operand(self)),
+                _ => Instruction::Input,
+            };
+            instructions.push(inst);
+        }
+        instructions
+    }
+
+    /// Render `program` in the dialect of `spec`, inserting random comments between tokens.
+    ///
+    /// Comments consist of ASCII letters, digits and whitespace which do not appear in any token
+    /// of `spec`, so they never change the tokens.
+    /// Instructions which `spec` cannot render (e.g. [`Instruction::SetZero`]) are skipped.
+    pub fn source_for(&mut self, program: &Program, spec: &RenderSpec) -> String {
+        let tokens = [
+            TokenType::PInc,
+            TokenType::PDec,
+            TokenType::DInc,
+            TokenType::DDec,
+            TokenType::Output,
+            TokenType::Input,
+            TokenType::LoopHead,
+            TokenType::LoopTail,
+            TokenType::Breakpoint,
+        ];
+        let noise = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 \n"
+            .chars()
+            .filter(|&c| tokens.iter().all(|&t| !spec.token_str(t).contains(c)))
+            .collect::<Vec<_>>();
+        let mut source = String::new();
+        self.render(program.instructions(), spec, &noise, &mut source);
+        source
+    }
+
+    fn render(
+        &mut self,
+        instructions: &[Instruction],
+        spec: &RenderSpec,
+        noise: &[char],
+        source: &mut String,
+    ) {
+        let push = |generator: &mut Self, token_type: TokenType, source: &mut String| {
+            if !source.is_empty() {
+                source.push_str(&spec.token_separator);
+            }
+            if !noise.is_empty() && generator.below(4) == 0 {
+                for _ in 0..generator.below(4) + 1 {
+                    source.push(noise[generator.below(noise.len())]);
+                }
+                source.push_str(&spec.token_separator);
+            }
+            source.push_str(spec.token_str(token_type));
+        };
+        for inst in instructions {
+            match inst {
+                Instruction::PAdd(operand) | Instruction::DAdd(operand) => {
+                    let (inc, dec) = match inst {
+                        Instruction::PAdd(_) => (TokenType::PInc, TokenType::PDec),
+                        _ => (TokenType::DInc, TokenType::DDec),
+                    };
+                    let token_type = if *operand > 0 { inc } else { dec };
+                    for _ in 0..operand.unsigned_abs() {
+                        push(self, token_type, source);
+                    }
+                }
+                Instruction::Output => push(self, TokenType::Output, source),
+                Instruction::Input => push(self, TokenType::Input, source),
+                Instruction::Breakpoint if spec.breakpoint.is_some() => {
+                    push(self, TokenType::Breakpoint, source)
+                }
+                Instruction::UntilZero(body) => {
+                    push(self, TokenType::LoopHead, source);
+                    self.render(body, spec, noise, source);
+                    push(self, TokenType::LoopTail, source);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Generate random bytes whose length is in `len`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is empty.
+    pub fn input_bytes(&mut self, len: Range<usize>) -> Vec<u8> {
+        assert!(!len.is_empty(), "length range must not be empty.");
+        let len = len.start + self.below(len.len());
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+// Normalize instructions into a canonical form of equivalent programs: adjacent
+// increments/decrements are merged, zeros are dropped, and loops right after another loop, which
+// are never entered, are dropped.
+#[cfg(test)]
+pub(crate) fn normalize(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut result: Vec<Instruction> = vec![];
+    for inst in instructions {
+        match (result.last_mut(), inst) {
+            (Some(Instruction::PAdd(last)), Instruction::PAdd(operand))
+            | (Some(Instruction::DAdd(last)), Instruction::DAdd(operand)) => *last += operand,
+            (_, Instruction::PAdd(0) | Instruction::DAdd(0)) => {}
+            (Some(Instruction::UntilZero(_)), Instruction::UntilZero(_)) => {}
+            (_, Instruction::UntilZero(body)) => {
+                result.push(Instruction::UntilZero(normalize(body)))
+            }
+            (_, inst) => result.push(inst.clone()),
+        }
+        if matches!(
+            result.last(),
+            Some(Instruction::PAdd(0) | Instruction::DAdd(0))
+        ) {
+            result.pop();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{
+        assert_self_equivalent, bf_parser, bf_spec, EquivalenceConfig, RunnerKind,
+    };
+
+    #[test]
+    fn test_parse_rendered_program() {
+        let spec = RenderSpec::new(&bf_spec(), "");
+        let parser = bf_parser();
+        let mut generator = Generator::new(1);
+        for _ in 0..500 {
+            let program = generator.program(30, 3, true);
+            let source = generator.source_for(&program, &spec);
+            assert_eq!(
+                normalize(parser.parse_str(&source).unwrap().instructions()),
+                normalize(program.instructions()),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_runners_are_equivalent_on_generated_programs() {
+        let config = EquivalenceConfig::default().with_step_limit(1000);
+        let mut generator = Generator::new(2);
+        for _ in 0..200 {
+            let program = generator.program(30, 3, true);
+            let inputs = (0..3)
+                .map(|_| generator.input_bytes(0..8))
+                .collect::<Vec<_>>();
+            assert_self_equivalent(
+                &program,
+                &[RunnerKind::Runner, RunnerKind::StepRunner],
+                &inputs,
+                &config,
+            );
+        }
+    }
+
+    #[test]
+    fn test_generator_bounds() {
+        let mut generator = Generator::new(3);
+        fn depth_and_len(instructions: &[Instruction]) -> (usize, usize) {
+            instructions
+                .iter()
+                .fold((0, 0), |(depth, len), inst| match inst {
+                    Instruction::UntilZero(body) => {
+                        let (d, l) = depth_and_len(body);
+                        (depth.max(d + 1), len + l + 1)
+                    }
+                    _ => (depth, len + 1),
+                })
+        }
+        for _ in 0..200 {
+            let program = generator.program(10, 2, false);
+            let (depth, len) = depth_and_len(program.instructions());
+            assert!(depth <= 2 && len <= 10, "{:?}", program.instructions());
+            assert_eq!(program.instruction_counts().input, 0);
+            let input = generator.input_bytes(3..5);
+            assert!((3..5).contains(&input.len()));
+        }
+    }
+}
@@ -0,0 +1,513 @@
+//! Differential testing of programs and runners.
+//!
+//! These helpers run programs on the same inputs and compare their outputs and termination
+//! statuses. They are useful for checking that an optimizer preserves the behavior of programs,
+//! or that different runners agree.
+mod fixture;
+mod generate;
+
+use std::{cell::RefCell, fmt};
+
+use crate::{
+    error::RuntimeError,
+    program::{Instruction, Program},
+    runtime::{MachineView, MeteredOutcome, Runner, RuntimeConfig, StepOutcome, StepRunner},
+};
+
+pub use self::fixture::{run_fixture_dir, FixtureOutcome, FixtureResult};
+#[cfg(test)]
+pub(crate) use self::generate::normalize;
+pub use self::generate::Generator;
+
+/// Returns the token specification of Brainfuck, for the tests in this crate.
+#[cfg(test)]
+pub(crate) fn bf_spec() -> crate::token::simple::SimpleTokenSpec1<char> {
+    crate::token::simple::SimpleTokenSpec {
+        ptr_inc: '>',
+        ptr_dec: '<',
+        data_inc: '+',
+        data_dec: '-',
+        output: '.',
+        input: ',',
+        loop_head: '[',
+        loop_tail: ']',
+    }
+}
+
+/// Returns a Brainfuck parser, for the tests in this crate.
+#[cfg(test)]
+pub(crate) fn bf_parser() -> crate::parser::Parser<crate::token::simple::SimpleTokenizer> {
+    crate::parser::Parser::new(bf_spec().to_tokenizer())
+}
+
+/// A runner used to run a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RunnerKind {
+    /// [`Runner`]
+    Runner,
+    /// [`StepRunner`]
+    StepRunner,
+}
+
+/// A configuration of the comparisons.
+#[derive(Debug, Clone)]
+pub struct EquivalenceConfig {
+    /// The maximum number of steps of each run.
+    ///
+    /// Steps are counted in the same way as [`UnitCost`](crate::runtime::UnitCost),
+    /// i.e. each check of a loop condition is also a step.
+    pub step_limit: u64,
+    /// The runtime configuration.
+    pub runtime: RuntimeConfig,
+    /// The runner used by [`assert_equivalent`].
+    pub runner: RunnerKind,
+}
+
+impl Default for EquivalenceConfig {
+    fn default() -> Self {
+        Self {
+            step_limit: 1_000_000,
+            runtime: RuntimeConfig::default(),
+            runner: RunnerKind::Runner,
+        }
+    }
+}
+
+impl EquivalenceConfig {
+    /// Set the maximum number of steps of each run.
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Set the runtime configuration.
+    pub fn with_runtime_config(mut self, runtime: RuntimeConfig) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Set the runner used by [`assert_equivalent`].
+    pub fn with_runner(mut self, runner: RunnerKind) -> Self {
+        self.runner = runner;
+        self
+    }
+}
+
+/// How a run terminated.
+#[derive(Debug, Clone)]
+pub enum Termination {
+    /// The program finished.
+    Finished,
+    /// The run was stopped by the step limit.
+    StepLimit,
+    /// The program failed.
+    Error(RuntimeError),
+}
+
+impl Termination {
+    // Runtime errors are compared by their messages since `RuntimeError` is not `PartialEq`.
+    fn same_as(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Finished, Self::Finished) | (Self::StepLimit, Self::StepLimit) => true,
+            (Self::Error(a), Self::Error(b)) => a.to_string() == b.to_string(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Finished => write!(f, "finished"),
+            Self::StepLimit => write!(f, "stopped by the step limit"),
+            Self::Error(e) => write!(f, "failed: {e}"),
+        }
+    }
+}
+
+/// The result of a run.
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    /// The output.
+    pub output: Vec<u8>,
+    /// The step at which each byte of the output was written.
+    pub output_steps: Vec<u64>,
+    /// The number of executed steps.
+    pub steps: u64,
+    /// How the run terminated.
+    pub termination: Termination,
+}
+
+/// Run `program` with `input` by the runner `kind`.
+pub fn run_with(
+    program: &Program,
+    input: &[u8],
+    kind: RunnerKind,
+    config: &EquivalenceConfig,
+) -> RunResult {
+    match kind {
+        RunnerKind::Runner => run_runner(program, input, config),
+        RunnerKind::StepRunner => run_step_runner(program, input, config),
+    }
+}
+
+fn run_runner(program: &Program, input: &[u8], config: &EquivalenceConfig) -> RunResult {
+    // The cost model is called before each instruction is executed, so it counts the steps.
+    let counter = RefCell::new((0, vec![]));
+    let mut output = vec![];
+    let runner = Runner::with_config(program, input, &mut output, config.runtime.clone());
+    let result = runner.run_metered(
+        |inst: &Instruction, _: MachineView<'_>| {
+            let (steps, output_steps) = &mut *counter.borrow_mut();
+            if matches!(inst, Instruction::Output) {
+                output_steps.push(*steps);
+            }
+            *steps += 1;
+            1
+        },
+        config.step_limit,
+    );
+    let (counted, mut output_steps) = counter.into_inner();
+    // The last instruction may have failed or been stopped before writing.
+    output_steps.truncate(output.len());
+    let (steps, termination) = match result {
+        Ok(MeteredOutcome::Finished { spent }) => (spent, Termination::Finished),
+        Ok(MeteredOutcome::BudgetExhausted { spent }) => (spent, Termination::StepLimit),
+        // The failed instruction is counted but not completed.
+        Err(e) => (counted - 1, Termination::Error(e)),
+    };
+    RunResult {
+        output,
+        output_steps,
+        steps,
+        termination,
+    }
+}
+
+fn run_step_runner(program: &Program, input: &[u8], config: &EquivalenceConfig) -> RunResult {
+    let mut output = vec![];
+    let mut output_steps = vec![];
+    let mut steps = 0;
+    let termination = {
+        let mut runner =
+            StepRunner::with_config(program, input, &mut output, config.runtime.clone());
+        loop {
+            if !runner.is_running() {
+                break Termination::Finished;
+            }
+            if steps == config.step_limit {
+                break Termination::StepLimit;
+            }
+            if matches!(runner.get_current_instruction(), Some(Instruction::Output)) {
+                output_steps.push(steps);
+            }
+            match runner.step() {
+                Ok(StepOutcome::Finished) => break Termination::Finished,
+                Ok(_) => steps += 1,
+                Err(e) => break Termination::Error(e),
+            }
+        }
+    };
+    output_steps.truncate(output.len());
+    RunResult {
+        output,
+        output_steps,
+        steps,
+        termination,
+    }
+}
+
+/// A difference between two runs found by [`check_equivalent`].
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    /// The index of the input in the inputs.
+    pub input_index: usize,
+    /// The index of the first diverging output byte, or `None` if the outputs are the same.
+    pub output_index: Option<usize>,
+    /// The result of the first run.
+    pub a: RunResult,
+    /// The result of the second run.
+    pub b: RunResult,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "runs differ on input #{}", self.input_index)?;
+        if let Some(index) = self.output_index {
+            writeln!(f, "  outputs diverge at byte {index}")?;
+            for (name, result) in [("a", &self.a), ("b", &self.b)] {
+                match (result.output.get(index), result.output_steps.get(index)) {
+                    (Some(byte), Some(step)) => {
+                        writeln!(f, "    {name}: {byte:#04x} written at step {step}")?
+                    }
+                    _ => writeln!(f, "    {name}: no output (ended at step {})", result.steps)?,
+                }
+            }
+        }
+        for (name, result) in [("a", &self.a), ("b", &self.b)] {
+            writeln!(
+                f,
+                "  {name}: {} after {} steps, {} bytes written",
+                result.termination,
+                result.steps,
+                result.output.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the results of two runs of the input `input_index`.
+///
+/// If either run is stopped by the step limit, only the outputs written before it are compared,
+/// since two different programs may take different numbers of steps.
+fn compare(input_index: usize, a: RunResult, b: RunResult) -> Result<(), Box<Mismatch>> {
+    let limited = matches!(a.termination, Termination::StepLimit)
+        || matches!(b.termination, Termination::StepLimit);
+    let common = a.output.len().min(b.output.len());
+    let output_index = a.output[..common]
+        .iter()
+        .zip(&b.output[..common])
+        .position(|(a, b)| a != b)
+        .or((!limited && a.output.len() != b.output.len()).then_some(common));
+    if output_index.is_none() && (limited || a.termination.same_as(&b.termination)) {
+        return Ok(());
+    }
+    Err(Box::new(Mismatch {
+        input_index,
+        output_index,
+        a,
+        b,
+    }))
+}
+
+/// Check whether `a` and `b` behave equivalently on each of `inputs`.
+///
+/// Both programs are run by `config.runner`. Their outputs and termination statuses are compared.
+/// Runtime errors are compared by their messages.
+pub fn check_equivalent(
+    a: &Program,
+    b: &Program,
+    inputs: &[Vec<u8>],
+    config: &EquivalenceConfig,
+) -> Result<(), Box<Mismatch>> {
+    inputs.iter().enumerate().try_for_each(|(i, input)| {
+        compare(
+            i,
+            run_with(a, input, config.runner, config),
+            run_with(b, input, config.runner, config),
+        )
+    })
+}
+
+/// Assert that `a` and `b` behave equivalently on each of `inputs`.
+///
+/// See [`check_equivalent`] for details.
+///
+/// # Panics
+///
+/// Panics with a report of the first mismatch if any.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{optimize, program::{Instruction::*, Program}, testing};
+///
+/// let program = Program::new([Input, UntilZero(vec![DAdd(-1)]), DAdd(65), Output]);
+/// let optimized = optimize::clear_loops(&program);
+///
+/// testing::assert_equivalent(
+///     &program,
+///     &optimized,
+///     &[vec![0], vec![42], vec![]],
+///     &testing::EquivalenceConfig::default(),
+/// );
+/// ```
+#[track_caller]
+pub fn assert_equivalent(a: &Program, b: &Program, inputs: &[Vec<u8>], config: &EquivalenceConfig) {
+    if let Err(mismatch) = check_equivalent(a, b, inputs, config) {
+        panic!("programs are not equivalent: {mismatch}");
+    }
+}
+
+/// Check whether the runners `kinds` run `program` equivalently on each of `inputs`.
+///
+/// Each runner is compared with the first one. Since the program is the same,
+/// the numbers of steps and the steps at which the output bytes are written are also compared.
+/// `config.runner` is ignored.
+pub fn check_self_equivalent(
+    program: &Program,
+    kinds: &[RunnerKind],
+    inputs: &[Vec<u8>],
+    config: &EquivalenceConfig,
+) -> Result<(), Box<Mismatch>> {
+    let Some((&first, rest)) = kinds.split_first() else {
+        return Ok(());
+    };
+    for (i, input) in inputs.iter().enumerate() {
+        let expected = run_with(program, input, first, config);
+        for &kind in rest {
+            let actual = run_with(program, input, kind, config);
+            compare(i, expected.clone(), actual.clone())?;
+            if expected.steps != actual.steps || expected.output_steps != actual.output_steps {
+                let output_index = expected
+                    .output_steps
+                    .iter()
+                    .zip(&actual.output_steps)
+                    .position(|(a, b)| a != b);
+                return Err(Box::new(Mismatch {
+                    input_index: i,
+                    output_index,
+                    a: expected,
+                    b: actual,
+                }));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Assert that the runners `kinds` run `program` equivalently on each of `inputs`.
+///
+/// See [`check_self_equivalent`] for details.
+///
+/// # Panics
+///
+/// Panics with a report of the first mismatch if any.
+#[track_caller]
+pub fn assert_self_equivalent(
+    program: &Program,
+    kinds: &[RunnerKind],
+    inputs: &[Vec<u8>],
+    config: &EquivalenceConfig,
+) {
+    if let Err(mismatch) = check_self_equivalent(program, kinds, inputs, config) {
+        panic!("runners are not equivalent: {mismatch}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{optimize, runtime::MemorySize};
+
+    const SOURCES: &[&str] = &[
+        "",
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.",
+        include_str!("../../tests/data/hello.bf"),
+        // cat
+        ",[.,]",
+        // reverse
+        ">,[>,]<[.<]",
+        // clears and copies
+        ",[->+>+<<]>[-]>[-<<+>>]<<.>>++++[-]+[>+<-]>.",
+        // out of memory bounds
+        "<+",
+        // infinite loop
+        "+[]",
+        // input
+        ",.,.,.",
+    ];
+
+    fn inputs() -> Vec<Vec<u8>> {
+        vec![vec![], vec![0], vec![3, 1, 4, 1, 5], b"libbf\0".to_vec()]
+    }
+
+    #[test]
+    fn test_runners_are_equivalent() {
+        let parser = bf_parser();
+        let config = EquivalenceConfig::default().with_step_limit(10_000);
+        for source in SOURCES {
+            let program = parser.parse_str(source).unwrap();
+            assert_self_equivalent(
+                &program,
+                &[RunnerKind::Runner, RunnerKind::StepRunner],
+                &inputs(),
+                &config,
+            );
+        }
+
+        let config = config
+            .with_runtime_config(RuntimeConfig::default().with_memsize(MemorySize::BothInfinite));
+        let program = parser.parse_str("<<+.").unwrap();
+        assert_self_equivalent(
+            &program,
+            &[RunnerKind::StepRunner, RunnerKind::Runner],
+            &inputs(),
+            &config,
+        );
+    }
+
+    #[test]
+    fn test_optimized_programs_are_equivalent() {
+        let parser = bf_parser();
+        for runner in [RunnerKind::Runner, RunnerKind::StepRunner] {
+            let config = EquivalenceConfig::default()
+                .with_step_limit(10_000)
+                .with_runner(runner);
+            for source in SOURCES {
+                let program = parser.parse_str(source).unwrap();
+                assert_equivalent(&program, &optimize::optimize(&program), &inputs(), &config);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatch() {
+        use Instruction::*;
+        let config = EquivalenceConfig::default();
+        let a = Program::new([DAdd(65), Output, DAdd(1), Output, Output]);
+        let b = Program::new([DAdd(65), Output, Output, Output]);
+        let mismatch = check_equivalent(&a, &b, &[vec![], vec![]], &config).unwrap_err();
+        assert_eq!(mismatch.input_index, 0);
+        assert_eq!(mismatch.output_index, Some(1));
+        assert_eq!(mismatch.a.output_steps, [1, 3, 4]);
+        assert_eq!(mismatch.b.output_steps, [1, 2, 3]);
+        let report = mismatch.to_string();
+        assert!(report.contains("outputs diverge at byte 1"), "{report}");
+        assert!(report.contains("a: 0x42 written at step 3"), "{report}");
+        assert!(report.contains("b: 0x41 written at step 2"), "{report}");
+
+        // a prefix of the output
+        let b = Program::new([DAdd(65), Output, DAdd(1), Output]);
+        let mismatch = check_equivalent(&a, &b, &[vec![]], &config).unwrap_err();
+        assert_eq!(mismatch.output_index, Some(2));
+        assert!(mismatch
+            .to_string()
+            .contains("b: no output (ended at step 4)"));
+
+        // termination status
+        let b = Program::new([DAdd(65), Output, DAdd(1), Output, Output, PAdd(-1), Output]);
+        let mismatch = check_equivalent(&a, &b, &[vec![]], &config).unwrap_err();
+        assert_eq!(mismatch.output_index, None);
+        assert!(matches!(mismatch.a.termination, Termination::Finished));
+        assert!(matches!(
+            mismatch.b.termination,
+            Termination::Error(RuntimeError::OutOfMemoryBounds { .. })
+        ));
+
+        // outputs before the step limit are compared
+        let b = Program::new([DAdd(65), Output, DAdd(1), Output, UntilZero(vec![])]);
+        assert!(check_equivalent(&a, &b, &[vec![]], &config).is_ok());
+        let b = Program::new([DAdd(65), Output, Output, UntilZero(vec![])]);
+        assert!(check_equivalent(&a, &b, &[vec![]], &config).is_err());
+    }
+
+    #[test]
+    fn test_step_limit() {
+        use Instruction::*;
+        let program = Program::new([DAdd(1), UntilZero(vec![Output])]);
+        for kind in [RunnerKind::Runner, RunnerKind::StepRunner] {
+            let result = run_with(
+                &program,
+                &[],
+                kind,
+                &EquivalenceConfig::default().with_step_limit(10),
+            );
+            assert!(matches!(result.termination, Termination::StepLimit));
+            assert_eq!(result.steps, 10);
+            assert_eq!(result.output_steps, [2, 4, 6, 8]);
+        }
+    }
+}
@@ -1,4 +1,7 @@
 //! Token related definitions.
+//!
+//! The tokenizers in this module are `Send` and `Sync`, so a [`Parser`](crate::parser::Parser)
+//! with one of them can be shared between threads.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 use crate::error::ParseError;
@@ -7,6 +10,9 @@ use crate::error::ParseError;
 #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
 pub mod regex;
 pub mod simple;
+mod switching;
+
+pub use self::switching::{SwitchingTokenStream, SwitchingTokenizer};
 
 /// A token type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,6 +33,14 @@ pub enum TokenType {
     LoopHead,
     /// loop tail (Brainfuck: ']')
     LoopTail,
+    /// breakpoint (no Brainfuck counterpart; see [`Instruction::Breakpoint`](crate::program::Instruction::Breakpoint))
+    Breakpoint,
+    /// assertion of the current cell (no Brainfuck counterpart; see
+    /// [`Instruction::AssertCell`](crate::program::Instruction::AssertCell))
+    ///
+    /// The expected value is the decimal number at the end of the token string
+    /// (e.g. `65` for `=65`), or 0 if the token string does not end with digits.
+    AssertCell,
 }
 
 /// A token.
@@ -71,9 +85,77 @@ pub trait Tokenizer<'a> {
 ///
 /// This trait iterates over tokens in the source string.
 ///
+/// # EOF
+///
+/// When the stream reaches the end of the source, `next` returns a [`TokenInfo`] whose `token`
+/// is `None` and whose `pos_in_chars` is the length of the source in Unicode scalar units.
+/// Subsequent calls keep returning the same [`TokenInfo`].
+///
 /// # Note
 ///
 /// This is not related with the [`Iterator`] trait.
 pub trait TokenStream<'a> {
     fn next(&mut self) -> Result<TokenInfo<'a>, ParseError>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::bf_spec;
+
+    const SOURCE: &str = "ＡＢＣ + Ook. Ook. ＸＹＺ\n";
+
+    fn assert_eof_consistent<'a>(tokenizer: &'a impl Tokenizer<'a>) {
+        let source_len = SOURCE.chars().count();
+        let mut stream = tokenizer.token_stream(SOURCE);
+        let mut info = stream.next().unwrap();
+        while info.token.is_some() {
+            info = stream.next().unwrap();
+        }
+        let eof = TokenInfo {
+            token: None,
+            pos_in_chars: source_len,
+        };
+        assert_eq!(info, eof);
+        // EOF is sticky.
+        assert_eq!(stream.next().unwrap(), eof);
+        assert_eq!(stream.next().unwrap(), eof);
+    }
+
+    #[test]
+    fn test_simple_eof() {
+        let tokenizer = bf_spec().to_tokenizer();
+        assert_eof_consistent(&tokenizer);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_eof() {
+        let tokenizer = regex::RegexTokenizer::from_str_spec(&[
+            (TokenType::DInc, r"\+"),
+            (TokenType::Output, r"\."),
+        ])
+        .unwrap();
+        assert_eof_consistent(&tokenizer);
+    }
+
+    #[cfg(feature = "ook")]
+    #[test]
+    fn test_ook_eof() {
+        assert_eof_consistent(&crate::predefined::ook::OokTokenizer);
+    }
+
+    #[cfg(feature = "asm")]
+    #[test]
+    fn test_asm_eof() {
+        let tokenizer = crate::predefined::asm::MnemonicTokenizer;
+        let mut stream = tokenizer.token_stream("add 2 ; comment\nout\n");
+        while stream.next().unwrap().token.is_some() {}
+        let eof = TokenInfo {
+            token: None,
+            pos_in_chars: 20,
+        };
+        assert_eq!(stream.next().unwrap(), eof);
+        assert_eq!(stream.next().unwrap(), eof);
+    }
+}
@@ -60,31 +60,97 @@
 
 use super::{Token, TokenInfo, TokenStream, TokenType, Tokenizer};
 use crate::error::ParseError;
-use regex::{Match, Regex};
+use regex::{Match, Regex, RegexSet};
 use thiserror::Error;
 
 /// An error that occurred during compiling regular expressions in a token specification.
-#[derive(Debug, Error)]
-#[error("Regex errors: {0:?}")]
-pub struct RegexErrors(pub Vec<RegexErrorDescription>);
-
-/// An element of [`RegexErrors`] field.
+///
+/// Its [`Display`](std::fmt::Display) lists one error per line,
+/// and its [source](std::error::Error::source) is the first error.
 #[derive(Debug)]
+pub struct RegexErrors {
+    errors: Vec<RegexErrorDescription>,
+    partial: RegexTokenizer,
+}
+
+impl RegexErrors {
+    /// Get an iterator over the errors, in the order of the token specifications.
+    pub fn iter(&self) -> std::slice::Iter<'_, RegexErrorDescription> {
+        self.errors.iter()
+    }
+
+    /// Get a tokenizer which consists only of the successfully compiled token specifications.
+    ///
+    /// This is useful for lenient tools which want to go on with the valid part of the specification.
+    pub fn partial_tokenizer(&self) -> &RegexTokenizer {
+        &self.partial
+    }
+
+    /// Unwrap this error, returning the errors and the partial tokenizer.
+    pub fn into_parts(self) -> (Vec<RegexErrorDescription>, RegexTokenizer) {
+        (self.errors, self.partial)
+    }
+}
+
+impl std::fmt::Display for RegexErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} regex error(s) in the token specification:",
+            self.errors.len()
+        )?;
+        for description in &self.errors {
+            write!(f, "\n  {description}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RegexErrors {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.errors
+            .first()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl<'a> IntoIterator for &'a RegexErrors {
+    type Item = &'a RegexErrorDescription;
+    type IntoIter = std::slice::Iter<'a, RegexErrorDescription>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An element of [`RegexErrors`].
+///
+/// The underlying [`regex::Error`] is available as the [source](std::error::Error::source).
+#[derive(Debug, Error)]
+#[error("token specification #{index} has an invalid regex `{pattern}`")]
 pub struct RegexErrorDescription {
     /// The index of a token specification which caused the error.
     pub index: usize,
+    /// The pattern string of the token specification.
+    pub pattern: String,
     /// The caused error.
+    #[source]
     pub error: regex::Error,
 }
 
+#[derive(Debug)]
 struct RegexTokenDef {
     token_type: TokenType,
     regex: Regex,
 }
 
 /// A tokenizer that each token is represented in a regular expression.
+#[derive(Debug)]
 pub struct RegexTokenizer {
     token_defs: Vec<RegexTokenDef>,
+    // A pre-filter to skip the token definitions which do not match the remaining text at all.
+    // `None` if the set could not be compiled (e.g. it exceeded the size limit).
+    set: Option<RegexSet>,
 }
 
 impl RegexTokenizer {
@@ -99,7 +165,12 @@ impl RegexTokenizer {
                 regex: r.clone(),
             })
             .collect();
-        Self { token_defs }
+        Self::with_token_defs(token_defs)
+    }
+
+    fn with_token_defs(token_defs: Vec<RegexTokenDef>) -> Self {
+        let set = RegexSet::new(token_defs.iter().map(|def| def.regex.as_str())).ok();
+        Self { token_defs, set }
     }
 
     /// Create a new [`RegexTokenizer`] with pairs of [`TokenType`] and string.
@@ -109,6 +180,7 @@ impl RegexTokenizer {
     /// # Errors
     ///
     /// If some compile errors occur, these errors are collected into [`RegexErrors`] and returned.
+    /// The successfully compiled specifications are available via [`RegexErrors::partial_tokenizer`].
     pub fn from_str_spec(spec: &[(TokenType, &str)]) -> Result<Self, RegexErrors> {
         let mut token_defs = Vec::with_capacity(spec.len());
         let mut errors = Vec::new();
@@ -121,14 +193,21 @@ impl RegexTokenizer {
                         regex,
                     });
                 }
-                Err(error) => errors.push(RegexErrorDescription { index, error }),
+                Err(error) => errors.push(RegexErrorDescription {
+                    index,
+                    pattern: re.to_string(),
+                    error,
+                }),
             }
         }
 
         if !errors.is_empty() {
-            Err(RegexErrors(errors))
+            Err(RegexErrors {
+                errors,
+                partial: Self::with_token_defs(token_defs),
+            })
         } else {
-            Ok(Self { token_defs })
+            Ok(Self::with_token_defs(token_defs))
         }
     }
 }
@@ -139,6 +218,7 @@ impl<'a> Tokenizer<'a> for RegexTokenizer {
     fn token_stream(&'a self, source: &'a str) -> Self::Stream {
         RegexTokenStream {
             token_defs: &self.token_defs,
+            set: self.set.as_ref(),
             source,
             pos: 0,
             pos_in_chars: 0,
@@ -160,6 +240,7 @@ impl<'a> Tokenizer<'a> for RegexTokenizer {
 /// the stream was created.
 pub struct RegexTokenStream<'a> {
     token_defs: &'a [RegexTokenDef],
+    set: Option<&'a RegexSet>,
     source: &'a str,
     pos: usize,
     pos_in_chars: usize,
@@ -168,7 +249,7 @@ pub struct RegexTokenStream<'a> {
 impl<'a> TokenStream<'a> for RegexTokenStream<'a> {
     fn next(&mut self) -> Result<TokenInfo<'a>, ParseError> {
         let subtext = &self.source[self.pos..];
-        match next_match(subtext, self.token_defs) {
+        match next_match(subtext, self.token_defs, self.set) {
             Some((m, def)) => {
                 let matched_str = m.as_str();
                 let pos_in_chars = self.pos_in_chars + subtext[..m.start()].chars().count();
@@ -199,11 +280,23 @@ impl<'a> TokenStream<'a> for RegexTokenStream<'a> {
 fn next_match<'a, 'b>(
     text: &'a str,
     token_defs: &'b [RegexTokenDef],
+    set: Option<&RegexSet>,
 ) -> Option<(Match<'a>, &'b RegexTokenDef)> {
-    token_defs
-        .iter()
-        .filter_map(|def| def.regex.find(text).map(|m| (m, def)))
-        .min_by_key(|&(m, _)| m.start())
+    match set {
+        // `min_by_key` returns the first minimum, and the set yields indices in ascending order.
+        Some(set) => set
+            .matches(text)
+            .into_iter()
+            .filter_map(|index| {
+                let def = &token_defs[index];
+                def.regex.find(text).map(|m| (m, def))
+            })
+            .min_by_key(|&(m, _)| m.start()),
+        None => token_defs
+            .iter()
+            .filter_map(|def| def.regex.find(text).map(|m| (m, def)))
+            .min_by_key(|&(m, _)| m.start()),
+    }
 }
 
 #[cfg(test)]
@@ -223,10 +316,12 @@ mod test {
             (TokenType::LoopTail, r#"]"#),
         ]);
 
-        if let Err(RegexErrors(es)) = bad_tokenizer_result {
+        if let Err(e) = bad_tokenizer_result {
+            let es = e.iter().collect::<Vec<_>>();
             assert_eq!(es.len(), 2);
             assert_eq!(es[0].index, 2);
             assert_eq!(es[1].index, 6);
+            assert_eq!(e.partial_tokenizer().token_defs.len(), 6);
         } else {
             panic!("unexpected result");
         }
@@ -244,6 +339,39 @@ mod test {
         assert!(ok_tokenizer_result.is_ok());
     }
 
+    #[test]
+    fn test_regex_errors_display() {
+        let e = RegexTokenizer::from_str_spec(&[
+            (TokenType::PInc, r#">"#),
+            (TokenType::DInc, r#"+"#),     // bad
+            (TokenType::LoopHead, r#"["#), // bad
+        ])
+        .expect_err("the spec should be rejected");
+
+        assert_eq!(
+            e.to_string(),
+            "2 regex error(s) in the token specification:\n  \
+             token specification #1 has an invalid regex `+`\n  \
+             token specification #2 has an invalid regex `[`",
+        );
+        let description = e.iter().next().unwrap();
+        let source = std::error::Error::source(description).expect("source should exist");
+        assert_eq!(source.to_string(), description.error.to_string());
+
+        // the partial tokenizer still works for the valid specs.
+        let mut stream = e.partial_tokenizer().token_stream("+>");
+        assert_eq!(
+            stream.next().unwrap(),
+            TokenInfo {
+                token: Some(Token {
+                    token_type: TokenType::PInc,
+                    token_str: ">",
+                }),
+                pos_in_chars: 1,
+            },
+        );
+    }
+
     #[test]
     fn test_stream() {
         let tokenizer = RegexTokenizer::from_str_spec(&[
@@ -289,4 +417,61 @@ mod test {
             },
         );
     }
+
+    #[test]
+    fn test_stream_with_set_matches_without_set() {
+        let specs: [&[(TokenType, &str)]; 3] = [
+            &[
+                (TokenType::PInc, r">"),
+                (TokenType::PDec, r"<"),
+                (TokenType::DInc, r"\+"),
+                (TokenType::DDec, r"-"),
+                (TokenType::Output, r"\."),
+                (TokenType::Input, r","),
+                (TokenType::LoopHead, r"\["),
+                (TokenType::LoopTail, r"\]"),
+            ],
+            // overlapping patterns: the earlier definition wins at the same position.
+            &[
+                (TokenType::DInc, r"ab"),
+                (TokenType::DDec, r"a"),
+                (TokenType::PInc, r"b+"),
+                (TokenType::PDec, r"[a-c]{2}"),
+                (TokenType::Output, r"c"),
+            ],
+            // anchors are evaluated on the remaining text.
+            &[
+                (TokenType::DInc, r"^a"),
+                (TokenType::DDec, r"\bb"),
+                (TokenType::LoopHead, r"(?m)^c"),
+                (TokenType::LoopTail, r"d$"),
+            ],
+        ];
+        let chars = "abcd +-<>.,[]\n\u{3042}".chars().collect::<Vec<_>>();
+        let mut generator = crate::testing::Generator::new(0x1234_5678_9abc_def1);
+        let mut next = || generator.next_u64();
+        for spec in specs {
+            let tokenizer = RegexTokenizer::from_str_spec(spec).unwrap();
+            assert!(tokenizer.set.is_some());
+            let reference = RegexTokenizer {
+                token_defs: RegexTokenizer::from_str_spec(spec).unwrap().token_defs,
+                set: None,
+            };
+            for _ in 0..200 {
+                let len = next() % 30;
+                let source = (0..len)
+                    .map(|_| chars[next() as usize % chars.len()])
+                    .collect::<String>();
+                let mut stream = tokenizer.token_stream(&source);
+                let mut reference_stream = reference.token_stream(&source);
+                loop {
+                    let info = stream.next().unwrap();
+                    assert_eq!(info, reference_stream.next().unwrap(), "source: {source:?}");
+                    if info.token.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
@@ -58,8 +58,10 @@
 //! );
 //! ```
 use super::{Token, TokenInfo, TokenStream, TokenType, Tokenizer};
+use crate::error::ParseError;
 
 /// A token specification for [`SimpleTokenizer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SimpleTokenSpec<S1, S2, S3, S4, S5, S6, S7, S8> {
     /// The token representing pointer increment (`>').
     pub ptr_inc: S1,
@@ -106,7 +108,11 @@ where
         ];
         // Sort the table by token length in descending order in order to fetch token by longest match strategy.
         token_table.sort_by_key(|def| usize::MAX - def.char_count);
-        SimpleTokenizer { token_table }
+        SimpleTokenizer {
+            token_table,
+            strict: false,
+            line_comment: None,
+        }
     }
 }
 
@@ -224,7 +230,11 @@ where
             .collect::<Vec<_>>();
         // Sort the table by token length in descending order in order to fetch token by longest match strategy.
         token_table.sort_by_key(|def| usize::MAX - def.char_count);
-        SimpleTokenizer { token_table }
+        SimpleTokenizer {
+            token_table,
+            strict: false,
+            line_comment: None,
+        }
     }
 
     fn to_token_defs(
@@ -237,6 +247,55 @@ where
     }
 }
 
+/// Converts a single-token specification into a multi-token one,
+/// where each token type has the one token.
+///
+/// Alternatives can be added by struct update syntax.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     parser::Parser,
+///     program::Instruction::*,
+///     token::simple::{SimpleMultiTokenSpec, SimpleTokenSpec},
+/// };
+///
+/// let spec = SimpleTokenSpec {
+///     ptr_inc: '>',
+///     ptr_dec: '<',
+///     data_inc: '+',
+///     data_dec: '-',
+///     output: '.',
+///     input: ',',
+///     loop_head: '[',
+///     loop_tail: ']',
+/// };
+/// let multi = SimpleMultiTokenSpec {
+///     output: &['.', '!'],
+///     ..SimpleMultiTokenSpec::from(&spec)
+/// };
+/// let parser = Parser::new(multi.to_tokenizer());
+///
+/// assert_eq!(parser.parse_str(".!").unwrap().instructions(), [Output, Output]);
+/// ```
+impl<'a, S1, S2, S3, S4, S5, S6, S7, S8> From<&'a SimpleTokenSpec<S1, S2, S3, S4, S5, S6, S7, S8>>
+    for SimpleMultiTokenSpec<'a, S1, S2, S3, S4, S5, S6, S7, S8>
+{
+    fn from(spec: &'a SimpleTokenSpec<S1, S2, S3, S4, S5, S6, S7, S8>) -> Self {
+        Self {
+            ptr_inc: std::slice::from_ref(&spec.ptr_inc),
+            ptr_dec: std::slice::from_ref(&spec.ptr_dec),
+            data_inc: std::slice::from_ref(&spec.data_inc),
+            data_dec: std::slice::from_ref(&spec.data_dec),
+            output: std::slice::from_ref(&spec.output),
+            input: std::slice::from_ref(&spec.input),
+            loop_head: std::slice::from_ref(&spec.loop_head),
+            loop_tail: std::slice::from_ref(&spec.loop_tail),
+        }
+    }
+}
+
 #[test]
 fn test_multiple_simple_def_to_tokenizer() {
     let spec = SimpleMultiTokenSpec {
@@ -300,6 +359,32 @@ fn test_multiple_simple_def_to_tokenizer() {
     assert_simple_def_eq(&tokenizer.token_table, &expected);
 }
 
+#[test]
+fn test_simple_def_to_multiple_simple_def() {
+    use crate::{parser::Parser, program::Instruction::*};
+
+    let spec: SimpleTokenSpec1<char> = crate::testing::bf_spec();
+    let multi = SimpleMultiTokenSpec::from(&spec);
+    assert_eq!(multi.data_inc, ['+']);
+    assert_eq!(multi.loop_tail, [']']);
+    let single = Parser::new(multi.to_tokenizer());
+
+    let data_inc = ['+', 'p'];
+    let multi = SimpleMultiTokenSpec {
+        data_inc: &data_inc,
+        ..multi
+    };
+    let parser = Parser::new(multi.to_tokenizer());
+    assert_eq!(
+        parser.parse_str("+p[-p>]").unwrap().instructions(),
+        [DAdd(2), UntilZero(vec![PAdd(1)])]
+    );
+    assert_eq!(
+        single.parse_str("+p[-p>]").unwrap().instructions(),
+        [DAdd(1), UntilZero(vec![DAdd(-1), PAdd(1)])]
+    );
+}
+
 // Token definition
 #[derive(Debug, PartialEq, Eq)]
 struct SimpleTokenDef {
@@ -324,30 +409,180 @@ impl SimpleTokenDef {
 }
 
 /// A tokenizer that each token in source code simply corresponds to a single [`TokenType`].
+///
+/// By default, characters which are not a part of any token are treated as comments.
+/// In strict mode (see [`SimpleTokenizer::with_strict`]), only whitespace is allowed between tokens.
+/// Explicit line comments can be enabled by [`SimpleTokenizer::with_line_comment`].
 pub struct SimpleTokenizer {
     token_table: Vec<SimpleTokenDef>,
+    strict: bool,
+    line_comment: Option<String>,
+}
+
+impl SimpleTokenizer {
+    /// Set whether characters other than tokens and whitespace are rejected.
+    ///
+    /// In strict mode, the token stream returns [`ParseError::MiscError`] at the first character
+    /// which is neither a part of a token nor whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::Parser, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer().with_strict(true);
+    /// let parser = Parser::new(tokenizer);
+    ///
+    /// assert!(parser.parse_str("+ +\n").is_ok());
+    /// assert!(parser.parse_str("+a").is_err());
+    /// ```
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the prefix of line comments.
+    ///
+    /// The prefix and the rest of the line, including tokens in it, are skipped. Unlike other
+    /// comment characters, line comments are also allowed in strict mode. The prefix takes
+    /// precedence over tokens which start at the same position.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::Parser, program::Instruction::*, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer().with_line_comment(';');
+    /// let parser = Parser::new(tokenizer);
+    ///
+    /// assert_eq!(parser.parse_str("+ ; add 1 to the next cell.\n.").unwrap().instructions(), [DAdd(1), Output]);
+    /// ```
+    pub fn with_line_comment(mut self, prefix: impl ToString) -> Self {
+        self.line_comment = Some(prefix.to_string());
+        self
+    }
+
+    /// Add a token which represents a breakpoint ([`TokenType::Breakpoint`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::Parser, program::Instruction::*, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer().with_breakpoint('#');
+    /// let parser = Parser::new(tokenizer);
+    ///
+    /// assert_eq!(parser.parse_str("+#.").unwrap().instructions(), [DAdd(1), Breakpoint, Output]);
+    /// ```
+    pub fn with_breakpoint(mut self, token: impl ToString) -> Self {
+        self.token_table
+            .push(SimpleTokenDef::new(&token, TokenType::Breakpoint));
+        // Keep the longest match strategy.
+        self.token_table
+            .sort_by_key(|def| usize::MAX - def.char_count);
+        self
+    }
+
+    /// Add a token which represents an assertion of the current cell ([`TokenType::AssertCell`]).
+    ///
+    /// The expected value is the decimal number at the end of `token`, or 0 if there is none.
+    /// Since the tokens are fixed strings, add one token for each expected value to use.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libbf::{parser::Parser, program::Instruction::*, token::simple::SimpleTokenSpec};
+    ///
+    /// let tokenizer = SimpleTokenSpec {
+    ///     ptr_inc: '>',
+    ///     ptr_dec: '<',
+    ///     data_inc: '+',
+    ///     data_dec: '-',
+    ///     output: '.',
+    ///     input: ',',
+    ///     loop_head: '[',
+    ///     loop_tail: ']',
+    /// }.to_tokenizer().with_assertion('=').with_assertion("=2");
+    /// let parser = Parser::new(tokenizer);
+    ///
+    /// assert_eq!(
+    ///     parser.parse_str("++=2[-]=").unwrap().instructions(),
+    ///     [DAdd(2), AssertCell { expected: 2 }, UntilZero(vec![DAdd(-1)]), AssertCell { expected: 0 }]
+    /// );
+    /// ```
+    pub fn with_assertion(mut self, token: impl ToString) -> Self {
+        self.token_table
+            .push(SimpleTokenDef::new(&token, TokenType::AssertCell));
+        // Keep the longest match strategy.
+        self.token_table
+            .sort_by_key(|def| usize::MAX - def.char_count);
+        self
+    }
 }
 
 impl<'a> Tokenizer<'a> for SimpleTokenizer {
     type Stream = SimpleTokenStream<'a>;
 
     fn token_stream(&'a self, source: &'a str) -> SimpleTokenStream<'a> {
-        SimpleTokenStream::new(source, &self.token_table)
+        SimpleTokenStream::new(
+            source,
+            &self.token_table,
+            self.strict,
+            self.line_comment
+                .as_deref()
+                .filter(|prefix| !prefix.is_empty()),
+        )
     }
 }
 
 /// A token stream generated by [`SimpleTokenizer`].
 pub struct SimpleTokenStream<'a> {
     token_table: &'a [SimpleTokenDef],
+    strict: bool,
+    line_comment: Option<&'a str>,
     source: &'a str,
     pos: usize,
     pos_in_chars: usize,
 }
 
 impl<'a> SimpleTokenStream<'a> {
-    fn new(source: &'a str, token_table: &'a [SimpleTokenDef]) -> Self {
+    fn new(
+        source: &'a str,
+        token_table: &'a [SimpleTokenDef],
+        strict: bool,
+        line_comment: Option<&'a str>,
+    ) -> Self {
         SimpleTokenStream {
             token_table,
+            strict,
+            line_comment,
             source,
             pos: 0,
             pos_in_chars: 0,
@@ -356,12 +591,22 @@ impl<'a> SimpleTokenStream<'a> {
 }
 
 impl<'a> TokenStream<'a> for SimpleTokenStream<'a> {
-    fn next(&mut self) -> Result<TokenInfo<'a>, crate::error::ParseError> {
+    fn next(&mut self) -> Result<TokenInfo<'a>, ParseError> {
         // TODO: This loop is too dumb. It should use more efficient algorithm.
 
         let mut rel_pos_in_chars = 0;
-        for (rel_pos, _) in self.source[self.pos..].char_indices() {
+        let mut in_comment = false;
+        for (rel_pos, c) in self.source[self.pos..].char_indices() {
             let pos = self.pos + rel_pos;
+            if in_comment
+                || self
+                    .line_comment
+                    .is_some_and(|p| self.source[pos..].starts_with(p))
+            {
+                in_comment = c != '\n';
+                rel_pos_in_chars += 1;
+                continue;
+            }
             if let Some(def) = find_token_at(self.source, pos, self.token_table) {
                 let info = TokenInfo {
                     token: Some(Token {
@@ -375,6 +620,12 @@ impl<'a> TokenStream<'a> for SimpleTokenStream<'a> {
                 self.pos_in_chars += rel_pos_in_chars + def.char_count;
                 return Ok(info);
             }
+            if self.strict && !c.is_whitespace() {
+                return Err(ParseError::MiscError {
+                    pos_in_chars: self.pos_in_chars + rel_pos_in_chars,
+                    message: format!("unexpected character {c:?}"),
+                });
+            }
             rel_pos_in_chars += 1;
         }
 
@@ -404,6 +655,7 @@ fn find_token_at<'a>(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::testing::bf_spec;
 
     #[test]
     fn test_token_stream() {
@@ -451,4 +703,49 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_line_comment() {
+        use crate::{parser::Parser, program::Instruction::*};
+
+        let tokenizer = bf_spec().to_tokenizer().with_line_comment(';');
+        assert_eq!(
+            Parser::new(tokenizer.with_strict(true))
+                .parse_str("+ ; >")
+                .unwrap()
+                .instructions(),
+            [DAdd(1)]
+        );
+
+        // "//" in multi-byte text, and a token after the comment
+        let tokenizer = bf_spec().to_tokenizer().with_line_comment("//");
+        let mut stream = tokenizer.token_stream("い/+// あ>\r\n<");
+        assert_eq!(
+            stream.next().unwrap(),
+            TokenInfo {
+                token: Some(Token {
+                    token_type: TokenType::DInc,
+                    token_str: "+",
+                }),
+                pos_in_chars: 2,
+            }
+        );
+        assert_eq!(
+            stream.next().unwrap(),
+            TokenInfo {
+                token: Some(Token {
+                    token_type: TokenType::PDec,
+                    token_str: "<",
+                }),
+                pos_in_chars: 10,
+            }
+        );
+        assert_eq!(
+            stream.next().unwrap(),
+            TokenInfo {
+                token: None,
+                pos_in_chars: 11,
+            }
+        );
+    }
 }
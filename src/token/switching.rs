@@ -0,0 +1,231 @@
+//! A tokenizer which switches between two tokenizers.
+use super::{TokenInfo, TokenStream, Tokenizer};
+use crate::error::ParseError;
+
+/// A tokenizer which switches between two tokenizers at each marker in the source.
+///
+/// The source is split into regions by `marker`. The first region is tokenized by `first`,
+/// and the active tokenizer alternates at each marker, so that a file can embed two dialects
+/// (e.g. Brainfuck and Ook!). The markers themselves generate no tokens.
+///
+/// A marker is searched for in the source as is, regardless of the active tokenizer;
+/// it switches the tokenizer even in what would be a comment. The tokens of all regions form
+/// a single stream, so a loop may begin in one dialect and end in the other.
+///
+/// # Examples
+///
+/// ```
+/// use libbf::{
+///     parser::Parser, program::Instruction::*, token::simple::SimpleTokenSpec,
+///     token::SwitchingTokenizer,
+/// };
+///
+/// let spec = |inc, dec| SimpleTokenSpec {
+///     ptr_inc: '>',
+///     ptr_dec: '<',
+///     data_inc: inc,
+///     data_dec: dec,
+///     output: '.',
+///     input: ',',
+///     loop_head: '[',
+///     loop_tail: ']',
+/// };
+/// let tokenizer = SwitchingTokenizer::new(
+///     spec('+', '-').to_tokenizer(),
+///     spec('i', 'd').to_tokenizer(),
+///     "#switch",
+/// );
+/// let parser = Parser::new(tokenizer);
+///
+/// let program = parser.parse_str("++[ #switch d ]+").unwrap();
+/// assert_eq!(program.instructions(), [DAdd(2), UntilZero(vec![DAdd(-1)])]);
+/// ```
+pub struct SwitchingTokenizer<A, B> {
+    first: A,
+    second: B,
+    marker: String,
+}
+
+impl<A, B> SwitchingTokenizer<A, B> {
+    /// Creates a new tokenizer which begins with `first` and switches at each `marker`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `marker` is empty.
+    pub fn new(first: A, second: B, marker: impl Into<String>) -> Self {
+        let marker = marker.into();
+        if marker.is_empty() {
+            panic!("marker must not be empty.");
+        }
+        Self {
+            first,
+            second,
+            marker,
+        }
+    }
+
+    // Split `source` into the region before the first marker and the rest after it.
+    fn split<'s>(&self, source: &'s str) -> (&'s str, Option<&'s str>) {
+        match source.split_once(&self.marker) {
+            Some((region, rest)) => (region, Some(rest)),
+            None => (source, None),
+        }
+    }
+}
+
+impl<'a, A, B> Tokenizer<'a> for SwitchingTokenizer<A, B>
+where
+    A: Tokenizer<'a> + 'a,
+    B: Tokenizer<'a> + 'a,
+{
+    type Stream = SwitchingTokenStream<'a, A, B>;
+
+    fn token_stream(&'a self, source: &'a str) -> Self::Stream {
+        let (region, rest) = self.split(source);
+        SwitchingTokenStream {
+            tokenizer: self,
+            active: Active::First(self.first.token_stream(region)),
+            rest,
+            offset: 0,
+        }
+    }
+}
+
+/// A token stream for [`SwitchingTokenizer`].
+pub struct SwitchingTokenStream<'a, A, B>
+where
+    A: Tokenizer<'a> + 'a,
+    B: Tokenizer<'a> + 'a,
+{
+    tokenizer: &'a SwitchingTokenizer<A, B>,
+    active: Active<A::Stream, B::Stream>,
+    // the source after the current region, or `None` if the current region is the last one
+    rest: Option<&'a str>,
+    // the position of the current region in Unicode scalar units
+    offset: usize,
+}
+
+enum Active<S, T> {
+    First(S),
+    Second(T),
+}
+
+impl<'a, A, B> TokenStream<'a> for SwitchingTokenStream<'a, A, B>
+where
+    A: Tokenizer<'a> + 'a,
+    B: Tokenizer<'a> + 'a,
+{
+    fn next(&mut self) -> Result<TokenInfo<'a>, ParseError> {
+        loop {
+            let info = match &mut self.active {
+                Active::First(stream) => stream.next(),
+                Active::Second(stream) => stream.next(),
+            }
+            .map_err(|e| shift_error(e, self.offset))?;
+            let Some(rest) = self.rest.filter(|_| info.token.is_none()) else {
+                return Ok(TokenInfo {
+                    token: info.token,
+                    pos_in_chars: self.offset + info.pos_in_chars,
+                });
+            };
+
+            // the end of the region: switch the tokenizer after the marker.
+            self.offset += info.pos_in_chars + self.tokenizer.marker.chars().count();
+            let (region, rest) = self.tokenizer.split(rest);
+            self.rest = rest;
+            self.active = match self.active {
+                Active::First(_) => Active::Second(self.tokenizer.second.token_stream(region)),
+                Active::Second(_) => Active::First(self.tokenizer.first.token_stream(region)),
+            };
+        }
+    }
+}
+
+// Shift the position of an error in a region to the one in the whole source.
+fn shift_error(error: ParseError, offset: usize) -> ParseError {
+    match error {
+        ParseError::UnexpectedEndOfFile {
+            pos_in_chars,
+            loop_head_pos_in_chars,
+        } => ParseError::UnexpectedEndOfFile {
+            pos_in_chars: pos_in_chars + offset,
+            loop_head_pos_in_chars: loop_head_pos_in_chars.map(|pos| pos + offset),
+        },
+        ParseError::UnexpectedEndOfLoop { pos_in_chars } => ParseError::UnexpectedEndOfLoop {
+            pos_in_chars: pos_in_chars + offset,
+        },
+        ParseError::MiscError {
+            pos_in_chars,
+            message,
+        } => ParseError::MiscError {
+            pos_in_chars: pos_in_chars + offset,
+            message,
+        },
+        ParseError::OperandTooLarge {
+            pos_in_chars,
+            limit,
+        } => ParseError::OperandTooLarge {
+            pos_in_chars: pos_in_chars + offset,
+            limit,
+        },
+    }
+}
+
+#[cfg(feature = "ook")]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::Parser,
+        predefined::ook::OokTokenizer,
+        program::Instruction::*,
+        testing::bf_spec,
+        token::{simple::SimpleTokenizer, TokenType},
+    };
+
+    fn tokenizer() -> SwitchingTokenizer<SimpleTokenizer, OokTokenizer> {
+        let bf = bf_spec().to_tokenizer();
+        SwitchingTokenizer::new(bf, OokTokenizer, "@@")
+    }
+
+    #[test]
+    fn test_switching_tokenizer() {
+        let parser = Parser::new(tokenizer());
+        let source = "+++[>++<-]@@ Ook! Ook? Ook. Ook? Ook! Ook. Ook? Ook! @@ >.";
+        let program = parser.parse_str(source).unwrap();
+        assert_eq!(
+            program.instructions(),
+            [
+                DAdd(3),
+                UntilZero(vec![PAdd(1), DAdd(2), PAdd(-1), DAdd(-1)]),
+                UntilZero(vec![PAdd(1), Output]),
+                PAdd(1),
+                Output,
+            ]
+        );
+
+        // the positions are in the whole source.
+        let tokenizer = tokenizer();
+        let mut stream = tokenizer.token_stream("+@@Ook. Ook!@@.");
+        let positions = std::iter::from_fn(|| {
+            let info = stream.next().unwrap();
+            Some((info.token_type(), info.pos_in_chars))
+        })
+        .take(4)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [
+                (Some(TokenType::DInc), 0),
+                (Some(TokenType::Input), 3),
+                (Some(TokenType::Output), 14),
+                (None, 15),
+            ]
+        );
+
+        match parser.parse_str("+@@Ook? Ook!") {
+            Err(ParseError::UnexpectedEndOfLoop { pos_in_chars: 3 }) => {}
+            result => panic!("unexpected result: {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Tape visualization.
+//!
+//! [`capture_every`] runs a [`StepRunner`] and takes [`TapeSnapshot`]s of a range of the tape
+//! at a fixed step interval. The snapshots can be exported by [`export_csv`] or [`export_pgm`]
+//! to draw the tape over time, e.g. as a heatmap.
+//!
+//! # Examples
+//!
+//! ```
+//! use libbf::{program::{Instruction::*, Program}, runtime::StepRunner, visual};
+//!
+//! // +++[>++<-]
+//! let program = Program::new([DAdd(3), UntilZero(vec![PAdd(1), DAdd(2), PAdd(-1), DAdd(-1)])]);
+//! let mut runner = StepRunner::new(&program, &[][..], vec![]);
+//! let snapshots = visual::capture_every(&mut runner, 5, 0..2).unwrap();
+//!
+//! assert_eq!(
+//!     visual::export_csv(&snapshots),
+//!     "step,0,1\n0,0,0\n5,3,2\n10,2,4\n15,1,6\n17,0,6\n",
+//! );
+//! ```
+use std::{fmt::Write as _, io::Read, io::Write, ops::Range};
+
+use crate::{error::RuntimeError, runtime::StepRunner};
+
+/// A snapshot of a range of the tape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeSnapshot {
+    /// The number of steps executed before the snapshot was taken.
+    pub step: u64,
+    /// The pointer at the time.
+    pub pointer: isize,
+    /// The address of the first cell in `cells`.
+    pub start: isize,
+    /// The cell values from `start`. Cells out of memory bounds are 0.
+    pub cells: Vec<u8>,
+}
+
+impl TapeSnapshot {
+    /// Take a snapshot of the cells in `range` of the runner.
+    pub fn capture<R, W>(runner: &StepRunner<R, W>, step: u64, range: Range<isize>) -> Self
+    where
+        R: Read,
+        W: Write,
+    {
+        let state = runner.fork_state();
+        Self {
+            step,
+            pointer: state.pointer(),
+            start: range.start,
+            cells: range
+                .map(|address| state.data_at(address).unwrap_or(0))
+                .collect(),
+        }
+    }
+}
+
+/// Run the program to the end, taking a snapshot of `range` every `n` steps.
+///
+/// Snapshots are taken before the first step, after every `n` steps and after the last step
+/// (if it is not a multiple of `n`).
+/// If an error occurred, the snapshots taken so far are discarded and the error is returned.
+///
+/// # Panics
+///
+/// Panics if `n` is 0.
+pub fn capture_every<R, W>(
+    runner: &mut StepRunner<R, W>,
+    n: u64,
+    range: Range<isize>,
+) -> Result<Vec<TapeSnapshot>, RuntimeError>
+where
+    R: Read,
+    W: Write,
+{
+    assert!(n > 0, "step interval must be positive.");
+    let mut snapshots = vec![TapeSnapshot::capture(runner, 0, range.clone())];
+    let mut step = 0;
+    while runner.is_running() {
+        runner.step()?;
+        step += 1;
+        if step % n == 0 || !runner.is_running() {
+            snapshots.push(TapeSnapshot::capture(runner, step, range.clone()));
+        }
+    }
+    Ok(snapshots)
+}
+
+/// Export snapshots as CSV.
+///
+/// The first row is the header, which consists of `step` and the address of each cell
+/// of the first snapshot. Each following row consists of the step and the cell values of a snapshot.
+pub fn export_csv(snapshots: &[TapeSnapshot]) -> String {
+    let mut csv = "step".to_string();
+    if let Some(first) = snapshots.first() {
+        for address in (first.start..).take(first.cells.len()) {
+            write!(csv, ",{address}").unwrap();
+        }
+    }
+    csv.push('\n');
+    for snapshot in snapshots {
+        write!(csv, "{}", snapshot.step).unwrap();
+        for cell in &snapshot.cells {
+            write!(csv, ",{cell}").unwrap();
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Export snapshots as a binary PGM (`P5`) grayscale image.
+///
+/// Each row of the image is a snapshot and each pixel is a cell, whose intensity is the cell value
+/// (maximum 255). The image is as wide as the longest snapshot, and shorter rows are padded with 0.
+pub fn export_pgm(snapshots: &[TapeSnapshot]) -> Vec<u8> {
+    let width = snapshots
+        .iter()
+        .map(|snapshot| snapshot.cells.len())
+        .max()
+        .unwrap_or(0);
+    let mut pgm = format!("P5\n{width} {}\n255\n", snapshots.len()).into_bytes();
+    for snapshot in snapshots {
+        pgm.extend_from_slice(&snapshot.cells);
+        pgm.resize(pgm.len() + width - snapshot.cells.len(), 0);
+    }
+    pgm
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::program::{Instruction::*, Program};
+
+    #[test]
+    fn test_export_pgm() {
+        // +++[>++<-]>.
+        let program = Program::new([
+            DAdd(3),
+            UntilZero(vec![PAdd(1), DAdd(2), PAdd(-1), DAdd(-1)]),
+            PAdd(1),
+            Output,
+        ]);
+        let mut output = vec![];
+        let mut runner = StepRunner::new(&program, &[][..], &mut output);
+        let snapshots = capture_every(&mut runner, 4, -1..2).unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.step).collect::<Vec<_>>(),
+            [0, 4, 8, 12, 16, 19]
+        );
+        drop(runner);
+        assert_eq!(output, [6]);
+
+        let pgm = export_pgm(&snapshots);
+        let header = b"P5\n3 6\n255\n";
+        assert_eq!(&pgm[..header.len()], header);
+        let pixels = &pgm[header.len()..];
+        assert_eq!(pixels.len(), 3 * 6);
+        // the address -1 is out of bounds
+        assert!(pixels.chunks(3).all(|row| row[0] == 0));
+        assert_eq!(pixels[1..3], [0, 0]);
+        assert_eq!(pixels[15..18], [0, 0, 6]);
+        assert_eq!(snapshots[5].pointer, 1);
+
+        assert_eq!(export_pgm(&[]), b"P5\n0 0\n255\n");
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let snapshots = [
+            TapeSnapshot {
+                step: 0,
+                pointer: 0,
+                start: -1,
+                cells: vec![0, 1],
+            },
+            TapeSnapshot {
+                step: 3,
+                pointer: 0,
+                start: -1,
+                cells: vec![255, 2],
+            },
+        ];
+        assert_eq!(export_csv(&snapshots), "step,-1,0\n0,0,1\n3,255,2\n");
+        assert_eq!(export_csv(&[]), "step\n");
+    }
+}
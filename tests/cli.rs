@@ -0,0 +1,125 @@
+//! Integration tests of the `bf` binary.
+#![cfg(feature = "cli")]
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+fn data(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+fn bf(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn bf");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_run_hello_world() {
+    let file = data("hello.bf");
+    let output = bf(&["run", file.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!\n");
+
+    let file = data("hello.ook");
+    let output = bf(&["run", file.to_str().unwrap(), "--dialect", "ook"], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!\n");
+}
+
+#[test]
+fn test_run_options() {
+    let file = data("hello.bf");
+    let file = file.to_str().unwrap();
+
+    let output = bf(&["run", file, "--memsize", "3"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error[E0101]: "));
+
+    let output = bf(
+        &["run", file, "--memsize", "right-inf", "--max-steps", "10"],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: the program did not finish in 10 steps\n"
+    );
+
+    let output = bf(&["run", file, "--max-steps", "100000"], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hello World!\n");
+
+    let output = bf(&["run", file, "--memsize", "many"], b"");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_check() {
+    let file = data("hello.bf");
+    let output = bf(&["check", file.to_str().unwrap()], b"");
+    assert!(output.status.success());
+
+    let dir = std::env::temp_dir().join(format!("libbf-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("broken.bf");
+    std::fs::write(&file, "+[>+\n").unwrap();
+    let output = bf(&["check", file.to_str().unwrap()], b"");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error[E0001]: Unexpected end-of-file\n"));
+    assert!(stderr.contains(&format!(" --> {}:1:5\n", file.display())));
+    assert!(stderr.contains("= note: the loop at 1:2 is not closed"));
+
+    // validation findings are warnings.
+    let file = dir.join("hang.bf");
+    std::fs::write(&file, "[This is a header comment.]\n+[]\n").unwrap();
+    let output = bf(&["check", file.to_str().unwrap()], b"");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr,
+        format!(
+            "warning[E0302]: {}: [2]: loop never terminates once entered\n",
+            file.display()
+        )
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fmt() {
+    let file = data("hello.bf");
+    let file = file.to_str().unwrap();
+
+    let output = bf(&["fmt", file], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        std::fs::read(data("fmt/hello.default.bf")).unwrap()
+    );
+
+    let output = bf(
+        &["fmt", file, "--align", "--width", "30", "--group", "4"],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        std::fs::read(data("fmt/hello.aligned.bf")).unwrap()
+    );
+
+    let output = bf(&["fmt", file, "--dialect", "asm"], b"");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,2 @@
+Echo the input until EOF which is an error in libbf
+,[.,]